@@ -0,0 +1,152 @@
+use std::collections::{hash_map::Entry, HashMap};
+use std::ffi::c_void;
+use std::os::fd::RawFd;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::io::unix::AsyncFd;
+use tokio::sync::oneshot;
+
+use crate::ibmad::{self};
+use crate::umad::{agent::UmadAgent, mad::ib_user_mad, UmadError, UmadPort};
+
+use ibmad::sys::*;
+use ibmad::transport::{class_version, MadRequest, MadResponse};
+use ibmad::IBSmpError;
+
+//How long past the kernel's own timeout and retries a query waits before
+//giving up on its response
+const RESPONSE_GRACE: Duration = Duration::from_millis(100);
+
+type Reply = Result<MadResponse, IBSmpError>;
+
+//A port whose umad fd is driven by the tokio reactor. Any number of queries
+//can be outstanding at once, whichever of them is polling when the fd turns
+//readable drains it and hands each response to its waiter by TID.
+//
+//Agents are registered without kernel RMPP, so responses are limited to a
+//single MAD.
+pub struct AsyncMadPort {
+    port: UmadPort,
+    fd: AsyncFd<RawFd>,
+    agents: Mutex<HashMap<u32, UmadAgent>>,
+    pending: Mutex<HashMap<u32, oneshot::Sender<Reply>>>,
+    next_tid: AtomicU32,
+    pub retries: u32,
+}
+
+impl AsyncMadPort {
+    //Must be called from within a tokio runtime. The port stays open when
+    //the AsyncMadPort is dropped, only its agents are unregistered.
+    pub fn new(port: &UmadPort) -> Result<AsyncMadPort, UmadError> {
+        let fd = unsafe { umad_get_fd(port.port_id) };
+        if fd < 0 {
+            return Err(UmadError::OpenCaPortError);
+        }
+
+        Ok(AsyncMadPort {
+            port: port.clone(),
+            fd: AsyncFd::new(fd).map_err(|_| UmadError::AsyncFdError)?,
+            agents: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            next_tid: AtomicU32::new(1),
+            retries: 0,
+        })
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> AsyncMadPort {
+        self.retries = retries;
+        self
+    }
+
+    //Number of queries waiting on a response
+    pub fn outstanding(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    //Send a request and wait for its response. A response with a non-zero
+    //status is still a response; Err means nothing usable came back.
+    pub async fn call(&self, request: &MadRequest) -> Result<MadResponse, IBSmpError> {
+        let tid = self.next_tid.fetch_add(1, Ordering::Relaxed);
+        let (tx, mut rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(tid, tx);
+
+        if let Err(e) = self.send(request, tid) {
+            self.pending.lock().unwrap().remove(&tid);
+            return Err(e);
+        }
+
+        //The kernel times the send out and hands it back, this only guards
+        //against that never arriving
+        let limit = Duration::from_millis(request.timeout as u64 * (self.retries as u64 + 1)) + RESPONSE_GRACE;
+        let result = tokio::time::timeout(limit, async {
+            loop {
+                tokio::select! {
+                    reply = &mut rx => return reply.unwrap_or(Err(IBSmpError::SendMADError)),
+                    polled = self.poll_responses() => {
+                        if polled.is_err() {
+                            return Err(IBSmpError::SendMADError);
+                        }
+                    }
+                }
+            }
+        })
+        .await;
+
+        self.pending.lock().unwrap().remove(&tid);
+        result.unwrap_or(Err(IBSmpError::SendMADError))
+    }
+
+    //Send a request and return its attribute data, failing on a bad status
+    pub async fn query(&self, request: &MadRequest) -> Result<Vec<u8>, IBSmpError> {
+        self.call(request).await?.into_data(request.mgmt_class)
+    }
+
+    fn send(&self, request: &MadRequest, tid: u32) -> Result<(), IBSmpError> {
+        let mut agents = self.agents.lock().unwrap();
+        let agent = match agents.entry(request.mgmt_class) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let agent = UmadAgent::register(&self.port, request.mgmt_class as u8, class_version(request.mgmt_class), &[])
+                    .map_err(|_| IBSmpError::SendMADError)?;
+                entry.insert(agent)
+            }
+        };
+
+        agent.send(&request.to_umad(tid as u64), request.timeout, self.retries).map_err(|_| IBSmpError::SendMADError)
+    }
+
+    //Wait for the fd to turn readable and dispatch everything queued on it
+    async fn poll_responses(&self) -> std::io::Result<()> {
+        let mut guard = self.fd.readable().await?;
+
+        loop {
+            let mut umad = ib_user_mad::new();
+            let mut length = IB_MAD_SIZE as i32;
+
+            let r = unsafe { umad_recv(self.port.port_id, &mut umad as *mut ib_user_mad as *mut c_void, &mut length, 0) };
+            if r < 0 {
+                guard.clear_ready();
+                return Ok(());
+            }
+
+            self.dispatch(&umad);
+        }
+    }
+
+    fn dispatch(&self, umad: &ib_user_mad) {
+        //The kernel owns the upper 32 bits of the TID
+        let tid = u32::from_be_bytes([umad.data[12], umad.data[13], umad.data[14], umad.data[15]]);
+        let Some(tx) = self.pending.lock().unwrap().remove(&tid) else {
+            return;
+        };
+
+        //A non-zero umad status means the send itself timed out
+        let _ = tx.send(if umad.status != 0 {
+            Err(IBSmpError::SendMADError)
+        } else {
+            Ok(MadResponse::decode(&umad.data[..IB_MAD_SIZE as usize]))
+        });
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::ibmad::{self};
+use crate::umad::{agent::UmadAgent, mad::ib_user_mad, UmadError, UmadPort};
+
+use ibmad::sys::IB_MAD_SIZE;
+use ibmad::transport::{class_version, MadRequest, MadResponse, MadTransport};
+use ibmad::IBSmpError;
+
+pub const DEFAULT_MAX_OUTSTANDING: usize = 512;
+
+//How often the receive thread wakes up to check whether it should stop
+const RECV_POLL_MS: i32 = 100;
+
+//How long past its timeout and retries a caller waits before giving up on
+//a response the channel never handed back
+const RESPONSE_GRACE: Duration = Duration::from_millis(100);
+
+type Reply = Result<MadResponse, IBSmpError>;
+
+//Where a dispatcher sends its MADs and receives their responses. A send
+//with a timeout must come back through recv, either as the response or as
+//the send itself with a non-zero status.
+pub trait MadChannel: Send + Sync {
+    fn send(&self, umad: &ib_user_mad, timeout: u32) -> Result<(), UmadError>;
+    fn recv(&self, timeout: i32) -> Result<ib_user_mad, UmadError>;
+}
+
+impl MadChannel for UmadAgent {
+    fn send(&self, umad: &ib_user_mad, timeout: u32) -> Result<(), UmadError> {
+        //The agent's QP is picked by its class, a MAD of another class would
+        //go out on the wrong one
+        if umad.data[1] != self.mgmt_class {
+            return Err(UmadError::SendFailure);
+        }
+        UmadAgent::send(self, umad, timeout, 0)
+    }
+
+    fn recv(&self, timeout: i32) -> Result<ib_user_mad, UmadError> {
+        self.recv_completion(timeout)
+    }
+}
+
+struct Pending {
+    umad: ib_user_mad,
+    timeout: u32,
+    retries: u32,
+    tx: mpsc::Sender<Reply>,
+}
+
+struct Shared {
+    channel: Arc<dyn MadChannel>,
+    pending: Mutex<HashMap<u32, Pending>>,
+    running: AtomicBool,
+}
+
+impl Shared {
+    //Route a received MAD to the caller waiting on its TID, sending the
+    //request again if it timed out with retries left
+    fn dispatch(&self, umad: &ib_user_mad) {
+        //The kernel owns the upper 32 bits of the TID
+        let tid = u32::from_be_bytes([umad.data[12], umad.data[13], umad.data[14], umad.data[15]]);
+        let mut pending = self.pending.lock().unwrap();
+        let Some(mut entry) = pending.remove(&tid) else {
+            return;
+        };
+
+        if umad.status == 0 {
+            let _ = entry.tx.send(Ok(MadResponse::decode(&umad.data[..IB_MAD_SIZE as usize])));
+            return;
+        }
+
+        if entry.retries > 0 && self.channel.send(&entry.umad, entry.timeout).is_ok() {
+            entry.retries -= 1;
+            pending.insert(tid, entry);
+            return;
+        }
+
+        let _ = entry.tx.send(Err(IBSmpError::SendMADError));
+    }
+
+    fn run(&self) {
+        while self.running.load(Ordering::Relaxed) {
+            match self.channel.recv(RECV_POLL_MS) {
+                Ok(umad) => self.dispatch(&umad),
+                Err(UmadError::Timeout) => {}
+                Err(_) => std::thread::sleep(Duration::from_millis(RECV_POLL_MS as u64)),
+            }
+        }
+    }
+}
+
+//A response to wait on, from MadDispatcher::submit. Dropping it gives up on
+//the response, the request isn't sent again.
+pub struct MadTicket {
+    shared: Arc<Shared>,
+    tid: u32,
+    rx: Option<mpsc::Receiver<Reply>>,
+    limit: Duration,
+}
+
+impl MadTicket {
+    pub fn wait(self) -> Result<MadResponse, IBSmpError> {
+        let Some(rx) = &self.rx else {
+            return Err(IBSmpError::SendMADError);
+        };
+
+        rx.recv_timeout(self.limit).unwrap_or(Err(IBSmpError::SendMADError))
+    }
+}
+
+//Whether answered, timed out or abandoned, the request is no longer pending
+impl Drop for MadTicket {
+    fn drop(&mut self) {
+        if self.rx.is_some() {
+            self.shared.pending.lock().unwrap().remove(&self.tid);
+        }
+    }
+}
+
+//Multiplexes any number of requests, from any number of threads, over one
+//channel. Each request gets its own TID and a thread receiving on the
+//channel hands every response to whoever is waiting on that TID.
+pub struct MadDispatcher {
+    shared: Arc<Shared>,
+    receiver: Option<JoinHandle<()>>,
+    next_tid: AtomicU32,
+    pub retries: u32,
+    pub max_outstanding: usize,
+}
+
+impl MadDispatcher {
+    pub fn new(channel: Arc<dyn MadChannel>) -> MadDispatcher {
+        let shared = Arc::new(Shared {
+            channel,
+            pending: Mutex::new(HashMap::new()),
+            running: AtomicBool::new(true),
+        });
+
+        let receiver = {
+            let shared = shared.clone();
+            std::thread::spawn(move || shared.run())
+        };
+
+        MadDispatcher {
+            shared,
+            receiver: Some(receiver),
+            next_tid: AtomicU32::new(1),
+            retries: 0,
+            max_outstanding: DEFAULT_MAX_OUTSTANDING,
+        }
+    }
+
+    //Register an agent for mgmt_class on port and dispatch over it
+    pub fn open(port: &UmadPort, mgmt_class: u32) -> Result<MadDispatcher, UmadError> {
+        let agent = UmadAgent::register(port, mgmt_class as u8, class_version(mgmt_class), &[])?;
+        Ok(MadDispatcher::new(Arc::new(agent)))
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> MadDispatcher {
+        self.retries = retries;
+        self
+    }
+
+    //Most requests call_batch keeps outstanding at once
+    pub fn with_max_outstanding(mut self, max_outstanding: usize) -> MadDispatcher {
+        self.max_outstanding = max_outstanding.max(1);
+        self
+    }
+
+    //Number of requests waiting on a response
+    pub fn outstanding(&self) -> usize {
+        self.shared.pending.lock().unwrap().len()
+    }
+
+    //Send a request without waiting for its response. max_outstanding only
+    //applies to call_batch, submit sends right away however many are pending.
+    pub fn submit(&self, request: &MadRequest) -> MadTicket {
+        let tid = self.next_tid.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel();
+        let umad = request.to_umad(tid as u64);
+        let limit = Duration::from_millis(request.timeout as u64 * (self.retries as u64 + 1)) + RESPONSE_GRACE;
+
+        //Pending before it's sent, the response can arrive before send returns
+        let entry = Pending { umad, timeout: request.timeout, retries: self.retries, tx };
+        self.shared.pending.lock().unwrap().insert(tid, entry);
+
+        let rx = match self.shared.channel.send(&umad, request.timeout) {
+            Ok(()) => Some(rx),
+            Err(_) => {
+                self.shared.pending.lock().unwrap().remove(&tid);
+                None
+            }
+        };
+
+        MadTicket { shared: self.shared.clone(), tid, rx, limit }
+    }
+}
+
+impl MadTransport for MadDispatcher {
+    fn call(&self, request: &MadRequest) -> Result<MadResponse, IBSmpError> {
+        self.submit(request).wait()
+    }
+
+    //Keep up to max_outstanding of the batch in flight, waiting on the
+    //oldest before sending the next
+    fn call_batch(&self, requests: &[MadRequest]) -> Vec<Result<MadResponse, IBSmpError>> {
+        let mut results = Vec::with_capacity(requests.len());
+        let mut in_flight = VecDeque::new();
+
+        for request in requests {
+            if in_flight.len() >= self.max_outstanding {
+                let ticket: MadTicket = in_flight.pop_front().unwrap();
+                results.push(ticket.wait());
+            }
+            in_flight.push_back(self.submit(request));
+        }
+        results.extend(in_flight.into_iter().map(|ticket| ticket.wait()));
+
+        results
+    }
+}
+
+impl Drop for MadDispatcher {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        if let Some(receiver) = self.receiver.take() {
+            let _ = receiver.join();
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{ffi::{c_void, CStr, CString}, mem::MaybeUninit};
+use std::ffi::{c_void, CString};
 
 use thiserror::Error;
 
@@ -7,6 +7,10 @@ use crate::ibmad::{self};
 
 use ibmad::sys::*;
 use ibmad::enums::*;
+use ibmad::transport::{DrPath, MadAddress, MadRequest, MadTransport};
+use ibmad::port::{MlnxExtPortInfo, PortInfo};
+use ibmad::switch::{LinearForwardingTable, SwitchInfo, LFT_BLOCK_SIZE};
+use crate::umad::rmpp::RmppError;
 
 #[derive(Error, Debug)]
 pub enum IBMadError {
@@ -20,6 +24,10 @@ pub enum IBSmpError {
     SendMADError,
     #[error("Unable to construct DR MAD path.")]
     DRMADPathError,
+    #[error("MAD completed with error status 0x{0:x}.")]
+    MadStatusError(u16),
+    #[error("RMPP transfer failed: {0}")]
+    RmppError(#[from] RmppError),
 }
 
 #[derive(Clone, Debug)]
@@ -30,7 +38,7 @@ pub struct IBMadPort {
 unsafe impl Send for IBMadPort {}
 unsafe impl Sync for IBMadPort {}
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct NodeInfo {
     pub base_ver: u64,
     pub class_vers: u64,
@@ -111,6 +119,25 @@ impl NodeInfo {
 
         node_info
     }
+
+    pub fn to_mad_fields(&self, data: &mut [u8]) {
+        let ptr = data.as_mut_ptr() as *mut c_void;
+
+        unsafe {
+            mad_set_field(ptr, 0, MAD_FIELDS_IB_NODE_BASE_VERS_F, self.base_ver as u32);
+            mad_set_field(ptr, 0, MAD_FIELDS_IB_NODE_CLASS_VERS_F, self.class_vers as u32);
+            mad_set_field(ptr, 0, MAD_FIELDS_IB_NODE_TYPE_F, self.node_type as u32);
+            mad_set_field(ptr, 0, MAD_FIELDS_IB_NODE_NPORTS_F, self.num_ports as u32);
+            mad_set_field64(ptr, 0, MAD_FIELDS_IB_NODE_SYSTEM_GUID_F, self.system_guid);
+            mad_set_field64(ptr, 0, MAD_FIELDS_IB_NODE_GUID_F, self.guid);
+            mad_set_field64(ptr, 0, MAD_FIELDS_IB_NODE_PORT_GUID_F, self.port_guid);
+            mad_set_field(ptr, 0, MAD_FIELDS_IB_NODE_PARTITION_CAP_F, self.part_cap as u32);
+            mad_set_field(ptr, 0, MAD_FIELDS_IB_NODE_DEVID_F, self.dev_id as u32);
+            mad_set_field(ptr, 0, MAD_FIELDS_IB_NODE_REVISION_F, self.revision as u32);
+            mad_set_field(ptr, 0, MAD_FIELDS_IB_NODE_LOCAL_PORT_F, self.local_port as u32);
+            mad_set_field(ptr, 0, MAD_FIELDS_IB_NODE_VENDORID_F, self.vendor_id as u32);
+        }
+    }
 }
 
 pub fn mad_rpc_open_port(device_name: &str, mgmt_classes: &[u32]) -> Result<IBMadPort, IBMadError> {
@@ -133,198 +160,271 @@ pub fn mad_rpc_close_port(ibmad_port: &mut IBMadPort) -> Result<(), IBMadError>
 
 }
 
-pub fn send_dr_node_info_mad(port: &IBMadPort, path: &str, timeout: u32) -> Result<NodeInfo, IBSmpError> {
+pub fn send_dr_node_info_mad<T: MadTransport + ?Sized>(port: &T, path: &str, timeout: u32) -> Result<NodeInfo, IBSmpError> {
 
-    let drpath = Box::new(ib_dr_path_t{
-        cnt: 0,
-        p: unsafe { MaybeUninit::<[u8; 64]>::zeroed().assume_init() },
-        drslid: 0xffff,
-        drdlid: 0xffff,
-    });
+    let address = MadAddress::DirectedRoute(DrPath::parse(path)?);
+    let request = MadRequest::smp_get(address, SMI_ATTR_ID_IB_ATTR_NODE_INFO, 0, timeout);
 
-    let mut portid = Box::new(ib_portid_t{
-        lid: 0,
-        drpath: *drpath,
-        grh_present: 0,
-        gid: unsafe { MaybeUninit::<[u8; 16]>::zeroed().assume_init() },
-        qp: 0,
-        qkey: 0,
-        sl: 0,
-        pkey_idx: 0,
-    });
+    let mut data = port.query(&request)?;
 
-    let drpath_ptr = Box::into_raw(drpath);
+    let ni = NodeInfo::from_mad_fields(&mut data);
 
-    let routepath =  CString::new(path).unwrap();
+    Ok(ni)
 
-    let r = unsafe { str2drpath(drpath_ptr, routepath.as_ptr() as *mut i8, 0xffff, 0xffff) };
+}
 
-    let drpath:Box<ib_dr_path_t> = unsafe { Box::from_raw(drpath_ptr) };
+pub fn send_lid_node_info_mad<T: MadTransport + ?Sized>(port: &T, lid: i32, timeout: u32) -> Result<NodeInfo, IBSmpError> {
 
-    if r > 0 {
-        portid.drpath = *drpath;        
-    } else {
-        return Err(IBSmpError::DRMADPathError);
-    }
-    
-    let mut data: [u8; IB_SMP_DATA_SIZE as usize] = [0; IB_SMP_DATA_SIZE as usize];
-    let data_ptr = data.as_mut_ptr() as *mut c_void;  
+    let request = MadRequest::smp_get(MadAddress::Lid(lid as u16), SMI_ATTR_ID_IB_ATTR_NODE_INFO, 0, timeout);
 
-    let portid_ptr = Box::into_raw(portid);
+    let mut data = port.query(&request)?;
 
-    let r: *mut u8 = unsafe { smp_query_via(data_ptr, portid_ptr, ibmad::sys::SMI_ATTR_ID_IB_ATTR_NODE_INFO, 0, timeout, port.port) };
+    let ni = NodeInfo::from_mad_fields(&mut data);
 
-    let _portid = unsafe { Box::from_raw(portid_ptr) };
+    Ok(ni)
 
-    if r.is_null() {
-        return Err(IBSmpError::SendMADError);
-    }
+}
 
-    let ni = NodeInfo::from_mad_fields(&mut data);
+pub fn send_dr_node_desc_mad<T: MadTransport + ?Sized>(port: &T, path: &str, timeout: u32) -> Result<String, IBSmpError> {
 
-    Ok(ni)
+    let address = MadAddress::DirectedRoute(DrPath::parse(path)?);
+    let request = MadRequest::smp_get(address, SMI_ATTR_ID_IB_ATTR_NODE_DESC, 0, timeout);
+
+    let data = port.query(&request)?;
 
+    Ok(node_desc_from_mad_fields(&data))
 }
 
-pub fn send_lid_node_info_mad(port:&IBMadPort, lid: i32, timeout: u32) -> Result<NodeInfo, IBSmpError> {
+pub fn send_dr_port_info_mad<T: MadTransport + ?Sized>(port: &T, path: &str, portnum: i32, timeout: u32) -> Result<PortInfo, IBSmpError> {
 
-    let portid = Box::new(ib_portid_t{
-        lid: lid,
-        drpath: unsafe { MaybeUninit::<ib_dr_path_t>::zeroed().assume_init() },
-        grh_present: 0,
-        gid: unsafe { MaybeUninit::<[u8; 16]>::zeroed().assume_init() },
-        qp: 0,
-        qkey: 0,
-        sl: 0,
-        pkey_idx: 0,
-    });
+    let address = MadAddress::DirectedRoute(DrPath::parse(path)?);
+    let request = MadRequest::smp_get(address, SMI_ATTR_ID_IB_ATTR_PORT_INFO, portnum as u32, timeout);
 
-    let mut data: [u8; IB_SMP_DATA_SIZE as usize] = [0; IB_SMP_DATA_SIZE as usize];
-    let data_ptr = data.as_mut_ptr() as *mut c_void;  
+    let mut data = port.query(&request)?;
 
-    let portid_ptr = Box::into_raw(portid);
+    Ok(PortInfo::from_mad_fields(&mut data))
+}
 
-    let r: *mut u8 = unsafe { smp_query_via(data_ptr, portid_ptr, ibmad::sys::SMI_ATTR_ID_IB_ATTR_NODE_INFO, 0, timeout, port.port) };
+pub fn send_lid_port_info_mad<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, timeout: u32) -> Result<PortInfo, IBSmpError> {
 
-    let _portid = unsafe { Box::from_raw(portid_ptr) };
+    let request = MadRequest::smp_get(MadAddress::Lid(lid as u16), SMI_ATTR_ID_IB_ATTR_PORT_INFO, portnum as u32, timeout);
 
-    if r.is_null() {
-        return Err(IBSmpError::SendMADError);
-    }
+    let mut data = port.query(&request)?;
 
-    let ni = NodeInfo::from_mad_fields(&mut data);
+    Ok(PortInfo::from_mad_fields(&mut data))
+}
 
-    Ok(ni)
+//Vendor specific, only answered by Mellanox devices
+pub fn send_lid_mlnx_ext_port_info_mad<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, timeout: u32) -> Result<MlnxExtPortInfo, IBSmpError> {
+
+    let request = MadRequest::smp_get(MadAddress::Lid(lid as u16), SMI_ATTR_ID_IB_ATTR_MLNX_EXT_PORT_INFO, portnum as u32, timeout);
+
+    let mut data = port.query(&request)?;
 
+    Ok(MlnxExtPortInfo::from_mad_fields(&mut data))
 }
 
-pub fn send_dr_node_desc_mad(port: &IBMadPort, path: &str, timeout: u32) -> Result<String, IBSmpError> {
-
-    let drpath = Box::new(ib_dr_path_t{
-        cnt: 0,
-        p: unsafe { MaybeUninit::<[u8; 64]>::zeroed().assume_init() },
-        drslid: 0xffff,
-        drdlid: 0xffff,
-    });
-
-    let mut portid = Box::new(ib_portid_t{
-        lid: 0,
-        drpath: *drpath,
-        grh_present: 0,
-        gid: unsafe { MaybeUninit::<[u8; 16]>::zeroed().assume_init() },
-        qp: 100,
-        qkey: 0,
-        sl: 100,
-        pkey_idx: 0,
-    });
-
-    let drpath_ptr = Box::into_raw(drpath);
-    let routepath =  CString::new(path).unwrap();
-
-    let r = unsafe { str2drpath(drpath_ptr, routepath.as_ptr() as *mut i8, 0xffff, 0xffff) };
-
-    let drpath:Box<ib_dr_path_t> = unsafe { Box::from_raw(drpath_ptr) };
-
-    if r > 0 {
-        portid.drpath = *drpath;        
-    } else {
-        return Err(IBSmpError::DRMADPathError);
-    }
-    
-    let mut data: [u8; IB_SMP_DATA_SIZE as usize] = [0; IB_SMP_DATA_SIZE as usize];
-    let data_ptr = data.as_mut_ptr() as *mut c_void;  
-    let portid_ptr = Box::into_raw(portid);
+pub fn send_dr_switch_info_mad<T: MadTransport + ?Sized>(port: &T, path: &str, timeout: u32) -> Result<SwitchInfo, IBSmpError> {
 
-    let r: *mut u8 = unsafe { smp_query_via(data_ptr, portid_ptr, ibmad::sys::SMI_ATTR_ID_IB_ATTR_NODE_DESC  , 0, timeout, port.port) };
+    let address = MadAddress::DirectedRoute(DrPath::parse(path)?);
+    let request = MadRequest::smp_get(address, SMI_ATTR_ID_IB_ATTR_SWITCH_INFO, 0, timeout);
 
-    let _portid = unsafe { Box::from_raw(portid_ptr) };
+    let mut data = port.query(&request)?;
 
-    if r.is_null() {
-        return Err(IBSmpError::SendMADError);
-    }
+    Ok(SwitchInfo::from_mad_fields(&mut data))
+}
+
+pub fn send_lid_switch_info_mad<T: MadTransport + ?Sized>(port: &T, lid: i32, timeout: u32) -> Result<SwitchInfo, IBSmpError> {
+
+    let request = MadRequest::smp_get(MadAddress::Lid(lid as u16), SMI_ATTR_ID_IB_ATTR_SWITCH_INFO, 0, timeout);
+
+    let mut data = port.query(&request)?;
+
+    Ok(SwitchInfo::from_mad_fields(&mut data))
+}
 
+//One block of 64 egress ports, block n holds LIDs 64n..64n+63
+pub fn send_lid_lft_block_mad<T: MadTransport + ?Sized>(port: &T, lid: i32, block: u32, timeout: u32) -> Result<Vec<u8>, IBSmpError> {
+
+    let request = MadRequest::smp_get(MadAddress::Lid(lid as u16), SMI_ATTR_ID_IB_ATTR_LINEARFORWTBL, block, timeout);
+
+    let data = port.query(&request)?;
+
+    Ok(data[..LFT_BLOCK_SIZE].to_vec())
+}
+
+pub fn send_dr_lft_mad<T: MadTransport + ?Sized>(port: &T, path: &str, timeout: u32) -> Result<(SwitchInfo, LinearForwardingTable), IBSmpError> {
+    query_lft(port, MadAddress::DirectedRoute(DrPath::parse(path)?), timeout)
+}
+
+pub fn send_lid_lft_mad<T: MadTransport + ?Sized>(port: &T, lid: i32, timeout: u32) -> Result<(SwitchInfo, LinearForwardingTable), IBSmpError> {
+    query_lft(port, MadAddress::Lid(lid as u16), timeout)
+}
+
+//SwitchInfo for LinearFDBTop, then every block up to it in one batch
+fn query_lft<T: MadTransport + ?Sized>(port: &T, address: MadAddress, timeout: u32) -> Result<(SwitchInfo, LinearForwardingTable), IBSmpError> {
+    let request = MadRequest::smp_get(address.clone(), SMI_ATTR_ID_IB_ATTR_SWITCH_INFO, 0, timeout);
+    let switch_info = SwitchInfo::from_mad_fields(&mut port.query(&request)?);
+
+    let requests: Vec<MadRequest> = (0..switch_info.lft_blocks())
+        .map(|block| MadRequest::smp_get(address.clone(), SMI_ATTR_ID_IB_ATTR_LINEARFORWTBL, block, timeout))
+        .collect();
+
+    let blocks = port.query_batch(&requests).into_iter().collect::<Result<Vec<_>, _>>()?;
+    let lft = LinearForwardingTable::from_blocks(&blocks, switch_info.linear_fdb_top);
+
+    Ok((switch_info, lft))
+}
+
+pub fn node_desc_from_mad_fields(data: &[u8]) -> String {
     let null_terminator_index = data
         .iter()
         .position(|&byte| byte == 0)
         .unwrap_or(data.len());
 
-    let node_desc_c_str = unsafe { CStr::from_bytes_with_nul_unchecked(&data[..null_terminator_index + 1]) };
-
-    Ok(
-        node_desc_c_str.to_string_lossy().to_string()
-    )
+    String::from_utf8_lossy(&data[..null_terminator_index]).to_string()
 }
 
-pub fn perfquery(port:&IBMadPort, lid: i32, portnum: i32, pkey: u32, timeout: u32) -> Result<ibmad::perf::ExtPerfCounters, IBSmpError>{
-    let portid = Box::new(ib_portid_t{
-        lid: lid,
-        drpath: unsafe { MaybeUninit::<ib_dr_path_t>::zeroed().assume_init() },
-        grh_present: 0,
-        gid: unsafe { MaybeUninit::<[u8; 16]>::zeroed().assume_init() },
-        qp: 0,
-        qkey: 0,
-        sl: 0,
-        pkey_idx: pkey,
-    });
-    let mut data: [u8; 1024 as usize] = [0; 1024];
-    let portid_ptr = Box::into_raw(portid);
-    let data_ptr = data.as_mut_ptr();
-    let r: *mut u8 = unsafe { 
-        pma_query_via(data_ptr as *mut c_void, portid_ptr, portnum, timeout, GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT, port.port)
-    };
-    let _portid = unsafe { Box::from_raw(portid_ptr) };
-    if r.is_null() {
-        return Err(IBSmpError::SendMADError);
-    }
+pub fn perfquery<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, pkey: u32, timeout: u32) -> Result<ibmad::perf::ExtPerfCounters, IBSmpError>{
+    let request = MadRequest::pma_get(lid as u16, GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT, portnum, timeout)
+        .with_pkey_index(pkey);
+
+    let mut data = port.query(&request)?;
+
     let perf_counter = ibmad::perf::ExtPerfCounters::from_mad_fields(&mut data);
     Ok(perf_counter)
 }
 
-//smp_set_via
-pub fn set_node_desc(port:&IBMadPort, lid: i32, timeout: u32) {
-    let portid = Box::new(ib_portid_t{
-        lid: lid,
-        drpath: unsafe { MaybeUninit::<ib_dr_path_t>::zeroed().assume_init() },
-        grh_present: 0,
-        gid: unsafe { MaybeUninit::<[u8; 16]>::zeroed().assume_init() },
-        qp: 0,
-        qkey: 0,
-        sl: 0,
-        pkey_idx: 0,
-    });
-
-    let device_name_c_str = CString::new("switch-spine").unwrap();
-    let portid_ptr: *mut portid = Box::into_raw(portid);
-
-    //unsafe { smp_mkey_set(port.port, 0x0) };
-
-    let r: *mut u8 = unsafe { 
-        smp_set_via(device_name_c_str.as_bytes_with_nul().as_ptr() as *mut c_void, portid_ptr, SMI_ATTR_ID_IB_ATTR_NODE_DESC, 0, timeout , port.port)
-    };
-
-    let _portid = unsafe { Box::from_raw(portid_ptr) };
-    if r.is_null() {
-        println!("ERROR");
+pub fn pma_class_port_info<T: MadTransport + ?Sized>(port: &T, lid: i32, timeout: u32) -> Result<ibmad::perf::PmaClassPortInfo, IBSmpError> {
+    let request = MadRequest::new(MadAddress::Lid(lid as u16), MAD_CLASSES_IB_PERFORMANCE_CLASS, MAD_METHODS_IB_MAD_METHOD_GET, MadAttrId::ClassPortInfo as u32, 0, timeout);
+
+    let mut data = port.query(&request)?;
+
+    Ok(ibmad::perf::PmaClassPortInfo::from_mad_fields(&mut data))
+}
+
+//The 32 bit PortCounters, for PMAs without PortCountersExtended
+pub fn perfquery_legacy<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, pkey: u32, timeout: u32) -> Result<ibmad::perf::PortCounters, IBSmpError> {
+    let request = MadRequest::pma_get(lid as u16, GSI_ATTR_ID_IB_GSI_PORT_COUNTERS, portnum, timeout)
+        .with_pkey_index(pkey);
+
+    let mut data = port.query(&request)?;
+
+    Ok(ibmad::perf::PortCounters::from_mad_fields(&mut data))
+}
+
+//Read whichever counter attributes the PMA at lid supports, by its
+//ClassPortInfo, and merge them into one view. Counters the PMA doesn't
+//have read as 0.
+pub fn perfquery_auto<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, pkey: u32, timeout: u32) -> Result<ibmad::perf::ExtPerfCounters, IBSmpError> {
+    let cpi = pma_class_port_info(port, lid, timeout)?;
+    perfquery_with_cpi(port, lid, portnum, &cpi, pkey, timeout)
+}
+
+//As perfquery_auto, with the ClassPortInfo already at hand so a sweep of a
+//node's ports reads it once
+pub fn perfquery_with_cpi<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, cpi: &ibmad::perf::PmaClassPortInfo, pkey: u32, timeout: u32) -> Result<ibmad::perf::ExtPerfCounters, IBSmpError> {
+    if cpi.legacy_only() {
+        let mut counters = perfquery_legacy(port, lid, portnum, pkey, timeout)?.to_ext();
+        if !cpi.xmit_wait() {
+            counters.xmit_waits = 0;
+        }
+        return Ok(counters);
+    }
+
+    let mut counters = perfquery(port, lid, portnum, pkey, timeout)?;
+    if !cpi.extended_width() {
+        counters.clear_cast_counters();
     }
+
+    //Older PMAs leave the error counters of PortCountersExtended empty
+    if !cpi.additional_ext_counters() {
+        let legacy = perfquery_legacy(port, lid, portnum, pkey, timeout)?.to_ext();
+        counters.copy_errors(&legacy);
+    }
+
+    Ok(counters)
+}
+
+//Clear the selected PortCounters of portnum, 0xFF for all ports if the PMA
+//supports AllPortSelect. Returns the counters as the PMA left them.
+pub fn reset_port_counters<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, select: ibmad::perf::CounterSelect, pkey: u32, timeout: u32) -> Result<ibmad::perf::PortCounters, IBSmpError> {
+    let (counter_select, counter_select2) = select.port_counters_mask();
+    let counters = ibmad::perf::PortCounters { port_select: portnum as u8, counter_select, counter_select2, ..Default::default() };
+
+    let mut data = [0u8; IB_PC_DATA_SZ as usize];
+    counters.to_mad_fields(&mut data);
+    let request = MadRequest::pma_set(lid as u16, GSI_ATTR_ID_IB_GSI_PORT_COUNTERS, &data, timeout)
+        .with_pkey_index(pkey);
+
+    let mut data = port.query(&request)?;
+
+    Ok(ibmad::perf::PortCounters::from_mad_fields(&mut data))
 }
 
+//As reset_port_counters, for PortCountersExtended. The error counters are
+//only cleared by PMAs with the additional extended counters.
+pub fn reset_ext_port_counters<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, select: ibmad::perf::CounterSelect, pkey: u32, timeout: u32) -> Result<ibmad::perf::ExtPerfCounters, IBSmpError> {
+    let (counter_select, counter_select2) = select.ext_mask();
+    ext_counters_set(port, lid, portnum, counter_select, counter_select2, pkey, timeout)
+}
+
+fn ext_counters_set<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, counter_select: u16, counter_select2: u32, pkey: u32, timeout: u32) -> Result<ibmad::perf::ExtPerfCounters, IBSmpError> {
+    let offset = ibmad::perf::PC_EXT_COUNTER_SELECT2_OFFSET;
+    let mut data = [0u8; IB_PC_DATA_SZ as usize];
+    unsafe {
+        mad_set_field(data.as_mut_ptr() as *mut c_void, 0, MAD_FIELDS_IB_PC_EXT_PORT_SELECT_F, portnum as u32);
+        mad_set_field(data.as_mut_ptr() as *mut c_void, 0, MAD_FIELDS_IB_PC_EXT_COUNTER_SELECT_F, counter_select as u32);
+    }
+    data[offset..offset + 4].copy_from_slice(&counter_select2.to_be_bytes());
+
+    let request = MadRequest::pma_set(lid as u16, GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT, &data, timeout)
+        .with_pkey_index(pkey);
+
+    let mut data = port.query(&request)?;
+
+    Ok(ibmad::perf::ExtPerfCounters::from_mad_fields(&mut data))
+}
+
+//Clear the selected counters of portnum in every counter attribute the PMA
+//at lid supports, by its ClassPortInfo
+pub fn reset_counters<T: MadTransport + ?Sized>(port: &T, lid: i32, portnum: i32, select: ibmad::perf::CounterSelect, pkey: u32, timeout: u32) -> Result<(), IBSmpError> {
+    let cpi = pma_class_port_info(port, lid, timeout)?;
+
+    //PortCounters is mandatory and holds the only error counters of older PMAs
+    reset_port_counters(port, lid, portnum, select, pkey, timeout)?;
+
+    if !cpi.extended_width() && !cpi.extended_width_noietf() {
+        return Ok(());
+    }
+
+    let (counter_select, mut counter_select2) = select.ext_mask();
+    if !cpi.additional_ext_counters() {
+        counter_select2 = 0;
+    }
+    if counter_select != 0 || counter_select2 != 0 {
+        ext_counters_set(port, lid, portnum, counter_select, counter_select2, pkey, timeout)?;
+    }
+
+    Ok(())
+}
+
+//smp_set_via
+pub fn set_node_desc<T: MadTransport + ?Sized>(port: &T, lid: i32, node_desc: &str, timeout: u32) -> Result<(), IBSmpError> {
+    //NodeDescription is 64 bytes, zero padded, longer ones are cut short
+    let mut data = [0u8; 64];
+    let len = node_desc.len().min(data.len());
+    data[..len].copy_from_slice(&node_desc.as_bytes()[..len]);
+
+    let request = MadRequest::smp_set(
+        MadAddress::Lid(lid as u16),
+        SMI_ATTR_ID_IB_ATTR_NODE_DESC,
+        0,
+        &data,
+        timeout,
+    );
+
+    port.query(&request)?;
+
+    Ok(())
+}
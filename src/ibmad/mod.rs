@@ -2,5 +2,13 @@ pub mod sys;
 pub mod lib;
 pub mod enums;
 pub mod perf;
+pub mod transport;
+pub mod port;
+pub mod switch;
+pub mod sa;
+pub mod dispatch;
+pub mod notice;
+#[cfg(feature = "async")]
+pub mod async_port;
 
 pub use lib::*;
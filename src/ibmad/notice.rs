@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use crate::ibmad::{self};
+use crate::umad::{agent::UmadAgent, mad::ib_user_mad, UmadError, UmadPort};
+
+use ibmad::dispatch::MadChannel;
+use ibmad::enums::MadAttrId;
+use ibmad::sa::Gid;
+use ibmad::sys::*;
+use ibmad::transport::{class_data_offset, class_version};
+
+//Generic traps, by trap number
+pub const TRAP_GID_IN_SERVICE: u16 = 64;
+pub const TRAP_GID_OUT_OF_SERVICE: u16 = 65;
+pub const TRAP_LINK_STATE_CHANGE: u16 = 128;
+pub const TRAP_LINK_INTEGRITY: u16 = 129;
+pub const TRAP_BUFFER_OVERRUN: u16 = 130;
+pub const TRAP_FLOW_CONTROL_WATCHDOG: u16 = 131;
+pub const TRAP_CAPABILITY_CHANGE: u16 = 144;
+pub const TRAP_SYSTEM_IMAGE_GUID_CHANGE: u16 = 145;
+
+//Notice types
+pub const NOTICE_TYPE_FATAL: u8 = 0;
+pub const NOTICE_TYPE_URGENT: u8 = 1;
+pub const NOTICE_TYPE_SECURITY: u8 = 2;
+pub const NOTICE_TYPE_SM: u8 = 3;
+pub const NOTICE_TYPE_INFO: u8 = 4;
+
+//Producer types of generic notices
+pub const NOTICE_PRODUCER_CA: u32 = 1;
+pub const NOTICE_PRODUCER_SWITCH: u32 = 2;
+pub const NOTICE_PRODUCER_ROUTER: u32 = 3;
+pub const NOTICE_PRODUCER_SM: u32 = 4;
+
+pub const NOTICE_SIZE: usize = 80;
+
+//Trap 144 ChangeFlags
+pub const CHANGE_NODE_DESCRIPTION: u16 = 1 << 0;
+pub const CHANGE_LINK_WIDTH_ENABLED: u16 = 1 << 1;
+pub const CHANGE_LINK_SPEED_ENABLED: u16 = 1 << 2;
+pub const CHANGE_SM_PRIORITY: u16 = 1 << 3;
+pub const CHANGE_HIERARCHY_INFO: u16 = 1 << 4;
+pub const CHANGE_CAPABILITY_MASK2: u16 = 1 << 5;
+
+//The Notice attribute. For a vendor notice producer_type holds the vendor
+//id and trap_number the device id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notice {
+    pub is_generic: bool,
+    pub notice_type: u8,
+    pub producer_type: u32,
+    pub trap_number: u16,
+    pub toggle: bool,
+    pub count: u16,
+    pub issuer_lid: u16,
+    pub data_details: [u8; 54],
+    pub issuer_gid: Gid,
+}
+
+impl Default for Notice {
+    fn default() -> Self {
+        Notice {
+            is_generic: true,
+            notice_type: NOTICE_TYPE_INFO,
+            producer_type: 0,
+            trap_number: 0,
+            toggle: false,
+            count: 0,
+            issuer_lid: 0,
+            data_details: [0; 54],
+            issuer_gid: [0; 16],
+        }
+    }
+}
+
+//What a generic Notice is about, from its trap number and DataDetails
+#[derive(Debug, Clone, PartialEq)]
+pub enum NoticeEvent {
+    GidInService { gid: Gid },
+    GidOutOfService { gid: Gid },
+    //Some port of the switch at lid changed state, which one isn't reported
+    LinkStateChange { lid: u16 },
+    LinkIntegrity { lid: u16, port: u8 },
+    BufferOverrun { lid: u16, port: u8 },
+    FlowControlWatchdog { lid: u16, port: u8 },
+    CapabilityChange { lid: u16, capability_mask: u32, capability_mask2: u16, change_flags: u16, other_local_changes: bool },
+    SystemImageGuidChange { lid: u16, system_image_guid: u64 },
+    Other(Notice),
+}
+
+fn get16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn get32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+impl Notice {
+    pub fn decode(data: &[u8]) -> Notice {
+        Notice {
+            is_generic: data[0] & 0x80 != 0,
+            notice_type: data[0] & 0x7f,
+            producer_type: get32(data, 0) & 0xffffff,
+            trap_number: get16(data, 4),
+            issuer_lid: get16(data, 6),
+            toggle: data[8] & 0x80 != 0,
+            count: get16(data, 8) & 0x7fff,
+            data_details: data[10..64].try_into().unwrap(),
+            issuer_gid: data[64..80].try_into().unwrap(),
+        }
+    }
+
+    pub fn encode(&self, data: &mut [u8]) {
+        let first = (self.is_generic as u32) << 31 | ((self.notice_type & 0x7f) as u32) << 24 | (self.producer_type & 0xffffff);
+        data[0..4].copy_from_slice(&first.to_be_bytes());
+        data[4..6].copy_from_slice(&self.trap_number.to_be_bytes());
+        data[6..8].copy_from_slice(&self.issuer_lid.to_be_bytes());
+        data[8..10].copy_from_slice(&((self.toggle as u16) << 15 | (self.count & 0x7fff)).to_be_bytes());
+        data[10..64].copy_from_slice(&self.data_details);
+        data[64..80].copy_from_slice(&self.issuer_gid);
+    }
+
+    pub fn event(&self) -> NoticeEvent {
+        let d = &self.data_details;
+        if !self.is_generic {
+            return NoticeEvent::Other(self.clone());
+        }
+
+        match self.trap_number {
+            TRAP_GID_IN_SERVICE => NoticeEvent::GidInService { gid: d[6..22].try_into().unwrap() },
+            TRAP_GID_OUT_OF_SERVICE => NoticeEvent::GidOutOfService { gid: d[6..22].try_into().unwrap() },
+            TRAP_LINK_STATE_CHANGE => NoticeEvent::LinkStateChange { lid: get16(d, 0) },
+            TRAP_LINK_INTEGRITY => NoticeEvent::LinkIntegrity { lid: get16(d, 2), port: d[4] },
+            TRAP_BUFFER_OVERRUN => NoticeEvent::BufferOverrun { lid: get16(d, 2), port: d[4] },
+            TRAP_FLOW_CONTROL_WATCHDOG => NoticeEvent::FlowControlWatchdog { lid: get16(d, 2), port: d[4] },
+            TRAP_CAPABILITY_CHANGE => NoticeEvent::CapabilityChange {
+                lid: get16(d, 2),
+                other_local_changes: d[5] & 1 != 0,
+                capability_mask: get32(d, 6),
+                change_flags: get16(d, 10),
+                capability_mask2: get16(d, 12),
+            },
+            TRAP_SYSTEM_IMAGE_GUID_CHANGE => NoticeEvent::SystemImageGuidChange {
+                lid: get16(d, 2),
+                system_image_guid: u64::from_be_bytes(d[6..14].try_into().unwrap()),
+            },
+            _ => NoticeEvent::Other(self.clone()),
+        }
+    }
+}
+
+//Receives the Reports the SA sends for subscribed traps, see
+//SaClient::subscribe, and acknowledges each with a ReportResponse.
+pub struct NoticeReceiver {
+    channel: Arc<dyn MadChannel>,
+}
+
+impl NoticeReceiver {
+    pub fn new(channel: Arc<dyn MadChannel>) -> NoticeReceiver {
+        NoticeReceiver { channel }
+    }
+
+    //Register for SA Reports on port
+    pub fn open(port: &UmadPort) -> Result<NoticeReceiver, UmadError> {
+        let class = MAD_CLASSES_IB_SA_CLASS;
+        let agent = UmadAgent::register(port, class as u8, class_version(class), &[MAD_METHODS_IB_MAD_METHOD_REPORT as u8])?;
+        Ok(NoticeReceiver::new(Arc::new(agent)))
+    }
+
+    //Wait up to timeout ms for the next Notice, a negative timeout waits
+    //forever
+    pub fn recv(&self, timeout: i32) -> Result<Notice, UmadError> {
+        loop {
+            let umad = self.channel.recv(timeout)?;
+            let mad = &umad.data;
+            let attr_id = u16::from_be_bytes([mad[16], mad[17]]) as u32;
+            if umad.status != 0 || mad[3] as u32 != MAD_METHODS_IB_MAD_METHOD_REPORT || attr_id != MadAttrId::Notice as u32 {
+                continue;
+            }
+
+            //The SA resends a Report until it hears back
+            let mut response = ib_user_mad::new();
+            response.addr = umad.addr;
+            response.data[..IB_MAD_SIZE as usize].copy_from_slice(&mad[..IB_MAD_SIZE as usize]);
+            response.data[3] = MAD_METHODS_IB_MAD_METHOD_REPORT_RESPONSE as u8;
+            self.channel.send(&response, 0)?;
+
+            let offset = class_data_offset(MAD_CLASSES_IB_SA_CLASS);
+            return Ok(Notice::decode(&mad[offset..offset + NOTICE_SIZE]));
+        }
+    }
+
+    //Events as they arrive, until receiving fails
+    pub fn events(&self) -> impl Iterator<Item = NoticeEvent> + '_ {
+        std::iter::from_fn(|| self.recv(-1).ok().map(|notice| notice.event()))
+    }
+}
@@ -1,7 +1,70 @@
 use std::ops::Sub;
 use super::enums::MadFields;
 use crate::ibmad;
-use std::{collections::HashMap, ffi::c_void};
+use crate::ibmad::sys::*;
+use std::ffi::c_void;
+
+use serde::{Deserialize, Serialize};
+
+//libibmad has no field for the CounterSelect2 of PortCountersExtended
+pub const PC_EXT_COUNTER_SELECT2_OFFSET: usize = 4;
+
+//PortCounters CounterSelect, by bit
+pub const PC_COUNTER_SELECT: [&str; 16] = [
+    "symbol_errors",
+    "link_recovers",
+    "link_downed",
+    "rcv_errors",
+    "phys_rcv_errors",
+    "switch_rel_errors",
+    "xmt_discards",
+    "xmt_constraint_errors",
+    "rcv_constraint_errors",
+    "local_link_integrity_errors",
+    "excess_overrun_errors",
+    "vl15dropped",
+    "xmt_bytes",
+    "rcv_bytes",
+    "xmt_pkts",
+    "rcv_pkts",
+];
+
+//PortCounters CounterSelect2, by bit
+pub const PC_COUNTER_SELECT2: [&str; 1] = ["xmit_waits"];
+
+//PortCountersExtended CounterSelect, by bit
+pub const PC_EXT_COUNTER_SELECT: [&str; 8] = [
+    "xmt_bytes",
+    "rcv_bytes",
+    "xmt_pkts",
+    "rcv_pkts",
+    "xmt_upkts",
+    "rcv_upkts",
+    "xmt_mpkts",
+    "rcv_mpkts",
+];
+
+//PortCountersExtended CounterSelect2, by bit
+pub const PC_EXT_COUNTER_SELECT2: [&str; 14] = [
+    "symbol_errors",
+    "link_recovers",
+    "link_downed",
+    "rcv_errors",
+    "phys_rcv_errors",
+    "switch_rel_errors",
+    "xmt_discards",
+    "xmt_constraint_errors",
+    "rcv_constraint_errors",
+    "local_link_integrity_errors",
+    "excess_overrun_errors",
+    "vl15dropped",
+    "xmit_waits",
+    "qp1_drops",
+];
+
+//PMA ClassPortInfo CapabilityMask2, the error counters of
+//PortCountersExtended are only filled in with this set
+pub const PMA_CAP2_ADDL_PORT_COUNTERS_EXT: u32 = 1 << 1;
 
 const PERF_COUNTERS_FIELDS: [(i32, &str); 28] = [
     (MadFields::IBPcExtXmtBytes_F as i32, "xmt_bytes"),
@@ -34,9 +97,36 @@ const PERF_COUNTERS_FIELDS: [(i32, &str); 28] = [
     (MadFields::IBPcExtQP1Drop_F as i32, "qp1_drops"),
 ];
 
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ExtPerfCounters {
-    pub counters: HashMap<String, u64>,
+    pub xmt_bytes: u64,
+    pub rcv_bytes: u64,
+    pub xmt_pkts: u64,
+    pub rcv_pkts: u64,
+    pub xmt_upkts: u64,
+    pub rcv_upkts: u64,
+    pub xmt_mpkts: u64,
+    pub rcv_mpkts: u64,
+    pub symbol_errors: u64,
+    pub link_recovers: u64,
+    pub link_downed: u64,
+    pub rcv_errors: u64,
+    pub phys_rcv_errors: u64,
+    pub switch_rel_errors: u64,
+    pub xmt_discards: u64,
+    pub xmt_discard_last: u64,
+    pub rcv_local_phy_errors: u64,
+    pub rcv_malformed_pkt_errors: u64,
+    pub rcv_buffer_overrun_errors: u64,
+    pub rcv_dlid_map_errors: u64,
+    pub rcv_vl_map_errors: u64,
+    pub rcv_looping_errors: u64,
+    pub xmt_constraint_errors: u64,
+    pub rcv_constraint_errors: u64,
+    pub excess_overrun_errors: u64,
+    pub vl15dropped: u64,
+    pub xmit_waits: u64,
+    pub qp1_drops: u64,
 }
 
 impl ExtPerfCounters {
@@ -55,61 +145,409 @@ impl ExtPerfCounters {
                 );
             }
 
-            let result = u64::from_le_bytes(val);
-            ext_perf.counters.insert(field.1.to_string(), result);
+            if let Some(counter) = ext_perf.get_mut(field.1) {
+                *counter = u64::from_le_bytes(val);
+            }
         }
 
         ext_perf
     }
 
-    pub fn delta(&self, other: &ExtPerfCounters) -> HashMap<String, u64> {
-        let mut delta_map = HashMap::new();
+    //Only the IBPcExt* fields belong to PortCountersExtended, the discard and
+    //receive error details live in their own attributes.
+    pub fn to_mad_fields(&self, data: &mut [u8]) {
+        let ext_fields = PERF_COUNTERS_FIELDS.iter().filter(|field| {
+            (MadFields::IBPcExtXmtBytes_F as i32..=MadFields::IBPcExtRcvMPkts_F as i32).contains(&field.0)
+                || (MadFields::IBPcExtErrSym_F as i32..=MadFields::IBPcExtQP1Drop_F as i32).contains(&field.0)
+        });
 
-        for (name, &value) in &self.counters {
-            let delta = if let Some(&other_value) = other.counters.get(name) {
-                value.saturating_sub(other_value) // Use saturating_sub to prevent underflow
-            } else {
-                value // If the counter isn't in 'other', the delta is the full value
-            };
+        for field in ext_fields {
+            let mut val = self.get(field.1).unwrap_or(0).to_le_bytes();
+            let val_ptr = val.as_mut_ptr();
 
-            // Include only non-zero deltas
-            if delta != 0 {
-                delta_map.insert(name.clone(), delta);
+            unsafe {
+                ibmad::sys::mad_encode_field(
+                    data.as_mut_ptr(),
+                    field.0.try_into().unwrap(),
+                    val_ptr as *mut c_void,
+                );
             }
         }
+    }
+
+    //Every counter with its name, in attribute order
+    pub fn iter(&self) -> std::array::IntoIter<(&'static str, u64), 28> {
+        [
+            ("xmt_bytes", self.xmt_bytes),
+            ("rcv_bytes", self.rcv_bytes),
+            ("xmt_pkts", self.xmt_pkts),
+            ("rcv_pkts", self.rcv_pkts),
+            ("xmt_upkts", self.xmt_upkts),
+            ("rcv_upkts", self.rcv_upkts),
+            ("xmt_mpkts", self.xmt_mpkts),
+            ("rcv_mpkts", self.rcv_mpkts),
+            ("symbol_errors", self.symbol_errors),
+            ("link_recovers", self.link_recovers),
+            ("link_downed", self.link_downed),
+            ("rcv_errors", self.rcv_errors),
+            ("phys_rcv_errors", self.phys_rcv_errors),
+            ("switch_rel_errors", self.switch_rel_errors),
+            ("xmt_discards", self.xmt_discards),
+            ("xmt_discard_last", self.xmt_discard_last),
+            ("rcv_local_phy_errors", self.rcv_local_phy_errors),
+            ("rcv_malformed_pkt_errors", self.rcv_malformed_pkt_errors),
+            ("rcv_buffer_overrun_errors", self.rcv_buffer_overrun_errors),
+            ("rcv_dlid_map_errors", self.rcv_dlid_map_errors),
+            ("rcv_vl_map_errors", self.rcv_vl_map_errors),
+            ("rcv_looping_errors", self.rcv_looping_errors),
+            ("xmt_constraint_errors", self.xmt_constraint_errors),
+            ("rcv_constraint_errors", self.rcv_constraint_errors),
+            ("excess_overrun_errors", self.excess_overrun_errors),
+            ("vl15dropped", self.vl15dropped),
+            ("xmit_waits", self.xmit_waits),
+            ("qp1_drops", self.qp1_drops),
+        ]
+        .into_iter()
+    }
 
-        delta_map // Return the HashMap with deltas
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.iter().find(|(n, _)| *n == name).map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut u64> {
+        let counter = match name {
+            "xmt_bytes" => &mut self.xmt_bytes,
+            "rcv_bytes" => &mut self.rcv_bytes,
+            "xmt_pkts" => &mut self.xmt_pkts,
+            "rcv_pkts" => &mut self.rcv_pkts,
+            "xmt_upkts" => &mut self.xmt_upkts,
+            "rcv_upkts" => &mut self.rcv_upkts,
+            "xmt_mpkts" => &mut self.xmt_mpkts,
+            "rcv_mpkts" => &mut self.rcv_mpkts,
+            "symbol_errors" => &mut self.symbol_errors,
+            "link_recovers" => &mut self.link_recovers,
+            "link_downed" => &mut self.link_downed,
+            "rcv_errors" => &mut self.rcv_errors,
+            "phys_rcv_errors" => &mut self.phys_rcv_errors,
+            "switch_rel_errors" => &mut self.switch_rel_errors,
+            "xmt_discards" => &mut self.xmt_discards,
+            "xmt_discard_last" => &mut self.xmt_discard_last,
+            "rcv_local_phy_errors" => &mut self.rcv_local_phy_errors,
+            "rcv_malformed_pkt_errors" => &mut self.rcv_malformed_pkt_errors,
+            "rcv_buffer_overrun_errors" => &mut self.rcv_buffer_overrun_errors,
+            "rcv_dlid_map_errors" => &mut self.rcv_dlid_map_errors,
+            "rcv_vl_map_errors" => &mut self.rcv_vl_map_errors,
+            "rcv_looping_errors" => &mut self.rcv_looping_errors,
+            "xmt_constraint_errors" => &mut self.xmt_constraint_errors,
+            "rcv_constraint_errors" => &mut self.rcv_constraint_errors,
+            "excess_overrun_errors" => &mut self.excess_overrun_errors,
+            "vl15dropped" => &mut self.vl15dropped,
+            "xmit_waits" => &mut self.xmit_waits,
+            "qp1_drops" => &mut self.qp1_drops,
+            _ => return None,
+        };
+        Some(counter)
+    }
+
+    //Counters that went up since other, a counter that went down (was reset)
+    //reads 0
+    pub fn delta(&self, other: &ExtPerfCounters) -> ExtPerfCounters {
+        *self - *other
+    }
+
+    //Take the error counters, VL15Dropped and XmitWait of other
+    pub fn copy_errors(&mut self, other: &ExtPerfCounters) {
+        self.symbol_errors = other.symbol_errors;
+        self.link_recovers = other.link_recovers;
+        self.link_downed = other.link_downed;
+        self.rcv_errors = other.rcv_errors;
+        self.phys_rcv_errors = other.phys_rcv_errors;
+        self.switch_rel_errors = other.switch_rel_errors;
+        self.xmt_discards = other.xmt_discards;
+        self.xmt_constraint_errors = other.xmt_constraint_errors;
+        self.rcv_constraint_errors = other.rcv_constraint_errors;
+        self.excess_overrun_errors = other.excess_overrun_errors;
+        self.vl15dropped = other.vl15dropped;
+        self.xmit_waits = other.xmit_waits;
+        self.qp1_drops = other.qp1_drops;
+    }
+
+    //Zero the unicast and multicast packet counters
+    pub fn clear_cast_counters(&mut self) {
+        self.xmt_upkts = 0;
+        self.rcv_upkts = 0;
+        self.xmt_mpkts = 0;
+        self.rcv_mpkts = 0;
     }
 
     #[allow(dead_code)]
     fn display(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for (name, value) in self.delta(&ExtPerfCounters::default()) {
+        for (name, value) in self.iter().filter(|(_, value)| *value != 0) {
             writeln!(f, "{}: {}", name, value)?;
         }
         Ok(())
     }
 }
 
+//Saturating, so a counter reset between two samples gives 0 rather than
+//wrapping
 impl Sub for ExtPerfCounters {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self::Output {
-        let mut output = ExtPerfCounters {
-            counters: HashMap::new(),
-        };
+        ExtPerfCounters {
+            xmt_bytes: self.xmt_bytes.saturating_sub(other.xmt_bytes),
+            rcv_bytes: self.rcv_bytes.saturating_sub(other.rcv_bytes),
+            xmt_pkts: self.xmt_pkts.saturating_sub(other.xmt_pkts),
+            rcv_pkts: self.rcv_pkts.saturating_sub(other.rcv_pkts),
+            xmt_upkts: self.xmt_upkts.saturating_sub(other.xmt_upkts),
+            rcv_upkts: self.rcv_upkts.saturating_sub(other.rcv_upkts),
+            xmt_mpkts: self.xmt_mpkts.saturating_sub(other.xmt_mpkts),
+            rcv_mpkts: self.rcv_mpkts.saturating_sub(other.rcv_mpkts),
+            symbol_errors: self.symbol_errors.saturating_sub(other.symbol_errors),
+            link_recovers: self.link_recovers.saturating_sub(other.link_recovers),
+            link_downed: self.link_downed.saturating_sub(other.link_downed),
+            rcv_errors: self.rcv_errors.saturating_sub(other.rcv_errors),
+            phys_rcv_errors: self.phys_rcv_errors.saturating_sub(other.phys_rcv_errors),
+            switch_rel_errors: self.switch_rel_errors.saturating_sub(other.switch_rel_errors),
+            xmt_discards: self.xmt_discards.saturating_sub(other.xmt_discards),
+            xmt_discard_last: self.xmt_discard_last.saturating_sub(other.xmt_discard_last),
+            rcv_local_phy_errors: self.rcv_local_phy_errors.saturating_sub(other.rcv_local_phy_errors),
+            rcv_malformed_pkt_errors: self.rcv_malformed_pkt_errors.saturating_sub(other.rcv_malformed_pkt_errors),
+            rcv_buffer_overrun_errors: self.rcv_buffer_overrun_errors.saturating_sub(other.rcv_buffer_overrun_errors),
+            rcv_dlid_map_errors: self.rcv_dlid_map_errors.saturating_sub(other.rcv_dlid_map_errors),
+            rcv_vl_map_errors: self.rcv_vl_map_errors.saturating_sub(other.rcv_vl_map_errors),
+            rcv_looping_errors: self.rcv_looping_errors.saturating_sub(other.rcv_looping_errors),
+            xmt_constraint_errors: self.xmt_constraint_errors.saturating_sub(other.xmt_constraint_errors),
+            rcv_constraint_errors: self.rcv_constraint_errors.saturating_sub(other.rcv_constraint_errors),
+            excess_overrun_errors: self.excess_overrun_errors.saturating_sub(other.excess_overrun_errors),
+            vl15dropped: self.vl15dropped.saturating_sub(other.vl15dropped),
+            xmit_waits: self.xmit_waits.saturating_sub(other.xmit_waits),
+            qp1_drops: self.qp1_drops.saturating_sub(other.qp1_drops),
+        }
+    }
+}
+
+fn get_field(data: &mut [u8], field: MAD_FIELDS) -> u32 {
+    unsafe { mad_get_field(data.as_mut_ptr() as *mut c_void, 0, field) }
+}
+
+fn set_field(data: &mut [u8], field: MAD_FIELDS, value: u32) {
+    unsafe { mad_set_field(data.as_mut_ptr() as *mut c_void, 0, field, value) }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PmaClassPortInfo {
+    pub base_version: u8,
+    pub class_version: u8,
+    pub capability_mask: u16,
+    pub capability_mask2: u32,
+    pub resp_time_value: u8,
+}
 
-        // Iterate over self.counters, checking for corresponding values in other.counters
-        for (name, &value) in &self.counters {
-            let delta = if let Some(&other_value) = other.counters.get(name) {
-                value.saturating_sub(other_value)
-            } else {
-                value 
-            };
+impl PmaClassPortInfo {
+    pub fn from_mad_fields(data: &mut [u8]) -> Self {
+        PmaClassPortInfo {
+            base_version: get_field(data, MAD_FIELDS_IB_CPI_BASEVER_F) as u8,
+            class_version: get_field(data, MAD_FIELDS_IB_CPI_CLASSVER_F) as u8,
+            capability_mask: get_field(data, MAD_FIELDS_IB_CPI_CAPMASK_F) as u16,
+            capability_mask2: get_field(data, MAD_FIELDS_IB_CPI_CAPMASK2_F),
+            resp_time_value: get_field(data, MAD_FIELDS_IB_CPI_RESP_TIME_VALUE_F) as u8,
+        }
+    }
+
+    pub fn to_mad_fields(&self, data: &mut [u8]) {
+        set_field(data, MAD_FIELDS_IB_CPI_BASEVER_F, self.base_version as u32);
+        set_field(data, MAD_FIELDS_IB_CPI_CLASSVER_F, self.class_version as u32);
+        set_field(data, MAD_FIELDS_IB_CPI_CAPMASK_F, self.capability_mask as u32);
+        set_field(data, MAD_FIELDS_IB_CPI_CAPMASK2_F, self.capability_mask2);
+        set_field(data, MAD_FIELDS_IB_CPI_RESP_TIME_VALUE_F, self.resp_time_value as u32);
+    }
+
+    pub fn all_port_select(&self) -> bool {
+        self.capability_mask as u32 & IB_PM_ALL_PORT_SELECT != 0
+    }
+
+    //IsExtendedWidthSupported, PortCountersExtended in full
+    pub fn extended_width(&self) -> bool {
+        self.capability_mask as u32 & IB_PM_EXT_WIDTH_SUPPORTED != 0
+    }
+
+    //IsExtendedWidthSupportedNoIETF, PortCountersExtended without the
+    //unicast and multicast packet counters
+    pub fn extended_width_noietf(&self) -> bool {
+        self.capability_mask as u32 & IB_PM_EXT_WIDTH_NOIETF_SUP != 0
+    }
+
+    //No PortCountersExtended, only the 32 bit PortCounters that stop at
+    //their maximum
+    pub fn legacy_only(&self) -> bool {
+        !self.extended_width() && !self.extended_width_noietf()
+    }
+
+    pub fn xmit_wait(&self) -> bool {
+        self.capability_mask as u32 & IB_PM_PC_XMIT_WAIT_SUP != 0
+    }
+
+    pub fn additional_ext_counters(&self) -> bool {
+        self.capability_mask2 & PMA_CAP2_ADDL_PORT_COUNTERS_EXT != 0
+    }
+}
+
+//The 32 bit PortCounters attribute. The counters stop at all ones rather
+//than wrap.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PortCounters {
+    pub port_select: u8,
+    pub counter_select: u16,
+    pub counter_select2: u8,
+    pub symbol_errors: u16,
+    pub link_recovers: u8,
+    pub link_downed: u8,
+    pub rcv_errors: u16,
+    pub phys_rcv_errors: u16,
+    pub switch_rel_errors: u16,
+    pub xmt_discards: u16,
+    pub xmt_constraint_errors: u8,
+    pub rcv_constraint_errors: u8,
+    pub local_link_integrity_errors: u8,
+    pub excess_overrun_errors: u8,
+    pub vl15dropped: u16,
+    pub xmt_bytes: u32,
+    pub rcv_bytes: u32,
+    pub xmt_pkts: u32,
+    pub rcv_pkts: u32,
+    pub xmit_waits: u32,
+}
 
-            output.counters.insert(name.clone(), delta);
-            
+impl PortCounters {
+    pub fn from_mad_fields(data: &mut [u8]) -> Self {
+        PortCounters {
+            port_select: get_field(data, MAD_FIELDS_IB_PC_PORT_SELECT_F) as u8,
+            counter_select: get_field(data, MAD_FIELDS_IB_PC_COUNTER_SELECT_F) as u16,
+            counter_select2: get_field(data, MAD_FIELDS_IB_PC_COUNTER_SELECT2_F) as u8,
+            symbol_errors: get_field(data, MAD_FIELDS_IB_PC_ERR_SYM_F) as u16,
+            link_recovers: get_field(data, MAD_FIELDS_IB_PC_LINK_RECOVERS_F) as u8,
+            link_downed: get_field(data, MAD_FIELDS_IB_PC_LINK_DOWNED_F) as u8,
+            rcv_errors: get_field(data, MAD_FIELDS_IB_PC_ERR_RCV_F) as u16,
+            phys_rcv_errors: get_field(data, MAD_FIELDS_IB_PC_ERR_PHYSRCV_F) as u16,
+            switch_rel_errors: get_field(data, MAD_FIELDS_IB_PC_ERR_SWITCH_REL_F) as u16,
+            xmt_discards: get_field(data, MAD_FIELDS_IB_PC_XMT_DISCARDS_F) as u16,
+            xmt_constraint_errors: get_field(data, MAD_FIELDS_IB_PC_ERR_XMTCONSTR_F) as u8,
+            rcv_constraint_errors: get_field(data, MAD_FIELDS_IB_PC_ERR_RCVCONSTR_F) as u8,
+            local_link_integrity_errors: get_field(data, MAD_FIELDS_IB_PC_ERR_LOCALINTEG_F) as u8,
+            excess_overrun_errors: get_field(data, MAD_FIELDS_IB_PC_ERR_EXCESS_OVR_F) as u8,
+            vl15dropped: get_field(data, MAD_FIELDS_IB_PC_VL15_DROPPED_F) as u16,
+            xmt_bytes: get_field(data, MAD_FIELDS_IB_PC_XMT_BYTES_F),
+            rcv_bytes: get_field(data, MAD_FIELDS_IB_PC_RCV_BYTES_F),
+            xmt_pkts: get_field(data, MAD_FIELDS_IB_PC_XMT_PKTS_F),
+            rcv_pkts: get_field(data, MAD_FIELDS_IB_PC_RCV_PKTS_F),
+            xmit_waits: get_field(data, MAD_FIELDS_IB_PC_XMT_WAIT_F),
+        }
+    }
+
+    pub fn to_mad_fields(&self, data: &mut [u8]) {
+        set_field(data, MAD_FIELDS_IB_PC_PORT_SELECT_F, self.port_select as u32);
+        set_field(data, MAD_FIELDS_IB_PC_COUNTER_SELECT_F, self.counter_select as u32);
+        set_field(data, MAD_FIELDS_IB_PC_COUNTER_SELECT2_F, self.counter_select2 as u32);
+        set_field(data, MAD_FIELDS_IB_PC_ERR_SYM_F, self.symbol_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PC_LINK_RECOVERS_F, self.link_recovers as u32);
+        set_field(data, MAD_FIELDS_IB_PC_LINK_DOWNED_F, self.link_downed as u32);
+        set_field(data, MAD_FIELDS_IB_PC_ERR_RCV_F, self.rcv_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PC_ERR_PHYSRCV_F, self.phys_rcv_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PC_ERR_SWITCH_REL_F, self.switch_rel_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PC_XMT_DISCARDS_F, self.xmt_discards as u32);
+        set_field(data, MAD_FIELDS_IB_PC_ERR_XMTCONSTR_F, self.xmt_constraint_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PC_ERR_RCVCONSTR_F, self.rcv_constraint_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PC_ERR_LOCALINTEG_F, self.local_link_integrity_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PC_ERR_EXCESS_OVR_F, self.excess_overrun_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PC_VL15_DROPPED_F, self.vl15dropped as u32);
+        set_field(data, MAD_FIELDS_IB_PC_XMT_BYTES_F, self.xmt_bytes);
+        set_field(data, MAD_FIELDS_IB_PC_RCV_BYTES_F, self.rcv_bytes);
+        set_field(data, MAD_FIELDS_IB_PC_XMT_PKTS_F, self.xmt_pkts);
+        set_field(data, MAD_FIELDS_IB_PC_RCV_PKTS_F, self.rcv_pkts);
+        set_field(data, MAD_FIELDS_IB_PC_XMT_WAIT_F, self.xmit_waits);
+    }
+
+    //The extended counters as a PMA without them would count them, each
+    //stopped at the width of its field
+    pub fn saturating_from(ext: &ExtPerfCounters) -> Self {
+        PortCounters {
+            symbol_errors: ext.symbol_errors.min(u16::MAX as u64) as u16,
+            link_recovers: ext.link_recovers.min(u8::MAX as u64) as u8,
+            link_downed: ext.link_downed.min(u8::MAX as u64) as u8,
+            rcv_errors: ext.rcv_errors.min(u16::MAX as u64) as u16,
+            phys_rcv_errors: ext.phys_rcv_errors.min(u16::MAX as u64) as u16,
+            switch_rel_errors: ext.switch_rel_errors.min(u16::MAX as u64) as u16,
+            xmt_discards: ext.xmt_discards.min(u16::MAX as u64) as u16,
+            xmt_constraint_errors: ext.xmt_constraint_errors.min(u8::MAX as u64) as u8,
+            rcv_constraint_errors: ext.rcv_constraint_errors.min(u8::MAX as u64) as u8,
+            excess_overrun_errors: ext.excess_overrun_errors.min(0xf) as u8,
+            vl15dropped: ext.vl15dropped.min(u16::MAX as u64) as u16,
+            xmt_bytes: ext.xmt_bytes.min(u32::MAX as u64) as u32,
+            rcv_bytes: ext.rcv_bytes.min(u32::MAX as u64) as u32,
+            xmt_pkts: ext.xmt_pkts.min(u32::MAX as u64) as u32,
+            rcv_pkts: ext.rcv_pkts.min(u32::MAX as u64) as u32,
+            xmit_waits: ext.xmit_waits.min(u32::MAX as u64) as u32,
+            ..Default::default()
+        }
+    }
+
+    //Widened into the ExtPerfCounters view, with no unicast or multicast
+    //packet counts
+    pub fn to_ext(&self) -> ExtPerfCounters {
+        ExtPerfCounters {
+            xmt_bytes: self.xmt_bytes as u64,
+            rcv_bytes: self.rcv_bytes as u64,
+            xmt_pkts: self.xmt_pkts as u64,
+            rcv_pkts: self.rcv_pkts as u64,
+            symbol_errors: self.symbol_errors as u64,
+            link_recovers: self.link_recovers as u64,
+            link_downed: self.link_downed as u64,
+            rcv_errors: self.rcv_errors as u64,
+            phys_rcv_errors: self.phys_rcv_errors as u64,
+            switch_rel_errors: self.switch_rel_errors as u64,
+            xmt_discards: self.xmt_discards as u64,
+            xmt_constraint_errors: self.xmt_constraint_errors as u64,
+            rcv_constraint_errors: self.rcv_constraint_errors as u64,
+            excess_overrun_errors: self.excess_overrun_errors as u64,
+            vl15dropped: self.vl15dropped as u64,
+            xmit_waits: self.xmit_waits as u64,
+            ..Default::default()
+        }
+    }
+}
+
+//Which counters a Set of PortCounters or PortCountersExtended clears
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterSelect {
+    Errors,
+    Data,
+    All,
+}
+
+impl CounterSelect {
+    //CounterSelect and CounterSelect2 of PortCounters
+    pub fn port_counters_mask(&self) -> (u16, u8) {
+        match self {
+            CounterSelect::Errors => (0x0fff, 0x1),
+            CounterSelect::Data => (0xf000, 0),
+            CounterSelect::All => (0xffff, 0x1),
         }
+    }
 
-        output 
+    //CounterSelect and CounterSelect2 of PortCountersExtended
+    pub fn ext_mask(&self) -> (u16, u32) {
+        match self {
+            CounterSelect::Errors => (0, 0x3fff),
+            CounterSelect::Data => (0xff, 0),
+            CounterSelect::All => (0xff, 0x3fff),
+        }
     }
-}
\ No newline at end of file
+}
+
+//Names of the counters set in a CounterSelect mask, against one of the
+//PC_*COUNTER_SELECT* tables
+pub fn selected_counters(names: &[&'static str], mask: u32) -> Vec<&'static str> {
+    names.iter().enumerate().filter(|(bit, _)| mask & (1 << bit) != 0).map(|(_, name)| *name).collect()
+}
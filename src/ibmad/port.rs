@@ -0,0 +1,372 @@
+use std::{ffi::c_void, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ibmad::sys::*;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortInfo {
+    pub mkey: u64,
+    pub gid_prefix: u64,
+    pub lid: u16,
+    pub sm_lid: u16,
+    pub cap_mask: u32,
+    pub diag_code: u16,
+    pub mkey_lease_period: u16,
+    pub local_port: u8,
+    pub link_width_enabled: u8,
+    pub link_width_supported: u8,
+    pub link_width_active: u8,
+    pub link_speed_supported: u8,
+    pub state: u8,
+    pub phys_state: u8,
+    pub link_down_default_state: u8,
+    pub mkey_protect_bits: u8,
+    pub lmc: u8,
+    pub link_speed_active: u8,
+    pub link_speed_enabled: u8,
+    pub neighbor_mtu: u8,
+    pub sm_sl: u8,
+    pub vl_cap: u8,
+    pub init_type: u8,
+    pub vl_high_limit: u8,
+    pub vl_arbitration_high_cap: u8,
+    pub vl_arbitration_low_cap: u8,
+    pub init_type_reply: u8,
+    pub mtu_cap: u8,
+    pub vl_stall_count: u8,
+    pub hoq_life: u8,
+    pub oper_vls: u8,
+    pub partition_enforcement_inbound: bool,
+    pub partition_enforcement_outbound: bool,
+    pub filter_raw_inbound: bool,
+    pub filter_raw_outbound: bool,
+    pub mkey_violations: u16,
+    pub pkey_violations: u16,
+    pub qkey_violations: u16,
+    pub guid_cap: u8,
+    pub client_reregister: bool,
+    pub multicast_pkey_trap_suppression: u8,
+    pub subnet_timeout: u8,
+    pub resp_time_value: u8,
+    //Error thresholds
+    pub local_phys_errors: u8,
+    pub overrun_errors: u8,
+    pub max_credit_hint: u16,
+    pub link_round_trip_latency: u32,
+    pub cap_mask2: u16,
+    pub link_speed_ext_active: u8,
+    pub link_speed_ext_supported: u8,
+    pub link_speed_ext_enabled: u8,
+}
+
+//Mellanox ExtendedPortInfo, the only place FDR10 shows up
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MlnxExtPortInfo {
+    pub state_change_enable: bool,
+    pub link_speed_supported: u8,
+    pub link_speed_enabled: u8,
+    pub link_speed_active: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkSpeed {
+    Sdr,
+    Ddr,
+    Qdr,
+    Fdr10,
+    Fdr,
+    Edr,
+    Hdr,
+    Ndr,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkWidth {
+    X1,
+    X2,
+    X4,
+    X8,
+    X12,
+    Unknown,
+}
+
+pub const PORT_STATE_ACTIVE: u8 = 4;
+pub const PORT_PHYS_STATE_LINKUP: u8 = 5;
+
+//PortInfo:CapabilityMask
+pub const IS_EXTENDED_SPEEDS_SUPPORTED: u32 = 1 << 14;
+
+const MTU_SIZES: [u32; 6] = [0, 256, 512, 1024, 2048, 4096];
+
+fn get_field(data: &mut [u8], field: MAD_FIELDS) -> u32 {
+    unsafe { mad_get_field(data.as_mut_ptr() as *mut c_void, 0, field) }
+}
+
+fn get_field64(data: &mut [u8], field: MAD_FIELDS) -> u64 {
+    unsafe { mad_get_field64(data.as_mut_ptr() as *mut c_void, 0, field) }
+}
+
+fn set_field(data: &mut [u8], field: MAD_FIELDS, value: u32) {
+    unsafe { mad_set_field(data.as_mut_ptr() as *mut c_void, 0, field, value) }
+}
+
+fn set_field64(data: &mut [u8], field: MAD_FIELDS, value: u64) {
+    unsafe { mad_set_field64(data.as_mut_ptr() as *mut c_void, 0, field, value) }
+}
+
+impl PortInfo {
+    pub fn from_mad_fields(data: &mut [u8]) -> Self {
+        PortInfo {
+            mkey: get_field64(data, MAD_FIELDS_IB_PORT_MKEY_F),
+            gid_prefix: get_field64(data, MAD_FIELDS_IB_PORT_GID_PREFIX_F),
+            lid: get_field(data, MAD_FIELDS_IB_PORT_LID_F) as u16,
+            sm_lid: get_field(data, MAD_FIELDS_IB_PORT_SMLID_F) as u16,
+            cap_mask: get_field(data, MAD_FIELDS_IB_PORT_CAPMASK_F),
+            diag_code: get_field(data, MAD_FIELDS_IB_PORT_DIAG_F) as u16,
+            mkey_lease_period: get_field(data, MAD_FIELDS_IB_PORT_MKEY_LEASE_F) as u16,
+            local_port: get_field(data, MAD_FIELDS_IB_PORT_LOCAL_PORT_F) as u8,
+            link_width_enabled: get_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_ENABLED_F) as u8,
+            link_width_supported: get_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_SUPPORTED_F) as u8,
+            link_width_active: get_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_ACTIVE_F) as u8,
+            link_speed_supported: get_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_SUPPORTED_F) as u8,
+            state: get_field(data, MAD_FIELDS_IB_PORT_STATE_F) as u8,
+            phys_state: get_field(data, MAD_FIELDS_IB_PORT_PHYS_STATE_F) as u8,
+            link_down_default_state: get_field(data, MAD_FIELDS_IB_PORT_LINK_DOWN_DEF_F) as u8,
+            mkey_protect_bits: get_field(data, MAD_FIELDS_IB_PORT_MKEY_PROT_BITS_F) as u8,
+            lmc: get_field(data, MAD_FIELDS_IB_PORT_LMC_F) as u8,
+            link_speed_active: get_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_ACTIVE_F) as u8,
+            link_speed_enabled: get_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_ENABLED_F) as u8,
+            neighbor_mtu: get_field(data, MAD_FIELDS_IB_PORT_NEIGHBOR_MTU_F) as u8,
+            sm_sl: get_field(data, MAD_FIELDS_IB_PORT_SMSL_F) as u8,
+            vl_cap: get_field(data, MAD_FIELDS_IB_PORT_VL_CAP_F) as u8,
+            init_type: get_field(data, MAD_FIELDS_IB_PORT_INIT_TYPE_F) as u8,
+            vl_high_limit: get_field(data, MAD_FIELDS_IB_PORT_VL_HIGH_LIMIT_F) as u8,
+            vl_arbitration_high_cap: get_field(data, MAD_FIELDS_IB_PORT_VL_ARBITRATION_HIGH_CAP_F) as u8,
+            vl_arbitration_low_cap: get_field(data, MAD_FIELDS_IB_PORT_VL_ARBITRATION_LOW_CAP_F) as u8,
+            init_type_reply: get_field(data, MAD_FIELDS_IB_PORT_INIT_TYPE_REPLY_F) as u8,
+            mtu_cap: get_field(data, MAD_FIELDS_IB_PORT_MTU_CAP_F) as u8,
+            vl_stall_count: get_field(data, MAD_FIELDS_IB_PORT_VL_STALL_COUNT_F) as u8,
+            hoq_life: get_field(data, MAD_FIELDS_IB_PORT_HOQ_LIFE_F) as u8,
+            oper_vls: get_field(data, MAD_FIELDS_IB_PORT_OPER_VLS_F) as u8,
+            partition_enforcement_inbound: get_field(data, MAD_FIELDS_IB_PORT_PART_EN_INB_F) != 0,
+            partition_enforcement_outbound: get_field(data, MAD_FIELDS_IB_PORT_PART_EN_OUTB_F) != 0,
+            filter_raw_inbound: get_field(data, MAD_FIELDS_IB_PORT_FILTER_RAW_INB_F) != 0,
+            filter_raw_outbound: get_field(data, MAD_FIELDS_IB_PORT_FILTER_RAW_OUTB_F) != 0,
+            mkey_violations: get_field(data, MAD_FIELDS_IB_PORT_MKEY_VIOL_F) as u16,
+            pkey_violations: get_field(data, MAD_FIELDS_IB_PORT_PKEY_VIOL_F) as u16,
+            qkey_violations: get_field(data, MAD_FIELDS_IB_PORT_QKEY_VIOL_F) as u16,
+            guid_cap: get_field(data, MAD_FIELDS_IB_PORT_GUID_CAP_F) as u8,
+            client_reregister: get_field(data, MAD_FIELDS_IB_PORT_CLIENT_REREG_F) != 0,
+            multicast_pkey_trap_suppression: get_field(data, MAD_FIELDS_IB_PORT_MCAST_PKEY_SUPR_ENAB_F) as u8,
+            subnet_timeout: get_field(data, MAD_FIELDS_IB_PORT_SUBN_TIMEOUT_F) as u8,
+            resp_time_value: get_field(data, MAD_FIELDS_IB_PORT_RESP_TIME_VAL_F) as u8,
+            local_phys_errors: get_field(data, MAD_FIELDS_IB_PORT_LOCAL_PHYS_ERR_F) as u8,
+            overrun_errors: get_field(data, MAD_FIELDS_IB_PORT_OVERRUN_ERR_F) as u8,
+            max_credit_hint: get_field(data, MAD_FIELDS_IB_PORT_MAX_CREDIT_HINT_F) as u16,
+            link_round_trip_latency: get_field(data, MAD_FIELDS_IB_PORT_LINK_ROUND_TRIP_F),
+            cap_mask2: get_field(data, MAD_FIELDS_IB_PORT_CAPMASK2_F) as u16,
+            link_speed_ext_active: get_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_EXT_ACTIVE_F) as u8,
+            link_speed_ext_supported: get_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_EXT_SUPPORTED_F) as u8,
+            link_speed_ext_enabled: get_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_EXT_ENABLED_F) as u8,
+        }
+    }
+
+    pub fn to_mad_fields(&self, data: &mut [u8]) {
+        set_field64(data, MAD_FIELDS_IB_PORT_MKEY_F, self.mkey);
+        set_field64(data, MAD_FIELDS_IB_PORT_GID_PREFIX_F, self.gid_prefix);
+        set_field(data, MAD_FIELDS_IB_PORT_LID_F, self.lid as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_SMLID_F, self.sm_lid as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_CAPMASK_F, self.cap_mask);
+        set_field(data, MAD_FIELDS_IB_PORT_DIAG_F, self.diag_code as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_MKEY_LEASE_F, self.mkey_lease_period as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LOCAL_PORT_F, self.local_port as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_ENABLED_F, self.link_width_enabled as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_SUPPORTED_F, self.link_width_supported as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_ACTIVE_F, self.link_width_active as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_SUPPORTED_F, self.link_speed_supported as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_STATE_F, self.state as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_PHYS_STATE_F, self.phys_state as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_DOWN_DEF_F, self.link_down_default_state as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_MKEY_PROT_BITS_F, self.mkey_protect_bits as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LMC_F, self.lmc as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_ACTIVE_F, self.link_speed_active as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_ENABLED_F, self.link_speed_enabled as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_NEIGHBOR_MTU_F, self.neighbor_mtu as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_SMSL_F, self.sm_sl as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_VL_CAP_F, self.vl_cap as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_INIT_TYPE_F, self.init_type as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_VL_HIGH_LIMIT_F, self.vl_high_limit as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_VL_ARBITRATION_HIGH_CAP_F, self.vl_arbitration_high_cap as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_VL_ARBITRATION_LOW_CAP_F, self.vl_arbitration_low_cap as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_INIT_TYPE_REPLY_F, self.init_type_reply as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_MTU_CAP_F, self.mtu_cap as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_VL_STALL_COUNT_F, self.vl_stall_count as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_HOQ_LIFE_F, self.hoq_life as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_OPER_VLS_F, self.oper_vls as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_PART_EN_INB_F, self.partition_enforcement_inbound as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_PART_EN_OUTB_F, self.partition_enforcement_outbound as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_FILTER_RAW_INB_F, self.filter_raw_inbound as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_FILTER_RAW_OUTB_F, self.filter_raw_outbound as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_MKEY_VIOL_F, self.mkey_violations as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_PKEY_VIOL_F, self.pkey_violations as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_QKEY_VIOL_F, self.qkey_violations as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_GUID_CAP_F, self.guid_cap as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_CLIENT_REREG_F, self.client_reregister as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_MCAST_PKEY_SUPR_ENAB_F, self.multicast_pkey_trap_suppression as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_SUBN_TIMEOUT_F, self.subnet_timeout as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_RESP_TIME_VAL_F, self.resp_time_value as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LOCAL_PHYS_ERR_F, self.local_phys_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_OVERRUN_ERR_F, self.overrun_errors as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_MAX_CREDIT_HINT_F, self.max_credit_hint as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_ROUND_TRIP_F, self.link_round_trip_latency);
+        set_field(data, MAD_FIELDS_IB_PORT_CAPMASK2_F, self.cap_mask2 as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_EXT_ACTIVE_F, self.link_speed_ext_active as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_EXT_SUPPORTED_F, self.link_speed_ext_supported as u32);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_EXT_ENABLED_F, self.link_speed_ext_enabled as u32);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == PORT_STATE_ACTIVE
+    }
+
+    pub fn is_link_up(&self) -> bool {
+        self.phys_state == PORT_PHYS_STATE_LINKUP
+    }
+
+    //LinkSpeedExtActive is only valid when the port advertises extended speeds
+    pub fn link_speed(&self) -> LinkSpeed {
+        if self.cap_mask & IS_EXTENDED_SPEEDS_SUPPORTED != 0 {
+            match self.link_speed_ext_active {
+                1 => return LinkSpeed::Fdr,
+                2 => return LinkSpeed::Edr,
+                4 => return LinkSpeed::Hdr,
+                8 => return LinkSpeed::Ndr,
+                _ => {}
+            }
+        }
+
+        LinkSpeed::from_link_speed_active(self.link_speed_active)
+    }
+
+    //Same as link_speed, but FDR10 is reported through ExtendedPortInfo
+    pub fn link_speed_mlnx(&self, ext: &MlnxExtPortInfo) -> LinkSpeed {
+        match self.link_speed() {
+            LinkSpeed::Qdr if ext.is_fdr10_active() => LinkSpeed::Fdr10,
+            speed => speed,
+        }
+    }
+
+    pub fn link_width(&self) -> LinkWidth {
+        LinkWidth::from_link_width_active(self.link_width_active)
+    }
+
+    pub fn mtu_cap_bytes(&self) -> u32 {
+        MTU_SIZES.get(self.mtu_cap as usize).copied().unwrap_or(0)
+    }
+
+    pub fn neighbor_mtu_bytes(&self) -> u32 {
+        MTU_SIZES.get(self.neighbor_mtu as usize).copied().unwrap_or(0)
+    }
+
+    //Number of data VLs, VLCap 1 is VL0 only, 5 is VL0-14
+    pub fn data_vls(&self) -> u8 {
+        match self.vl_cap {
+            1..=4 => 1 << (self.vl_cap - 1),
+            5 => 15,
+            _ => 0,
+        }
+    }
+}
+
+impl MlnxExtPortInfo {
+    pub fn from_mad_fields(data: &mut [u8]) -> Self {
+        MlnxExtPortInfo {
+            state_change_enable: get_field(data, MAD_FIELDS_IB_MLNX_EXT_PORT_STATE_CHG_ENABLE_F) != 0,
+            link_speed_supported: get_field(data, MAD_FIELDS_IB_MLNX_EXT_PORT_LINK_SPEED_SUPPORTED_F) as u8,
+            link_speed_enabled: get_field(data, MAD_FIELDS_IB_MLNX_EXT_PORT_LINK_SPEED_ENABLED_F) as u8,
+            link_speed_active: get_field(data, MAD_FIELDS_IB_MLNX_EXT_PORT_LINK_SPEED_ACTIVE_F) as u8,
+        }
+    }
+
+    pub fn is_fdr10_active(&self) -> bool {
+        self.link_speed_active & 0x1 != 0
+    }
+}
+
+impl LinkSpeed {
+    pub fn from_link_speed_active(value: u8) -> LinkSpeed {
+        match value {
+            1 => LinkSpeed::Sdr,
+            2 => LinkSpeed::Ddr,
+            4 => LinkSpeed::Qdr,
+            _ => LinkSpeed::Unknown,
+        }
+    }
+
+    //Signalling rate of one lane in Gb/s
+    pub fn lane_gbps(&self) -> f64 {
+        match self {
+            LinkSpeed::Sdr => 2.5,
+            LinkSpeed::Ddr => 5.0,
+            LinkSpeed::Qdr => 10.0,
+            LinkSpeed::Fdr10 => 10.3125,
+            LinkSpeed::Fdr => 14.0625,
+            LinkSpeed::Edr => 25.78125,
+            LinkSpeed::Hdr => 53.125,
+            LinkSpeed::Ndr => 106.25,
+            LinkSpeed::Unknown => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for LinkSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LinkSpeed::Sdr => "SDR",
+            LinkSpeed::Ddr => "DDR",
+            LinkSpeed::Qdr => "QDR",
+            LinkSpeed::Fdr10 => "FDR10",
+            LinkSpeed::Fdr => "FDR",
+            LinkSpeed::Edr => "EDR",
+            LinkSpeed::Hdr => "HDR",
+            LinkSpeed::Ndr => "NDR",
+            LinkSpeed::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl LinkWidth {
+    pub fn from_link_width_active(value: u8) -> LinkWidth {
+        match value {
+            1 => LinkWidth::X1,
+            2 => LinkWidth::X4,
+            4 => LinkWidth::X8,
+            8 => LinkWidth::X12,
+            16 => LinkWidth::X2,
+            _ => LinkWidth::Unknown,
+        }
+    }
+
+    pub fn lanes(&self) -> u32 {
+        match self {
+            LinkWidth::X1 => 1,
+            LinkWidth::X2 => 2,
+            LinkWidth::X4 => 4,
+            LinkWidth::X8 => 8,
+            LinkWidth::X12 => 12,
+            LinkWidth::Unknown => 0,
+        }
+    }
+}
+
+impl fmt::Display for LinkWidth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinkWidth::Unknown => write!(f, "unknown"),
+            width => write!(f, "{}x", width.lanes()),
+        }
+    }
+}
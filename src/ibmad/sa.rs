@@ -0,0 +1,673 @@
+use std::rc::Rc;
+
+use crate::ibmad::{
+    enums::MadAttrId,
+    port::PortInfo,
+    sys::*,
+    transport::{MadAddress, MadRequest, MadTransport},
+    IBSmpError, NodeInfo,
+};
+
+//SA attributes
+pub const SA_ATTR_INFORM_INFO: u32 = MadAttrId::InformInfo as u32;
+pub const SA_ATTR_NODE_RECORD: u32 = 0x11;
+pub const SA_ATTR_PORT_INFO_RECORD: u32 = 0x12;
+pub const SA_ATTR_SM_INFO_RECORD: u32 = 0x18;
+pub const SA_ATTR_LINK_RECORD: u32 = 0x20;
+pub const SA_ATTR_GUID_INFO_RECORD: u32 = 0x30;
+pub const SA_ATTR_PKEY_TABLE_RECORD: u32 = 0x33;
+pub const SA_ATTR_PATH_RECORD: u32 = 0x35;
+pub const SA_ATTR_MCMEMBER_RECORD: u32 = 0x38;
+
+//SA specific status codes, bits 8-14 of the MAD status
+pub const SA_STATUS_NO_RESOURCES: u16 = 1 << 8;
+pub const SA_STATUS_REQ_INVALID: u16 = 2 << 8;
+pub const SA_STATUS_NO_RECORDS: u16 = 3 << 8;
+pub const SA_STATUS_TOO_MANY_RECORDS: u16 = 4 << 8;
+pub const SA_STATUS_INVALID_GID: u16 = 5 << 8;
+pub const SA_STATUS_INSUFFICIENT_COMPONENTS: u16 = 6 << 8;
+pub const SA_STATUS_REQ_DENIED: u16 = 7 << 8;
+
+pub const DEFAULT_SA_TIMEOUT: u32 = 1000;
+
+pub type Gid = [u8; 16];
+
+//A record type the SA hands out. SIZE is the record on the wire, responses
+//pad each record to the AttributeOffset.
+pub trait SaRecord: Sized {
+    const ATTR_ID: u32;
+    const SIZE: usize;
+
+    fn decode(data: &[u8]) -> Self;
+    fn encode(&self, data: &mut [u8]);
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NodeRecord {
+    pub lid: u16,
+    pub node_info: NodeInfo,
+    pub node_desc: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PortInfoRecord {
+    pub lid: u16,
+    pub port_num: u8,
+    pub options: u8,
+    pub port_info: PortInfo,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LinkRecord {
+    pub from_lid: u16,
+    pub from_port: u8,
+    pub to_port: u8,
+    pub to_lid: u16,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PathRecord {
+    pub service_id: u64,
+    pub dgid: Gid,
+    pub sgid: Gid,
+    pub dlid: u16,
+    pub slid: u16,
+    pub raw_traffic: bool,
+    pub flow_label: u32,
+    pub hop_limit: u8,
+    pub tclass: u8,
+    pub reversible: bool,
+    pub num_path: u8,
+    pub pkey: u16,
+    pub qos_class: u16,
+    pub sl: u8,
+    pub mtu_selector: u8,
+    pub mtu: u8,
+    pub rate_selector: u8,
+    pub rate: u8,
+    pub pkt_life_selector: u8,
+    pub pkt_life: u8,
+    pub preference: u8,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SmInfoRecord {
+    pub lid: u16,
+    pub guid: u64,
+    pub sm_key: u64,
+    pub act_count: u32,
+    pub priority: u8,
+    pub sm_state: u8,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GuidInfoRecord {
+    pub lid: u16,
+    pub block_num: u8,
+    pub guids: [u64; 8],
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PKeyTableRecord {
+    pub lid: u16,
+    pub block_num: u16,
+    pub port_num: u8,
+    pub pkeys: [u16; 32],
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct McMemberRecord {
+    pub mgid: Gid,
+    pub port_gid: Gid,
+    pub qkey: u32,
+    pub mlid: u16,
+    pub mtu_selector: u8,
+    pub mtu: u8,
+    pub tclass: u8,
+    pub pkey: u16,
+    pub rate_selector: u8,
+    pub rate: u8,
+    pub pkt_life_selector: u8,
+    pub pkt_life: u8,
+    pub sl: u8,
+    pub flow_label: u32,
+    pub hop_limit: u8,
+    pub scope: u8,
+    pub join_state: u8,
+    pub proxy_join: bool,
+}
+
+//Not a record, but set on the SA the same way to subscribe to traps. The
+//SA forwards matching Notices to qpn of the subscribing port in Reports.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InformInfo {
+    pub gid: Gid,
+    pub lid_range_begin: u16,
+    pub lid_range_end: u16,
+    pub is_generic: bool,
+    pub subscribe: bool,
+    pub notice_type: u16,
+    pub trap_number: u16,
+    pub qpn: u32,
+    pub resp_time_value: u8,
+    pub producer_type: u32,
+}
+
+impl InformInfo {
+    //Wildcards, matching any LID, type, trap or producer
+    pub const ALL_LIDS: u16 = 0xffff;
+    pub const ALL_TYPES: u16 = 0xffff;
+    pub const ALL_TRAPS: u16 = 0xffff;
+    pub const ALL_PRODUCERS: u32 = 0xffffff;
+
+    //A generic trap from anywhere, reported to QP1
+    pub fn trap(trap_number: u16, subscribe: bool) -> InformInfo {
+        InformInfo {
+            lid_range_begin: InformInfo::ALL_LIDS,
+            is_generic: true,
+            subscribe,
+            notice_type: InformInfo::ALL_TYPES,
+            trap_number,
+            qpn: 1,
+            resp_time_value: 18,
+            producer_type: InformInfo::ALL_PRODUCERS,
+            ..Default::default()
+        }
+    }
+
+    pub fn matches(&self, trap_number: u16) -> bool {
+        self.is_generic && (self.trap_number == InformInfo::ALL_TRAPS || self.trap_number == trap_number)
+    }
+}
+
+//ComponentMask bits follow the field order of each record, reserved
+//fields included
+impl NodeRecord {
+    pub const COMP_LID: u64 = 1 << 0;
+    pub const COMP_NODE_TYPE: u64 = 1 << 4;
+    pub const COMP_SYSTEM_GUID: u64 = 1 << 6;
+    pub const COMP_NODE_GUID: u64 = 1 << 7;
+    pub const COMP_PORT_GUID: u64 = 1 << 8;
+    pub const COMP_NODE_DESC: u64 = 1 << 14;
+}
+
+impl PortInfoRecord {
+    pub const COMP_LID: u64 = 1 << 0;
+    pub const COMP_PORT_NUM: u64 = 1 << 1;
+    pub const COMP_OPTIONS: u64 = 1 << 2;
+    pub const COMP_BASE_LID: u64 = 1 << 5;
+    pub const COMP_CAP_MASK: u64 = 1 << 7;
+    pub const COMP_PORT_STATE: u64 = 1 << 15;
+}
+
+impl LinkRecord {
+    pub const COMP_FROM_LID: u64 = 1 << 0;
+    pub const COMP_FROM_PORT: u64 = 1 << 1;
+    pub const COMP_TO_PORT: u64 = 1 << 2;
+    pub const COMP_TO_LID: u64 = 1 << 3;
+}
+
+impl PathRecord {
+    pub const COMP_SERVICE_ID: u64 = 0x3;
+    pub const COMP_DGID: u64 = 1 << 2;
+    pub const COMP_SGID: u64 = 1 << 3;
+    pub const COMP_DLID: u64 = 1 << 4;
+    pub const COMP_SLID: u64 = 1 << 5;
+    pub const COMP_REVERSIBLE: u64 = 1 << 11;
+    pub const COMP_NUM_PATH: u64 = 1 << 12;
+    pub const COMP_PKEY: u64 = 1 << 13;
+    pub const COMP_SL: u64 = 1 << 15;
+    pub const COMP_MTU: u64 = 1 << 17;
+    pub const COMP_RATE: u64 = 1 << 19;
+}
+
+impl SmInfoRecord {
+    pub const COMP_LID: u64 = 1 << 0;
+    pub const COMP_GUID: u64 = 1 << 2;
+    pub const COMP_PRIORITY: u64 = 1 << 5;
+    pub const COMP_SM_STATE: u64 = 1 << 6;
+}
+
+impl GuidInfoRecord {
+    pub const COMP_LID: u64 = 1 << 0;
+    pub const COMP_BLOCK_NUM: u64 = 1 << 1;
+}
+
+impl PKeyTableRecord {
+    pub const COMP_LID: u64 = 1 << 0;
+    pub const COMP_BLOCK_NUM: u64 = 1 << 1;
+    pub const COMP_PORT_NUM: u64 = 1 << 2;
+}
+
+impl McMemberRecord {
+    pub const COMP_MGID: u64 = 1 << 0;
+    pub const COMP_PORT_GID: u64 = 1 << 1;
+    pub const COMP_QKEY: u64 = 1 << 2;
+    pub const COMP_MLID: u64 = 1 << 3;
+    pub const COMP_PKEY: u64 = 1 << 7;
+    pub const COMP_SL: u64 = 1 << 12;
+    pub const COMP_JOIN_STATE: u64 = 1 << 16;
+}
+
+fn get16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn get32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn get64(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn gid(data: &[u8], offset: usize) -> Gid {
+    data[offset..offset + 16].try_into().unwrap()
+}
+
+fn put16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+fn put32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+fn put64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_be_bytes());
+}
+
+//Embedded attributes are decoded with the SMP decoders, which want a full
+//SMP data buffer
+fn smp_data(data: &[u8]) -> Vec<u8> {
+    let mut buf = data.to_vec();
+    buf.resize(IB_SMP_DATA_SIZE as usize, 0);
+    buf
+}
+
+impl SaRecord for NodeRecord {
+    const ATTR_ID: u32 = SA_ATTR_NODE_RECORD;
+    const SIZE: usize = 108;
+
+    fn decode(data: &[u8]) -> Self {
+        NodeRecord {
+            lid: get16(data, 0),
+            node_info: NodeInfo::from_mad_fields(&mut smp_data(&data[4..44])),
+            node_desc: crate::ibmad::node_desc_from_mad_fields(&data[44..108]),
+        }
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        put16(data, 0, self.lid);
+        let mut info = smp_data(&[]);
+        self.node_info.to_mad_fields(&mut info);
+        data[4..44].copy_from_slice(&info[..40]);
+        let desc = self.node_desc.as_bytes();
+        let len = desc.len().min(64);
+        data[44..44 + len].copy_from_slice(&desc[..len]);
+    }
+}
+
+impl SaRecord for PortInfoRecord {
+    const ATTR_ID: u32 = SA_ATTR_PORT_INFO_RECORD;
+    const SIZE: usize = 68;
+
+    fn decode(data: &[u8]) -> Self {
+        PortInfoRecord {
+            lid: get16(data, 0),
+            port_num: data[2],
+            options: data[3],
+            port_info: PortInfo::from_mad_fields(&mut smp_data(&data[4..68])),
+        }
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        put16(data, 0, self.lid);
+        data[2] = self.port_num;
+        data[3] = self.options;
+        let mut info = smp_data(&[]);
+        self.port_info.to_mad_fields(&mut info);
+        data[4..68].copy_from_slice(&info);
+    }
+}
+
+impl SaRecord for LinkRecord {
+    const ATTR_ID: u32 = SA_ATTR_LINK_RECORD;
+    const SIZE: usize = 8;
+
+    fn decode(data: &[u8]) -> Self {
+        LinkRecord {
+            from_lid: get16(data, 0),
+            from_port: data[2],
+            to_port: data[3],
+            to_lid: get16(data, 4),
+        }
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        put16(data, 0, self.from_lid);
+        data[2] = self.from_port;
+        data[3] = self.to_port;
+        put16(data, 4, self.to_lid);
+    }
+}
+
+impl SaRecord for PathRecord {
+    const ATTR_ID: u32 = SA_ATTR_PATH_RECORD;
+    const SIZE: usize = 64;
+
+    fn decode(data: &[u8]) -> Self {
+        let flow = get32(data, 44);
+        PathRecord {
+            service_id: get64(data, 0),
+            dgid: gid(data, 8),
+            sgid: gid(data, 24),
+            dlid: get16(data, 40),
+            slid: get16(data, 42),
+            raw_traffic: flow & 0x8000_0000 != 0,
+            flow_label: (flow >> 8) & 0xfffff,
+            hop_limit: flow as u8,
+            tclass: data[48],
+            reversible: data[49] & 0x80 != 0,
+            num_path: data[49] & 0x7f,
+            pkey: get16(data, 50),
+            qos_class: get16(data, 52) >> 4,
+            sl: data[53] & 0xf,
+            mtu_selector: data[54] >> 6,
+            mtu: data[54] & 0x3f,
+            rate_selector: data[55] >> 6,
+            rate: data[55] & 0x3f,
+            pkt_life_selector: data[56] >> 6,
+            pkt_life: data[56] & 0x3f,
+            preference: data[57],
+        }
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        put64(data, 0, self.service_id);
+        data[8..24].copy_from_slice(&self.dgid);
+        data[24..40].copy_from_slice(&self.sgid);
+        put16(data, 40, self.dlid);
+        put16(data, 42, self.slid);
+        put32(data, 44, (self.raw_traffic as u32) << 31 | (self.flow_label & 0xfffff) << 8 | self.hop_limit as u32);
+        data[48] = self.tclass;
+        data[49] = (self.reversible as u8) << 7 | (self.num_path & 0x7f);
+        put16(data, 50, self.pkey);
+        put16(data, 52, (self.qos_class & 0xfff) << 4 | (self.sl & 0xf) as u16);
+        data[54] = self.mtu_selector << 6 | (self.mtu & 0x3f);
+        data[55] = self.rate_selector << 6 | (self.rate & 0x3f);
+        data[56] = self.pkt_life_selector << 6 | (self.pkt_life & 0x3f);
+        data[57] = self.preference;
+    }
+}
+
+impl SaRecord for SmInfoRecord {
+    const ATTR_ID: u32 = SA_ATTR_SM_INFO_RECORD;
+    const SIZE: usize = 28;
+
+    fn decode(data: &[u8]) -> Self {
+        SmInfoRecord {
+            lid: get16(data, 0),
+            guid: get64(data, 4),
+            sm_key: get64(data, 12),
+            act_count: get32(data, 20),
+            priority: data[24] >> 4,
+            sm_state: data[24] & 0xf,
+        }
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        put16(data, 0, self.lid);
+        put64(data, 4, self.guid);
+        put64(data, 12, self.sm_key);
+        put32(data, 20, self.act_count);
+        data[24] = self.priority << 4 | (self.sm_state & 0xf);
+    }
+}
+
+impl SaRecord for GuidInfoRecord {
+    const ATTR_ID: u32 = SA_ATTR_GUID_INFO_RECORD;
+    const SIZE: usize = 72;
+
+    fn decode(data: &[u8]) -> Self {
+        let mut guids = [0; 8];
+        for (i, guid) in guids.iter_mut().enumerate() {
+            *guid = get64(data, 8 + i * 8);
+        }
+        GuidInfoRecord {
+            lid: get16(data, 0),
+            block_num: data[2],
+            guids,
+        }
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        put16(data, 0, self.lid);
+        data[2] = self.block_num;
+        for (i, guid) in self.guids.iter().enumerate() {
+            put64(data, 8 + i * 8, *guid);
+        }
+    }
+}
+
+impl SaRecord for PKeyTableRecord {
+    const ATTR_ID: u32 = SA_ATTR_PKEY_TABLE_RECORD;
+    const SIZE: usize = 72;
+
+    fn decode(data: &[u8]) -> Self {
+        let mut pkeys = [0; 32];
+        for (i, pkey) in pkeys.iter_mut().enumerate() {
+            *pkey = get16(data, 8 + i * 2);
+        }
+        PKeyTableRecord {
+            lid: get16(data, 0),
+            block_num: get16(data, 2),
+            port_num: data[4],
+            pkeys,
+        }
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        put16(data, 0, self.lid);
+        put16(data, 2, self.block_num);
+        data[4] = self.port_num;
+        for (i, pkey) in self.pkeys.iter().enumerate() {
+            put16(data, 8 + i * 2, *pkey);
+        }
+    }
+}
+
+impl SaRecord for McMemberRecord {
+    const ATTR_ID: u32 = SA_ATTR_MCMEMBER_RECORD;
+    const SIZE: usize = 52;
+
+    fn decode(data: &[u8]) -> Self {
+        let flow = get32(data, 44);
+        McMemberRecord {
+            mgid: gid(data, 0),
+            port_gid: gid(data, 16),
+            qkey: get32(data, 32),
+            mlid: get16(data, 36),
+            mtu_selector: data[38] >> 6,
+            mtu: data[38] & 0x3f,
+            tclass: data[39],
+            pkey: get16(data, 40),
+            rate_selector: data[42] >> 6,
+            rate: data[42] & 0x3f,
+            pkt_life_selector: data[43] >> 6,
+            pkt_life: data[43] & 0x3f,
+            sl: (flow >> 28) as u8,
+            flow_label: (flow >> 8) & 0xfffff,
+            hop_limit: flow as u8,
+            scope: data[48] >> 4,
+            join_state: data[48] & 0xf,
+            proxy_join: data[49] & 0x80 != 0,
+        }
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        data[0..16].copy_from_slice(&self.mgid);
+        data[16..32].copy_from_slice(&self.port_gid);
+        put32(data, 32, self.qkey);
+        put16(data, 36, self.mlid);
+        data[38] = self.mtu_selector << 6 | (self.mtu & 0x3f);
+        data[39] = self.tclass;
+        put16(data, 40, self.pkey);
+        data[42] = self.rate_selector << 6 | (self.rate & 0x3f);
+        data[43] = self.pkt_life_selector << 6 | (self.pkt_life & 0x3f);
+        put32(data, 44, (self.sl as u32) << 28 | (self.flow_label & 0xfffff) << 8 | self.hop_limit as u32);
+        data[48] = self.scope << 4 | (self.join_state & 0xf);
+        data[49] = (self.proxy_join as u8) << 7;
+    }
+}
+
+impl SaRecord for InformInfo {
+    const ATTR_ID: u32 = SA_ATTR_INFORM_INFO;
+    const SIZE: usize = 36;
+
+    fn decode(data: &[u8]) -> Self {
+        InformInfo {
+            gid: gid(data, 0),
+            lid_range_begin: get16(data, 16),
+            lid_range_end: get16(data, 18),
+            is_generic: data[22] != 0,
+            subscribe: data[23] != 0,
+            notice_type: get16(data, 24),
+            trap_number: get16(data, 26),
+            qpn: get32(data, 28) >> 8,
+            resp_time_value: data[31] & 0x1f,
+            producer_type: get32(data, 32) & 0xffffff,
+        }
+    }
+
+    fn encode(&self, data: &mut [u8]) {
+        data[0..16].copy_from_slice(&self.gid);
+        put16(data, 16, self.lid_range_begin);
+        put16(data, 18, self.lid_range_end);
+        data[22] = self.is_generic as u8;
+        data[23] = self.subscribe as u8;
+        put16(data, 24, self.notice_type);
+        put16(data, 26, self.trap_number);
+        put32(data, 28, (self.qpn & 0xffffff) << 8 | (self.resp_time_value & 0x1f) as u32);
+        put32(data, 32, self.producer_type & 0xffffff);
+    }
+}
+
+//Records in a GetTable response, each padded to attr_offset 8 byte words
+pub fn records_from_table<R: SaRecord>(data: &[u8], attr_offset: u16) -> Vec<R> {
+    let stride = match attr_offset {
+        0 => R::SIZE.div_ceil(8) * 8,
+        n => n as usize * 8,
+    };
+
+    data.chunks_exact(stride).filter(|chunk| chunk.len() >= R::SIZE).map(R::decode).collect()
+}
+
+//Queries the SA at the SM's LID. Get wants exactly one match, GetTable any
+//number. Only the template fields selected by comp_mask are matched.
+pub struct SaClient {
+    transport: Rc<dyn MadTransport>,
+    pub sm_lid: u16,
+    pub timeout: u32,
+    pub pkey_index: u32,
+}
+
+impl SaClient {
+    pub fn new(transport: Rc<dyn MadTransport>, sm_lid: u16) -> SaClient {
+        SaClient {
+            transport,
+            sm_lid,
+            timeout: DEFAULT_SA_TIMEOUT,
+            pkey_index: 0,
+        }
+    }
+
+    fn request<R: SaRecord>(&self, method: u32, template: &R, comp_mask: u64) -> MadRequest {
+        let mut request = MadRequest::new(MadAddress::Lid(self.sm_lid), MAD_CLASSES_IB_SA_CLASS, method, R::ATTR_ID, 0, self.timeout)
+            .with_pkey_index(self.pkey_index)
+            .with_comp_mask(comp_mask);
+        template.encode(&mut request.data);
+        request
+    }
+
+    pub fn get<R: SaRecord>(&self, template: &R, comp_mask: u64) -> Result<R, IBSmpError> {
+        let request = self.request(MAD_METHODS_IB_MAD_METHOD_GET, template, comp_mask);
+        let data = self.transport.query(&request)?;
+
+        Ok(R::decode(&data))
+    }
+
+    pub fn get_table<R: SaRecord>(&self, template: &R, comp_mask: u64) -> Result<Vec<R>, IBSmpError> {
+        let request = self.request(MAD_METHODS_IB_MAD_METHOD_GET_TABLE, template, comp_mask);
+        let response = self.transport.call(&request)?;
+
+        //Some SAs answer an empty table with an error instead of no records
+        match response.status {
+            0 => Ok(records_from_table(&response.data, response.attr_offset)),
+            SA_STATUS_NO_RECORDS => Ok(Vec::new()),
+            status => Err(IBSmpError::MadStatusError(status)),
+        }
+    }
+
+    pub fn set<R: SaRecord>(&self, record: &R, comp_mask: u64) -> Result<R, IBSmpError> {
+        let request = self.request(MAD_METHODS_IB_MAD_METHOD_SET, record, comp_mask);
+        let data = self.transport.query(&request)?;
+
+        Ok(R::decode(&data))
+    }
+
+    //Have the SA report a trap to this port, trap_number may be
+    //InformInfo::ALL_TRAPS
+    pub fn subscribe(&self, trap_number: u16) -> Result<InformInfo, IBSmpError> {
+        self.set(&InformInfo::trap(trap_number, true), 0)
+    }
+
+    pub fn unsubscribe(&self, trap_number: u16) -> Result<InformInfo, IBSmpError> {
+        self.set(&InformInfo::trap(trap_number, false), 0)
+    }
+
+    pub fn node_records(&self) -> Result<Vec<NodeRecord>, IBSmpError> {
+        self.get_table(&NodeRecord::default(), 0)
+    }
+
+    pub fn node_record(&self, lid: u16) -> Result<NodeRecord, IBSmpError> {
+        let template = NodeRecord { lid, ..Default::default() };
+        self.get(&template, NodeRecord::COMP_LID)
+    }
+
+    pub fn port_info_records(&self) -> Result<Vec<PortInfoRecord>, IBSmpError> {
+        self.get_table(&PortInfoRecord::default(), 0)
+    }
+
+    pub fn port_info_record(&self, lid: u16, port_num: u8) -> Result<PortInfoRecord, IBSmpError> {
+        let template = PortInfoRecord { lid, port_num, ..Default::default() };
+        self.get(&template, PortInfoRecord::COMP_LID | PortInfoRecord::COMP_PORT_NUM)
+    }
+
+    pub fn link_records(&self) -> Result<Vec<LinkRecord>, IBSmpError> {
+        self.get_table(&LinkRecord::default(), 0)
+    }
+
+    pub fn path_records(&self, slid: u16, dlid: u16) -> Result<Vec<PathRecord>, IBSmpError> {
+        let template = PathRecord { slid, dlid, ..Default::default() };
+        self.get_table(&template, PathRecord::COMP_SLID | PathRecord::COMP_DLID)
+    }
+
+    pub fn sm_info_records(&self) -> Result<Vec<SmInfoRecord>, IBSmpError> {
+        self.get_table(&SmInfoRecord::default(), 0)
+    }
+
+    pub fn guid_info_records(&self, lid: u16) -> Result<Vec<GuidInfoRecord>, IBSmpError> {
+        let template = GuidInfoRecord { lid, ..Default::default() };
+        self.get_table(&template, GuidInfoRecord::COMP_LID)
+    }
+
+    pub fn pkey_table_records(&self, lid: u16) -> Result<Vec<PKeyTableRecord>, IBSmpError> {
+        let template = PKeyTableRecord { lid, ..Default::default() };
+        self.get_table(&template, PKeyTableRecord::COMP_LID)
+    }
+
+    pub fn mcmember_records(&self) -> Result<Vec<McMemberRecord>, IBSmpError> {
+        self.get_table(&McMemberRecord::default(), 0)
+    }
+}
@@ -0,0 +1,103 @@
+use std::ffi::c_void;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ibmad::sys::*;
+
+//LIDs per LinearForwardingTable block
+pub const LFT_BLOCK_SIZE: usize = 64;
+
+//Egress port of a LID without a route
+pub const LFT_NO_PORT: u8 = 0xff;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwitchInfo {
+    pub linear_fdb_cap: u16,
+    pub random_fdb_cap: u16,
+    pub multicast_fdb_cap: u16,
+    pub linear_fdb_top: u16,
+    pub default_port: u8,
+    pub default_mcast_primary_port: u8,
+    pub default_mcast_not_primary_port: u8,
+    pub lifetime: u8,
+    pub port_state_change: bool,
+    pub optimized_sl_to_vl_mapping: u8,
+    pub lids_per_port: u16,
+    pub partition_enforcement_cap: u16,
+    pub inbound_enforcement_cap: bool,
+    pub outbound_enforcement_cap: bool,
+    pub filter_raw_inbound_cap: bool,
+    pub filter_raw_outbound_cap: bool,
+    pub enhanced_port0: bool,
+    pub multicast_fdb_top: u16,
+}
+
+//Unicast routing of a switch, indexed by LID up to LinearFDBTop
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinearForwardingTable {
+    pub ports: Vec<u8>,
+}
+
+fn get_field(data: &mut [u8], field: MAD_FIELDS) -> u32 {
+    unsafe { mad_get_field(data.as_mut_ptr() as *mut c_void, 0, field) }
+}
+
+impl SwitchInfo {
+    pub fn from_mad_fields(data: &mut [u8]) -> Self {
+        SwitchInfo {
+            linear_fdb_cap: get_field(data, MAD_FIELDS_IB_SW_LINEAR_FDB_CAP_F) as u16,
+            random_fdb_cap: get_field(data, MAD_FIELDS_IB_SW_RANDOM_FDB_CAP_F) as u16,
+            multicast_fdb_cap: get_field(data, MAD_FIELDS_IB_SW_MCAST_FDB_CAP_F) as u16,
+            linear_fdb_top: get_field(data, MAD_FIELDS_IB_SW_LINEAR_FDB_TOP_F) as u16,
+            default_port: get_field(data, MAD_FIELDS_IB_SW_DEF_PORT_F) as u8,
+            default_mcast_primary_port: get_field(data, MAD_FIELDS_IB_SW_DEF_MCAST_PRIM_F) as u8,
+            default_mcast_not_primary_port: get_field(data, MAD_FIELDS_IB_SW_DEF_MCAST_NOT_PRIM_F) as u8,
+            lifetime: get_field(data, MAD_FIELDS_IB_SW_LIFE_TIME_F) as u8,
+            port_state_change: get_field(data, MAD_FIELDS_IB_SW_STATE_CHANGE_F) != 0,
+            optimized_sl_to_vl_mapping: get_field(data, MAD_FIELDS_IB_SW_OPT_SLTOVL_MAPPING_F) as u8,
+            lids_per_port: get_field(data, MAD_FIELDS_IB_SW_LIDS_PER_PORT_F) as u16,
+            partition_enforcement_cap: get_field(data, MAD_FIELDS_IB_SW_PARTITION_ENFORCE_CAP_F) as u16,
+            inbound_enforcement_cap: get_field(data, MAD_FIELDS_IB_SW_PARTITION_ENF_INB_F) != 0,
+            outbound_enforcement_cap: get_field(data, MAD_FIELDS_IB_SW_PARTITION_ENF_OUTB_F) != 0,
+            filter_raw_inbound_cap: get_field(data, MAD_FIELDS_IB_SW_FILTER_RAW_INB_F) != 0,
+            filter_raw_outbound_cap: get_field(data, MAD_FIELDS_IB_SW_FILTER_RAW_OUTB_F) != 0,
+            enhanced_port0: get_field(data, MAD_FIELDS_IB_SW_ENHANCED_PORT0_F) != 0,
+            multicast_fdb_top: get_field(data, MAD_FIELDS_IB_SW_MCAST_FDB_TOP_F) as u16,
+        }
+    }
+
+    //Number of LFT blocks holding LIDs 0..=LinearFDBTop
+    pub fn lft_blocks(&self) -> u32 {
+        let top = self.linear_fdb_top.min(self.linear_fdb_cap.saturating_sub(1));
+        top as u32 / LFT_BLOCK_SIZE as u32 + 1
+    }
+}
+
+impl LinearForwardingTable {
+    //Blocks in order starting at block 0, truncated to LinearFDBTop
+    pub fn from_blocks(blocks: &[Vec<u8>], linear_fdb_top: u16) -> Self {
+        let mut ports: Vec<u8> = blocks.iter().flat_map(|b| b.iter().take(LFT_BLOCK_SIZE).copied()).collect();
+        ports.truncate(linear_fdb_top as usize + 1);
+        LinearForwardingTable { ports }
+    }
+
+    pub fn egress_port(&self, lid: u16) -> Option<u8> {
+        match self.ports.get(lid as usize) {
+            Some(&LFT_NO_PORT) | None => None,
+            Some(&port) => Some(port),
+        }
+    }
+
+    pub fn top(&self) -> u16 {
+        self.ports.len().saturating_sub(1) as u16
+    }
+
+    //(LID, egress port) of every routed LID
+    pub fn routes(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.ports
+            .iter()
+            .enumerate()
+            .filter(|(_, &port)| port != LFT_NO_PORT)
+            .map(|(lid, &port)| (lid as u16, port))
+    }
+}
@@ -0,0 +1,483 @@
+use std::{collections::HashMap, ffi::c_void, mem::{offset_of, MaybeUninit}, sync::Arc};
+
+use crate::ibmad::{self};
+use crate::umad::mad::ib_user_mad;
+use crate::umad::rmpp::{RmppHeader, RMPP_FLAG_ACTIVE, RMPP_FLAG_FIRST, RMPP_FLAG_LAST, RMPP_HDR_OFFSET, RMPP_HDR_SIZE};
+
+use ibmad::sys::*;
+use ibmad::{IBMadPort, IBSmpError};
+
+pub const DR_INITIAL_PATH_OFFSET: usize = 128;
+pub const DR_RETURN_PATH_OFFSET: usize = 192;
+
+//SA class header, after the RMPP header
+pub const SA_ATTR_OFFSET_OFFSET: usize = 44;
+pub const SA_COMP_MASK_OFFSET: usize = 48;
+
+//Directed route path, without the leading 0 of the "0,1,3" notation
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct DrPath {
+    pub hops: Vec<u8>,
+}
+
+impl DrPath {
+    pub fn new(hops: &[u8]) -> DrPath {
+        DrPath { hops: hops.to_vec() }
+    }
+
+    //Same notation as str2drpath: "0,1,1,1,45"
+    pub fn parse(path: &str) -> Result<DrPath, IBSmpError> {
+        let mut ports = Vec::new();
+
+        for p in path.split(',') {
+            let port = p.trim().parse::<u8>().map_err(|_| IBSmpError::DRMADPathError)?;
+            ports.push(port);
+        }
+
+        if ports.len() > IB_SUBNET_PATH_HOPS_MAX as usize {
+            return Err(IBSmpError::DRMADPathError);
+        }
+
+        Ok(DrPath {
+            hops: ports[1..].to_vec(),
+        })
+    }
+
+    //The path one hop further, as long as it fits in an SMP
+    pub fn push(&self, port: u8) -> Result<DrPath, IBSmpError> {
+        if self.hops.len() + 1 >= IB_SUBNET_PATH_HOPS_MAX as usize {
+            return Err(IBSmpError::DRMADPathError);
+        }
+
+        let mut hops = self.hops.clone();
+        hops.push(port);
+        Ok(DrPath { hops })
+    }
+
+    fn to_ib_dr_path(&self) -> ib_dr_path_t {
+        let mut drpath = ib_dr_path_t {
+            cnt: self.hops.len() as u8,
+            p: unsafe { MaybeUninit::<[u8; 64]>::zeroed().assume_init() },
+            drslid: 0xffff,
+            drdlid: 0xffff,
+        };
+        drpath.p[1..=self.hops.len()].copy_from_slice(&self.hops);
+        drpath
+    }
+}
+
+impl std::fmt::Display for DrPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0")?;
+        for hop in &self.hops {
+            write!(f, ",{}", hop)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MadAddress {
+    Lid(u16),
+    DirectedRoute(DrPath),
+}
+
+#[derive(Debug, Clone)]
+pub struct MadRequest {
+    pub address: MadAddress,
+    pub mgmt_class: u32,
+    pub method: u32,
+    pub attr_id: u32,
+    pub attr_mod: u32,
+    pub data: Vec<u8>,
+    pub pkey_index: u32,
+    //SA ComponentMask
+    pub comp_mask: u64,
+    pub timeout: u32,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MadResponse {
+    pub status: u16,
+    pub data: Vec<u8>,
+    //SA AttributeOffset, the record size in 8 byte words
+    pub attr_offset: u16,
+}
+
+impl MadResponse {
+    //mad can be longer than a MAD for a reassembled RMPP response, whose data
+    //then runs to the end of the buffer
+    pub fn decode(mad: &[u8]) -> MadResponse {
+        let mgmt_class = mad[1] as u32;
+        let mut status = u16::from_be_bytes([mad[4], mad[5]]);
+
+        //The top bit of a directed route status is the direction bit
+        if mgmt_class == MAD_CLASSES_IB_SMI_DIRECT_CLASS {
+            status &= 0x7fff;
+        }
+
+        let offset = class_data_offset(mgmt_class);
+        let size = class_data_size(mgmt_class);
+
+        if mgmt_class != MAD_CLASSES_IB_SA_CLASS {
+            return MadResponse {
+                status,
+                data: mad[offset..offset + size].to_vec(),
+                attr_offset: 0,
+            };
+        }
+
+        //A single segment RMPP response says how much of it is data. The
+        //PayloadLength counts from the end of the RMPP header.
+        let mut end = mad.len();
+        let rmpp = RmppHeader::from_mad(mad);
+        if rmpp.flags == RMPP_FLAG_ACTIVE | RMPP_FLAG_FIRST | RMPP_FLAG_LAST {
+            end = end.min(RMPP_HDR_OFFSET + RMPP_HDR_SIZE + rmpp.paylen_newwin as usize).max(offset);
+        }
+
+        MadResponse {
+            status,
+            data: mad[offset..end].to_vec(),
+            attr_offset: u16::from_be_bytes([mad[SA_ATTR_OFFSET_OFFSET], mad[SA_ATTR_OFFSET_OFFSET + 1]]),
+        }
+    }
+
+    //Attribute data of a successful response, padded to the class data size
+    pub fn into_data(self, mgmt_class: u32) -> Result<Vec<u8>, IBSmpError> {
+        if self.status != 0 {
+            return Err(IBSmpError::MadStatusError(self.status));
+        }
+
+        let mut data = self.data;
+        let size = class_data_size(mgmt_class);
+        if data.len() < size {
+            data.resize(size, 0);
+        }
+
+        Ok(data)
+    }
+}
+
+//Offset of the attribute data within a MAD for a management class
+pub fn class_data_offset(mgmt_class: u32) -> usize {
+    match mgmt_class {
+        MAD_CLASSES_IB_SMI_CLASS | MAD_CLASSES_IB_SMI_DIRECT_CLASS => IB_SMP_DATA_OFFS as usize,
+        MAD_CLASSES_IB_SA_CLASS => IB_SA_DATA_OFFS as usize,
+        MAD_CLASSES_IB_VENDOR_RANGE1_START_CLASS..=MAD_CLASSES_IB_VENDOR_RANGE1_END_CLASS => IB_VENDOR_RANGE1_DATA_OFFS as usize,
+        MAD_CLASSES_IB_VENDOR_RANGE2_START_CLASS..=MAD_CLASSES_IB_VENDOR_RANGE2_END_CLASS => IB_VENDOR_RANGE2_DATA_OFFS as usize,
+        _ => IB_PC_DATA_OFFS as usize,
+    }
+}
+
+pub fn class_version(mgmt_class: u32) -> u8 {
+    match mgmt_class {
+        MAD_CLASSES_IB_SA_CLASS | MAD_CLASSES_IB_CC_CLASS => 2,
+        _ => 1,
+    }
+}
+
+//Size of the attribute data within a MAD for a management class
+pub fn class_data_size(mgmt_class: u32) -> usize {
+    match mgmt_class {
+        MAD_CLASSES_IB_SMI_CLASS | MAD_CLASSES_IB_SMI_DIRECT_CLASS => IB_SMP_DATA_SIZE as usize,
+        _ => IB_MAD_SIZE as usize - class_data_offset(mgmt_class),
+    }
+}
+
+impl MadRequest {
+    pub fn new(address: MadAddress, mgmt_class: u32, method: u32, attr_id: u32, attr_mod: u32, timeout: u32) -> MadRequest {
+        MadRequest {
+            address,
+            mgmt_class,
+            method,
+            attr_id,
+            attr_mod,
+            data: vec![0; class_data_size(mgmt_class)],
+            pkey_index: 0,
+            comp_mask: 0,
+            timeout,
+        }
+    }
+
+    pub fn smp_get(address: MadAddress, attr_id: u32, attr_mod: u32, timeout: u32) -> MadRequest {
+        let mgmt_class = match address {
+            MadAddress::Lid(_) => MAD_CLASSES_IB_SMI_CLASS,
+            MadAddress::DirectedRoute(_) => MAD_CLASSES_IB_SMI_DIRECT_CLASS,
+        };
+        MadRequest::new(address, mgmt_class, MAD_METHODS_IB_MAD_METHOD_GET, attr_id, attr_mod, timeout)
+    }
+
+    pub fn smp_set(address: MadAddress, attr_id: u32, attr_mod: u32, data: &[u8], timeout: u32) -> MadRequest {
+        let mut request = MadRequest::smp_get(address, attr_id, attr_mod, timeout);
+        request.method = MAD_METHODS_IB_MAD_METHOD_SET;
+        request.with_data(data)
+    }
+
+    //PMA attributes carry the port in the PortSelect field of the data
+    pub fn pma_get(lid: u16, attr_id: u32, portnum: i32, timeout: u32) -> MadRequest {
+        let mut request = MadRequest::new(MadAddress::Lid(lid), MAD_CLASSES_IB_PERFORMANCE_CLASS, MAD_METHODS_IB_MAD_METHOD_GET, attr_id, 0, timeout);
+        unsafe {
+            mad_set_field(request.data.as_mut_ptr() as *mut c_void, 0, MAD_FIELDS_IB_PC_PORT_SELECT_F, portnum as u32);
+        }
+        request
+    }
+
+    //data carries the PortSelect, as filled in by the caller
+    pub fn pma_set(lid: u16, attr_id: u32, data: &[u8], timeout: u32) -> MadRequest {
+        let request = MadRequest::new(MadAddress::Lid(lid), MAD_CLASSES_IB_PERFORMANCE_CLASS, MAD_METHODS_IB_MAD_METHOD_SET, attr_id, 0, timeout);
+        request.with_data(data)
+    }
+
+    pub fn with_data(mut self, data: &[u8]) -> MadRequest {
+        let len = data.len().min(self.data.len());
+        self.data[..len].copy_from_slice(&data[..len]);
+        self
+    }
+
+    pub fn with_pkey_index(mut self, pkey_index: u32) -> MadRequest {
+        self.pkey_index = pkey_index;
+        self
+    }
+
+    pub fn with_comp_mask(mut self, comp_mask: u64) -> MadRequest {
+        self.comp_mask = comp_mask;
+        self
+    }
+
+    //Wire format of the request, as it would be handed to umad_send
+    pub fn encode(&self, tid: u64) -> [u8; IB_MAD_SIZE as usize] {
+        let mut mad = [0; IB_MAD_SIZE as usize];
+
+        mad[0] = 1; //Base Version
+        mad[1] = self.mgmt_class as u8;
+        mad[2] = class_version(self.mgmt_class);
+        mad[3] = self.method as u8;
+        mad[8..16].copy_from_slice(&tid.to_be_bytes());
+        mad[16..18].copy_from_slice(&(self.attr_id as u16).to_be_bytes());
+        mad[20..24].copy_from_slice(&self.attr_mod.to_be_bytes());
+
+        if let MadAddress::DirectedRoute(path) = &self.address {
+            mad[7] = path.hops.len() as u8; //Hop Count
+            mad[32..34].copy_from_slice(&0xffff_u16.to_be_bytes()); //DrSLID
+            mad[34..36].copy_from_slice(&0xffff_u16.to_be_bytes()); //DrDLID
+            mad[DR_INITIAL_PATH_OFFSET + 1..DR_INITIAL_PATH_OFFSET + 1 + path.hops.len()].copy_from_slice(&path.hops);
+        }
+
+        if self.mgmt_class == MAD_CLASSES_IB_SA_CLASS {
+            mad[SA_COMP_MASK_OFFSET..SA_COMP_MASK_OFFSET + 8].copy_from_slice(&self.comp_mask.to_be_bytes());
+        }
+
+        let offset = class_data_offset(self.mgmt_class);
+        let len = self.data.len().min(class_data_size(self.mgmt_class));
+        mad[offset..offset + len].copy_from_slice(&self.data[..len]);
+
+        mad
+    }
+
+    //The request addressed and ready for umad_send, less the agent id
+    pub fn to_umad(&self, tid: u64) -> ib_user_mad {
+        let mut umad = ib_user_mad::new();
+        umad.addr.pkey_index = self.pkey_index as u16;
+        umad.addr.lid = match &self.address {
+            MadAddress::Lid(lid) => lid.to_be(),
+            MadAddress::DirectedRoute(_) => 0xffff_u16.to_be(),
+        };
+        if self.mgmt_class != MAD_CLASSES_IB_SMI_CLASS && self.mgmt_class != MAD_CLASSES_IB_SMI_DIRECT_CLASS {
+            umad.addr.qpn = 1_u32.to_be();
+            umad.addr.qkey = IB_DEFAULT_QP1_QKEY.to_be();
+        }
+        umad.data[..IB_MAD_SIZE as usize].copy_from_slice(&self.encode(tid));
+        umad
+    }
+
+    fn to_portid(&self) -> ib_portid_t {
+        let mut portid = ib_portid_t {
+            lid: 0,
+            drpath: unsafe { MaybeUninit::<ib_dr_path_t>::zeroed().assume_init() },
+            grh_present: 0,
+            gid: unsafe { MaybeUninit::<[u8; 16]>::zeroed().assume_init() },
+            qp: 0,
+            qkey: 0,
+            sl: 0,
+            pkey_idx: self.pkey_index,
+        };
+
+        match &self.address {
+            MadAddress::Lid(lid) => portid.lid = (*lid).into(),
+            MadAddress::DirectedRoute(path) => portid.drpath = path.to_ib_dr_path(),
+        }
+
+        if self.mgmt_class != MAD_CLASSES_IB_SMI_CLASS && self.mgmt_class != MAD_CLASSES_IB_SMI_DIRECT_CLASS {
+            portid.qp = 1;
+            portid.qkey = IB_DEFAULT_QP1_QKEY;
+        }
+
+        portid
+    }
+}
+
+pub trait MadTransport {
+    //Send a request and wait for its response. A response with a non-zero
+    //status is still a response; Err means nothing usable came back.
+    fn call(&self, request: &MadRequest) -> Result<MadResponse, IBSmpError>;
+
+    //Send a request and return its attribute data, failing on a bad status
+    fn query(&self, request: &MadRequest) -> Result<Vec<u8>, IBSmpError> {
+        self.call(request)?.into_data(request.mgmt_class)
+    }
+
+    //Send several requests. Transports that can keep MADs outstanding
+    //concurrently override this, by default they go one at a time.
+    fn call_batch(&self, requests: &[MadRequest]) -> Vec<Result<MadResponse, IBSmpError>> {
+        requests.iter().map(|request| self.call(request)).collect()
+    }
+
+    fn query_batch(&self, requests: &[MadRequest]) -> Vec<Result<Vec<u8>, IBSmpError>> {
+        self.call_batch(requests)
+            .into_iter()
+            .zip(requests)
+            .map(|(response, request)| response.and_then(|r| r.into_data(request.mgmt_class)))
+            .collect()
+    }
+}
+
+impl<T: MadTransport + ?Sized> MadTransport for Arc<T> {
+    fn call(&self, request: &MadRequest) -> Result<MadResponse, IBSmpError> {
+        (**self).call(request)
+    }
+
+    fn call_batch(&self, requests: &[MadRequest]) -> Vec<Result<MadResponse, IBSmpError>> {
+        (**self).call_batch(requests)
+    }
+}
+
+impl MadTransport for IBMadPort {
+    fn call(&self, request: &MadRequest) -> Result<MadResponse, IBSmpError> {
+        //mad_rpc only hands back the data, SA needs the attribute offset and
+        //RMPP header too
+        if request.mgmt_class == MAD_CLASSES_IB_SA_CLASS {
+            return self.call_batch(std::slice::from_ref(request)).remove(0);
+        }
+
+        let mut portid = request.to_portid();
+        let mut data = request.data.clone();
+        data.resize(class_data_size(request.mgmt_class).max(data.len()), 0);
+        let data_ptr = data.as_mut_ptr() as *mut c_void;
+
+        let (r, status) = match request.mgmt_class {
+            MAD_CLASSES_IB_SMI_CLASS | MAD_CLASSES_IB_SMI_DIRECT_CLASS => {
+                let mut rstatus: i32 = 0;
+                let r = if request.method == MAD_METHODS_IB_MAD_METHOD_SET {
+                    unsafe { smp_set_status_via(data_ptr, &mut portid, request.attr_id, request.attr_mod, request.timeout, &mut rstatus, self.port) }
+                } else {
+                    unsafe { smp_query_status_via(data_ptr, &mut portid, request.attr_id, request.attr_mod, request.timeout, &mut rstatus, self.port) }
+                };
+                (r as *mut c_void, rstatus as u16)
+            }
+            _ => {
+                let mut rpc = ib_rpc_t {
+                    mgtclass: request.mgmt_class as i32,
+                    method: request.method as i32,
+                    attr: ib_attr_t {
+                        id: request.attr_id,
+                        mod_: request.attr_mod,
+                    },
+                    rstatus: 0,
+                    dataoffs: class_data_offset(request.mgmt_class) as i32,
+                    datasz: class_data_size(request.mgmt_class) as i32,
+                    mkey: 0,
+                    trid: unsafe { mad_trid() },
+                    mask: request.comp_mask,
+                    recsz: 0,
+                    timeout: request.timeout as i32,
+                    oui: 0,
+                };
+                let r = unsafe { mad_rpc(self.port, &mut rpc, &mut portid, data_ptr, data_ptr) };
+                (r, rpc.rstatus as u16)
+            }
+        };
+
+        //libibmad drops responses with an error status, but still reports the status
+        if r.is_null() && status == 0 {
+            return Err(IBSmpError::SendMADError);
+        }
+
+        Ok(MadResponse { status, data, attr_offset: 0 })
+    }
+
+    //Keep the whole batch outstanding on the port's umad agents and match
+    //the responses back up by TID
+    fn call_batch(&self, requests: &[MadRequest]) -> Vec<Result<MadResponse, IBSmpError>> {
+        let fd = unsafe { mad_rpc_portid(self.port) };
+        let mut results: Vec<Option<Result<MadResponse, IBSmpError>>> = requests.iter().map(|_| None).collect();
+        let mut pending: HashMap<u32, usize> = HashMap::new();
+        let mut timeout = 0;
+
+        for (i, request) in requests.iter().enumerate() {
+            let agent_id = unsafe { mad_rpc_class_agent(self.port, request.mgmt_class as i32) };
+            if agent_id < 0 {
+                results[i] = Some(Err(IBSmpError::SendMADError));
+                continue;
+            }
+            let tid = unsafe { mad_trid() };
+
+            let mut umad = request.to_umad(tid);
+            umad.agent_id = agent_id as u32;
+
+            let r = unsafe { umad_send(fd, agent_id, umad.as_c_void_ptr(), IB_MAD_SIZE as i32, request.timeout as i32, 0) };
+            if r < 0 {
+                results[i] = Some(Err(IBSmpError::SendMADError));
+                continue;
+            }
+
+            //The kernel owns the upper 32 bits of the TID
+            pending.insert(tid as u32, i);
+            timeout = timeout.max(request.timeout);
+        }
+
+        let hdr_size = offset_of!(ib_user_mad, data);
+        while !pending.is_empty() {
+            let mut length = IB_MAD_SIZE as i32;
+            let mut buf = vec![0_u8; hdr_size + length as usize];
+
+            let mut r = unsafe { umad_recv(fd, buf.as_mut_ptr() as *mut c_void, &mut length, timeout as i32) };
+
+            //RMPP responses reassembled by the kernel don't fit in one MAD,
+            //it hands back the length needed and keeps the response queued
+            if r == -libc::ENOSPC {
+                buf.resize(hdr_size + length as usize, 0);
+                r = unsafe { umad_recv(fd, buf.as_mut_ptr() as *mut c_void, &mut length, timeout as i32) };
+            }
+            if r < 0 {
+                break;
+            }
+
+            let status = u32::from_ne_bytes([buf[4], buf[5], buf[6], buf[7]]);
+            let mad = &buf[hdr_size..hdr_size + (length as usize).max(IB_MAD_SIZE as usize)];
+
+            let tid = u32::from_be_bytes([mad[12], mad[13], mad[14], mad[15]]);
+            let Some(i) = pending.remove(&tid) else {
+                continue;
+            };
+
+            //A non-zero umad status means the send itself timed out
+            results[i] = Some(if status != 0 {
+                Err(IBSmpError::SendMADError)
+            } else {
+                Ok(MadResponse::decode(mad))
+            });
+        }
+
+        results.into_iter().map(|r| r.unwrap_or(Err(IBSmpError::SendMADError))).collect()
+    }
+}
+
+//For fabrics loaded from a file, there's no port to send MADs through
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OfflineTransport;
+
+impl MadTransport for OfflineTransport {
+    fn call(&self, _request: &MadRequest) -> Result<MadResponse, IBSmpError> {
+        Err(IBSmpError::SendMADError)
+    }
+}
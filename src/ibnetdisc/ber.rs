@@ -0,0 +1,126 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::ibmad::{
+    self,
+    perf::ExtPerfCounters,
+    port::{LinkSpeed, LinkWidth},
+};
+
+use super::{fabric::Fabric, sampler::PortSample};
+
+//The bit error rate the IBA asks of a link
+pub const DEFAULT_MAX_BER: f64 = 1e-12;
+
+//Errors per bit sent over a link of width and speed in window. The errors
+//needn't be symbol errors, vendor FEC or PhysLayer counts work as well.
+pub fn bit_error_rate(errors: u64, width: LinkWidth, speed: LinkSpeed, window: Duration) -> Option<f64> {
+    let bits = width.lanes() as f64 * speed.lane_gbps() * 1e9 * window.as_secs_f64();
+    if bits <= 0.0 {
+        return None;
+    }
+    Some(errors as f64 / bits)
+}
+
+//Effective bit error rate of a port's link over a window of samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkBer {
+    //Key of the port in Fabric::ports
+    pub port: (u64, i32),
+    pub width: LinkWidth,
+    pub speed: LinkSpeed,
+    pub window: Duration,
+    pub symbol_errors: u64,
+    pub ber: f64,
+}
+
+impl LinkBer {
+    pub fn is_marginal(&self, max_ber: f64) -> bool {
+        self.ber > max_ber
+    }
+}
+
+//Keeps the symbol errors of each port over a window of sampler rounds and
+//estimates the BER of its link from them, at the width and speed PortInfo
+//has active. PortInfo shows FDR10 as QDR, so QDR ports are asked for their
+//MlnxExtPortInfo once.
+pub struct BerMonitor {
+    pub window: Duration,
+    pub timeout: u32,
+    history: HashMap<(u64, i32), VecDeque<(Instant, ExtPerfCounters)>>,
+    qdr_speeds: HashMap<(u64, i32), LinkSpeed>,
+}
+
+impl BerMonitor {
+    pub fn new(window: Duration) -> BerMonitor {
+        BerMonitor { window, timeout: 200, history: HashMap::new(), qdr_speeds: HashMap::new() }
+    }
+
+    pub fn with_timeout(mut self, timeout: u32) -> BerMonitor {
+        self.timeout = timeout;
+        self
+    }
+
+    //Add a round of samples, returning the BER of every port with at least
+    //two samples. Ports missing from the round are forgotten.
+    pub fn add_round(&mut self, fabric: &Fabric, samples: &[PortSample]) -> Vec<LinkBer> {
+        let mut history = HashMap::new();
+        let mut qdr_speeds = HashMap::new();
+        let mut rates = Vec::new();
+
+        for sample in samples {
+            let mut errors = self.history.remove(&sample.port).unwrap_or_default();
+
+            //A reset, what came before no longer adds up
+            if errors.back().is_some_and(|(_, before)| before.symbol_errors > sample.counters.symbol_errors) {
+                errors.clear();
+            }
+            errors.push_back((sample.time, sample.counters));
+
+            //Keep the newest samples that span the window
+            while errors.len() > 2 && sample.time.duration_since(errors[1].0) >= self.window {
+                errors.pop_front();
+            }
+
+            if let Some(ber) = self.estimate(fabric, sample, &errors) {
+                rates.push(ber);
+            }
+            history.insert(sample.port, errors);
+            if let Some(speed) = self.qdr_speeds.remove(&sample.port) {
+                qdr_speeds.insert(sample.port, speed);
+            }
+        }
+
+        self.history = history;
+        self.qdr_speeds = qdr_speeds;
+        rates.sort_by_key(|ber| ber.port);
+        rates
+    }
+
+    fn estimate(&mut self, fabric: &Fabric, sample: &PortSample, errors: &VecDeque<(Instant, ExtPerfCounters)>) -> Option<LinkBer> {
+        let (Some((first_time, first)), Some((last_time, last))) = (errors.front(), errors.back()) else {
+            return None;
+        };
+
+        let key = sample.port;
+        let port = fabric.ports.get(&key)?.borrow();
+        let info = port.info.as_ref()?;
+        let width = info.link_width();
+        let speed = match info.link_speed() {
+            //Ports without MlnxExtPortInfo can't run FDR10
+            LinkSpeed::Qdr => *self.qdr_speeds.entry(key).or_insert_with(|| {
+                ibmad::send_lid_mlnx_ext_port_info_mad(fabric.ib_port.as_ref(), sample.lid as i32, port.number, self.timeout)
+                    .map_or(LinkSpeed::Qdr, |ext| info.link_speed_mlnx(&ext))
+            }),
+            speed => speed,
+        };
+
+        let window = last_time.checked_duration_since(*first_time).filter(|d| !d.is_zero())?;
+        let symbol_errors = last.delta(first).symbol_errors;
+        let ber = bit_error_rate(symbol_errors, width, speed, window)?;
+
+        Some(LinkBer { port: key, width, speed, window, symbol_errors, ber })
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path,
+};
+
+use thiserror::Error;
+
+use crate::ibmad;
+use crate::ibmad::port::{LinkSpeed, LinkWidth};
+
+use super::fabric::Fabric;
+
+#[derive(Error, Debug)]
+pub enum CablingError {
+    #[error("Unable to read cabling plan: {0}")]
+    ReadError(#[from] std::io::Error),
+    #[error("Line {0}: {1}")]
+    ParseError(usize, String),
+}
+
+//A port by the NodeDescription of its node
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Endpoint {
+    pub name: String,
+    pub port: i32,
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"[{}]", self.name, self.port)
+    }
+}
+
+//A cable of the plan, with the width and speed it should run at if given
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedLink {
+    pub line: usize,
+    pub a: Endpoint,
+    pub b: Endpoint,
+    pub width: Option<LinkWidth>,
+    pub speed: Option<LinkSpeed>,
+}
+
+//A planned cable with an end linked somewhere else. actual_a is what a is
+//linked to, if anything, same for b.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Miswire {
+    pub planned: PlannedLink,
+    pub actual_a: Option<Endpoint>,
+    pub actual_b: Option<Endpoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateMismatch {
+    pub planned: PlannedLink,
+    pub width: LinkWidth,
+    pub speed: LinkSpeed,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CablingReport {
+    pub miswired: Vec<Miswire>,
+    //Planned cables with neither end linked
+    pub missing: Vec<PlannedLink>,
+    //Links with neither end in the plan
+    pub unexpected: Vec<(Endpoint, Endpoint)>,
+    pub wrong_rate: Vec<RateMismatch>,
+}
+
+impl CablingReport {
+    pub fn is_clean(&self) -> bool {
+        self.miswired.is_empty() && self.missing.is_empty() && self.unexpected.is_empty() && self.wrong_rate.is_empty()
+    }
+}
+
+//Fields split on commas, a field in double quotes can hold commas
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+fn parse_width(text: &str) -> Option<LinkWidth> {
+    [LinkWidth::X1, LinkWidth::X2, LinkWidth::X4, LinkWidth::X8, LinkWidth::X12].into_iter().find(|w| w.to_string().eq_ignore_ascii_case(text))
+}
+
+fn parse_speed(text: &str) -> Option<LinkSpeed> {
+    [LinkSpeed::Sdr, LinkSpeed::Ddr, LinkSpeed::Qdr, LinkSpeed::Fdr10, LinkSpeed::Fdr, LinkSpeed::Edr, LinkSpeed::Hdr, LinkSpeed::Ndr]
+        .into_iter()
+        .find(|s| s.to_string().eq_ignore_ascii_case(text))
+}
+
+//The cables a fabric should have, from a CSV of
+//name,port,remote name,remote port[,width,speed]
+//where names are NodeDescriptions, width is 4x and speed HDR. Blank lines and
+//lines starting with # are skipped, as is a header line.
+#[derive(Debug, Clone, Default)]
+pub struct CablingPlan {
+    pub links: Vec<PlannedLink>,
+}
+
+impl CablingPlan {
+    pub fn from_csv(csv: &str) -> Result<CablingPlan, CablingError> {
+        let mut links = Vec::new();
+        let mut first = true;
+
+        for (i, line) in csv.lines().enumerate() {
+            let line_number = i + 1;
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            let fields = split_fields(line);
+            let header = std::mem::take(&mut first) && fields.get(1).is_some_and(|f| f.parse::<i32>().is_err());
+            if header {
+                continue;
+            }
+            if fields.len() < 4 {
+                return Err(CablingError::ParseError(line_number, format!("Expected at least 4 fields, found {}", fields.len())));
+            }
+
+            let (Ok(port_a), Ok(port_b)) = (fields[1].parse::<i32>(), fields[3].parse::<i32>()) else {
+                return Err(CablingError::ParseError(line_number, String::from("Bad port number")));
+            };
+
+            let field = |i: usize| fields.get(i).filter(|f| !f.is_empty());
+            let width = match field(4) {
+                Some(w) => Some(parse_width(w).ok_or_else(|| CablingError::ParseError(line_number, format!("Unknown width {}", w)))?),
+                None => None,
+            };
+            let speed = match field(5) {
+                Some(s) => Some(parse_speed(s).ok_or_else(|| CablingError::ParseError(line_number, format!("Unknown speed {}", s)))?),
+                None => None,
+            };
+
+            links.push(PlannedLink {
+                line: line_number,
+                a: Endpoint { name: fields[0].clone(), port: port_a },
+                b: Endpoint { name: fields[2].clone(), port: port_b },
+                width,
+                speed,
+            });
+        }
+
+        Ok(CablingPlan { links })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<CablingPlan, CablingError> {
+        CablingPlan::from_csv(&std::fs::read_to_string(path)?)
+    }
+
+    //Check the links of a fabric against the plan. Nodes are matched by
+    //NodeDescription, so those in the plan need to be unique.
+    pub fn validate(&self, fabric: &Fabric) -> CablingReport {
+        let mut linked: BTreeMap<Endpoint, (Endpoint, LinkWidth, LinkSpeed)> = BTreeMap::new();
+
+        for port in fabric.ports.values() {
+            let port = port.borrow();
+            let (Some(node), Some(remote_node), Some(remote)) = (
+                port.parent.as_ref().and_then(|n| n.upgrade()),
+                port.remote_node.as_ref().and_then(|n| n.upgrade()),
+                port.remote_port.as_ref().and_then(|p| p.upgrade()),
+            ) else {
+                continue;
+            };
+
+            let end = Endpoint { name: node.borrow().node_desc.clone(), port: port.number };
+            let remote_end = Endpoint { name: remote_node.borrow().node_desc.clone(), port: remote.borrow().number };
+            let (width, speed) = match port.info.as_ref() {
+                //FDR10 shows up as QDR, only ExtendedPortInfo tells them apart
+                Some(info) if info.link_speed() == LinkSpeed::Qdr => {
+                    let speed = ibmad::send_lid_mlnx_ext_port_info_mad(fabric.ib_port.as_ref(), port.base_lid as i32, port.number, 200)
+                        .map_or(LinkSpeed::Qdr, |ext| info.link_speed_mlnx(&ext));
+                    (info.link_width(), speed)
+                }
+                Some(info) => (info.link_width(), info.link_speed()),
+                None => (LinkWidth::Unknown, LinkSpeed::Unknown),
+            };
+            linked.insert(end, (remote_end, width, speed));
+        }
+
+        let mut report = CablingReport::default();
+        let mut planned_ends: BTreeSet<&Endpoint> = BTreeSet::new();
+
+        for link in &self.links {
+            planned_ends.insert(&link.a);
+            planned_ends.insert(&link.b);
+
+            let actual_a = linked.get(&link.a);
+            let actual_b = linked.get(&link.b);
+
+            match actual_a {
+                Some((remote, width, speed)) if *remote == link.b => {
+                    if link.width.is_some_and(|w| w != *width) || link.speed.is_some_and(|s| s != *speed) {
+                        report.wrong_rate.push(RateMismatch { planned: link.clone(), width: *width, speed: *speed });
+                    }
+                }
+                None if actual_b.is_none() => report.missing.push(link.clone()),
+                _ => report.miswired.push(Miswire {
+                    planned: link.clone(),
+                    actual_a: actual_a.map(|(remote, _, _)| remote.clone()),
+                    actual_b: actual_b.map(|(remote, _, _)| remote.clone()),
+                }),
+            }
+        }
+
+        //Each link is in linked from both ends, the lower one reports it
+        for (end, (remote, _, _)) in &linked {
+            if end < remote && !planned_ends.contains(end) && !planned_ends.contains(remote) {
+                report.unexpected.push((end.clone(), remote.clone()));
+            }
+        }
+
+        report
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use super::{fabric::Fabric, graph::port_state_name, node::NodeType};
+
+//A port by node GUID and port number, so CA ports read the same as switch
+//ports
+pub type PortKey = (u64, i32);
+
+//PortInfo:PortPhysicalState
+fn phys_state_name(state: u32) -> &'static str {
+    match state {
+        1 => "Sleep",
+        2 => "Polling",
+        3 => "Disabled",
+        4 => "PortConfigurationTraining",
+        5 => "LinkUp",
+        6 => "LinkErrorRecovery",
+        7 => "PhyTest",
+        _ => "Unknown",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeRef {
+    pub guid: u64,
+    pub node_desc: String,
+    pub node_type: NodeType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LidChange {
+    pub port: PortKey,
+    //Base LID and LMC
+    pub before: (u16, u8),
+    pub after: (u16, u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescChange {
+    pub guid: u64,
+    pub before: String,
+    pub after: String,
+}
+
+//A cable with one end where it was and the other on a different port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkMove {
+    pub end: PortKey,
+    pub before: PortKey,
+    pub after: PortKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortStateChange {
+    pub port: PortKey,
+    //PortState and PortPhysicalState
+    pub before: (u32, u32),
+    pub after: (u32, u32),
+}
+
+//What changed from one fabric to another, everything in key order. Links are
+//listed once, the lower end first.
+#[derive(Debug, Clone, Default)]
+pub struct TopologyDiff {
+    pub nodes_added: Vec<NodeRef>,
+    pub nodes_removed: Vec<NodeRef>,
+    pub lid_changes: Vec<LidChange>,
+    pub desc_changes: Vec<DescChange>,
+    pub links_added: Vec<(PortKey, PortKey)>,
+    pub links_removed: Vec<(PortKey, PortKey)>,
+    pub links_moved: Vec<LinkMove>,
+    pub port_state_changes: Vec<PortStateChange>,
+}
+
+//What a diff needs of a fabric
+struct Topology {
+    nodes: BTreeMap<u64, NodeRef>,
+    lids: BTreeMap<PortKey, (u16, u8)>,
+    states: BTreeMap<PortKey, (u32, u32)>,
+    links: BTreeSet<(PortKey, PortKey)>,
+}
+
+impl Topology {
+    fn of(fabric: &Fabric) -> Topology {
+        let mut topology = Topology { nodes: BTreeMap::new(), lids: BTreeMap::new(), states: BTreeMap::new(), links: BTreeSet::new() };
+
+        for node in fabric.nodes.values() {
+            let node = node.borrow();
+            topology.nodes.insert(node.guid, NodeRef { guid: node.guid, node_desc: node.node_desc.clone(), node_type: node.node_type });
+        }
+
+        for port in fabric.ports.values() {
+            let port = port.borrow();
+            let Some(node) = port.parent.as_ref().and_then(|n| n.upgrade()) else {
+                continue;
+            };
+            let key = (node.borrow().guid, port.number);
+            topology.states.insert(key, (port.logical_state, port.phys_state));
+            topology.lids.insert(key, (port.base_lid, port.info.as_ref().map_or(0, |i| i.lmc)));
+
+            let remote_node = port.remote_node.as_ref().and_then(|n| n.upgrade());
+            let remote = port.remote_port.as_ref().and_then(|p| p.upgrade());
+            if let (Some(remote_node), Some(remote)) = (remote_node, remote) {
+                let remote_key = (remote_node.borrow().guid, remote.borrow().number);
+                topology.links.insert(if key <= remote_key { (key, remote_key) } else { (remote_key, key) });
+            }
+        }
+
+        topology
+    }
+}
+
+impl TopologyDiff {
+    pub fn between(before: &Fabric, after: &Fabric) -> TopologyDiff {
+        let (before, after) = (Topology::of(before), Topology::of(after));
+        let mut diff = TopologyDiff::default();
+
+        for (guid, node) in &after.nodes {
+            match before.nodes.get(guid) {
+                None => diff.nodes_added.push(node.clone()),
+                Some(old) if old.node_desc != node.node_desc => {
+                    diff.desc_changes.push(DescChange { guid: *guid, before: old.node_desc.clone(), after: node.node_desc.clone() });
+                }
+                Some(_) => {}
+            }
+        }
+        diff.nodes_removed = before.nodes.iter().filter(|(guid, _)| !after.nodes.contains_key(guid)).map(|(_, node)| node.clone()).collect();
+
+        for (port, lid) in &after.lids {
+            if let Some(old) = before.lids.get(port).filter(|old| *old != lid) {
+                diff.lid_changes.push(LidChange { port: *port, before: *old, after: *lid });
+            }
+        }
+
+        for (port, state) in &after.states {
+            if let Some(old) = before.states.get(port).filter(|old| *old != state) {
+                diff.port_state_changes.push(PortStateChange { port: *port, before: *old, after: *state });
+            }
+        }
+
+        //A removed link and an added one are the same cable moved when they
+        //share an end, on a node in both fabrics, and the other end went to
+        //another port of the same node. Another node on the same port is a
+        //removal and an addition.
+        let mut added: Vec<(PortKey, PortKey)> = after.links.difference(&before.links).copied().collect();
+        for (a, b) in before.links.difference(&after.links) {
+            let moved = [(*a, *b), (*b, *a)].into_iter().find_map(|(end, old)| {
+                if !before.nodes.contains_key(&end.0) || !after.nodes.contains_key(&end.0) {
+                    return None;
+                }
+                let other = |(x, y): &(PortKey, PortKey)| match (*x == end, *y == end) {
+                    (true, _) => Some(*y),
+                    (_, true) => Some(*x),
+                    _ => None,
+                };
+                let i = added.iter().position(|link| other(link).is_some_and(|new| new.0 == old.0 && new != old))?;
+                let new = other(&added.remove(i))?;
+                Some(LinkMove { end, before: old, after: new })
+            });
+
+            match moved {
+                Some(moved) => diff.links_moved.push(moved),
+                None => diff.links_removed.push((*a, *b)),
+            }
+        }
+        diff.links_added = added;
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes_added.is_empty()
+            && self.nodes_removed.is_empty()
+            && self.lid_changes.is_empty()
+            && self.desc_changes.is_empty()
+            && self.links_added.is_empty()
+            && self.links_removed.is_empty()
+            && self.links_moved.is_empty()
+            && self.port_state_changes.is_empty()
+    }
+}
+
+//One change per line, + added, - removed, ~ changed and > moved
+impl fmt::Display for TopologyDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let port = |(guid, number): PortKey| format!("0x{:016x}[{}]", guid, number);
+
+        for node in &self.nodes_added {
+            writeln!(f, "+ node 0x{:016x} \"{}\"", node.guid, node.node_desc)?;
+        }
+        for node in &self.nodes_removed {
+            writeln!(f, "- node 0x{:016x} \"{}\"", node.guid, node.node_desc)?;
+        }
+        for change in &self.lid_changes {
+            writeln!(
+                f,
+                "~ lid {} {} lmc {} -> {} lmc {}",
+                port(change.port),
+                change.before.0,
+                change.before.1,
+                change.after.0,
+                change.after.1
+            )?;
+        }
+        for change in &self.desc_changes {
+            writeln!(f, "~ node_desc 0x{:016x} \"{}\" -> \"{}\"", change.guid, change.before, change.after)?;
+        }
+        for (a, b) in &self.links_added {
+            writeln!(f, "+ link {} -- {}", port(*a), port(*b))?;
+        }
+        for (a, b) in &self.links_removed {
+            writeln!(f, "- link {} -- {}", port(*a), port(*b))?;
+        }
+        for moved in &self.links_moved {
+            writeln!(f, "> link {} moved from {} to {}", port(moved.end), port(moved.before), port(moved.after))?;
+        }
+        for change in &self.port_state_changes {
+            writeln!(
+                f,
+                "~ port {} {}/{} -> {}/{}",
+                port(change.port),
+                port_state_name(change.before.0),
+                phys_state_name(change.before.1),
+                port_state_name(change.after.0),
+                phys_state_name(change.after.1)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::ibmad::{
+    port::PortInfo,
+    switch::SwitchInfo,
+    sys::*,
+    transport::{DrPath, MadAddress, MadRequest, MadTransport},
+    IBSmpError, NodeInfo,
+};
+
+use super::{fabric::{Fabric, FabricError}, node::{Node, NodeType}, port::Port};
+
+//Same default as libibnetdisc
+pub const DEFAULT_MAX_SMPS: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct DiscoverConfig {
+    //SMPs kept outstanding at once
+    pub max_smps: usize,
+    //0 for no limit
+    pub max_hops: usize,
+    pub timeout: u32,
+    pub retries: u32,
+}
+
+impl Default for DiscoverConfig {
+    fn default() -> Self {
+        DiscoverConfig {
+            max_smps: DEFAULT_MAX_SMPS,
+            max_hops: 0,
+            timeout: 200,
+            retries: 3,
+        }
+    }
+}
+
+//A query that still failed after its retries. Discovery carries on without
+//whatever was behind it.
+#[derive(Debug)]
+pub struct DiscoveryFailure {
+    pub path: DrPath,
+    pub attr_id: u32,
+    pub attr_mod: u32,
+    pub error: IBSmpError,
+}
+
+#[derive(Debug, Default)]
+pub struct DiscoveryReport {
+    pub failures: Vec<DiscoveryFailure>,
+}
+
+#[derive(Debug, Default)]
+struct FoundPort {
+    guid: u64,
+    info: Option<PortInfo>,
+    remote: Option<(u64, u8)>,
+}
+
+#[derive(Debug)]
+struct FoundNode {
+    info: NodeInfo,
+    path: DrPath,
+    node_desc: String,
+    switch_info: Option<SwitchInfo>,
+    ports: HashMap<u8, FoundPort>,
+}
+
+//What a per-node query was for
+enum Query {
+    NodeDesc,
+    SwitchInfo,
+    PortInfo(u8),
+}
+
+impl FoundNode {
+    fn is_switch(&self) -> bool {
+        self.info.node_type == MAD_NODE_TYPE_IB_NODE_SWITCH as u64
+    }
+}
+
+struct Discovery<'a, T: MadTransport + ?Sized> {
+    transport: &'a T,
+    config: &'a DiscoverConfig,
+    nodes: HashMap<u64, FoundNode>,
+    report: DiscoveryReport,
+}
+
+impl<'a, T: MadTransport + ?Sized> Discovery<'a, T> {
+    fn new(transport: &'a T, config: &'a DiscoverConfig) -> Self {
+        Discovery {
+            transport,
+            config,
+            nodes: HashMap::new(),
+            report: DiscoveryReport::default(),
+        }
+    }
+
+    fn smp(&self, path: &DrPath, attr_id: u32, attr_mod: u32) -> MadRequest {
+        MadRequest::smp_get(MadAddress::DirectedRoute(path.clone()), attr_id, attr_mod, self.config.timeout)
+    }
+
+    //Run the requests max_smps at a time, retrying the ones that got no answer
+    fn query_all(&mut self, requests: &[MadRequest]) -> Vec<Result<Vec<u8>, IBSmpError>> {
+        let mut results: Vec<Option<Result<Vec<u8>, IBSmpError>>> = requests.iter().map(|_| None).collect();
+        let mut todo: Vec<usize> = (0..requests.len()).collect();
+
+        for attempt in 0..=self.config.retries {
+            let mut unanswered = Vec::new();
+
+            for chunk in todo.chunks(self.config.max_smps.max(1)) {
+                let batch: Vec<MadRequest> = chunk.iter().map(|&i| requests[i].clone()).collect();
+
+                for (&i, result) in chunk.iter().zip(self.transport.query_batch(&batch)) {
+                    match result {
+                        Err(IBSmpError::SendMADError) if attempt < self.config.retries => unanswered.push(i),
+                        r => results[i] = Some(r),
+                    }
+                }
+            }
+
+            todo = unanswered;
+            if todo.is_empty() {
+                break;
+            }
+        }
+
+        results.into_iter().map(|r| r.unwrap_or(Err(IBSmpError::SendMADError))).collect()
+    }
+
+    fn fail(&mut self, request: &MadRequest, error: IBSmpError) {
+        if let MadAddress::DirectedRoute(path) = &request.address {
+            self.report.failures.push(DiscoveryFailure {
+                path: path.clone(),
+                attr_id: request.attr_id,
+                attr_mod: request.attr_mod,
+                error,
+            });
+        }
+    }
+
+    fn link(&mut self, a: (u64, u8), b: (u64, u8)) {
+        for (local, remote) in [(a, b), (b, a)] {
+            if let Some(node) = self.nodes.get_mut(&local.0) {
+                node.ports.entry(local.1).or_default().remote = Some(remote);
+            }
+        }
+    }
+
+    //Breadth first, one hop further each round: NodeInfo on every new path,
+    //then NodeDescription and PortInfo for the nodes that turned up.
+    fn run(&mut self) -> Result<(), FabricError> {
+        let mut frontier: Vec<(DrPath, Option<(u64, u8)>)> = vec![(DrPath::default(), None)];
+
+        while !frontier.is_empty() {
+            let requests: Vec<MadRequest> = frontier.iter().map(|(path, _)| self.smp(path, SMI_ATTR_ID_IB_ATTR_NODE_INFO, 0)).collect();
+            let results = self.query_all(&requests);
+
+            let mut new_nodes = Vec::new();
+            let mut new_ports = Vec::new();
+
+            for (((path, from), request), result) in frontier.into_iter().zip(&requests).zip(results) {
+                let mut data = match result {
+                    Ok(data) => data,
+                    Err(_) if path.hops.is_empty() => return Err(FabricError::DiscoveryError),
+                    Err(e) => {
+                        self.fail(request, e);
+                        continue;
+                    }
+                };
+
+                let info = NodeInfo::from_mad_fields(&mut data);
+                let (guid, port_guid, local_port) = (info.guid, info.port_guid, info.local_port as u8);
+
+                let node = self.nodes.entry(guid).or_insert_with(|| {
+                    new_nodes.push(guid);
+                    FoundNode {
+                        info,
+                        path: path.clone(),
+                        node_desc: String::new(),
+                        switch_info: None,
+                        ports: HashMap::new(),
+                    }
+                });
+
+                //A CA shows up once per cabled port, each with its own GUID
+                if !node.is_switch() && !node.ports.contains_key(&local_port) {
+                    new_ports.push((guid, local_port, path.clone()));
+                }
+                node.ports.entry(local_port).or_default().guid = port_guid;
+
+                if let Some(from) = from {
+                    self.link(from, (guid, local_port));
+                }
+            }
+
+            let mut requests = Vec::new();
+            let mut targets = Vec::new();
+            for guid in &new_nodes {
+                let node = &self.nodes[guid];
+                requests.push(self.smp(&node.path, SMI_ATTR_ID_IB_ATTR_NODE_DESC, 0));
+                targets.push((*guid, Query::NodeDesc));
+
+                if node.is_switch() {
+                    requests.push(self.smp(&node.path, SMI_ATTR_ID_IB_ATTR_SWITCH_INFO, 0));
+                    targets.push((*guid, Query::SwitchInfo));
+
+                    for port in 0..=node.info.num_ports as u8 {
+                        requests.push(self.smp(&node.path, SMI_ATTR_ID_IB_ATTR_PORT_INFO, port as u32));
+                        targets.push((*guid, Query::PortInfo(port)));
+                    }
+                }
+            }
+            for (guid, port, path) in new_ports {
+                requests.push(self.smp(&path, SMI_ATTR_ID_IB_ATTR_PORT_INFO, port as u32));
+                targets.push((guid, Query::PortInfo(port)));
+            }
+
+            let results = self.query_all(&requests);
+            for ((request, (guid, query)), result) in requests.iter().zip(targets).zip(results) {
+                let mut data = match result {
+                    Ok(data) => data,
+                    Err(e) => {
+                        self.fail(request, e);
+                        continue;
+                    }
+                };
+
+                let node = self.nodes.get_mut(&guid).unwrap();
+                match query {
+                    Query::NodeDesc => node.node_desc = crate::ibmad::node_desc_from_mad_fields(&data),
+                    Query::SwitchInfo => node.switch_info = Some(SwitchInfo::from_mad_fields(&mut data)),
+                    Query::PortInfo(number) => {
+                        let is_switch = node.is_switch();
+                        let port = node.ports.entry(number).or_default();
+                        if is_switch {
+                            port.guid = guid;
+                        }
+                        port.info = Some(PortInfo::from_mad_fields(&mut data));
+                    }
+                }
+            }
+
+            //Only switches and the local node lead anywhere
+            frontier = Vec::new();
+            for guid in new_nodes {
+                let node = &self.nodes[&guid];
+                if !node.is_switch() && !node.path.hops.is_empty() {
+                    continue;
+                }
+                if self.config.max_hops != 0 && node.path.hops.len() >= self.config.max_hops {
+                    continue;
+                }
+
+                let mut ports: Vec<(&u8, &FoundPort)> = node.ports.iter().collect();
+                ports.sort_by_key(|(number, _)| **number);
+
+                for (number, port) in ports {
+                    if *number == 0 || port.remote.is_some() || !port.info.as_ref().is_some_and(|i| i.is_link_up()) {
+                        continue;
+                    }
+                    //Past the longest path an SMP can take
+                    if let Ok(path) = node.path.push(*number) {
+                        frontier.push((path, Some((guid, *number))));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Fabric {
+    //Walk the fabric with directed route SMPs through this fabric's transport,
+    //replacing whatever was discovered before. Paths that stop answering are
+    //listed in the report instead of failing the whole discovery.
+    pub fn discover_dr(&mut self, config: &DiscoverConfig) -> Result<DiscoveryReport, FabricError> {
+        let transport = self.ib_port.clone();
+        let mut discovery = Discovery::new(transport.as_ref(), config);
+        discovery.run()?;
+
+        self.nodes.clear();
+        self.ports.clear();
+        self.adapters.clear();
+        self.switches.clear();
+
+        let found = discovery.nodes;
+        let mut port_rcs: HashMap<(u64, u8), Rc<RefCell<Port>>> = HashMap::new();
+
+        for (guid, found_node) in &found {
+            let node_type = match found_node.info.node_type as u32 {
+                MAD_NODE_TYPE_IB_NODE_CA => NodeType::CA,
+                MAD_NODE_TYPE_IB_NODE_SWITCH => NodeType::SWITCH,
+                MAD_NODE_TYPE_IB_NODE_ROUTER => NodeType::ROUTER,
+                _ => NodeType::UNKNOWN,
+            };
+
+            //A switch is addressed through port 0, a CA through its lowest port
+            let mut numbers: Vec<u8> = found_node.ports.keys().copied().collect();
+            numbers.sort();
+            let lid_info = numbers.first().and_then(|n| found_node.ports[n].info.as_ref());
+            let lid = lid_info.map_or(0, |i| i.lid);
+            let switch = matches!(node_type, NodeType::SWITCH);
+
+            let node_rc = Rc::new(RefCell::new(Node {
+                guid: *guid,
+                system_guid: found_node.info.system_guid,
+                lid,
+                node_desc: found_node.node_desc.clone(),
+                node_type,
+                //Switch port 0 is the switch management agent
+                smalid: if switch { lid } else { 0 },
+                num_ports: found_node.info.num_ports as u8,
+                ports: None,
+                dev_id: found_node.info.dev_id as u32,
+                vendor_id: found_node.info.vendor_id as u32,
+                switch_info: found_node.switch_info.clone(),
+                lft: None,
+            }));
+
+            let mut ports = Vec::new();
+            for number in numbers.into_iter().filter(|n| *n != 0) {
+                let found_port = &found_node.ports[&number];
+                let info = found_port.info.clone();
+                let port_rc = Rc::new(RefCell::new(Port {
+                    guid: found_port.guid,
+                    number: number as i32,
+                    phys_state: info.as_ref().map_or(0, |i| i.phys_state as u32),
+                    logical_state: info.as_ref().map_or(0, |i| i.state as u32),
+                    //Switch external ports have no LID of their own
+                    base_lid: if switch { lid } else { info.as_ref().map_or(0, |i| i.lid) },
+                    info,
+                    remote_port: None,
+                    remote_node: None,
+                    parent: Some(Rc::downgrade(&node_rc)),
+                }));
+
+                self.ports.insert((found_port.guid, number as i32), port_rc.clone());
+                port_rcs.insert((*guid, number), port_rc.clone());
+                ports.push(port_rc);
+            }
+            node_rc.borrow_mut().ports = Some(ports);
+
+            match node_type {
+                NodeType::CA => {
+                    self.adapters.insert(*guid, Rc::downgrade(&node_rc));
+                }
+                NodeType::SWITCH => {
+                    self.switches.insert(*guid, Rc::downgrade(&node_rc));
+                }
+                _ => {}
+            }
+            self.nodes.insert(*guid, node_rc);
+        }
+
+        for (guid, found_node) in &found {
+            for (number, found_port) in &found_node.ports {
+                let (Some(port_rc), Some(remote)) = (port_rcs.get(&(*guid, *number)), found_port.remote) else {
+                    continue;
+                };
+
+                let mut port = port_rc.borrow_mut();
+                port.remote_port = port_rcs.get(&remote).map(Rc::downgrade);
+                port.remote_node = self.nodes.get(&remote.0).map(Rc::downgrade);
+            }
+        }
+
+        Ok(discovery.report)
+    }
+}
@@ -0,0 +1,210 @@
+use std::{cell::RefCell, collections::BTreeMap, path::Path, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ibmad::{perf::ExtPerfCounters, IBSmpError};
+
+use super::{
+    fabric::Fabric,
+    node::{Node, NodeType},
+    port::Port,
+    sampler::{FabricSampler, PortSample},
+};
+
+#[derive(Error, Debug)]
+pub enum ScanError {
+    #[error("Unable to read thresholds: {0}")]
+    ReadError(#[from] std::io::Error),
+    #[error("Unable to parse thresholds: {0}")]
+    ParseError(#[from] serde_json::Error),
+    #[error("Unknown counter {0}.")]
+    UnknownCounter(String),
+}
+
+//A counter is reported once it reaches count, or goes up by rate or more
+//per second between two scans
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Threshold {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<f64>,
+}
+
+//Thresholds by ExtPerfCounters name, as JSON:
+//{"thresholds": {"symbol_errors": {"count": 10, "rate": 0.1}}}
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdProfile {
+    pub thresholds: BTreeMap<String, Threshold>,
+}
+
+impl ThresholdProfile {
+    //The ibqueryerrors defaults
+    pub fn ibqueryerrors() -> ThresholdProfile {
+        let counts = [
+            ("symbol_errors", 10),
+            ("link_recovers", 10),
+            ("link_downed", 10),
+            ("rcv_errors", 10),
+            ("phys_rcv_errors", 100),
+            ("switch_rel_errors", 100),
+            ("xmt_discards", 100),
+            ("xmt_constraint_errors", 100),
+            ("rcv_constraint_errors", 100),
+            ("excess_overrun_errors", 10),
+            ("vl15dropped", 100),
+        ];
+
+        let thresholds = counts.iter().map(|(name, count)| (name.to_string(), Threshold { count: Some(*count), rate: None })).collect();
+        ThresholdProfile { thresholds }
+    }
+
+    pub fn from_json(json: &str) -> Result<ThresholdProfile, ScanError> {
+        let profile: ThresholdProfile = serde_json::from_str(json)?;
+
+        if let Some(name) = profile.thresholds.keys().find(|name| ExtPerfCounters::default().get(name).is_none()) {
+            return Err(ScanError::UnknownCounter(name.clone()));
+        }
+
+        Ok(profile)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ThresholdProfile, ScanError> {
+        ThresholdProfile::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, ScanError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    //Counters of cur over their threshold. Rates are only checked against an
+    //earlier sample of the same port.
+    pub fn check(&self, prev: Option<&PortSample>, cur: &PortSample) -> Vec<Violation> {
+        let secs = prev.and_then(|p| cur.time.checked_duration_since(p.time)).map(|d| d.as_secs_f64()).filter(|s| *s > 0.0);
+        let mut violations = Vec::new();
+
+        for (name, threshold) in &self.thresholds {
+            let Some(value) = cur.counters.get(name) else {
+                continue;
+            };
+
+            //A counter that went down was reset, it has counted value since
+            let rate = match (prev, secs) {
+                (Some(p), Some(secs)) => {
+                    let before = p.counters.get(name).unwrap_or(0);
+                    Some(if value >= before { value - before } else { value } as f64 / secs)
+                }
+                _ => None,
+            };
+
+            let over_count = threshold.count.is_some_and(|count| value >= count);
+            let over_rate = matches!((threshold.rate, rate), (Some(limit), Some(rate)) if rate >= limit);
+
+            if over_count || over_rate {
+                violations.push(Violation { counter: name.clone(), value, rate, threshold: threshold.clone() });
+            }
+        }
+
+        violations
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub counter: String,
+    pub value: u64,
+    //Per second since the previous scan, if there was one
+    pub rate: Option<f64>,
+    pub threshold: Threshold,
+}
+
+//One end of a link
+#[derive(Debug, Clone)]
+pub struct LinkEnd {
+    pub node_guid: u64,
+    pub node_desc: String,
+    pub node_type: NodeType,
+    pub port: i32,
+    pub lid: u16,
+}
+
+impl LinkEnd {
+    fn of(port: &Port, node: Option<Rc<RefCell<Node>>>) -> LinkEnd {
+        let mut end = LinkEnd { node_guid: port.guid, node_desc: String::new(), node_type: NodeType::UNKNOWN, port: port.number, lid: port.base_lid };
+
+        if let Some(node) = node {
+            let node = node.borrow();
+            end.node_guid = node.guid;
+            end.node_desc = node.node_desc.clone();
+            end.node_type = node.node_type;
+            if matches!(node.node_type, NodeType::SWITCH) {
+                end.lid = node.lid;
+            }
+        }
+
+        end
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PortErrors {
+    pub local: LinkEnd,
+    pub remote: Option<LinkEnd>,
+    pub violations: Vec<Violation>,
+}
+
+#[derive(Debug, Default)]
+pub struct ErrorReport {
+    pub ports: Vec<PortErrors>,
+    //Ports whose counters couldn't be read
+    pub failures: Vec<((u64, i32), IBSmpError)>,
+}
+
+//Reads the counters of every active port of a fabric and reports those
+//over a threshold of the profile, with both ends of their link
+pub struct ErrorScanner {
+    pub profile: ThresholdProfile,
+    sampler: FabricSampler,
+}
+
+impl ErrorScanner {
+    pub fn new(profile: ThresholdProfile) -> ErrorScanner {
+        ErrorScanner { profile, sampler: FabricSampler::new() }
+    }
+
+    pub fn with_timeout(mut self, timeout: u32) -> ErrorScanner {
+        self.sampler = self.sampler.with_timeout(timeout);
+        self
+    }
+
+    //Each scan after the first also checks rates since the one before
+    pub fn scan(&mut self, fabric: &Fabric) -> ErrorReport {
+        let previous = self.sampler.last().clone();
+        let round = self.sampler.sample(fabric);
+        let mut report = ErrorReport { ports: Vec::new(), failures: round.failures };
+
+        for sample in &round.samples {
+            let violations = self.profile.check(previous.get(&sample.port), sample);
+            if violations.is_empty() {
+                continue;
+            }
+
+            let Some(port) = fabric.ports.get(&sample.port) else {
+                continue;
+            };
+            let port = port.borrow();
+
+            let local = LinkEnd::of(&port, port.parent.as_ref().and_then(|n| n.upgrade()));
+            let remote = port.remote_port.as_ref().and_then(|p| p.upgrade()).map(|remote| {
+                let remote = remote.borrow();
+                let node = port.remote_node.as_ref().and_then(|n| n.upgrade());
+                LinkEnd::of(&remote, node)
+            });
+
+            report.ports.push(PortErrors { local, remote, violations });
+        }
+
+        report
+    }
+}
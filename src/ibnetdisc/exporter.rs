@@ -0,0 +1,180 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use super::{fabric::Fabric, sampler::{PortSample, DATA_COUNTER_BYTES}};
+
+pub const METRIC_PREFIX: &str = "ib_port_";
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+//How long a scrape gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+//Label values have backslash, double quote and newline escaped
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+//The labels of a sampled port, from what the fabric knows of it and of its
+//remote end
+fn labels(fabric: &Fabric, sample: &PortSample) -> String {
+    let mut node_desc = String::new();
+    let mut remote_guid = String::new();
+    let mut remote_desc = String::new();
+    let mut remote_port = String::new();
+
+    if let Some(port) = fabric.ports.get(&sample.port) {
+        let port = port.borrow();
+        if let Some(node) = port.parent.as_ref().and_then(|n| n.upgrade()) {
+            node_desc = node.borrow().node_desc.clone();
+        }
+        if let Some(node) = port.remote_node.as_ref().and_then(|n| n.upgrade()) {
+            remote_guid = format!("0x{:016x}", node.borrow().guid);
+            remote_desc = node.borrow().node_desc.clone();
+        }
+        if let Some(remote) = port.remote_port.as_ref().and_then(|p| p.upgrade()) {
+            remote_port = remote.borrow().number.to_string();
+        }
+    }
+
+    format!(
+        "node_guid=\"0x{:016x}\",node_desc=\"{}\",port=\"{}\",lid=\"{}\",remote_guid=\"{}\",remote_desc=\"{}\",remote_port=\"{}\"",
+        sample.node_guid,
+        escape(&node_desc),
+        sample.port.1,
+        sample.lid,
+        remote_guid,
+        escape(&remote_desc),
+        remote_port,
+    )
+}
+
+//Samples as OpenMetrics text, one counter family per ExtPerfCounters field.
+//The data counters are converted from 4 byte words to bytes.
+pub fn render(fabric: &Fabric, samples: &[PortSample]) -> String {
+    let mut samples: Vec<&PortSample> = samples.iter().collect();
+    samples.sort_by_key(|s| s.port);
+    let labels: Vec<String> = samples.iter().map(|s| labels(fabric, s)).collect();
+
+    let mut out = String::new();
+    let names: Vec<&str> = samples.first().map(|s| s.counters.iter().map(|(name, _)| name).collect()).unwrap_or_default();
+
+    for name in names {
+        let data = name == "xmt_bytes" || name == "rcv_bytes";
+        let family = format!("{}{}", METRIC_PREFIX, name);
+
+        let _ = writeln!(out, "# TYPE {} counter", family);
+        if data {
+            let _ = writeln!(out, "# UNIT {} bytes", family);
+        }
+
+        for (sample, labels) in samples.iter().zip(&labels) {
+            let mut value = sample.counters.get(name).unwrap_or(0);
+            if data {
+                value = value.saturating_mul(DATA_COUNTER_BYTES);
+            }
+            let _ = writeln!(out, "{}_total{{{}}} {}", family, labels, value);
+        }
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+//Serves the last published text on GET /metrics from a thread of its own.
+//The fabric can't leave the thread that discovered it, so rounds are rendered
+//there and handed over with publish.
+pub struct MetricsServer {
+    addr: SocketAddr,
+    metrics: Arc<Mutex<String>>,
+    running: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<MetricsServer> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let metrics = Arc::new(Mutex::new(String::from("# EOF\n")));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let acceptor = {
+            let metrics = metrics.clone();
+            let running = running.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if !running.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let _ = MetricsServer::handle(stream, &metrics);
+                    }
+                }
+            })
+        };
+
+        Ok(MetricsServer { addr, metrics, running, acceptor: Some(acceptor) })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn publish(&self, metrics: String) {
+        *self.metrics.lock().unwrap() = metrics;
+    }
+
+    //Render and publish the samples of a round
+    pub fn publish_samples(&self, fabric: &Fabric, samples: &[PortSample]) {
+        self.publish(render(fabric, samples));
+    }
+
+    fn handle(stream: TcpStream, metrics: &Mutex<String>) -> io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut reader = BufReader::new(&stream);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+
+        //Headers aren't needed, but are read up to the blank line so the
+        //client sees its whole request taken
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 2 {
+            line.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+        let (status, content_type, body) = if method == "GET" && path == "/metrics" {
+            ("200 OK", CONTENT_TYPE, metrics.lock().unwrap().clone())
+        } else {
+            ("404 Not Found", "text/plain; charset=utf-8", String::from("Not Found\n"))
+        };
+
+        let mut stream = &stream;
+        write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, content_type, body.len())?;
+        stream.write_all(body.as_bytes())?;
+        stream.flush()
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        //Wake the acceptor up so it sees it should stop
+        let _ = TcpStream::connect(self.addr);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::{cell::{RefCell, RefMut}, collections::{hash_map::Entry, HashMap}, error::Error, ffi::CString, fmt, mem::MaybeUninit, ptr, rc::{Rc, Weak}};
 
-use crate::ibmad;
+use crate::ibmad::{self, transport::MadTransport};
 
 use super::{node::{Node, NodeType}, port::{Port, PortPerfcounter}, sys};
 
@@ -14,6 +14,9 @@ pub enum FabricError {
     PortNotFound,
     OriginSameAsRemotePortError,
     NoPortError,
+    NodeNotFound,
+    NoForwardingTable,
+    LidNotFound,
 }
 
 impl fmt::Display for FabricError {
@@ -29,20 +32,34 @@ impl fmt::Display for FabricError {
                 write!(f, "The origin port is the same as the remote port")
             },
             FabricError::NoPortError => write!(f, "No port"),
+            FabricError::NodeNotFound => write!(f, "Node not found"),
+            FabricError::NoForwardingTable => write!(f, "No forwarding table loaded for switch"),
+            FabricError::LidNotFound => write!(f, "No port with this LID"),
         }
     }
 }
 
 impl Error for FabricError {}
 
-#[derive(Debug)]
 pub struct Fabric {
     pub ports: HashMap<(u64, i32), Rc<RefCell<Port>>>,
     pub nodes: HashMap<u64, Rc<RefCell<Node>>>,
     pub adapters: HashMap<u64, Weak<RefCell<Node>>>,
     pub switches: HashMap<u64, Weak<RefCell<Node>>>,
     pub hca_name: String,
-    pub ib_port: Rc<ibmad::IBMadPort>,
+    pub ib_port: Rc<dyn MadTransport>,
+}
+
+impl fmt::Debug for Fabric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fabric")
+            .field("ports", &self.ports)
+            .field("nodes", &self.nodes)
+            .field("adapters", &self.adapters)
+            .field("switches", &self.switches)
+            .field("hca_name", &self.hca_name)
+            .finish_non_exhaustive()
+    }
 }
 
 
@@ -58,13 +75,18 @@ impl Fabric {
 
         let port = ibmad::mad_rpc_open_port(hca_name, &mgmt_classes).unwrap();
 
+        Fabric::from_transport(hca_name, Rc::new(port))
+    }
+
+    //Fabric that sends its MADs through any transport, such as the simulator
+    pub fn from_transport(hca_name: &str, transport: Rc<dyn MadTransport>) -> Fabric {
         Fabric {
             nodes: HashMap::new(),
             ports: HashMap::new(),
             adapters: HashMap::new(),
             switches: HashMap::new(),
             hca_name: hca_name.to_string(),
-            ib_port: port.into(),
+            ib_port: transport,
         }
     }
 
@@ -0,0 +1,232 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ibmad::port::{LinkSpeed, LinkWidth};
+
+use super::{fabric::Fabric, hex_guid, node::NodeType};
+
+//The schema of TopologyGraph::to_json. GUIDs are hex strings since JSON
+//numbers can't hold every u64.
+pub const JSON_SCHEMA: &str = r#"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "InfiniBand fabric topology",
+  "type": "object",
+  "required": ["nodes", "edges"],
+  "properties": {
+    "nodes": {
+      "type": "array",
+      "items": {
+        "type": "object",
+        "required": ["guid", "lid", "node_desc", "node_type"],
+        "properties": {
+          "guid": {"type": "string", "pattern": "^0x[0-9a-f]{16}$"},
+          "lid": {"type": "integer", "minimum": 0, "maximum": 65535},
+          "node_desc": {"type": "string"},
+          "node_type": {"enum": ["CA", "Switch", "Router", "Unknown"]}
+        }
+      }
+    },
+    "edges": {
+      "type": "array",
+      "items": {
+        "type": "object",
+        "required": ["from_guid", "from_port", "to_guid", "to_port", "state"],
+        "properties": {
+          "from_guid": {"type": "string", "pattern": "^0x[0-9a-f]{16}$"},
+          "from_port": {"type": "integer", "minimum": 1, "maximum": 255},
+          "to_guid": {"type": "string", "pattern": "^0x[0-9a-f]{16}$"},
+          "to_port": {"type": "integer", "minimum": 1, "maximum": 255},
+          "state": {"enum": ["Down", "Init", "Armed", "Active", "Unknown"]},
+          "width": {"type": "string", "description": "Active link width, 4x, only when known"},
+          "speed": {"type": "string", "description": "Active link speed, HDR, only when known"}
+        }
+      }
+    }
+  }
+}
+"#;
+
+fn node_type_name(node_type: NodeType) -> &'static str {
+    match node_type {
+        NodeType::CA => "CA",
+        NodeType::SWITCH => "Switch",
+        NodeType::ROUTER => "Router",
+        NodeType::UNKNOWN => "Unknown",
+    }
+}
+
+//PortInfo:PortState
+pub(crate) fn port_state_name(state: u32) -> &'static str {
+    match state {
+        1 => "Down",
+        2 => "Init",
+        3 => "Armed",
+        4 => "Active",
+        _ => "Unknown",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphNode {
+    #[serde(with = "hex_guid")]
+    pub guid: u64,
+    pub lid: u16,
+    pub node_desc: String,
+    pub node_type: String,
+}
+
+//A link, listed once from the end with the lower node GUID and port
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    #[serde(with = "hex_guid")]
+    pub from_guid: u64,
+    pub from_port: i32,
+    #[serde(with = "hex_guid")]
+    pub to_guid: u64,
+    pub to_port: i32,
+    pub state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<String>,
+}
+
+//The nodes and links of a fabric, in GUID order, as DOT, GML or JSON
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopologyGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl TopologyGraph {
+    pub fn from_fabric(fabric: &Fabric) -> TopologyGraph {
+        let mut nodes: Vec<GraphNode> = fabric
+            .nodes
+            .values()
+            .map(|node| {
+                let node = node.borrow();
+                GraphNode {
+                    guid: node.guid,
+                    lid: node.lid,
+                    node_desc: node.node_desc.clone(),
+                    node_type: node_type_name(node.node_type).to_string(),
+                }
+            })
+            .collect();
+        nodes.sort_by_key(|node| node.guid);
+
+        let mut links = BTreeMap::new();
+        for port in fabric.ports.values() {
+            let port = port.borrow();
+            let (Some(node), Some(remote_node), Some(remote)) = (
+                port.parent.as_ref().and_then(|n| n.upgrade()),
+                port.remote_node.as_ref().and_then(|n| n.upgrade()),
+                port.remote_port.as_ref().and_then(|p| p.upgrade()),
+            ) else {
+                continue;
+            };
+
+            let local = (node.borrow().guid, port.number);
+            let remote_end = (remote_node.borrow().guid, remote.borrow().number);
+            let (from, to) = if local <= remote_end { (local, remote_end) } else { (remote_end, local) };
+
+            let width = port.info.as_ref().map(|i| i.link_width()).filter(|w| *w != LinkWidth::Unknown);
+            let speed = port.info.as_ref().map(|i| i.link_speed()).filter(|s| *s != LinkSpeed::Unknown);
+
+            links.entry((from, to)).or_insert_with(|| GraphEdge {
+                from_guid: from.0,
+                from_port: from.1,
+                to_guid: to.0,
+                to_port: to.1,
+                state: port_state_name(port.logical_state).to_string(),
+                width: width.map(|w| w.to_string()),
+                speed: speed.map(|s| s.to_string()),
+            });
+        }
+
+        TopologyGraph {
+            nodes,
+            edges: links.into_values().collect(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<TopologyGraph, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    //Undirected GraphViz graph, switches as boxes. Links that aren't Active
+    //are dashed.
+    pub fn to_dot(&self) -> String {
+        let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut out = String::from("graph fabric {\n");
+
+        for node in &self.nodes {
+            let shape = if node.node_type == "Switch" { "box" } else { "ellipse" };
+            let guid = hex_guid::to_string(node.guid);
+            let _ = writeln!(
+                out,
+                "    \"{}\" [label=\"{}\\n{}\\nlid {}\", shape={}];",
+                guid,
+                escape(&node.node_desc),
+                guid,
+                node.lid,
+                shape
+            );
+        }
+
+        for edge in &self.edges {
+            let rate = [edge.width.as_deref(), edge.speed.as_deref()].into_iter().flatten().collect::<Vec<_>>().join(" ");
+            let (from, to) = (hex_guid::to_string(edge.from_guid), hex_guid::to_string(edge.to_guid));
+            let _ = write!(out, "    \"{}\" -- \"{}\" [taillabel=\"{}\", headlabel=\"{}\"", from, to, edge.from_port, edge.to_port);
+            if !rate.is_empty() {
+                let _ = write!(out, ", label=\"{}\"", escape(&rate));
+            }
+            if edge.state != "Active" {
+                out.push_str(", style=dashed");
+            }
+            out.push_str("];\n");
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    //GML graph, nodes numbered in order. GML strings can't hold a double
+    //quote, so it's written as &quot;
+    pub fn to_gml(&self) -> String {
+        let escape = |value: &str| value.replace('&', "&amp;").replace('"', "&quot;");
+        let ids: HashMap<u64, usize> = self.nodes.iter().enumerate().map(|(id, node)| (node.guid, id)).collect();
+        let mut out = String::from("graph [\n  directed 0\n");
+
+        for (id, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(out, "  node [\n    id {}\n    label \"{}\"", id, escape(&node.node_desc));
+            let _ = writeln!(out, "    guid \"{}\"\n    lid {}\n    type \"{}\"\n  ]", hex_guid::to_string(node.guid), node.lid, node.node_type);
+        }
+
+        for edge in &self.edges {
+            let (Some(source), Some(target)) = (ids.get(&edge.from_guid), ids.get(&edge.to_guid)) else {
+                continue;
+            };
+            let _ = writeln!(out, "  edge [\n    source {}\n    target {}", source, target);
+            let _ = writeln!(out, "    sourceport {}\n    targetport {}\n    state \"{}\"", edge.from_port, edge.to_port, edge.state);
+            if let Some(width) = &edge.width {
+                let _ = writeln!(out, "    width \"{}\"", width);
+            }
+            if let Some(speed) = &edge.speed {
+                let _ = writeln!(out, "    speed \"{}\"", speed);
+            }
+            out.push_str("  ]\n");
+        }
+
+        out.push_str("]\n");
+        out
+    }
+}
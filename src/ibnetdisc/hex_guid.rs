@@ -0,0 +1,77 @@
+//Serde helper for GUIDs as "0x%016x" strings, JSON numbers can't hold every
+//u64. Use with #[serde(with = "hex_guid")] on a GUID, a port key of
+//(GUID, number), or an Option or Vec of either.
+
+use serde::{de::DeserializeOwned, de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn to_string(guid: u64) -> String {
+    format!("0x{:016x}", guid)
+}
+
+pub fn from_str(text: &str) -> Option<u64> {
+    let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"))?;
+    u64::from_str_radix(hex, 16).ok()
+}
+
+pub trait HexGuid: Sized {
+    type Repr: Serialize + DeserializeOwned;
+
+    fn to_repr(&self) -> Self::Repr;
+    fn from_repr(repr: Self::Repr) -> Result<Self, String>;
+}
+
+impl HexGuid for u64 {
+    type Repr = String;
+
+    fn to_repr(&self) -> String {
+        to_string(*self)
+    }
+
+    fn from_repr(repr: String) -> Result<u64, String> {
+        from_str(&repr).ok_or_else(|| format!("Bad GUID {}", repr))
+    }
+}
+
+impl HexGuid for (u64, i32) {
+    type Repr = (String, i32);
+
+    fn to_repr(&self) -> (String, i32) {
+        (to_string(self.0), self.1)
+    }
+
+    fn from_repr(repr: (String, i32)) -> Result<(u64, i32), String> {
+        Ok((u64::from_repr(repr.0)?, repr.1))
+    }
+}
+
+impl<T: HexGuid> HexGuid for Option<T> {
+    type Repr = Option<T::Repr>;
+
+    fn to_repr(&self) -> Option<T::Repr> {
+        self.as_ref().map(T::to_repr)
+    }
+
+    fn from_repr(repr: Option<T::Repr>) -> Result<Option<T>, String> {
+        repr.map(T::from_repr).transpose()
+    }
+}
+
+impl<T: HexGuid> HexGuid for Vec<T> {
+    type Repr = Vec<T::Repr>;
+
+    fn to_repr(&self) -> Vec<T::Repr> {
+        self.iter().map(T::to_repr).collect()
+    }
+
+    fn from_repr(repr: Vec<T::Repr>) -> Result<Vec<T>, String> {
+        repr.into_iter().map(T::from_repr).collect()
+    }
+}
+
+pub fn serialize<T: HexGuid, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    value.to_repr().serialize(serializer)
+}
+
+pub fn deserialize<'de, T: HexGuid, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    T::from_repr(T::Repr::deserialize(deserializer)?).map_err(D::Error::custom)
+}
@@ -3,3 +3,15 @@ pub mod lib;
 pub mod fabric;
 pub mod node;
 pub mod port;
+pub mod discover;
+pub mod routing;
+pub mod sampler;
+pub mod exporter;
+pub mod error_scan;
+pub mod ber;
+pub(crate) mod hex_guid;
+pub mod graph;
+pub mod topology_file;
+pub mod snapshot;
+pub mod diff;
+pub mod cabling;
@@ -1,16 +1,20 @@
+use crate::ibmad::switch::{LinearForwardingTable, SwitchInfo};
 use crate::ibmad::sys::{
     mad_get_field, 
+    mad_get_field64, 
     MAD_FIELDS_IB_NODE_DEVID_F, 
+    MAD_FIELDS_IB_NODE_SYSTEM_GUID_F, 
     MAD_FIELDS_IB_NODE_VENDORID_F, 
     MAD_NODE_TYPE_IB_NODE_CA, 
     MAD_NODE_TYPE_IB_NODE_ROUTER,
     MAD_NODE_TYPE_IB_NODE_SWITCH
 };
+use serde::{Deserialize, Serialize};
 use std::{cell::RefCell, ffi::{c_void, CStr}, rc::Rc, slice};
 use super::{fabric::{Fabric, FabricError}, port::Port, sys::{self, ibnd_node}};
 
 #[repr(i32)]
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
 pub enum NodeType {
     #[default]
     UNKNOWN = 0,
@@ -22,13 +26,20 @@ pub enum NodeType {
 #[derive(Default, Debug, Clone)]
 pub struct Node {
     pub guid: u64,
+    //NodeInfo:SystemImageGUID, the same for every node of a chassis. 0 when
+    //not known.
+    pub system_guid: u64,
     pub lid: u16,
     pub node_desc: String,
     pub node_type: NodeType,
     pub smalid: u16,
+    //NodeInfo:NumPorts, not every port is in ports
+    pub num_ports: u8,
     pub ports: Option<Vec<Rc<RefCell<Port>>>>,
     pub dev_id: u32,
     pub vendor_id: u32,
+    pub switch_info: Option<SwitchInfo>,
+    pub lft: Option<LinearForwardingTable>,
 }
 
 
@@ -85,6 +96,14 @@ impl Node {
 
         new_node.guid = nd_node.guid;
 
+        new_node.system_guid = unsafe {
+            mad_get_field64(
+                nd_node.info.as_ptr() as *mut c_void,
+                0,
+                MAD_FIELDS_IB_NODE_SYSTEM_GUID_F,
+            )
+        };
+
         //NodeDesc
         let nodedesc_vec: Vec<u8> = nd_node.nodedesc.iter().map(|&x| x as u8).collect();
         let r = CStr::from_bytes_until_nul(&nodedesc_vec);
@@ -109,6 +128,7 @@ impl Node {
         };
 
         new_node.smalid = nd_node.smalid;
+        new_node.num_ports = nd_node.numports as u8;
 
         match nd_node.type_ {
             i if i == MAD_NODE_TYPE_IB_NODE_CA as i32 => {
@@ -116,6 +136,9 @@ impl Node {
             }
             i if i == MAD_NODE_TYPE_IB_NODE_SWITCH as i32 => {
                 new_node.node_type = NodeType::SWITCH;
+
+                let mut switch_info = nd_node.switchinfo;
+                new_node.switch_info = Some(SwitchInfo::from_mad_fields(&mut switch_info));
             }
             i if i == MAD_NODE_TYPE_IB_NODE_ROUTER as i32 => {
                 new_node.node_type = NodeType::ROUTER;
@@ -1,5 +1,5 @@
 use std::{cell::RefCell, ffi::c_void, rc::{Rc, Weak}, thread, time::Duration};
-use crate::ibmad::{self};
+use crate::ibmad::{self, port::PortInfo, transport::MadTransport};
 use super::{fabric::{Fabric, FabricError}, node::Node, sys::ibnd_port};
 
 #[derive(Debug, Clone)]
@@ -9,6 +9,7 @@ pub struct Port {
     pub phys_state: u32,
     pub logical_state: u32,
     pub base_lid: u16,
+    pub info: Option<PortInfo>,
     pub remote_port: Option<Weak<RefCell<Port>>>,
     pub remote_node: Option<Weak<RefCell<Node>>>,
     pub parent: Option<Weak<RefCell<Node>>>,
@@ -17,7 +18,7 @@ pub struct Port {
 
 pub struct PortPerfcounter {
     pub port: Weak<RefCell<Port>>,
-    pub ib_port: Weak<ibmad::IBMadPort>,
+    pub ib_port: Weak<dyn MadTransport>,
     pub msecs_wait: u64,
 }
 
@@ -35,7 +36,7 @@ impl Iterator for PortPerfcounter {
         if let (Some(port_rc), Some(ib_port)) = (self.port.upgrade(), self.ib_port.upgrade()) {
             let port_ref = RefCell::borrow(&port_rc);
             thread::sleep(Duration::from_millis(self.msecs_wait)); 
-            if let Ok(perfctrs) = ibmad::perfquery(&ib_port, port_ref.base_lid.into(), port_ref.number, 0,  200){
+            if let Ok(perfctrs) = ibmad::perfquery(ib_port.as_ref(), port_ref.base_lid.into(), port_ref.number, 0,  200){
                 return Some(perfctrs);
             }
 
@@ -70,12 +71,15 @@ impl Port {
             )
         };
 
+        let mut info = nd_port.info;
+
         let port = Port {
             guid: nd_port.guid,
             number: nd_port.portnum,
             phys_state: phys_state,
             logical_state: logical_state,
             base_lid: nd_port.base_lid,
+            info: Some(PortInfo::from_mad_fields(&mut info)),
             remote_port: None,
             remote_node: None,
             parent: None,
@@ -141,6 +145,7 @@ impl Port {
                         phys_state: remote_port_ref.phys_state,
                         logical_state: remote_port_ref.logical_state,
                         base_lid: remote_port_ref.base_lid,
+                        info: remote_port_ref.info.clone(),
                         remote_port: None,
                         remote_node: None,
                         parent: None,
@@ -0,0 +1,245 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet}, fmt::Write, rc::Rc};
+
+use crate::ibmad::{self, IBSmpError};
+
+use super::{fabric::{Fabric, FabricError}, node::{Node, NodeType}, port::Port};
+
+const PORT_STATE_ACTIVE: u32 = 4;
+
+//One node on a route. The source has no in-port, the destination no out-port.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hop {
+    pub guid: u64,
+    pub in_port: Option<u8>,
+    pub out_port: Option<u8>,
+}
+
+//Why a route stops short of its destination
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteProblem {
+    NoForwardingTable { guid: u64 },
+    MissingLftEntry { guid: u64, lid: u16 },
+    PortNotActive { guid: u64, port: u8 },
+    NoLink { guid: u64, port: u8 },
+    Loop { guid: u64 },
+    //Delivered to a node that doesn't own the destination LID
+    WrongDestination { guid: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub slid: u16,
+    pub dlid: u16,
+    pub hops: Vec<Hop>,
+    pub problem: Option<RouteProblem>,
+}
+
+impl Route {
+    pub fn is_complete(&self) -> bool {
+        self.problem.is_none()
+    }
+}
+
+fn node_port(node: &Node, number: u8) -> Option<Rc<RefCell<Port>>> {
+    node.ports.iter().flatten().find(|p| p.borrow().number == number as i32).cloned()
+}
+
+impl Fabric {
+    //Read SwitchInfo and the whole LFT of every switch by LID, attaching them
+    //to the switch nodes. Switches that don't answer are returned with their
+    //error and keep whatever they had before.
+    pub fn fetch_lfts(&self, timeout: u32) -> Vec<(u64, IBSmpError)> {
+        let mut guids: Vec<u64> = self.switches.keys().copied().collect();
+        guids.sort();
+
+        let mut failures = Vec::new();
+        for guid in guids {
+            let Some(node_rc) = self.switches[&guid].upgrade() else {
+                continue;
+            };
+
+            let lid = node_rc.borrow().lid;
+            match ibmad::send_lid_lft_mad(self.ib_port.as_ref(), lid as i32, timeout) {
+                Ok((switch_info, lft)) => {
+                    let mut node = node_rc.borrow_mut();
+                    node.switch_info = Some(switch_info);
+                    node.lft = Some(lft);
+                }
+                Err(e) => failures.push((guid, e)),
+            }
+        }
+
+        failures
+    }
+
+    //Node type, port GUID and description of the port owning each LID
+    pub(crate) fn lid_owners(&self) -> HashMap<u16, (NodeType, u64, String)> {
+        let mut owners = HashMap::new();
+
+        for node_rc in self.nodes.values() {
+            let node = node_rc.borrow();
+            if let NodeType::SWITCH = node.node_type {
+                owners.insert(node.lid, (node.node_type, node.guid, node.node_desc.clone()));
+                continue;
+            }
+
+            for port_rc in node.ports.iter().flatten() {
+                let port = port_rc.borrow();
+                let lmc = port.info.as_ref().map_or(0, |i| i.lmc);
+                for offset in 0..1u16 << lmc {
+                    owners.insert(port.base_lid + offset, (node.node_type, port.guid, node.node_desc.clone()));
+                }
+            }
+        }
+
+        owners
+    }
+
+    //The unicast routes of one switch, laid out like ibroute
+    pub fn dump_lft(&self, guid: u64) -> Result<String, FabricError> {
+        let node_rc = self.nodes.get(&guid).ok_or(FabricError::NodeNotFound)?;
+        let node = node_rc.borrow();
+        let lft = node.lft.as_ref().ok_or(FabricError::NoForwardingTable)?;
+        let owners = self.lid_owners();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "Unicast lids [0x0-0x{:x}] of switch Lid {} guid 0x{:016x} ({}):", lft.top(), node.lid, node.guid, node.node_desc);
+        let _ = writeln!(out, "  Lid  Out   Destination");
+        let _ = writeln!(out, "       Port     Info ");
+
+        let mut count = 0;
+        for (lid, port) in lft.routes() {
+            let info = match owners.get(&lid) {
+                Some((node_type, port_guid, desc)) => {
+                    let node_type = match node_type {
+                        NodeType::SWITCH => "Switch",
+                        NodeType::CA => "Channel Adapter",
+                        NodeType::ROUTER => "Router",
+                        NodeType::UNKNOWN => "Unknown",
+                    };
+                    format!("({} portguid 0x{:016x}: '{}')", node_type, port_guid, desc)
+                }
+                None => "(unknown)".to_string(),
+            };
+            let _ = writeln!(out, "0x{:04x} {:03} : {}", lid, port, info);
+            count += 1;
+        }
+        let _ = writeln!(out, "{} valid lids dumped ", count);
+
+        Ok(out)
+    }
+
+    //The node owning a LID, and the port for anything but a switch
+    fn lid_node(&self, lid: u16) -> Option<(Rc<RefCell<Node>>, Option<u8>)> {
+        for node_rc in self.nodes.values() {
+            let node = node_rc.borrow();
+            if let NodeType::SWITCH = node.node_type {
+                if node.lid == lid {
+                    return Some((node_rc.clone(), None));
+                }
+                continue;
+            }
+
+            for port_rc in node.ports.iter().flatten() {
+                let port = port_rc.borrow();
+                let lmc = port.info.as_ref().map_or(0, |i| i.lmc);
+                if port.base_lid != 0 && (port.base_lid as u32..port.base_lid as u32 + (1 << lmc)).contains(&(lid as u32)) {
+                    return Some((node_rc.clone(), Some(port.number as u8)));
+                }
+            }
+        }
+        None
+    }
+
+    //Follow the switch LFTs from slid to dlid, like ibtracert. The LFTs have
+    //to be loaded with fetch_lfts first. Whatever breaks the route ends the
+    //trace and is reported in the Route.
+    pub fn trace_route(&self, slid: u16, dlid: u16) -> Result<Route, FabricError> {
+        let (src, src_port) = self.lid_node(slid).ok_or(FabricError::LidNotFound)?;
+        let (dst, dst_port) = self.lid_node(dlid).ok_or(FabricError::LidNotFound)?;
+        let dst_guid = dst.borrow().guid;
+
+        let mut route = Route { slid, dlid, hops: Vec::new(), problem: None };
+        let mut visited = HashSet::new();
+        let (mut node_rc, mut in_port) = (src, None);
+
+        loop {
+            let node = node_rc.clone();
+            let node = node.borrow();
+            let is_switch = matches!(node.node_type, NodeType::SWITCH);
+
+            //A CA only accepts the LIDs of the port it arrives on
+            let arrived = is_switch || in_port == dst_port || (route.hops.is_empty() && src_port == dst_port);
+            if node.guid == dst_guid && arrived {
+                route.hops.push(Hop { guid: node.guid, in_port, out_port: None });
+                return Ok(route);
+            }
+
+            let out_port = if !is_switch {
+                match (route.hops.is_empty(), src_port) {
+                    (true, Some(port)) => port,
+                    _ => {
+                        route.hops.push(Hop { guid: node.guid, in_port, out_port: None });
+                        route.problem = Some(RouteProblem::WrongDestination { guid: node.guid });
+                        return Ok(route);
+                    }
+                }
+            } else {
+                if !visited.insert(node.guid) {
+                    route.hops.push(Hop { guid: node.guid, in_port, out_port: None });
+                    route.problem = Some(RouteProblem::Loop { guid: node.guid });
+                    return Ok(route);
+                }
+
+                let Some(lft) = node.lft.as_ref() else {
+                    route.hops.push(Hop { guid: node.guid, in_port, out_port: None });
+                    route.problem = Some(RouteProblem::NoForwardingTable { guid: node.guid });
+                    return Ok(route);
+                };
+
+                match lft.egress_port(dlid) {
+                    Some(0) => {
+                        route.hops.push(Hop { guid: node.guid, in_port, out_port: Some(0) });
+                        route.problem = Some(RouteProblem::WrongDestination { guid: node.guid });
+                        return Ok(route);
+                    }
+                    Some(port) => port,
+                    None => {
+                        route.hops.push(Hop { guid: node.guid, in_port, out_port: None });
+                        route.problem = Some(RouteProblem::MissingLftEntry { guid: node.guid, lid: dlid });
+                        return Ok(route);
+                    }
+                }
+            };
+
+            route.hops.push(Hop { guid: node.guid, in_port, out_port: Some(out_port) });
+
+            let Some(port_rc) = node_port(&node, out_port) else {
+                route.problem = Some(RouteProblem::NoLink { guid: node.guid, port: out_port });
+                return Ok(route);
+            };
+            let port = port_rc.borrow();
+            if port.logical_state != PORT_STATE_ACTIVE {
+                route.problem = Some(RouteProblem::PortNotActive { guid: node.guid, port: out_port });
+                return Ok(route);
+            }
+
+            let remote = port.remote_port.as_ref().and_then(|p| p.upgrade());
+            let remote_node = port.remote_node.as_ref().and_then(|n| n.upgrade());
+            let (Some(remote), Some(remote_node)) = (remote, remote_node) else {
+                route.problem = Some(RouteProblem::NoLink { guid: node.guid, port: out_port });
+                return Ok(route);
+            };
+
+            let remote = remote.borrow();
+            if remote.logical_state != PORT_STATE_ACTIVE {
+                let guid = remote_node.borrow().guid;
+                route.problem = Some(RouteProblem::PortNotActive { guid, port: remote.number as u8 });
+                return Ok(route);
+            }
+
+            in_port = Some(remote.number as u8);
+            node_rc = remote_node;
+        }
+    }
+}
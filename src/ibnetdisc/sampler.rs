@@ -0,0 +1,194 @@
+use std::{collections::HashMap, thread, time::{Duration, Instant}};
+
+use crate::ibmad::{self, perf::{ExtPerfCounters, PmaClassPortInfo}, port::PORT_STATE_ACTIVE, IBSmpError};
+
+use super::{fabric::Fabric, node::NodeType};
+
+//PortXmitData and PortRcvData count 4 byte words
+pub const DATA_COUNTER_BYTES: u64 = 4;
+
+//Per second rates between two samples of a port
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PortRates {
+    pub interval: Duration,
+    pub xmt_bytes: f64,
+    pub rcv_bytes: f64,
+    pub xmt_pkts: f64,
+    pub rcv_pkts: f64,
+    pub xmit_waits: f64,
+    //Some counter went backwards, it was reset within the interval and its
+    //rate only counts from the reset
+    pub reset: bool,
+    //Counters of a legacy sample pinned at the 32 bit maximum. PortCounters
+    //stop there instead of wrapping, so how much was counted is unknown and
+    //their rates are NaN.
+    pub saturated: Vec<&'static str>,
+}
+
+impl PortRates {
+    //None if cur isn't later than prev
+    pub fn between(prev: &PortSample, cur: &PortSample) -> Option<PortRates> {
+        let interval = cur.time.checked_duration_since(prev.time).filter(|d| !d.is_zero())?;
+        let secs = interval.as_secs_f64();
+        let mut rates = PortRates { interval, ..Default::default() };
+
+        //After a reset a counter holds what was counted since
+        let mut rate = |name: &'static str, p: u64, c: u64, scale: u64| {
+            if cur.legacy && c == u32::MAX as u64 {
+                rates.saturated.push(name);
+                return f64::NAN;
+            }
+            let delta = if c < p {
+                rates.reset = true;
+                c
+            } else {
+                c - p
+            };
+            (delta * scale) as f64 / secs
+        };
+
+        let (p, c) = (&prev.counters, &cur.counters);
+        let xmt_bytes = rate("xmt_bytes", p.xmt_bytes, c.xmt_bytes, DATA_COUNTER_BYTES);
+        let rcv_bytes = rate("rcv_bytes", p.rcv_bytes, c.rcv_bytes, DATA_COUNTER_BYTES);
+        let xmt_pkts = rate("xmt_pkts", p.xmt_pkts, c.xmt_pkts, 1);
+        let rcv_pkts = rate("rcv_pkts", p.rcv_pkts, c.rcv_pkts, 1);
+        let xmit_waits = rate("xmit_waits", p.xmit_waits, c.xmit_waits, 1);
+
+        Some(PortRates { xmt_bytes, rcv_bytes, xmt_pkts, rcv_pkts, xmit_waits, ..rates })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortSample {
+    pub node_guid: u64,
+    //Key of the port in Fabric::ports
+    pub port: (u64, i32),
+    pub lid: u16,
+    pub time: Instant,
+    pub counters: ExtPerfCounters,
+    //The counters came from legacy PortCounters, the PMA has no
+    //PortCountersExtended
+    pub legacy: bool,
+    //None for the first sample of a port
+    pub rates: Option<PortRates>,
+}
+
+#[derive(Debug, Default)]
+pub struct SampleRound {
+    pub samples: Vec<PortSample>,
+    pub failures: Vec<((u64, i32), IBSmpError)>,
+}
+
+//Polls the counters of every active port of a fabric, keeping the previous
+//sample of each port to compute rates from. The fabric is passed in each
+//round so it can be rediscovered in between. Ports that go away, or fail to
+//answer, start over without rates when they come back.
+pub struct FabricSampler {
+    pub interval: Duration,
+    pub timeout: u32,
+    last: HashMap<(u64, i32), PortSample>,
+    class_port_info: HashMap<u64, PmaClassPortInfo>,
+    next_round: Option<Instant>,
+}
+
+impl Default for FabricSampler {
+    fn default() -> Self {
+        FabricSampler::new()
+    }
+}
+
+impl FabricSampler {
+    pub fn new() -> FabricSampler {
+        FabricSampler {
+            interval: Duration::from_secs(10),
+            timeout: 200,
+            last: HashMap::new(),
+            class_port_info: HashMap::new(),
+            next_round: None,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> FabricSampler {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: u32) -> FabricSampler {
+        self.timeout = timeout;
+        self
+    }
+
+    //The last sample of each port
+    pub fn last(&self) -> &HashMap<(u64, i32), PortSample> {
+        &self.last
+    }
+
+    //Sample every active port once, now
+    pub fn sample(&mut self, fabric: &Fabric) -> SampleRound {
+        let mut round = SampleRound::default();
+        let mut last = HashMap::new();
+
+        let mut keys: Vec<&(u64, i32)> = fabric.ports.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            let port = fabric.ports[key].borrow();
+            if port.number == 0 || port.logical_state != PORT_STATE_ACTIVE as u32 {
+                continue;
+            }
+
+            //Switch ports answer on the switch's LID
+            let parent = port.parent.as_ref().and_then(|p| p.upgrade());
+            let (node_guid, lid) = match &parent {
+                Some(node) if matches!(node.borrow().node_type, NodeType::SWITCH) => (node.borrow().guid, node.borrow().lid),
+                Some(node) => (node.borrow().guid, port.base_lid),
+                None => (port.guid, port.base_lid),
+            };
+
+            match self.query(fabric, node_guid, lid, port.number) {
+                Ok((counters, legacy)) => {
+                    let mut sample = PortSample { node_guid, port: *key, lid, time: Instant::now(), counters, legacy, rates: None };
+                    sample.rates = self.last.get(key).and_then(|prev| PortRates::between(prev, &sample));
+                    last.insert(*key, sample.clone());
+                    round.samples.push(sample);
+                }
+                Err(e) => {
+                    //Read it again next time, the node may have been replaced
+                    self.class_port_info.remove(&node_guid);
+                    round.failures.push((*key, e));
+                }
+            }
+        }
+
+        self.last = last;
+        round
+    }
+
+    //Wait for the next round to be due, one interval after the previous
+    //one started, and sample it
+    pub fn next_round(&mut self, fabric: &Fabric) -> SampleRound {
+        let now = Instant::now();
+        let start = self.next_round.map_or(now, |at| at.max(now));
+        thread::sleep(start - now);
+
+        self.next_round = Some(start + self.interval);
+        self.sample(fabric)
+    }
+
+    //The counters, and whether they're legacy PortCounters
+    fn query(&mut self, fabric: &Fabric, node_guid: u64, lid: u16, portnum: i32) -> Result<(ExtPerfCounters, bool), IBSmpError> {
+        let transport = fabric.ib_port.as_ref();
+
+        let cpi = match self.class_port_info.get(&node_guid) {
+            Some(cpi) => cpi.clone(),
+            None => {
+                let cpi = ibmad::pma_class_port_info(transport, lid as i32, self.timeout)?;
+                self.class_port_info.insert(node_guid, cpi.clone());
+                cpi
+            }
+        };
+
+        let counters = ibmad::perfquery_with_cpi(transport, lid as i32, portnum, &cpi, 0, self.timeout)?;
+        Ok((counters, cpi.legacy_only()))
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::Path,
+    rc::{Rc, Weak},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ibmad::{
+    port::PortInfo,
+    switch::{LinearForwardingTable, SwitchInfo},
+    transport::{MadTransport, OfflineTransport},
+};
+
+use super::{
+    fabric::Fabric,
+    hex_guid,
+    node::{Node, NodeType},
+    port::Port,
+};
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("Unable to read snapshot: {0}")]
+    ReadError(#[from] std::io::Error),
+    #[error("Unable to parse snapshot: {0}")]
+    ParseError(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeSnapshot {
+    #[serde(with = "hex_guid")]
+    pub guid: u64,
+    #[serde(default, with = "hex_guid")]
+    pub system_guid: u64,
+    pub lid: u16,
+    pub node_desc: String,
+    pub node_type: NodeType,
+    pub smalid: u16,
+    pub num_ports: u8,
+    pub dev_id: u32,
+    pub vendor_id: u32,
+    pub switch_info: Option<SwitchInfo>,
+    pub lft: Option<LinearForwardingTable>,
+    //Keys of the node's ports in Fabric::ports, in order. None when the
+    //node's ports weren't set.
+    #[serde(with = "hex_guid")]
+    pub ports: Option<Vec<(u64, i32)>>,
+}
+
+//The links of a port are given by key, (port guid, number) for ports and the
+//GUID for nodes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortSnapshot {
+    #[serde(with = "hex_guid")]
+    pub guid: u64,
+    pub number: i32,
+    pub phys_state: u32,
+    pub logical_state: u32,
+    pub base_lid: u16,
+    pub info: Option<PortInfo>,
+    #[serde(with = "hex_guid")]
+    pub remote_port: Option<(u64, i32)>,
+    #[serde(with = "hex_guid")]
+    pub remote_node: Option<u64>,
+    #[serde(with = "hex_guid")]
+    pub parent: Option<u64>,
+}
+
+//A port under its key in Fabric::ports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortEntry {
+    #[serde(with = "hex_guid")]
+    pub key: (u64, i32),
+    pub port: PortSnapshot,
+}
+
+//A Fabric without the Rc graph, for archiving discoveries as JSON. Nodes and
+//ports are in key order, so equal fabrics give equal JSON. GUIDs are hex
+//strings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FabricSnapshot {
+    pub hca_name: String,
+    pub nodes: Vec<NodeSnapshot>,
+    pub ports: Vec<PortEntry>,
+    #[serde(with = "hex_guid")]
+    pub adapters: Vec<u64>,
+    #[serde(with = "hex_guid")]
+    pub switches: Vec<u64>,
+}
+
+impl FabricSnapshot {
+    pub fn from_fabric(fabric: &Fabric) -> FabricSnapshot {
+        //Ports are known by address, the key they're under is what's kept
+        let keys: HashMap<*const RefCell<Port>, (u64, i32)> = fabric.ports.iter().map(|(key, port)| (Rc::as_ptr(port), *key)).collect();
+        let node_key = |node: &Option<Weak<RefCell<Node>>>| node.as_ref().and_then(|n| n.upgrade()).map(|n| n.borrow().guid);
+
+        let mut nodes: Vec<NodeSnapshot> = fabric
+            .nodes
+            .values()
+            .map(|node| {
+                let node = node.borrow();
+                NodeSnapshot {
+                    guid: node.guid,
+                    system_guid: node.system_guid,
+                    lid: node.lid,
+                    node_desc: node.node_desc.clone(),
+                    node_type: node.node_type,
+                    smalid: node.smalid,
+                    num_ports: node.num_ports,
+                    dev_id: node.dev_id,
+                    vendor_id: node.vendor_id,
+                    switch_info: node.switch_info.clone(),
+                    lft: node.lft.clone(),
+                    ports: node.ports.as_ref().map(|ports| ports.iter().filter_map(|p| keys.get(&Rc::as_ptr(p)).copied()).collect()),
+                }
+            })
+            .collect();
+        nodes.sort_by_key(|n| n.guid);
+
+        let mut ports: Vec<PortEntry> = fabric
+            .ports
+            .iter()
+            .map(|(key, port)| {
+                let port = port.borrow();
+                let remote_port = port.remote_port.as_ref().and_then(|p| p.upgrade()).and_then(|p| keys.get(&Rc::as_ptr(&p)).copied());
+                let snapshot = PortSnapshot {
+                    guid: port.guid,
+                    number: port.number,
+                    phys_state: port.phys_state,
+                    logical_state: port.logical_state,
+                    base_lid: port.base_lid,
+                    info: port.info.clone(),
+                    remote_port,
+                    remote_node: node_key(&port.remote_node),
+                    parent: node_key(&port.parent),
+                };
+                PortEntry { key: *key, port: snapshot }
+            })
+            .collect();
+        ports.sort_by_key(|entry| entry.key);
+
+        let mut adapters: Vec<u64> = fabric.adapters.keys().copied().collect();
+        adapters.sort();
+        let mut switches: Vec<u64> = fabric.switches.keys().copied().collect();
+        switches.sort();
+
+        FabricSnapshot { hca_name: fabric.hca_name.clone(), nodes, ports, adapters, switches }
+    }
+
+    //Fabric with nothing to send MADs through, as loaded from an archive
+    pub fn to_fabric(&self) -> Fabric {
+        self.to_fabric_with_transport(Rc::new(OfflineTransport))
+    }
+
+    pub fn to_fabric_with_transport(&self, transport: Rc<dyn MadTransport>) -> Fabric {
+        let mut fabric = Fabric::from_transport(&self.hca_name, transport);
+
+        for n in &self.nodes {
+            let node = Node {
+                guid: n.guid,
+                system_guid: n.system_guid,
+                lid: n.lid,
+                node_desc: n.node_desc.clone(),
+                node_type: n.node_type,
+                smalid: n.smalid,
+                num_ports: n.num_ports,
+                ports: None,
+                dev_id: n.dev_id,
+                vendor_id: n.vendor_id,
+                switch_info: n.switch_info.clone(),
+                lft: n.lft.clone(),
+            };
+            fabric.nodes.insert(n.guid, Rc::new(RefCell::new(node)));
+        }
+
+        let node_ref = |guid: Option<u64>| guid.and_then(|g| fabric.nodes.get(&g)).map(Rc::downgrade);
+        let mut ports = HashMap::new();
+        for PortEntry { key, port: p } in &self.ports {
+            let port = Port {
+                guid: p.guid,
+                number: p.number,
+                phys_state: p.phys_state,
+                logical_state: p.logical_state,
+                base_lid: p.base_lid,
+                info: p.info.clone(),
+                remote_port: None,
+                remote_node: node_ref(p.remote_node),
+                parent: node_ref(p.parent),
+            };
+            ports.insert(*key, Rc::new(RefCell::new(port)));
+        }
+
+        for PortEntry { key, port: p } in &self.ports {
+            if let Some(remote) = p.remote_port.and_then(|r| ports.get(&r)) {
+                ports[key].borrow_mut().remote_port = Some(Rc::downgrade(remote));
+            }
+        }
+
+        for n in &self.nodes {
+            let node_ports = n.ports.as_ref().map(|keys| keys.iter().filter_map(|k| ports.get(k).cloned()).collect());
+            fabric.nodes[&n.guid].borrow_mut().ports = node_ports;
+        }
+
+        for guid in &self.adapters {
+            if let Some(node) = fabric.nodes.get(guid) {
+                fabric.adapters.insert(*guid, Rc::downgrade(node));
+            }
+        }
+        for guid in &self.switches {
+            if let Some(node) = fabric.nodes.get(guid) {
+                fabric.switches.insert(*guid, Rc::downgrade(node));
+            }
+        }
+
+        fabric.ports = ports;
+        fabric
+    }
+
+    pub fn from_json(json: &str) -> Result<FabricSnapshot, SnapshotError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, SnapshotError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<FabricSnapshot, SnapshotError> {
+        FabricSnapshot::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        Ok(std::fs::write(path, self.to_json()?)?)
+    }
+}
@@ -0,0 +1,446 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Write as _,
+    path::Path,
+    rc::Rc,
+};
+
+use thiserror::Error;
+
+use crate::ibmad::{
+    port::{LinkSpeed, LinkWidth, PortInfo, IS_EXTENDED_SPEEDS_SUPPORTED, PORT_PHYS_STATE_LINKUP, PORT_STATE_ACTIVE},
+    switch::SwitchInfo,
+    transport::OfflineTransport,
+};
+
+use super::{
+    fabric::Fabric,
+    node::{Node, NodeType},
+    port::Port,
+};
+
+#[derive(Error, Debug)]
+pub enum TopologyFileError {
+    #[error("Unable to read topology file: {0}")]
+    ReadError(#[from] std::io::Error),
+    #[error("Line {0}: {1}")]
+    ParseError(usize, String),
+}
+
+fn node_id(guid: u64, node_type: NodeType) -> String {
+    let prefix = match node_type {
+        NodeType::SWITCH => "S",
+        NodeType::ROUTER => "R",
+        _ => "H",
+    };
+    format!("\"{}-{:016x}\"", prefix, guid)
+}
+
+//Active width and speed as ibnetdiscover has them, 4xHDR, empty when unknown.
+//PortInfo has FDR10 as QDR, so FDR10 links are written as QDR.
+fn link_rate(info: Option<&PortInfo>) -> String {
+    match info.map(|i| (i.link_width(), i.link_speed())) {
+        Some((width, speed)) if width != LinkWidth::Unknown && speed != LinkSpeed::Unknown => format!("{}{}", width, speed),
+        _ => String::new(),
+    }
+}
+
+//Fabric in the text format of ibnetdiscover: switches, then CAs, then
+//routers, each with one line per linked port
+pub fn write_topology(fabric: &Fabric) -> String {
+    let mut nodes: Vec<Rc<RefCell<Node>>> = fabric.nodes.values().cloned().collect();
+    nodes.sort_by_key(|n| {
+        let n = n.borrow();
+        let order = match n.node_type {
+            NodeType::SWITCH => 0,
+            NodeType::CA => 1,
+            NodeType::ROUTER => 2,
+            NodeType::UNKNOWN => 3,
+        };
+        (order, n.guid)
+    });
+
+    let mut out = String::from("#\n# Topology file: generated by rsmad\n#\n");
+
+    for node in nodes {
+        let node = node.borrow();
+        let switch = matches!(node.node_type, NodeType::SWITCH);
+
+        let mut ports: Vec<Rc<RefCell<Port>>> = node.ports.clone().unwrap_or_default();
+        ports.sort_by_key(|p| p.borrow().number);
+        let num_ports = match node.num_ports {
+            0 => ports.last().map_or(0, |p| p.borrow().number),
+            n => n as i32,
+        };
+
+        let _ = writeln!(out, "\nvendid=0x{:x}\ndevid=0x{:x}", node.vendor_id, node.dev_id);
+        if node.system_guid != 0 {
+            let _ = writeln!(out, "sysimgguid=0x{:016x}", node.system_guid);
+        }
+        match node.node_type {
+            NodeType::SWITCH => {
+                let _ = writeln!(out, "switchguid=0x{:016x}({:x})", node.guid, node.guid);
+                let port0 = if node.switch_info.as_ref().is_some_and(|i| i.enhanced_port0) { "enhanced" } else { "base" };
+                let lmc = ports.first().and_then(|p| p.borrow().info.as_ref().map(|i| i.lmc)).unwrap_or(0);
+                let _ = writeln!(
+                    out,
+                    "Switch\t{} {}\t\t# \"{}\" {} port 0 lid {} lmc {}",
+                    num_ports,
+                    node_id(node.guid, node.node_type),
+                    node.node_desc,
+                    port0,
+                    node.lid,
+                    lmc
+                );
+            }
+            NodeType::ROUTER => {
+                let _ = writeln!(out, "rtguid=0x{:016x}", node.guid);
+                let _ = writeln!(out, "Rt\t{} {}\t\t# \"{}\"", num_ports, node_id(node.guid, node.node_type), node.node_desc);
+            }
+            _ => {
+                let _ = writeln!(out, "caguid=0x{:016x}", node.guid);
+                let _ = writeln!(out, "Ca\t{} {}\t\t# \"{}\"", num_ports, node_id(node.guid, node.node_type), node.node_desc);
+            }
+        }
+
+        for port in ports {
+            let port = port.borrow();
+            let (Some(remote), Some(remote_node)) = (
+                port.remote_port.as_ref().and_then(|p| p.upgrade()),
+                port.remote_node.as_ref().and_then(|n| n.upgrade()),
+            ) else {
+                continue;
+            };
+            let (remote, remote_node) = (remote.borrow(), remote_node.borrow());
+            let remote_switch = matches!(remote_node.node_type, NodeType::SWITCH);
+
+            let _ = write!(out, "[{}]", port.number);
+            if !switch {
+                let _ = write!(out, "({:x}) ", port.guid);
+            }
+            let _ = write!(out, "\t{}[{}]", node_id(remote_node.guid, remote_node.node_type), remote.number);
+            if !remote_switch {
+                let _ = write!(out, "({:x}) ", remote.guid);
+            }
+
+            out.push_str("\t\t# ");
+            if !switch {
+                let lmc = port.info.as_ref().map_or(0, |i| i.lmc);
+                let _ = write!(out, "lid {} lmc {} ", port.base_lid, lmc);
+            }
+            let remote_lid = if remote_switch { remote_node.lid } else { remote.base_lid };
+            let _ = writeln!(out, "\"{}\" lid {} {}", remote_node.node_desc, remote_lid, link_rate(port.info.as_ref()));
+        }
+    }
+
+    out
+}
+
+pub fn save_topology<P: AsRef<Path>>(fabric: &Fabric, path: P) -> Result<(), TopologyFileError> {
+    Ok(std::fs::write(path, write_topology(fabric))?)
+}
+
+struct ParsedLink {
+    line: usize,
+    port: i32,
+    port_guid: Option<u64>,
+    remote_id: String,
+    remote_port: i32,
+    //CA and router ports have their own LID
+    lid: Option<u16>,
+    lmc: u8,
+    rate: String,
+}
+
+struct ParsedNode {
+    id: String,
+    guid: u64,
+    system_guid: u64,
+    node_type: NodeType,
+    num_ports: u8,
+    node_desc: String,
+    lid: u16,
+    lmc: u8,
+    enhanced_port0: bool,
+    vendor_id: u32,
+    dev_id: u32,
+    links: Vec<ParsedLink>,
+}
+
+//Split off the comment, at the first # outside of a quoted node id
+fn split_comment(line: &str) -> (&str, &str) {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return (&line[..i], &line[i + 1..]),
+            _ => {}
+        }
+    }
+    (line, "")
+}
+
+//The node description quoted in a comment, and what comes before and after
+fn quoted_desc(comment: &str) -> (&str, String, &str) {
+    match (comment.find('"'), comment.rfind('"')) {
+        (Some(first), Some(last)) if first < last => (&comment[..first], comment[first + 1..last].to_string(), &comment[last + 1..]),
+        _ => (comment, String::new(), ""),
+    }
+}
+
+//The value following a keyword, "lid 4 lmc 0"
+fn value_after<T: std::str::FromStr>(text: &str, key: &str) -> Option<T> {
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        if word == key {
+            return words.next()?.parse().ok();
+        }
+    }
+    None
+}
+
+fn parse_hex(text: &str) -> Option<u64> {
+    u64::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok()
+}
+
+//[1](248a070300f8f4a4) -> (1, Some(0x248a070300f8f4a4), rest)
+fn parse_port(text: &str) -> Option<(i32, Option<u64>, &str)> {
+    let text = text.trim_start().strip_prefix('[')?;
+    let (number, rest) = text.split_once(']')?;
+    let number = number.trim().parse().ok()?;
+
+    match rest.strip_prefix('(') {
+        Some(rest) => {
+            let (guid, rest) = rest.split_once(')')?;
+            Some((number, Some(parse_hex(guid)?), rest))
+        }
+        None => Some((number, None, rest)),
+    }
+}
+
+//"S-fc6a1c0300e4a800" -> ("\"S-fc6a1c0300e4a800\"", rest)
+fn parse_id(text: &str) -> Option<(String, &str)> {
+    let text = text.trim_start().strip_prefix('"')?;
+    let (id, rest) = text.split_once('"')?;
+    Some((format!("\"{}\"", id), rest))
+}
+
+//Node id made up by ibnetdiscover from the type and GUID, when no node name
+//map was used
+fn guid_of_id(id: &str) -> Option<u64> {
+    let id = id.trim_matches('"');
+    let (_, guid) = id.split_once('-')?;
+    if guid.len() != 16 {
+        return None;
+    }
+    parse_hex(guid)
+}
+
+fn parse_link(line: usize, head: &str, comment: &str, node_type: NodeType) -> Result<ParsedLink, TopologyFileError> {
+    let bad = || TopologyFileError::ParseError(line, format!("Bad link \"{}\"", head.trim()));
+
+    let (port, port_guid, rest) = parse_port(head).ok_or_else(bad)?;
+    let (remote_id, rest) = parse_id(rest).ok_or_else(bad)?;
+    let (remote_port, _, _) = parse_port(rest).ok_or_else(bad)?;
+
+    let (before, _, after) = quoted_desc(comment);
+    let (lid, lmc) = match node_type {
+        NodeType::SWITCH => (None, 0),
+        _ => (value_after(before, "lid"), value_after(before, "lmc").unwrap_or(0)),
+    };
+    let rate = after.split_whitespace().skip_while(|w| *w == "lid").nth(1).unwrap_or("").to_string();
+
+    Ok(ParsedLink { line, port, port_guid, remote_id, remote_port, lid, lmc, rate })
+}
+
+fn parse_node(line: usize, head: &str, comment: &str, header: &HashMap<String, String>) -> Result<ParsedNode, TopologyFileError> {
+    let bad = || TopologyFileError::ParseError(line, format!("Bad node \"{}\"", head.trim()));
+
+    let mut words = head.split_whitespace();
+    let node_type = match words.next() {
+        Some("Switch") => NodeType::SWITCH,
+        Some("Ca") => NodeType::CA,
+        Some("Rt") => NodeType::ROUTER,
+        _ => return Err(bad()),
+    };
+    let num_ports = words.next().and_then(|n| n.parse().ok()).ok_or_else(bad)?;
+    let (id, _) = head.find('"').and_then(|i| parse_id(&head[i..])).ok_or_else(bad)?;
+
+    let guid_key = match node_type {
+        NodeType::SWITCH => "switchguid",
+        NodeType::ROUTER => "rtguid",
+        _ => "caguid",
+    };
+    //switchguid=0xfc6a1c0300e4a800(fc6a1c0300e4a800), the port 0 GUID after
+    let guid = header
+        .get(guid_key)
+        .and_then(|g| parse_hex(g.split('(').next().unwrap_or("")))
+        .or_else(|| guid_of_id(&id))
+        .ok_or_else(|| TopologyFileError::ParseError(line, format!("No GUID for {}", id)))?;
+
+    let (_, node_desc, after) = quoted_desc(comment);
+
+    Ok(ParsedNode {
+        id,
+        guid,
+        system_guid: header.get("sysimgguid").and_then(|g| parse_hex(g)).unwrap_or(0),
+        node_type,
+        num_ports,
+        node_desc,
+        lid: value_after(after, "lid").unwrap_or(0),
+        lmc: value_after(after, "lmc").unwrap_or(0),
+        enhanced_port0: after.split_whitespace().next() == Some("enhanced"),
+        vendor_id: header.get("vendid").and_then(|v| parse_hex(v)).unwrap_or(0) as u32,
+        dev_id: header.get("devid").and_then(|v| parse_hex(v)).unwrap_or(0) as u32,
+        links: Vec::new(),
+    })
+}
+
+//PortInfo of a linked port, from what the file has of it. Ports are only
+//listed once linked, so they're taken as up and Active.
+fn port_info(number: i32, lid: u16, lmc: u8, rate: &str) -> PortInfo {
+    let mut info = PortInfo {
+        lid,
+        lmc,
+        local_port: number as u8,
+        state: PORT_STATE_ACTIVE,
+        phys_state: PORT_PHYS_STATE_LINKUP,
+        ..Default::default()
+    };
+
+    let (lanes, speed) = rate.split_once('x').unwrap_or(("", ""));
+    info.link_width_active = match lanes {
+        "1" => 1,
+        "4" => 2,
+        "8" => 4,
+        "12" => 8,
+        "2" => 16,
+        _ => 0,
+    };
+
+    //PortInfo has no FDR10, it's read as the QDR it shows up as
+    match speed {
+        "SDR" => info.link_speed_active = 1,
+        "DDR" => info.link_speed_active = 2,
+        "QDR" | "FDR10" => info.link_speed_active = 4,
+        "FDR" | "EDR" | "HDR" | "NDR" => {
+            info.cap_mask |= IS_EXTENDED_SPEEDS_SUPPORTED;
+            info.link_speed_ext_active = match speed {
+                "FDR" => 1,
+                "EDR" => 2,
+                "HDR" => 4,
+                _ => 8,
+            };
+        }
+        _ => {}
+    }
+
+    info
+}
+
+//Fabric from the text format of ibnetdiscover. There's no port behind it to
+//send MADs through until ib_port is replaced.
+pub fn parse_topology(text: &str) -> Result<Fabric, TopologyFileError> {
+    let mut parsed: Vec<ParsedNode> = Vec::new();
+    let mut header: HashMap<String, String> = HashMap::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let (head, comment) = split_comment(line);
+        let head = head.trim();
+
+        if head.is_empty() {
+            continue;
+        }
+
+        if let Some((key, value)) = head.split_once('=') {
+            header.insert(key.trim().to_string(), value.trim().to_string());
+        } else if head.starts_with('[') {
+            let node = parsed
+                .last_mut()
+                .ok_or_else(|| TopologyFileError::ParseError(line_number, String::from("Link before any node")))?;
+            node.links.push(parse_link(line_number, head, comment, node.node_type)?);
+        } else {
+            parsed.push(parse_node(line_number, head, comment, &header)?);
+            header.clear();
+        }
+    }
+
+    let mut fabric = Fabric::from_transport("", Rc::new(OfflineTransport));
+    let mut by_id: HashMap<String, Rc<RefCell<Node>>> = HashMap::new();
+    let mut ports: HashMap<(String, i32), Rc<RefCell<Port>>> = HashMap::new();
+
+    for p in &parsed {
+        let switch = matches!(p.node_type, NodeType::SWITCH);
+        let lid = if switch { p.lid } else { p.links.iter().min_by_key(|l| l.port).and_then(|l| l.lid).unwrap_or(0) };
+
+        let node_rc = Rc::new(RefCell::new(Node {
+            guid: p.guid,
+            system_guid: p.system_guid,
+            lid,
+            node_desc: p.node_desc.clone(),
+            node_type: p.node_type,
+            num_ports: p.num_ports,
+            vendor_id: p.vendor_id,
+            dev_id: p.dev_id,
+            switch_info: switch.then(|| SwitchInfo { enhanced_port0: p.enhanced_port0, ..Default::default() }),
+            ..Default::default()
+        }));
+
+        let mut node_ports = Vec::new();
+        for link in &p.links {
+            let (base_lid, lmc) = if switch { (p.lid, p.lmc) } else { (link.lid.unwrap_or(0), link.lmc) };
+            let info = port_info(link.port, base_lid, lmc, &link.rate);
+            let guid = if switch { p.guid } else { link.port_guid.unwrap_or(p.guid) };
+
+            let port_rc = Rc::new(RefCell::new(Port {
+                guid,
+                number: link.port,
+                phys_state: info.phys_state as u32,
+                logical_state: info.state as u32,
+                base_lid,
+                info: Some(info),
+                remote_port: None,
+                remote_node: None,
+                parent: Some(Rc::downgrade(&node_rc)),
+            }));
+
+            fabric.ports.insert((guid, link.port), port_rc.clone());
+            ports.insert((p.id.clone(), link.port), port_rc.clone());
+            node_ports.push(port_rc);
+        }
+        node_rc.borrow_mut().ports = Some(node_ports);
+
+        match p.node_type {
+            NodeType::CA => {
+                fabric.adapters.insert(p.guid, Rc::downgrade(&node_rc));
+            }
+            NodeType::SWITCH => {
+                fabric.switches.insert(p.guid, Rc::downgrade(&node_rc));
+            }
+            _ => {}
+        }
+        fabric.nodes.insert(p.guid, node_rc.clone());
+        by_id.insert(p.id.clone(), node_rc);
+    }
+
+    //Every link is listed from both ends, each end only needs its own side
+    for p in &parsed {
+        for link in &p.links {
+            let (Some(remote_node), Some(remote)) = (by_id.get(&link.remote_id), ports.get(&(link.remote_id.clone(), link.remote_port))) else {
+                return Err(TopologyFileError::ParseError(link.line, format!("{}[{}] isn't in the file", link.remote_id, link.remote_port)));
+            };
+
+            let mut port = ports[&(p.id.clone(), link.port)].borrow_mut();
+            port.remote_port = Some(Rc::downgrade(remote));
+            port.remote_node = Some(Rc::downgrade(remote_node));
+        }
+    }
+
+    Ok(fabric)
+}
+
+pub fn load_topology<P: AsRef<Path>>(path: P) -> Result<Fabric, TopologyFileError> {
+    parse_topology(&std::fs::read_to_string(path)?)
+}
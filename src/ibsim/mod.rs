@@ -0,0 +1,4 @@
+pub mod topology;
+pub mod simulator;
+
+pub use simulator::*;
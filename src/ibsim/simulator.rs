@@ -0,0 +1,1223 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Condvar, Mutex,
+    },
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::ibmad::{
+    self,
+    perf::*,
+    port::PortInfo,
+    sa::*,
+    sys::*,
+    switch::{LFT_BLOCK_SIZE, LFT_NO_PORT},
+    transport::{
+        class_data_offset, class_data_size, MadAddress, MadRequest, MadResponse, MadTransport, DR_INITIAL_PATH_OFFSET, DR_RETURN_PATH_OFFSET,
+        SA_ATTR_OFFSET_OFFSET, SA_COMP_MASK_OFFSET,
+    },
+    IBSmpError, NodeInfo,
+};
+use crate::ibmad::dispatch::MadChannel;
+use crate::ibmad::enums::MadAttrId;
+use crate::ibmad::notice::*;
+use crate::umad::{mad::ib_user_mad, rmpp::*, UmadError};
+
+use super::topology::*;
+
+//MAD status codes, bits 2-4 of the status field
+pub const MAD_STATUS_BAD_VERSION: u16 = 0x04;
+pub const MAD_STATUS_UNSUPPORTED_METHOD: u16 = 0x08;
+pub const MAD_STATUS_UNSUPPORTED_ATTR: u16 = 0x0c;
+pub const MAD_STATUS_INVALID_ATTR_VALUE: u16 = 0x1c;
+
+const PMA_ALL_PORTS: u32 = 0xff;
+
+const LINEAR_FDB_CAP: u16 = 0xc000;
+
+const SM_STATE_MASTER: u8 = 3;
+const GID_PREFIX: u64 = 0xfe80_0000_0000_0000;
+//Paths are reported as 10 Gb/s, 4096 byte MTU
+const PATH_RATE: u8 = 3;
+const PATH_MTU: u8 = 5;
+
+#[derive(Error, Debug)]
+pub enum SimError {
+    #[error("Unable to parse topology: {0}")]
+    TopologyParseError(#[from] serde_json::Error),
+    #[error("Unknown node 0x{0:x}.")]
+    UnknownNode(u64),
+    #[error("Unknown port {1} on node 0x{0:x}.")]
+    UnknownPort(u64, u8),
+    #[error("Unknown counter {0}.")]
+    UnknownCounter(String),
+    #[error("Port {1} on node 0x{0:x} is linked more than once.")]
+    DuplicateLink(u64, u8),
+    #[error("Malformed MAD.")]
+    InvalidMad,
+    #[error("Management class 0x{0:x} is not simulated.")]
+    UnsupportedClass(u8),
+    #[error("MAD was dropped before reaching its destination.")]
+    Unreachable,
+}
+
+#[derive(Debug)]
+struct SimState {
+    nodes: HashMap<u64, SimNode>,
+    links: HashMap<(u64, u8), (u64, u8)>,
+    //Switch LFTs indexed by LID
+    lfts: HashMap<u64, Vec<u8>>,
+    //Port of the master SM, which also answers SA queries
+    sm: (u64, u8),
+    //RMPP segments to lose on their next transmission
+    rmpp_drops: HashSet<u32>,
+    //MADs sent through the MadChannel to lose
+    mad_drops: u32,
+    //Traps the origin port subscribed to through InformInfo
+    subscriptions: Vec<InformInfo>,
+    reports_acked: u32,
+}
+
+#[derive(Debug)]
+pub struct Simulator {
+    state: Mutex<SimState>,
+    origin: (u64, u8),
+    tid: AtomicU64,
+    //Responses and timed out sends waiting for MadChannel::recv
+    completions: Mutex<VecDeque<ib_user_mad>>,
+    completion_ready: Condvar,
+}
+
+impl SimState {
+    fn node(&self, guid: u64) -> Result<&SimNode, SimError> {
+        self.nodes.get(&guid).ok_or(SimError::UnknownNode(guid))
+    }
+
+    fn port(&self, guid: u64, number: u8) -> Option<&SimPort> {
+        self.nodes.get(&guid)?.ports.iter().find(|p| p.number == number)
+    }
+
+    fn port_mut(&mut self, guid: u64, number: u8) -> Result<&mut SimPort, SimError> {
+        let node = self.nodes.get_mut(&guid).ok_or(SimError::UnknownNode(guid))?;
+        node.ports.iter_mut().find(|p| p.number == number).ok_or(SimError::UnknownPort(guid, number))
+    }
+
+    //Node and port owning a LID. Switches answer on their management port.
+    fn lid_owner(&self, lid: u16) -> Option<(u64, u8)> {
+        for node in self.nodes.values() {
+            for port in &node.ports {
+                if node.node_type == SimNodeType::Switch && port.number != 0 {
+                    continue;
+                }
+                let first = port.lid as u32;
+                let last = first + (1 << port.lmc);
+                if port.lid != 0 && (first..last).contains(&(lid as u32)) {
+                    return Some((node.guid, port.number));
+                }
+            }
+        }
+        None
+    }
+
+    //The far end of a link that can carry a MAD. DR SMPs only need the
+    //physical link up, everything else needs both ends Active.
+    fn traverse(&self, guid: u64, port: u8, directed_route: bool) -> Option<(u64, u8)> {
+        let remote = *self.links.get(&(guid, port))?;
+        let up = |p: &SimPort| {
+            if directed_route {
+                p.phys_state == PORT_PHYS_STATE_LINKUP
+            } else {
+                p.state == PORT_STATE_ACTIVE
+            }
+        };
+
+        if up(self.port(guid, port)?) && up(self.port(remote.0, remote.1)?) {
+            Some(remote)
+        } else {
+            None
+        }
+    }
+
+    fn is_switch(&self, guid: u64) -> bool {
+        self.nodes.get(&guid).is_some_and(|n| n.node_type == SimNodeType::Switch)
+    }
+
+    //Min hop routing over the Active links, lowest port number on ties. Works
+    //back from each destination, like an SM would on its first sweep.
+    fn compute_lfts(&mut self) {
+        let top = self.nodes.values().flat_map(|n| n.ports.iter().map(|p| p.lid as usize + (1 << p.lmc) - 1)).max().unwrap_or(0);
+
+        let mut lfts: HashMap<u64, Vec<u8>> = self
+            .nodes
+            .values()
+            .filter(|n| n.node_type == SimNodeType::Switch)
+            .map(|n| (n.guid, vec![LFT_NO_PORT; top + 1]))
+            .collect();
+
+        for node in self.nodes.values() {
+            for port in &node.ports {
+                if port.lid == 0 || (node.node_type == SimNodeType::Switch && port.number != 0) {
+                    continue;
+                }
+                let lids = port.lid as usize..port.lid as usize + (1 << port.lmc);
+
+                let mut seen = HashSet::new();
+                let mut queue = VecDeque::new();
+                if node.node_type == SimNodeType::Switch {
+                    lfts.get_mut(&node.guid).unwrap()[lids.clone()].fill(0);
+                    seen.insert(node.guid);
+                    queue.push_back(node.guid);
+                } else if let Some((guid, number)) = self.traverse(node.guid, port.number, false) {
+                    if self.is_switch(guid) {
+                        lfts.get_mut(&guid).unwrap()[lids.clone()].fill(number);
+                        seen.insert(guid);
+                        queue.push_back(guid);
+                    }
+                }
+
+                while let Some(guid) = queue.pop_front() {
+                    for number in self.nodes[&guid].ports.iter().map(|p| p.number).filter(|&n| n != 0) {
+                        let Some((remote, remote_port)) = self.traverse(guid, number, false) else {
+                            continue;
+                        };
+                        if self.is_switch(remote) && seen.insert(remote) {
+                            lfts.get_mut(&remote).unwrap()[lids.clone()].fill(remote_port);
+                            queue.push_back(remote);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.lfts = lfts;
+    }
+
+    fn sm_lid(&self) -> u16 {
+        self.port(self.sm.0, self.sm.1).map_or(0, |p| p.lid)
+    }
+
+    //LID a port answers to, a switch has one for all of its ports
+    fn port_lid(&self, guid: u64, number: u8) -> u16 {
+        let number = if self.is_switch(guid) { 0 } else { number };
+        self.port(guid, number).map_or(0, |p| p.lid)
+    }
+
+    fn port_gid(&self, guid: u64, number: u8) -> Gid {
+        let mut gid = [0; 16];
+        gid[..8].copy_from_slice(&GID_PREFIX.to_be_bytes());
+        gid[8..].copy_from_slice(&self.port(guid, number).map_or(0, |p| p.guid).to_be_bytes());
+        gid
+    }
+
+    //Every port with a LID the way the SA lists them, switch management
+    //ports and CA ports, in LID order
+    fn lid_ports(&self) -> Vec<(&SimNode, &SimPort)> {
+        let mut ports: Vec<(&SimNode, &SimPort)> = self
+            .nodes
+            .values()
+            .flat_map(|n| n.ports.iter().map(move |p| (n, p)))
+            .filter(|(n, p)| p.lid != 0 && (n.node_type != SimNodeType::Switch || p.number == 0))
+            .collect();
+        ports.sort_by_key(|(_, p)| p.lid);
+        ports
+    }
+
+    fn node_records(&self) -> Vec<NodeRecord> {
+        self.lid_ports()
+            .into_iter()
+            .map(|(node, port)| {
+                let mut data = vec![0; IB_SMP_DATA_SIZE as usize];
+                encode_node_info(node, port.number, &mut data);
+                NodeRecord {
+                    lid: port.lid,
+                    node_info: NodeInfo::from_mad_fields(&mut data),
+                    node_desc: node.node_desc.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn port_info_records(&self) -> Vec<PortInfoRecord> {
+        let mut records = Vec::new();
+        for (node, lid_port) in self.lid_ports() {
+            let ports = match node.node_type {
+                SimNodeType::Switch => node.ports.iter().collect(),
+                _ => vec![lid_port],
+            };
+
+            for port in ports {
+                let mut data = vec![0; IB_SMP_DATA_SIZE as usize];
+                encode_port_info(node, port, self.sm_lid(), &mut data);
+                records.push(PortInfoRecord {
+                    lid: lid_port.lid,
+                    port_num: port.number,
+                    options: 0,
+                    port_info: PortInfo::from_mad_fields(&mut data),
+                });
+            }
+        }
+        records
+    }
+
+    fn link_records(&self) -> Vec<LinkRecord> {
+        let mut records: Vec<LinkRecord> = self
+            .links
+            .keys()
+            .filter_map(|&(guid, port)| {
+                let (remote, remote_port) = self.traverse(guid, port, false)?;
+                Some(LinkRecord {
+                    from_lid: self.port_lid(guid, port),
+                    from_port: port,
+                    to_port: remote_port,
+                    to_lid: self.port_lid(remote, remote_port),
+                })
+            })
+            .collect();
+        records.sort_by_key(|r| (r.from_lid, r.from_port));
+        records
+    }
+
+    //A single path per LID pair, if the LFTs lead from one to the other
+    fn path_records(&self, slid: u16, dlid: u16) -> Vec<PathRecord> {
+        let Some(source) = self.lid_owner(slid) else {
+            return Vec::new();
+        };
+        let Ok(dest) = Simulator::route_lid(self, source, dlid) else {
+            return Vec::new();
+        };
+
+        vec![PathRecord {
+            dgid: self.port_gid(dest.0, dest.1),
+            sgid: self.port_gid(source.0, source.1),
+            dlid,
+            slid,
+            reversible: true,
+            num_path: 1,
+            pkey: 0xffff,
+            mtu_selector: 2,
+            mtu: PATH_MTU,
+            rate_selector: 2,
+            rate: PATH_RATE,
+            pkt_life_selector: 2,
+            ..Default::default()
+        }]
+    }
+
+    fn sm_info_records(&self) -> Vec<SmInfoRecord> {
+        let Some(port) = self.port(self.sm.0, self.sm.1) else {
+            return Vec::new();
+        };
+
+        vec![SmInfoRecord {
+            lid: port.lid,
+            guid: port.guid,
+            sm_state: SM_STATE_MASTER,
+            ..Default::default()
+        }]
+    }
+
+    fn guid_info_records(&self) -> Vec<GuidInfoRecord> {
+        self.lid_ports()
+            .into_iter()
+            .map(|(_, port)| {
+                let mut guids = [0; 8];
+                guids[0] = port.guid;
+                GuidInfoRecord { lid: port.lid, block_num: 0, guids }
+            })
+            .collect()
+    }
+
+    fn pkey_table_records(&self) -> Vec<PKeyTableRecord> {
+        self.lid_ports()
+            .into_iter()
+            .map(|(_, port)| {
+                let mut pkeys = [0; 32];
+                pkeys[0] = 0xffff;
+                PKeyTableRecord { lid: port.lid, block_num: 0, port_num: port.number, pkeys }
+            })
+            .collect()
+    }
+}
+
+impl Simulator {
+    pub fn new(topology: Topology, origin_guid: u64, origin_port: u8) -> Result<Simulator, SimError> {
+        let mut links = HashMap::new();
+
+        for link in &topology.links {
+            let from = (link.from.guid, link.from.port);
+            let to = (link.to.guid, link.to.port);
+            for (a, b) in [(from, to), (to, from)] {
+                if links.insert(a, b).is_some() {
+                    return Err(SimError::DuplicateLink(a.0, a.1));
+                }
+            }
+        }
+
+        let mut nodes = HashMap::new();
+        for mut node in topology.nodes {
+            if node.system_guid == 0 {
+                node.system_guid = node.guid;
+            }
+
+            let first_port = if node.node_type == SimNodeType::Switch { 0 } else { 1 };
+            for number in first_port..=node.num_ports {
+                if node.ports.iter().any(|p| p.number == number) {
+                    continue;
+                }
+                //Ports without an explicit entry come up if they are cabled
+                let mut port = SimPort::new(number);
+                if number != 0 && !links.contains_key(&(node.guid, number)) {
+                    port.state = PORT_STATE_DOWN;
+                    port.phys_state = PORT_PHYS_STATE_POLLING;
+                }
+                node.ports.push(port);
+            }
+
+            let (guid, lid, node_type) = (node.guid, node.lid, node.node_type);
+            for port in node.ports.iter_mut() {
+                if port.number > node.num_ports {
+                    return Err(SimError::UnknownPort(guid, port.number));
+                }
+                if node_type == SimNodeType::Switch {
+                    port.guid = guid;
+                    port.lid = lid;
+                } else {
+                    if port.guid == 0 {
+                        port.guid = guid + port.number as u64;
+                    }
+                    if port.lid == 0 && lid != 0 {
+                        port.lid = lid + port.number as u16 - 1;
+                    }
+                }
+            }
+            node.ports.sort_by_key(|p| p.number);
+
+            nodes.insert(node.guid, node);
+        }
+
+        let mut state = SimState { nodes, links, lfts: HashMap::new(), sm: (origin_guid, origin_port), rmpp_drops: HashSet::new(), mad_drops: 0, subscriptions: Vec::new(), reports_acked: 0 };
+        state.compute_lfts();
+        for (guid, port) in state.links.keys() {
+            if state.port(*guid, *port).is_none() || *port == 0 {
+                return Err(SimError::UnknownPort(*guid, *port));
+            }
+        }
+        if state.port(origin_guid, origin_port).is_none() {
+            return Err(SimError::UnknownPort(origin_guid, origin_port));
+        }
+
+        Ok(Simulator {
+            state: Mutex::new(state),
+            origin: (origin_guid, origin_port),
+            tid: AtomicU64::new(1),
+            completions: Mutex::new(VecDeque::new()),
+            completion_ready: Condvar::new(),
+        })
+    }
+
+    pub fn from_json(json: &str, origin_guid: u64, origin_port: u8) -> Result<Simulator, SimError> {
+        Simulator::new(Topology::from_json(json)?, origin_guid, origin_port)
+    }
+
+    //A port going in or out of Active raises trap 128 from a switch, or
+    //64/65 from the SM for a CA port
+    pub fn set_port_state(&self, guid: u64, port: u8, state: u8, phys_state: u8) -> Result<(), SimError> {
+        let mut sim = self.state.lock().unwrap();
+        let p = sim.port_mut(guid, port)?;
+        let was_active = p.state == PORT_STATE_ACTIVE;
+        p.state = state;
+        p.phys_state = phys_state;
+
+        if was_active == (state == PORT_STATE_ACTIVE) {
+            return Ok(());
+        }
+
+        let mut notice = Notice::default();
+        if sim.is_switch(guid) {
+            notice.notice_type = NOTICE_TYPE_URGENT;
+            notice.producer_type = NOTICE_PRODUCER_SWITCH;
+            notice.trap_number = TRAP_LINK_STATE_CHANGE;
+            notice.issuer_lid = sim.port_lid(guid, 0);
+            notice.data_details[0..2].copy_from_slice(&notice.issuer_lid.to_be_bytes());
+        } else {
+            notice.producer_type = NOTICE_PRODUCER_SM;
+            notice.trap_number = if was_active { TRAP_GID_OUT_OF_SERVICE } else { TRAP_GID_IN_SERVICE };
+            notice.issuer_lid = sim.sm_lid();
+            notice.data_details[6..22].copy_from_slice(&sim.port_gid(guid, port));
+        }
+        notice.issuer_gid = sim.port_gid(sim.sm.0, sim.sm.1);
+
+        drop(sim);
+        self.notify(&notice);
+        Ok(())
+    }
+
+    pub fn set_counters(&self, guid: u64, port: u8, counters: &HashMap<String, u64>) -> Result<(), SimError> {
+        if let Some(name) = counters.keys().find(|name| ExtPerfCounters::default().get(name).is_none()) {
+            return Err(SimError::UnknownCounter(name.clone()));
+        }
+
+        let mut sim = self.state.lock().unwrap();
+        let p = sim.port_mut(guid, port)?;
+        for (name, value) in counters {
+            p.counters.insert(name.clone(), *value);
+        }
+        Ok(())
+    }
+
+    //Rerun routing, e.g. after changing port states
+    pub fn compute_lfts(&self) {
+        self.state.lock().unwrap().compute_lfts();
+    }
+
+    //Point a LID somewhere else on one switch, LFT_NO_PORT removes the route
+    pub fn set_lft_entry(&self, guid: u64, lid: u16, port: u8) -> Result<(), SimError> {
+        let mut sim = self.state.lock().unwrap();
+        let lft = sim.lfts.get_mut(&guid).ok_or(SimError::UnknownNode(guid))?;
+        if lft.len() <= lid as usize {
+            lft.resize(lid as usize + 1, LFT_NO_PORT);
+        }
+        lft[lid as usize] = port;
+        Ok(())
+    }
+
+    pub fn lft(&self, guid: u64) -> Result<Vec<u8>, SimError> {
+        let sim = self.state.lock().unwrap();
+        sim.lfts.get(&guid).cloned().ok_or(SimError::UnknownNode(guid))
+    }
+
+    //Move the SM, by default it runs on the origin port
+    pub fn set_sm(&self, guid: u64, port: u8) -> Result<(), SimError> {
+        let mut sim = self.state.lock().unwrap();
+        sim.port_mut(guid, port)?;
+        sim.sm = (guid, port);
+        Ok(())
+    }
+
+    //Lose the next transmission of a segment of an RMPP response
+    pub fn drop_rmpp_segment(&self, seg_num: u32) {
+        self.state.lock().unwrap().rmpp_drops.insert(seg_num);
+    }
+
+    //Report a Notice to the origin port if it subscribed to the trap. The
+    //Reports come back through MadChannel::recv.
+    pub fn notify(&self, notice: &Notice) {
+        let sim = self.state.lock().unwrap();
+        if !sim.subscriptions.iter().any(|s| s.matches(notice.trap_number)) {
+            return;
+        }
+
+        let mut report = ib_user_mad::new();
+        report.addr.lid = sim.sm_lid().to_be();
+        report.addr.qpn = 1_u32.to_be();
+        drop(sim);
+
+        let mad = &mut report.data;
+        mad[0] = 1;
+        mad[1] = MAD_CLASSES_IB_SA_CLASS as u8;
+        mad[2] = ibmad::transport::class_version(MAD_CLASSES_IB_SA_CLASS);
+        mad[3] = MAD_METHODS_IB_MAD_METHOD_REPORT as u8;
+        mad[8..16].copy_from_slice(&self.tid.fetch_add(1, Ordering::Relaxed).to_be_bytes());
+        mad[16..18].copy_from_slice(&(MadAttrId::Notice as u16).to_be_bytes());
+        let offset = class_data_offset(MAD_CLASSES_IB_SA_CLASS);
+        notice.encode(&mut mad[offset..offset + NOTICE_SIZE]);
+
+        self.completions.lock().unwrap().push_back(report);
+        self.completion_ready.notify_one();
+    }
+
+    //ReportResponses received for Reports sent by notify
+    pub fn reports_acked(&self) -> u32 {
+        self.state.lock().unwrap().reports_acked
+    }
+
+    //Lose the next count MADs sent through the simulator as a MadChannel
+    pub fn drop_mads(&self, count: u32) {
+        self.state.lock().unwrap().mad_drops += count;
+    }
+
+    pub fn counters(&self, guid: u64, port: u8) -> Result<HashMap<String, u64>, SimError> {
+        let mut sim = self.state.lock().unwrap();
+        Ok(sim.port_mut(guid, port)?.counters.clone())
+    }
+
+    pub fn node_desc(&self, guid: u64) -> Result<String, SimError> {
+        let sim = self.state.lock().unwrap();
+        Ok(sim.node(guid)?.node_desc.clone())
+    }
+
+    //Process a request MAD in place, leaving the response in the buffer.
+    //dlid is ignored for directed route SMPs. Of a response too large for one
+    //MAD only the first RMPP segment is left.
+    pub fn process_mad(&self, mad: &mut [u8], dlid: u16) -> Result<(), SimError> {
+        let mut response = self.respond(mad, dlid)?;
+        if response.len() > IB_MAD_SIZE as usize {
+            response = RmppSender::new(&response).next_segments().remove(0);
+        }
+
+        mad[..IB_MAD_SIZE as usize].copy_from_slice(&response[..IB_MAD_SIZE as usize]);
+        Ok(())
+    }
+
+    //The response to a request MAD. An RMPP response comes back whole, with
+    //the data running past the end of one MAD.
+    pub fn respond(&self, request: &[u8], dlid: u16) -> Result<Vec<u8>, SimError> {
+        if request.len() < IB_MAD_SIZE as usize {
+            return Err(SimError::InvalidMad);
+        }
+
+        let mut mad = request[..IB_MAD_SIZE as usize].to_vec();
+        let mut sim = self.state.lock().unwrap();
+        let mgmt_class = mad[1] as u32;
+
+        let (guid, in_port) = match mgmt_class {
+            MAD_CLASSES_IB_SMI_DIRECT_CLASS => Simulator::route_dr(&sim, self.origin, &mut mad)?,
+            MAD_CLASSES_IB_SMI_CLASS | MAD_CLASSES_IB_PERFORMANCE_CLASS => Simulator::route_lid(&sim, self.origin, dlid)?,
+            //Nothing but the SM port has an SA agent to answer
+            MAD_CLASSES_IB_SA_CLASS if sim.lid_owner(dlid) == Some(sim.sm) => Simulator::route_lid(&sim, self.origin, dlid)?,
+            MAD_CLASSES_IB_SA_CLASS => return Err(SimError::Unreachable),
+            _ => return Err(SimError::UnsupportedClass(mad[1])),
+        };
+
+        let method = mad[3] as u32;
+        let attr_id = u16::from_be_bytes([mad[16], mad[17]]) as u32;
+        let attr_mod = u32::from_be_bytes([mad[20], mad[21], mad[22], mad[23]]);
+        let offset = class_data_offset(mgmt_class);
+        let mut data = mad[offset..offset + class_data_size(mgmt_class)].to_vec();
+
+        let get_table = mgmt_class == MAD_CLASSES_IB_SA_CLASS && method == MAD_METHODS_IB_MAD_METHOD_GET_TABLE;
+        let mut payload = None;
+
+        let status = if method != MAD_METHODS_IB_MAD_METHOD_GET && method != MAD_METHODS_IB_MAD_METHOD_SET && !get_table {
+            MAD_STATUS_UNSUPPORTED_METHOD
+        } else if mad[2] != ibmad::transport::class_version(mgmt_class) {
+            MAD_STATUS_BAD_VERSION
+        } else if mgmt_class == MAD_CLASSES_IB_PERFORMANCE_CLASS {
+            Simulator::answer_pma(&mut sim, guid, method, attr_id, &mut data)
+        } else if mgmt_class == MAD_CLASSES_IB_SA_CLASS {
+            let comp_mask = u64::from_be_bytes(mad[SA_COMP_MASK_OFFSET..SA_COMP_MASK_OFFSET + 8].try_into().unwrap());
+            let (status, records, attr_offset) = Simulator::answer_sa(&mut sim, method, attr_id, comp_mask, &data);
+
+            mad[SA_ATTR_OFFSET_OFFSET..SA_ATTR_OFFSET_OFFSET + 2].copy_from_slice(&attr_offset.to_be_bytes());
+            if get_table {
+                RmppHeader {
+                    version: RMPP_VERSION,
+                    rmpp_type: RMPP_TYPE_DATA,
+                    flags: RMPP_FLAG_ACTIVE | RMPP_FLAG_FIRST | RMPP_FLAG_LAST,
+                    seg_num: 1,
+                    paylen_newwin: (offset - RMPP_HDR_OFFSET - RMPP_HDR_SIZE + records.len()) as u32,
+                    ..Default::default()
+                }
+                .write(&mut mad);
+            }
+            payload = Some(records);
+            status
+        } else {
+            Simulator::answer_smp(&mut sim, guid, in_port, method, attr_id, attr_mod, &mut data)
+        };
+
+        mad[offset..offset + data.len()].copy_from_slice(&data);
+        if let Some(records) = payload {
+            mad.truncate(offset);
+            mad.extend_from_slice(&records);
+            mad.resize(mad.len().max(IB_MAD_SIZE as usize), 0);
+        }
+        mad[3] = if get_table {
+            MAD_METHODS_IB_MAD_METHOD_GET_TABLE_RESPONSE as u8
+        } else {
+            MAD_METHODS_IB_MAD_METHOD_GET_RESPONSE as u8
+        };
+
+        let mut status_field = u16::from_be_bytes([mad[4], mad[5]]) & 0x8000;
+        status_field |= status;
+        mad[4..6].copy_from_slice(&status_field.to_be_bytes());
+
+        if mgmt_class == MAD_CLASSES_IB_SMI_DIRECT_CLASS {
+            Simulator::return_dr(&sim, self.origin, guid, &mut mad)?;
+        }
+
+        Ok(mad)
+    }
+
+    //Walk the initial path of an outgoing DR SMP, recording the arrival port
+    //of each hop in the return path. Returns the node and port it lands on.
+    fn route_dr(sim: &SimState, origin: (u64, u8), mad: &mut [u8]) -> Result<(u64, u8), SimError> {
+        let hop_cnt = mad[7] as usize;
+        if hop_cnt >= IB_SUBNET_PATH_HOPS_MAX as usize || mad[6] != 0 || mad[4] & 0x80 != 0 {
+            return Err(SimError::InvalidMad);
+        }
+
+        let (mut guid, mut port) = origin;
+        for hop_ptr in 0..hop_cnt {
+            let node = sim.node(guid)?;
+
+            //Only switches forward DR SMPs, the originating CA sends on its own port
+            let out_port = match (hop_ptr, node.node_type) {
+                (_, SimNodeType::Switch) => mad[DR_INITIAL_PATH_OFFSET + hop_ptr + 1],
+                (0, _) => origin.1,
+                _ => return Err(SimError::Unreachable),
+            };
+
+            (guid, port) = sim.traverse(guid, out_port, true).ok_or(SimError::Unreachable)?;
+
+            mad[6] = (hop_ptr + 1) as u8;
+            mad[DR_RETURN_PATH_OFFSET + hop_ptr + 1] = port;
+        }
+
+        if hop_cnt > 0 {
+            mad[6] = (hop_cnt + 1) as u8;
+        }
+
+        Ok((guid, port))
+    }
+
+    //Send the response back along the return path, setting the direction bit.
+    //The hop pointer is decremented at each hop and ends at 0 at the origin.
+    fn return_dr(sim: &SimState, origin: (u64, u8), responder: u64, mad: &mut [u8]) -> Result<(), SimError> {
+        let hop_cnt = mad[7] as usize;
+        mad[4] |= 0x80;
+
+        let mut guid = responder;
+        for hop_ptr in (1..=hop_cnt).rev() {
+            mad[6] = hop_ptr as u8;
+            (guid, _) = sim.traverse(guid, mad[DR_RETURN_PATH_OFFSET + hop_ptr], true).ok_or(SimError::Unreachable)?;
+        }
+        mad[6] = 0;
+
+        if guid != origin.0 {
+            return Err(SimError::Unreachable);
+        }
+
+        Ok(())
+    }
+
+    //LID routed MADs follow the switch LFTs over Active links. Responses are
+    //assumed to find their way back.
+    fn route_lid(sim: &SimState, origin: (u64, u8), dlid: u16) -> Result<(u64, u8), SimError> {
+        let (dest_guid, dest_port) = sim.lid_owner(dlid).ok_or(SimError::Unreachable)?;
+
+        if dest_guid == origin.0 {
+            return Ok((dest_guid, origin.1));
+        }
+
+        let (mut guid, mut in_port) = origin;
+        if !sim.is_switch(guid) {
+            (guid, in_port) = sim.traverse(guid, origin.1, false).ok_or(SimError::Unreachable)?;
+        }
+
+        for _ in 0..IB_SUBNET_PATH_HOPS_MAX {
+            if guid == dest_guid {
+                if sim.is_switch(guid) {
+                    return Ok((guid, in_port));
+                }
+                return Ok((guid, dest_port));
+            }
+            if !sim.is_switch(guid) {
+                break;
+            }
+
+            let out_port = sim.lfts.get(&guid).and_then(|lft| lft.get(dlid as usize)).copied().unwrap_or(LFT_NO_PORT);
+            if out_port == 0 || out_port == LFT_NO_PORT {
+                break;
+            }
+            (guid, in_port) = sim.traverse(guid, out_port, false).ok_or(SimError::Unreachable)?;
+        }
+
+        Err(SimError::Unreachable)
+    }
+
+    fn answer_smp(sim: &mut SimState, guid: u64, in_port: u8, method: u32, attr_id: u32, attr_mod: u32, data: &mut [u8]) -> u16 {
+        let Some(node) = sim.nodes.get_mut(&guid) else {
+            return MAD_STATUS_INVALID_ATTR_VALUE;
+        };
+
+        if method == MAD_METHODS_IB_MAD_METHOD_SET {
+            if attr_id != SMI_ATTR_ID_IB_ATTR_NODE_DESC {
+                return MAD_STATUS_UNSUPPORTED_METHOD;
+            }
+            node.node_desc = ibmad::node_desc_from_mad_fields(data);
+        }
+
+        data.fill(0);
+
+        match attr_id {
+            SMI_ATTR_ID_IB_ATTR_NODE_INFO => {
+                encode_node_info(node, in_port, data);
+                0
+            }
+            SMI_ATTR_ID_IB_ATTR_NODE_DESC => {
+                let desc = node.node_desc.as_bytes();
+                let len = desc.len().min(data.len());
+                data[..len].copy_from_slice(&desc[..len]);
+                0
+            }
+            SMI_ATTR_ID_IB_ATTR_PORT_INFO => {
+                //Port 0 of a CA means the port the SMP arrived on
+                let number = match (attr_mod, node.node_type) {
+                    (0, SimNodeType::Switch) => 0,
+                    (0, _) => in_port,
+                    (n, _) if n <= node.num_ports as u32 => n as u8,
+                    _ => return MAD_STATUS_INVALID_ATTR_VALUE,
+                };
+
+                let node = &sim.nodes[&guid];
+                let Some(port) = node.ports.iter().find(|p| p.number == number) else {
+                    return MAD_STATUS_INVALID_ATTR_VALUE;
+                };
+                encode_port_info(node, port, sim.sm_lid(), data);
+                0
+            }
+            SMI_ATTR_ID_IB_ATTR_MLNX_EXT_PORT_INFO => {
+                let Some(port) = node.ports.iter().find(|p| p.number as u32 == attr_mod) else {
+                    return MAD_STATUS_INVALID_ATTR_VALUE;
+                };
+                set_field(data, MAD_FIELDS_IB_MLNX_EXT_PORT_LINK_SPEED_SUPPORTED_F, 1);
+                set_field(data, MAD_FIELDS_IB_MLNX_EXT_PORT_LINK_SPEED_ENABLED_F, 1);
+                set_field(data, MAD_FIELDS_IB_MLNX_EXT_PORT_LINK_SPEED_ACTIVE_F, port.mlnx_link_speed_active as u32);
+                0
+            }
+            SMI_ATTR_ID_IB_ATTR_SWITCH_INFO if node.node_type == SimNodeType::Switch => {
+                let lft_top = sim.lfts.get(&guid).map_or(0, |lft| lft.len().saturating_sub(1));
+                encode_switch_info(lft_top as u16, data);
+                0
+            }
+            SMI_ATTR_ID_IB_ATTR_LINEARFORWTBL if node.node_type == SimNodeType::Switch => {
+                let first = attr_mod as usize * LFT_BLOCK_SIZE;
+                if first >= LINEAR_FDB_CAP as usize {
+                    return MAD_STATUS_INVALID_ATTR_VALUE;
+                }
+
+                let lft = sim.lfts.get(&guid).map_or(&[][..], |lft| lft.as_slice());
+                for (i, entry) in data[..LFT_BLOCK_SIZE].iter_mut().enumerate() {
+                    *entry = lft.get(first + i).copied().unwrap_or(LFT_NO_PORT);
+                }
+                0
+            }
+            _ => MAD_STATUS_UNSUPPORTED_ATTR,
+        }
+    }
+
+    //Records matching the template on the components in comp_mask, padded to
+    //the attribute offset. Returns the status, records and attribute offset.
+    fn answer_sa(sim: &mut SimState, method: u32, attr_id: u32, comp_mask: u64, data: &[u8]) -> (u16, Vec<u8>, u16) {
+        //Subscriptions replace any earlier one for the same trap
+        if attr_id == SA_ATTR_INFORM_INFO {
+            if method != MAD_METHODS_IB_MAD_METHOD_SET {
+                return (MAD_STATUS_UNSUPPORTED_METHOD, Vec::new(), 0);
+            }
+            let info = InformInfo::decode(data);
+            sim.subscriptions.retain(|s| s.trap_number != info.trap_number);
+            if info.subscribe {
+                sim.subscriptions.push(info.clone());
+            }
+            return sa_response(MAD_METHODS_IB_MAD_METHOD_GET, vec![info]);
+        }
+
+        if method != MAD_METHODS_IB_MAD_METHOD_GET && method != MAD_METHODS_IB_MAD_METHOD_GET_TABLE {
+            return (MAD_STATUS_UNSUPPORTED_METHOD, Vec::new(), 0);
+        }
+
+        match attr_id {
+            SA_ATTR_NODE_RECORD => {
+                let t = NodeRecord::decode(data);
+                let records = sim.node_records().into_iter().filter(|r| {
+                    component(comp_mask, NodeRecord::COMP_LID, t.lid, r.lid)
+                        && component(comp_mask, NodeRecord::COMP_NODE_TYPE, t.node_info.node_type, r.node_info.node_type)
+                        && component(comp_mask, NodeRecord::COMP_SYSTEM_GUID, t.node_info.system_guid, r.node_info.system_guid)
+                        && component(comp_mask, NodeRecord::COMP_NODE_GUID, t.node_info.guid, r.node_info.guid)
+                        && component(comp_mask, NodeRecord::COMP_PORT_GUID, t.node_info.port_guid, r.node_info.port_guid)
+                        && component(comp_mask, NodeRecord::COMP_NODE_DESC, &t.node_desc, &r.node_desc)
+                });
+                sa_response(method, records.collect())
+            }
+            SA_ATTR_PORT_INFO_RECORD => {
+                let t = PortInfoRecord::decode(data);
+                let records = sim.port_info_records().into_iter().filter(|r| {
+                    component(comp_mask, PortInfoRecord::COMP_LID, t.lid, r.lid)
+                        && component(comp_mask, PortInfoRecord::COMP_PORT_NUM, t.port_num, r.port_num)
+                        && component(comp_mask, PortInfoRecord::COMP_BASE_LID, t.port_info.lid, r.port_info.lid)
+                        && component(comp_mask, PortInfoRecord::COMP_CAP_MASK, t.port_info.cap_mask, r.port_info.cap_mask)
+                        && component(comp_mask, PortInfoRecord::COMP_PORT_STATE, t.port_info.state, r.port_info.state)
+                });
+                sa_response(method, records.collect())
+            }
+            SA_ATTR_LINK_RECORD => {
+                let t = LinkRecord::decode(data);
+                let records = sim.link_records().into_iter().filter(|r| {
+                    component(comp_mask, LinkRecord::COMP_FROM_LID, t.from_lid, r.from_lid)
+                        && component(comp_mask, LinkRecord::COMP_FROM_PORT, t.from_port, r.from_port)
+                        && component(comp_mask, LinkRecord::COMP_TO_PORT, t.to_port, r.to_port)
+                        && component(comp_mask, LinkRecord::COMP_TO_LID, t.to_lid, r.to_lid)
+                });
+                sa_response(method, records.collect())
+            }
+            SA_ATTR_PATH_RECORD => {
+                let both = PathRecord::COMP_SLID | PathRecord::COMP_DLID;
+                if comp_mask & both != both {
+                    return (SA_STATUS_INSUFFICIENT_COMPONENTS, Vec::new(), 0);
+                }
+                let t = PathRecord::decode(data);
+                let records = sim.path_records(t.slid, t.dlid).into_iter().filter(|r| {
+                    component(comp_mask, PathRecord::COMP_PKEY, t.pkey, r.pkey) && component(comp_mask, PathRecord::COMP_SL, t.sl, r.sl)
+                });
+                sa_response(method, records.collect())
+            }
+            SA_ATTR_SM_INFO_RECORD => {
+                let t = SmInfoRecord::decode(data);
+                let records = sim.sm_info_records().into_iter().filter(|r| {
+                    component(comp_mask, SmInfoRecord::COMP_LID, t.lid, r.lid)
+                        && component(comp_mask, SmInfoRecord::COMP_GUID, t.guid, r.guid)
+                        && component(comp_mask, SmInfoRecord::COMP_PRIORITY, t.priority, r.priority)
+                        && component(comp_mask, SmInfoRecord::COMP_SM_STATE, t.sm_state, r.sm_state)
+                });
+                sa_response(method, records.collect())
+            }
+            SA_ATTR_GUID_INFO_RECORD => {
+                let t = GuidInfoRecord::decode(data);
+                let records = sim.guid_info_records().into_iter().filter(|r| {
+                    component(comp_mask, GuidInfoRecord::COMP_LID, t.lid, r.lid)
+                        && component(comp_mask, GuidInfoRecord::COMP_BLOCK_NUM, t.block_num, r.block_num)
+                });
+                sa_response(method, records.collect())
+            }
+            SA_ATTR_PKEY_TABLE_RECORD => {
+                let t = PKeyTableRecord::decode(data);
+                let records = sim.pkey_table_records().into_iter().filter(|r| {
+                    component(comp_mask, PKeyTableRecord::COMP_LID, t.lid, r.lid)
+                        && component(comp_mask, PKeyTableRecord::COMP_BLOCK_NUM, t.block_num, r.block_num)
+                        && component(comp_mask, PKeyTableRecord::COMP_PORT_NUM, t.port_num, r.port_num)
+                });
+                sa_response(method, records.collect())
+            }
+            //No multicast groups are simulated
+            SA_ATTR_MCMEMBER_RECORD => sa_response::<McMemberRecord>(method, Vec::new()),
+            _ => (MAD_STATUS_UNSUPPORTED_ATTR, Vec::new(), 0),
+        }
+    }
+
+    fn answer_pma(sim: &mut SimState, guid: u64, method: u32, attr_id: u32, data: &mut [u8]) -> u16 {
+        let Some(node) = sim.nodes.get_mut(&guid) else {
+            return MAD_STATUS_INVALID_ATTR_VALUE;
+        };
+
+        if method != MAD_METHODS_IB_MAD_METHOD_GET && method != MAD_METHODS_IB_MAD_METHOD_SET {
+            return MAD_STATUS_UNSUPPORTED_METHOD;
+        }
+
+        let ext_width = node.pma_cap_mask as u32 & (IB_PM_EXT_WIDTH_SUPPORTED | IB_PM_EXT_WIDTH_NOIETF_SUP) != 0;
+
+        //A Set clears the selected counters, then is answered as a Get
+        if method == MAD_METHODS_IB_MAD_METHOD_SET {
+            let (port_select, names) = match attr_id {
+                GSI_ATTR_ID_IB_GSI_PORT_COUNTERS => {
+                    let pc = PortCounters::from_mad_fields(data);
+                    let mut names = selected_counters(&PC_COUNTER_SELECT, pc.counter_select as u32);
+                    if node.pma_cap_mask as u32 & IB_PM_PC_XMIT_WAIT_SUP != 0 {
+                        names.extend(selected_counters(&PC_COUNTER_SELECT2, pc.counter_select2 as u32));
+                    }
+                    (pc.port_select as u32, names)
+                }
+                GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT if ext_width => {
+                    let offset = PC_EXT_COUNTER_SELECT2_OFFSET;
+                    let counter_select = get_field(data, MAD_FIELDS_IB_PC_EXT_COUNTER_SELECT_F);
+                    let counter_select2 = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap());
+                    let mut names = selected_counters(&PC_EXT_COUNTER_SELECT, counter_select);
+                    if node.pma_cap_mask2 & PMA_CAP2_ADDL_PORT_COUNTERS_EXT != 0 {
+                        names.extend(selected_counters(&PC_EXT_COUNTER_SELECT2, counter_select2));
+                    }
+                    (get_field(data, MAD_FIELDS_IB_PC_EXT_PORT_SELECT_F), names)
+                }
+                _ => return MAD_STATUS_UNSUPPORTED_ATTR,
+            };
+
+            if Simulator::pma_counters(node, port_select).is_none() {
+                return MAD_STATUS_INVALID_ATTR_VALUE;
+            }
+            for port in node.ports.iter_mut().filter(|p| p.number != 0 && (port_select == PMA_ALL_PORTS || p.number as u32 == port_select)) {
+                for name in &names {
+                    if let Some(value) = port.counters.get_mut(*name) {
+                        *value = 0;
+                    }
+                }
+            }
+        }
+
+        match attr_id {
+            MAD_ATTR_ID_CLASS_PORT_INFO => {
+                let cpi = PmaClassPortInfo {
+                    base_version: 1,
+                    class_version: 1,
+                    capability_mask: node.pma_cap_mask,
+                    capability_mask2: node.pma_cap_mask2,
+                    ..Default::default()
+                };
+                data.fill(0);
+                cpi.to_mad_fields(data);
+                0
+            }
+            GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT if ext_width => {
+                let port_select = get_field(data, MAD_FIELDS_IB_PC_EXT_PORT_SELECT_F);
+                let Some(mut counters) = Simulator::pma_counters(node, port_select) else {
+                    return MAD_STATUS_INVALID_ATTR_VALUE;
+                };
+
+                //What a PMA without the full extended attribute leaves out
+                if node.pma_cap_mask as u32 & IB_PM_EXT_WIDTH_SUPPORTED == 0 {
+                    counters.clear_cast_counters();
+                }
+                if node.pma_cap_mask2 & PMA_CAP2_ADDL_PORT_COUNTERS_EXT == 0 {
+                    counters.copy_errors(&ExtPerfCounters::default());
+                }
+
+                data.fill(0);
+                counters.to_mad_fields(data);
+                set_field(data, MAD_FIELDS_IB_PC_EXT_PORT_SELECT_F, port_select);
+                0
+            }
+            GSI_ATTR_ID_IB_GSI_PORT_COUNTERS => {
+                let port_select = get_field(data, MAD_FIELDS_IB_PC_PORT_SELECT_F);
+                let Some(counters) = Simulator::pma_counters(node, port_select) else {
+                    return MAD_STATUS_INVALID_ATTR_VALUE;
+                };
+
+                let mut legacy = PortCounters::saturating_from(&counters);
+                legacy.port_select = port_select as u8;
+                if node.pma_cap_mask as u32 & IB_PM_PC_XMIT_WAIT_SUP == 0 {
+                    legacy.xmit_waits = 0;
+                }
+
+                data.fill(0);
+                legacy.to_mad_fields(data);
+                0
+            }
+            _ => MAD_STATUS_UNSUPPORTED_ATTR,
+        }
+    }
+
+    //Counters of the selected port, or summed over all ports
+    fn pma_counters(node: &SimNode, port_select: u32) -> Option<ExtPerfCounters> {
+        if port_select == PMA_ALL_PORTS && node.pma_cap_mask as u32 & IB_PM_ALL_PORT_SELECT == 0 {
+            return None;
+        }
+
+        let ports: Vec<&SimPort> = if port_select == PMA_ALL_PORTS {
+            node.ports.iter().filter(|p| p.number != 0).collect()
+        } else {
+            node.ports.iter().filter(|p| p.number as u32 == port_select).collect()
+        };
+        if ports.is_empty() {
+            return None;
+        }
+
+        let mut counters = ExtPerfCounters::default();
+        for port in ports {
+            for (name, value) in &port.counters {
+                if let Some(total) = counters.get_mut(name) {
+                    *total = total.saturating_add(*value);
+                }
+            }
+        }
+        Some(counters)
+    }
+}
+
+fn component<T: PartialEq>(comp_mask: u64, bit: u64, template: T, value: T) -> bool {
+    comp_mask & bit == 0 || template == value
+}
+
+//A Get has to match exactly one record, GetTable takes any number
+fn sa_response<R: SaRecord>(method: u32, records: Vec<R>) -> (u16, Vec<u8>, u16) {
+    let stride = R::SIZE.div_ceil(8) * 8;
+
+    if method == MAD_METHODS_IB_MAD_METHOD_GET && records.len() != 1 {
+        let status = if records.is_empty() { SA_STATUS_NO_RECORDS } else { SA_STATUS_TOO_MANY_RECORDS };
+        return (status, Vec::new(), (stride / 8) as u16);
+    }
+
+    let mut data = vec![0; stride * records.len()];
+    for (record, chunk) in records.iter().zip(data.chunks_exact_mut(stride)) {
+        record.encode(chunk);
+    }
+    (0, data, (stride / 8) as u16)
+}
+
+fn get_field(data: &mut [u8], field: MAD_FIELDS) -> u32 {
+    unsafe { mad_get_field(data.as_mut_ptr() as *mut c_void, 0, field) }
+}
+
+fn set_field(data: &mut [u8], field: MAD_FIELDS, value: u32) {
+    unsafe { mad_set_field(data.as_mut_ptr() as *mut c_void, 0, field, value) }
+}
+
+fn set_field64(data: &mut [u8], field: MAD_FIELDS, value: u64) {
+    unsafe { mad_set_field64(data.as_mut_ptr() as *mut c_void, 0, field, value) }
+}
+
+fn encode_node_info(node: &SimNode, in_port: u8, data: &mut [u8]) {
+    let (node_type, port_guid) = match node.node_type {
+        SimNodeType::Ca => (MAD_NODE_TYPE_IB_NODE_CA, node.ports.iter().find(|p| p.number == in_port).map_or(node.guid, |p| p.guid)),
+        SimNodeType::Switch => (MAD_NODE_TYPE_IB_NODE_SWITCH, node.guid),
+        SimNodeType::Router => (MAD_NODE_TYPE_IB_NODE_ROUTER, node.guid),
+    };
+
+    set_field(data, MAD_FIELDS_IB_NODE_BASE_VERS_F, 1);
+    set_field(data, MAD_FIELDS_IB_NODE_CLASS_VERS_F, 1);
+    set_field(data, MAD_FIELDS_IB_NODE_TYPE_F, node_type);
+    set_field(data, MAD_FIELDS_IB_NODE_NPORTS_F, node.num_ports as u32);
+    set_field64(data, MAD_FIELDS_IB_NODE_SYSTEM_GUID_F, node.system_guid);
+    set_field64(data, MAD_FIELDS_IB_NODE_GUID_F, node.guid);
+    set_field64(data, MAD_FIELDS_IB_NODE_PORT_GUID_F, port_guid);
+    set_field(data, MAD_FIELDS_IB_NODE_PARTITION_CAP_F, 128);
+    set_field(data, MAD_FIELDS_IB_NODE_DEVID_F, node.device_id);
+    set_field(data, MAD_FIELDS_IB_NODE_LOCAL_PORT_F, in_port as u32);
+    set_field(data, MAD_FIELDS_IB_NODE_VENDORID_F, node.vendor_id);
+}
+
+fn encode_port_info(node: &SimNode, port: &SimPort, sm_lid: u16, data: &mut [u8]) {
+    let speed_ext = if port.link_speed_ext_active != 0 { 0x0f } else { 0 };
+
+    set_field(data, MAD_FIELDS_IB_PORT_LID_F, port.lid as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_SMLID_F, sm_lid as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_LOCAL_PORT_F, port.number as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_ENABLED_F, 0xff);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_SUPPORTED_F, 0x1f);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_ACTIVE_F, port.link_width_active as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_SUPPORTED_F, 0x07);
+    set_field(data, MAD_FIELDS_IB_PORT_STATE_F, port.state as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_PHYS_STATE_F, port.phys_state as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_DOWN_DEF_F, PORT_PHYS_STATE_POLLING as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_LMC_F, port.lmc as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_ACTIVE_F, port.link_speed_active as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_ENABLED_F, 0x07);
+    set_field(data, MAD_FIELDS_IB_PORT_NEIGHBOR_MTU_F, 5); //4096
+    set_field(data, MAD_FIELDS_IB_PORT_VL_CAP_F, 4); //VL0-7
+    set_field(data, MAD_FIELDS_IB_PORT_MTU_CAP_F, 5);
+    set_field(data, MAD_FIELDS_IB_PORT_OPER_VLS_F, 4);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_EXT_ACTIVE_F, port.link_speed_ext_active as u32);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_EXT_SUPPORTED_F, speed_ext);
+    set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_EXT_ENABLED_F, speed_ext);
+
+    //IsExtendedSpeedsSupported
+    if speed_ext != 0 {
+        set_field(data, MAD_FIELDS_IB_PORT_CAPMASK_F, 1 << 14);
+    }
+
+    if node.node_type == SimNodeType::Switch && port.number == 0 {
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_WIDTH_ACTIVE_F, 0);
+        set_field(data, MAD_FIELDS_IB_PORT_LINK_SPEED_ACTIVE_F, 0);
+    }
+    //Only port 0 of a switch carries its LID and LMC
+    if node.node_type == SimNodeType::Switch && port.number != 0 {
+        set_field(data, MAD_FIELDS_IB_PORT_LID_F, 0);
+        set_field(data, MAD_FIELDS_IB_PORT_LMC_F, 0);
+    }
+}
+
+fn encode_switch_info(lft_top: u16, data: &mut [u8]) {
+    set_field(data, MAD_FIELDS_IB_SW_LINEAR_FDB_CAP_F, LINEAR_FDB_CAP as u32);
+    set_field(data, MAD_FIELDS_IB_SW_MCAST_FDB_CAP_F, 0x1000);
+    set_field(data, MAD_FIELDS_IB_SW_LINEAR_FDB_TOP_F, lft_top as u32);
+    set_field(data, MAD_FIELDS_IB_SW_LIFE_TIME_F, 18);
+    set_field(data, MAD_FIELDS_IB_SW_LIDS_PER_PORT_F, 1);
+    set_field(data, MAD_FIELDS_IB_SW_PARTITION_ENFORCE_CAP_F, 8);
+    set_field(data, MAD_FIELDS_IB_SW_ENHANCED_PORT0_F, 1);
+}
+
+impl MadTransport for Simulator {
+    fn call(&self, request: &MadRequest) -> Result<MadResponse, IBSmpError> {
+        let tid = self.tid.fetch_add(1, Ordering::Relaxed);
+        let mad = request.encode(tid);
+
+        let dlid = match request.address {
+            MadAddress::Lid(lid) => lid,
+            MadAddress::DirectedRoute(_) => 0xffff,
+        };
+
+        let mut response = self.respond(&mad, dlid).map_err(|_| IBSmpError::SendMADError)?;
+        if response.len() > IB_MAD_SIZE as usize {
+            response = self.transfer(&response)?;
+        }
+
+        Ok(MadResponse::decode(&response))
+    }
+}
+
+impl Simulator {
+    //Send a response through RMPP segmentation and reassembly, losing the
+    //segments asked for along the way
+    fn transfer(&self, response: &[u8]) -> Result<Vec<u8>, IBSmpError> {
+        let mut sender = RmppSender::new(response);
+        let mut receiver = RmppReceiver::new(response[1]);
+
+        loop {
+            let segments = sender.next_segments();
+            if segments.is_empty() {
+                sender.on_timeout()?;
+                continue;
+            }
+
+            for segment in segments {
+                let seg_num = RmppHeader::from_mad(&segment).seg_num;
+                if self.state.lock().unwrap().rmpp_drops.remove(&seg_num) {
+                    continue;
+                }
+
+                match receiver.receive(&segment) {
+                    RmppReply::Wait => {}
+                    RmppReply::Ack(ack) => {
+                        sender.on_reply(&ack)?;
+                    }
+                    RmppReply::Done { mad, .. } => return Ok(mad),
+                    RmppReply::Fail(_, e) => return Err(e.into()),
+                }
+            }
+        }
+    }
+}
+
+//Answers as the kernel would an agent on the origin port. A send that is
+//lost, or that can't be delivered, comes back with its status set.
+impl MadChannel for Simulator {
+    fn send(&self, umad: &ib_user_mad, _timeout: u32) -> Result<(), UmadError> {
+        if umad.data[3] as u32 == MAD_METHODS_IB_MAD_METHOD_REPORT_RESPONSE {
+            self.state.lock().unwrap().reports_acked += 1;
+            return Ok(());
+        }
+
+        let mut completion = *umad;
+
+        let lost = {
+            let mut sim = self.state.lock().unwrap();
+            let lost = sim.mad_drops > 0;
+            sim.mad_drops = sim.mad_drops.saturating_sub(1);
+            lost
+        };
+
+        let dlid = u16::from_be(umad.addr.lid);
+        if lost || self.process_mad(&mut completion.data, dlid).is_err() {
+            completion = *umad;
+            completion.status = libc::ETIMEDOUT as u32;
+        }
+
+        self.completions.lock().unwrap().push_back(completion);
+        self.completion_ready.notify_one();
+        Ok(())
+    }
+
+    fn recv(&self, timeout: i32) -> Result<ib_user_mad, UmadError> {
+        let completions = self.completions.lock().unwrap();
+        let mut completions = if timeout < 0 {
+            self.completion_ready.wait_while(completions, |c| c.is_empty()).unwrap()
+        } else {
+            let timeout = Duration::from_millis(timeout as u64);
+            self.completion_ready.wait_timeout_while(completions, timeout, |c| c.is_empty()).unwrap().0
+        };
+
+        completions.pop_front().ok_or(UmadError::Timeout)
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ibmad::perf::PMA_CAP2_ADDL_PORT_COUNTERS_EXT;
+use crate::ibmad::sys::{IB_PM_ALL_PORT_SELECT, IB_PM_EXT_WIDTH_SUPPORTED, IB_PM_PC_XMIT_WAIT_SUP};
+
+use super::SimError;
+
+pub const PORT_STATE_DOWN: u8 = 1;
+pub const PORT_STATE_INIT: u8 = 2;
+pub const PORT_STATE_ARMED: u8 = 3;
+pub const PORT_STATE_ACTIVE: u8 = 4;
+
+pub const PORT_PHYS_STATE_POLLING: u8 = 2;
+pub const PORT_PHYS_STATE_DISABLED: u8 = 3;
+pub const PORT_PHYS_STATE_LINKUP: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SimNodeType {
+    Ca,
+    Switch,
+    Router,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimPort {
+    pub number: u8,
+    //CA and router port GUID, switches use the node GUID. 0 derives it from the node.
+    #[serde(default)]
+    pub guid: u64,
+    #[serde(default)]
+    pub lid: u16,
+    #[serde(default)]
+    pub lmc: u8,
+    #[serde(default = "default_state")]
+    pub state: u8,
+    #[serde(default = "default_phys_state")]
+    pub phys_state: u8,
+    #[serde(default = "default_link_width")]
+    pub link_width_active: u8,
+    #[serde(default = "default_link_speed")]
+    pub link_speed_active: u8,
+    #[serde(default)]
+    pub link_speed_ext_active: u8,
+    //MlnxExtPortInfo LinkSpeedActive, 1 is FDR10 with QDR in PortInfo
+    #[serde(default)]
+    pub mlnx_link_speed_active: u8,
+    #[serde(default)]
+    pub counters: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimNode {
+    pub guid: u64,
+    pub node_type: SimNodeType,
+    #[serde(default)]
+    pub node_desc: String,
+    pub num_ports: u8,
+    //Switch LID, or the LID of the first CA port. CA ports without their own
+    //LID are numbered on from it.
+    #[serde(default)]
+    pub lid: u16,
+    #[serde(default)]
+    pub system_guid: u64,
+    #[serde(default = "default_vendor_id")]
+    pub vendor_id: u32,
+    #[serde(default)]
+    pub device_id: u32,
+    //PMA ClassPortInfo CapabilityMask and CapabilityMask2
+    #[serde(default = "default_pma_cap_mask")]
+    pub pma_cap_mask: u16,
+    #[serde(default = "default_pma_cap_mask2")]
+    pub pma_cap_mask2: u32,
+    #[serde(default)]
+    pub ports: Vec<SimPort>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SimEndpoint {
+    pub guid: u64,
+    pub port: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimLink {
+    pub from: SimEndpoint,
+    pub to: SimEndpoint,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Topology {
+    pub nodes: Vec<SimNode>,
+    pub links: Vec<SimLink>,
+}
+
+fn default_state() -> u8 {
+    PORT_STATE_ACTIVE
+}
+
+fn default_phys_state() -> u8 {
+    PORT_PHYS_STATE_LINKUP
+}
+
+fn default_link_width() -> u8 {
+    2 //4x
+}
+
+fn default_link_speed() -> u8 {
+    1 //SDR, see link_speed_ext_active for FDR and above
+}
+
+fn default_vendor_id() -> u32 {
+    0x02c9
+}
+
+fn default_pma_cap_mask() -> u16 {
+    (IB_PM_ALL_PORT_SELECT | IB_PM_EXT_WIDTH_SUPPORTED | IB_PM_PC_XMIT_WAIT_SUP) as u16
+}
+
+fn default_pma_cap_mask2() -> u32 {
+    PMA_CAP2_ADDL_PORT_COUNTERS_EXT
+}
+
+impl SimPort {
+    pub fn new(number: u8) -> SimPort {
+        SimPort {
+            number,
+            guid: 0,
+            lid: 0,
+            lmc: 0,
+            state: default_state(),
+            phys_state: default_phys_state(),
+            link_width_active: default_link_width(),
+            link_speed_active: default_link_speed(),
+            link_speed_ext_active: 0,
+            mlnx_link_speed_active: 0,
+            counters: HashMap::new(),
+        }
+    }
+}
+
+impl SimNode {
+    pub fn new(guid: u64, node_type: SimNodeType, node_desc: &str, num_ports: u8, lid: u16) -> SimNode {
+        SimNode {
+            guid,
+            node_type,
+            node_desc: node_desc.to_string(),
+            num_ports,
+            lid,
+            system_guid: guid,
+            vendor_id: default_vendor_id(),
+            device_id: 0,
+            pma_cap_mask: default_pma_cap_mask(),
+            pma_cap_mask2: default_pma_cap_mask2(),
+            ports: Vec::new(),
+        }
+    }
+
+    pub fn port_mut(&mut self, number: u8) -> &mut SimPort {
+        if let Some(i) = self.ports.iter().position(|p| p.number == number) {
+            return &mut self.ports[i];
+        }
+        self.ports.push(SimPort::new(number));
+        self.ports.last_mut().unwrap()
+    }
+}
+
+impl Topology {
+    pub fn new() -> Topology {
+        Topology::default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Topology, SimError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, SimError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn add_switch(&mut self, guid: u64, node_desc: &str, num_ports: u8, lid: u16) -> &mut SimNode {
+        self.nodes.push(SimNode::new(guid, SimNodeType::Switch, node_desc, num_ports, lid));
+        self.nodes.last_mut().unwrap()
+    }
+
+    pub fn add_ca(&mut self, guid: u64, node_desc: &str, num_ports: u8, lid: u16) -> &mut SimNode {
+        self.nodes.push(SimNode::new(guid, SimNodeType::Ca, node_desc, num_ports, lid));
+        self.nodes.last_mut().unwrap()
+    }
+
+    pub fn link(&mut self, from: (u64, u8), to: (u64, u8)) {
+        self.links.push(SimLink {
+            from: SimEndpoint { guid: from.0, port: from.1 },
+            to: SimEndpoint { guid: to.0, port: to.1 },
+        });
+    }
+
+    pub fn node_mut(&mut self, guid: u64) -> Option<&mut SimNode> {
+        self.nodes.iter_mut().find(|n| n.guid == guid)
+    }
+}
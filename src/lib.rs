@@ -1,3 +1,4 @@
 pub mod ibmad;
 pub mod umad;
 pub mod ibnetdisc;
+pub mod ibsim;
\ No newline at end of file
@@ -0,0 +1,154 @@
+use std::ffi::{c_long, c_void};
+
+use crate::umad::{self};
+
+use umad::sys::*;
+use umad::mad::ib_user_mad;
+use umad::rmpp::{RmppHeader, RmppReceiver, RmppReply};
+use umad::{UmadError, UmadPort};
+
+const MAD_SIZE: usize = 256;
+
+//A MAD agent registered on an open port. Responses to the agent's own
+//requests come back to it, as do unsolicited requests of the class with one
+//of the methods it registered for. RMPP is left to umad::rmpp rather than
+//the kernel, so segments arrive one MAD at a time.
+#[derive(Debug)]
+pub struct UmadAgent {
+    port_id: i32,
+    agent_id: i32,
+    pub mgmt_class: u8,
+    pub class_version: u8,
+}
+
+impl UmadAgent {
+    pub fn register(port: &UmadPort, mgmt_class: u8, class_version: u8, methods: &[u8]) -> Result<UmadAgent, UmadError> {
+        let mut method_mask: [c_long; 4] = [0; 4];
+        for method in methods {
+            method_mask[*method as usize / 64] |= 1 << (method % 64);
+        }
+
+        let mask_ptr = if methods.is_empty() { std::ptr::null_mut() } else { method_mask.as_mut_ptr() };
+        let agent_id = unsafe { umad_register(port.port_id, mgmt_class.into(), class_version.into(), 0, mask_ptr) };
+
+        if agent_id < 0 {
+            return Err(UmadError::RegisterMadAgentError);
+        }
+
+        Ok(UmadAgent {
+            port_id: port.port_id,
+            agent_id,
+            mgmt_class,
+            class_version,
+        })
+    }
+
+    pub fn id(&self) -> i32 {
+        self.agent_id
+    }
+
+    //The port's file descriptor, shared by all of its agents
+    pub fn fd(&self) -> i32 {
+        unsafe { umad_get_fd(self.port_id) }
+    }
+
+    //Send a MAD from this agent. With a timeout the kernel keeps the send
+    //outstanding until a response with the same TID arrives, retrying as
+    //asked, and hands back the send with a non-zero status if none does.
+    pub fn send(&self, umad: &ib_user_mad, timeout: u32, retries: u32) -> Result<(), UmadError> {
+        let mut umad = *umad;
+        umad.agent_id = self.agent_id as u32;
+
+        let r = unsafe { umad_send(self.port_id, self.agent_id, umad.as_c_void_ptr(), MAD_SIZE as i32, timeout as i32, retries as i32) };
+        if r < 0 {
+            return Err(UmadError::SendFailure);
+        }
+
+        Ok(())
+    }
+
+    //Wait up to timeout ms for the next MAD for this agent. MADs for other
+    //agents on the same port are dropped, a negative timeout waits forever.
+    pub fn recv(&self, timeout: i32) -> Result<ib_user_mad, UmadError> {
+        let umad = self.recv_completion(timeout)?;
+
+        //A send that got no response comes back with its status set
+        if umad.status != 0 {
+            return Err(UmadError::Timeout);
+        }
+
+        Ok(umad)
+    }
+
+    //As recv, but a send that got no response is handed back as is, with
+    //its status set, rather than as an error
+    pub fn recv_completion(&self, timeout: i32) -> Result<ib_user_mad, UmadError> {
+        loop {
+            let mut umad = ib_user_mad::new();
+            let mut length = MAD_SIZE as i32;
+
+            let r = unsafe { umad_recv(self.port_id, &mut umad as *mut ib_user_mad as *mut c_void, &mut length, timeout) };
+            if r == -libc::ETIMEDOUT {
+                return Err(UmadError::Timeout);
+            }
+            if r < 0 {
+                return Err(UmadError::RecvFailure);
+            }
+            if umad.agent_id == self.agent_id as u32 {
+                return Ok(umad);
+            }
+        }
+    }
+
+    //Send a MAD back to where umad came from
+    pub fn reply(&self, umad: &ib_user_mad, mad: &[u8]) -> Result<(), UmadError> {
+        let mut response = ib_user_mad::new();
+        response.addr = umad.addr;
+        let len = mad.len().min(MAD_SIZE);
+        response.data[..len].copy_from_slice(&mad[..len]);
+
+        self.send(&response, 0, 0)
+    }
+
+    //Receive the rest of an RMPP transfer starting with first, ACKing each
+    //window back to the sender. Returns the reassembled MAD.
+    pub fn recv_rmpp(&self, first: &ib_user_mad, timeout: i32) -> Result<Vec<u8>, UmadError> {
+        if !RmppHeader::from_mad(&first.data).is_active() {
+            return Ok(first.data[..MAD_SIZE].to_vec());
+        }
+
+        let mut receiver = RmppReceiver::new(first.data[1]);
+        let tid = &first.data[8..16];
+        let mut segment = *first;
+
+        loop {
+            match receiver.receive(&segment.data[..MAD_SIZE]) {
+                RmppReply::Wait => {}
+                RmppReply::Ack(ack) => self.reply(&segment, &ack)?,
+                RmppReply::Done { ack, mad } => {
+                    self.reply(&segment, &ack)?;
+                    return Ok(mad);
+                }
+                RmppReply::Fail(abort, e) => {
+                    if let Some(abort) = abort {
+                        let _ = self.reply(&segment, &abort);
+                    }
+                    return Err(e.into());
+                }
+            }
+
+            segment = loop {
+                let umad = self.recv(timeout)?;
+                if &umad.data[8..16] == tid {
+                    break umad;
+                }
+            };
+        }
+    }
+}
+
+impl Drop for UmadAgent {
+    fn drop(&mut self) {
+        unsafe { umad_unregister(self.port_id, self.agent_id) };
+    }
+}
@@ -0,0 +1,299 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::umad::{self};
+
+use umad::agent::UmadAgent;
+use umad::mad::ib_user_mad;
+use umad::{UmadError, UmadPort};
+
+//I2C address of the module EEPROM (A0h >> 1)
+pub const QSFP_SFP_DEVICE_ADDRESS: u8 = 0x50;
+
+//Mellanox vendor specific SMP attribute
+pub const CABLE_INFO_ATTR_ID: u16 = 0xff60;
+pub const CABLE_INFO_MAX_DATA_SIZE: u16 = 48;
+
+const SMI_LID_ROUTED_CLASS: u8 = 0x01;
+const SMP_METHOD_GET: u8 = 0x01;
+const SMP_DATA_OFFSET: usize = 64;
+const CABLE_INFO_RETRIES: u32 = 3;
+
+static CABLE_INFO_TID: AtomicU32 = AtomicU32::new(1);
+
+//SMP CableInfo attribute data. Multi-byte fields are kept in network byte order,
+//exactly as they are on the wire.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct CableInfo {
+    pub device_address: u16,
+    pub page_number: u8,
+    pub i2c_device_address: u8,
+    pub size: u16,
+    pub res1: u16,
+    pub res2: u64,
+    pub data: [u32; 12],
+}
+
+impl CableInfo {
+    pub fn new(page: u8, offset: u16) -> CableInfo {
+        CableInfo {
+            i2c_device_address: QSFP_SFP_DEVICE_ADDRESS,
+            page_number: page,
+            device_address: offset.to_be(),
+            res1: 0,
+            size: CABLE_INFO_MAX_DATA_SIZE.to_be(),
+            res2: 0,
+            data: [0; 12],
+        }
+    }
+
+    //EEPROM bytes in the order they were read
+    pub fn bytes(&self) -> [u8; 48] {
+        let data = self.data;
+        let mut bytes = [0; 48];
+        for (i, word) in data.iter().enumerate() {
+            bytes[i * 4..i * 4 + 4].copy_from_slice(&word.to_ne_bytes());
+        }
+        bytes
+    }
+
+    fn to_smp_data(self) -> [u8; 64] {
+        unsafe { std::mem::transmute::<CableInfo, [u8; 64]>(self) }
+    }
+
+    fn from_smp_data(data: [u8; 64]) -> CableInfo {
+        unsafe { std::mem::transmute::<[u8; 64], CableInfo>(data) }
+    }
+}
+
+//CMIS lower page module flags (bytes 8-11)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CableFlags {
+    pub module_state_changed: bool,
+    pub module_firmware_error: bool,
+    pub datapath_firmware_error: bool,
+    pub temp_mon_high_alarm: bool,
+    pub temp_mon_low_alarm: bool,
+    pub temp_mon_high_warn: bool,
+    pub temp_mon_low_warn: bool,
+    pub vcc_mon_high_alarm: bool,
+    pub vcc_mon_low_alarm: bool,
+    pub vcc_mon_high_warn: bool,
+    pub vcc_mon_low_warn: bool,
+    pub aux1_mon_high_alarm: bool,
+    pub aux1_mon_low_alarm: bool,
+    pub aux1_mon_high_warn: bool,
+    pub aux1_mon_low_warn: bool,
+    pub aux2_mon_high_alarm: bool,
+    pub aux2_mon_low_alarm: bool,
+    pub aux2_mon_high_warn: bool,
+    pub aux2_mon_low_warn: bool,
+    pub aux3_mon_high_alarm: bool,
+    pub aux3_mon_low_alarm: bool,
+    pub aux3_mon_high_warn: bool,
+    pub aux3_mon_low_warn: bool,
+}
+
+//CMIS page 11h lane flags (bytes 128-153), one bit per lane unless noted
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LaneSpecificFlags {
+    pub datapath_state: [u8; 8],
+    pub rx_output_status: u8,
+    pub tx_output_status: u8,
+    pub datapath_state_changed: u8,
+    pub tx_fault: u8,
+    pub tx_los: u8,
+    pub tx_cdr_lol: u8,
+    pub tx_adaptive_eq_fail: u8,
+    pub tx_power_high_alarm: u8,
+    pub tx_power_low_alarm: u8,
+    pub tx_power_high_warn: u8,
+    pub tx_power_low_warn: u8,
+    pub tx_bias_high_alarm: u8,
+    pub tx_bias_low_alarm: u8,
+    pub tx_bias_high_warn: u8,
+    pub tx_bias_low_warn: u8,
+    pub rx_los: u8,
+    pub rx_cdr_lol: u8,
+    pub rx_power_high_alarm: u8,
+    pub rx_power_low_alarm: u8,
+    pub rx_power_high_warn: u8,
+    pub rx_power_low_warn: u8,
+}
+
+//CMIS page 14h SNR diagnostics (bytes 192-223), in dB
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LaneSnr {
+    pub host: [f32; 8],
+    pub media: [f32; 8],
+}
+
+//The agent to read CableInfo through, register it once and read every page
+//with it
+pub fn register_cable_agent(port: &UmadPort) -> Result<UmadAgent, UmadError> {
+    UmadAgent::register(port, SMI_LID_ROUTED_CLASS, 1, &[])
+}
+
+pub fn get_cable_info(agent: &UmadAgent, lid: u16, portnum: u8, page: u8, offset: u16, timeout: u32) -> Result<CableInfo, UmadError> {
+    send_cable_info_mad(agent, lid, portnum, CableInfo::new(page, offset), timeout)
+}
+
+fn send_cable_info_mad(agent: &UmadAgent, lid: u16, portnum: u8, request: CableInfo, timeout: u32) -> Result<CableInfo, UmadError> {
+    let tid = CABLE_INFO_TID.fetch_add(1, Ordering::Relaxed);
+
+    let mut umad = ib_user_mad::new();
+    umad.addr.lid = lid.to_be();
+    umad.addr.qpn = 0;
+    umad.addr.qkey = 0;
+    umad.addr.sl = 0;
+
+    let mad = &mut umad.data;
+    mad[0] = 1; //Base Version
+    mad[1] = SMI_LID_ROUTED_CLASS;
+    mad[2] = 1; //Class Version
+    mad[3] = SMP_METHOD_GET;
+    mad[12..16].copy_from_slice(&tid.to_be_bytes());
+    mad[16..18].copy_from_slice(&CABLE_INFO_ATTR_ID.to_be_bytes());
+    mad[20..24].copy_from_slice(&u32::from(portnum).to_be_bytes());
+    mad[SMP_DATA_OFFSET..SMP_DATA_OFFSET + 64].copy_from_slice(&request.to_smp_data());
+
+    agent.send(&umad, timeout, CABLE_INFO_RETRIES)?;
+
+    loop {
+        let response = agent.recv(timeout as i32)?;
+
+        let mad = &response.data;
+        if mad[12..16] != tid.to_be_bytes() {
+            continue;
+        }
+
+        let status = u16::from_be_bytes([mad[4], mad[5]]);
+        if status != 0 {
+            return Err(UmadError::MadStatusError(status));
+        }
+
+        let mut data = [0; 64];
+        data.copy_from_slice(&mad[SMP_DATA_OFFSET..SMP_DATA_OFFSET + 64]);
+
+        return Ok(CableInfo::from_smp_data(data));
+    }
+}
+
+fn bit(byte: u8, n: u8) -> bool {
+    (byte >> n) & 0x1 == 0x1
+}
+
+fn get_ascii(cable_info: &CableInfo, len: usize) -> String {
+    let bytes = cable_info.bytes();
+    String::from_utf8_lossy(&bytes[..len])
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string()
+}
+
+//Page 0, offset 0: byte 3 bits 3-1
+pub fn get_cable_module_status(cable_info: &CableInfo) -> u8 {
+    (cable_info.bytes()[3] >> 1) & 0x7
+}
+
+//Page 0, offset 0: bytes 8-11
+pub fn get_cable_flags(cable_info: &CableInfo) -> CableFlags {
+    let bytes = cable_info.bytes();
+    let (module, temp_vcc, aux12, aux3) = (bytes[8], bytes[9], bytes[10], bytes[11]);
+
+    CableFlags {
+        module_state_changed: bit(module, 0),
+        module_firmware_error: bit(module, 1),
+        datapath_firmware_error: bit(module, 2),
+        temp_mon_high_alarm: bit(temp_vcc, 0),
+        temp_mon_low_alarm: bit(temp_vcc, 1),
+        temp_mon_high_warn: bit(temp_vcc, 2),
+        temp_mon_low_warn: bit(temp_vcc, 3),
+        vcc_mon_high_alarm: bit(temp_vcc, 4),
+        vcc_mon_low_alarm: bit(temp_vcc, 5),
+        vcc_mon_high_warn: bit(temp_vcc, 6),
+        vcc_mon_low_warn: bit(temp_vcc, 7),
+        aux1_mon_high_alarm: bit(aux12, 0),
+        aux1_mon_low_alarm: bit(aux12, 1),
+        aux1_mon_high_warn: bit(aux12, 2),
+        aux1_mon_low_warn: bit(aux12, 3),
+        aux2_mon_high_alarm: bit(aux12, 4),
+        aux2_mon_low_alarm: bit(aux12, 5),
+        aux2_mon_high_warn: bit(aux12, 6),
+        aux2_mon_low_warn: bit(aux12, 7),
+        aux3_mon_high_alarm: bit(aux3, 0),
+        aux3_mon_low_alarm: bit(aux3, 1),
+        aux3_mon_high_warn: bit(aux3, 2),
+        aux3_mon_low_warn: bit(aux3, 3),
+    }
+}
+
+//Page 0, offset 0: bytes 14-15, signed 1/256 degree C
+pub fn get_cable_temperature(cable_info: &CableInfo) -> f32 {
+    let bytes = cable_info.bytes();
+    i16::from_be_bytes([bytes[14], bytes[15]]) as f32 / 256.0
+}
+
+//Page 0, offset 0: bytes 16-17, 100 uV units
+pub fn get_cable_voltage(cable_info: &CableInfo) -> f32 {
+    let bytes = cable_info.bytes();
+    u16::from_be_bytes([bytes[16], bytes[17]]) as f32 * 0.0001
+}
+
+//Page 0, offset 166: bytes 166-181
+pub fn get_serial_number(cable_info: &CableInfo) -> String {
+    get_ascii(cable_info, 16)
+}
+
+//Page 0, offset 129: bytes 129-144
+pub fn get_vendor_name(cable_info: &CableInfo) -> String {
+    get_ascii(cable_info, 16)
+}
+
+//Page 11h, offset 128
+pub fn get_lane_specific_flags(cable_info: &CableInfo) -> LaneSpecificFlags {
+    let bytes = cable_info.bytes();
+    let mut datapath_state = [0; 8];
+
+    for (lane, state) in datapath_state.iter_mut().enumerate() {
+        *state = (bytes[lane / 2] >> ((lane % 2) * 4)) & 0xf;
+    }
+
+    LaneSpecificFlags {
+        datapath_state,
+        rx_output_status: bytes[4],
+        tx_output_status: bytes[5],
+        datapath_state_changed: bytes[6],
+        tx_fault: bytes[7],
+        tx_los: bytes[8],
+        tx_cdr_lol: bytes[9],
+        tx_adaptive_eq_fail: bytes[10],
+        tx_power_high_alarm: bytes[11],
+        tx_power_low_alarm: bytes[12],
+        tx_power_high_warn: bytes[13],
+        tx_power_low_warn: bytes[14],
+        tx_bias_high_alarm: bytes[15],
+        tx_bias_low_alarm: bytes[16],
+        tx_bias_high_warn: bytes[17],
+        tx_bias_low_warn: bytes[18],
+        rx_los: bytes[19],
+        rx_cdr_lol: bytes[20],
+        rx_power_high_alarm: bytes[21],
+        rx_power_low_alarm: bytes[22],
+        rx_power_high_warn: bytes[23],
+        rx_power_low_warn: bytes[24],
+    }
+}
+
+//Page 14h, offset 192 with the diagnostics selector set to SNR.
+//SNR values are U16 in 1/256 dB units, stored low byte first.
+pub fn get_lane_snr(cable_info: &CableInfo) -> LaneSnr {
+    let bytes = cable_info.bytes();
+    let mut snr = LaneSnr::default();
+
+    for lane in 0..8 {
+        snr.host[lane] = u16::from_le_bytes([bytes[lane * 2], bytes[lane * 2 + 1]]) as f32 / 256.0;
+        snr.media[lane] = u16::from_le_bytes([bytes[16 + lane * 2], bytes[17 + lane * 2]]) as f32 / 256.0;
+    }
+
+    snr
+}
@@ -45,7 +45,15 @@ pub enum UmadError {
     #[error("Unable to send MAD.")]
     SendFailure,
     #[error("Unable to receive MAD.")]
-    RecvFailure
+    RecvFailure,
+    #[error("MAD completed with error status 0x{0:x}.")]
+    MadStatusError(u16),
+    #[error("Timed out waiting for a MAD.")]
+    Timeout,
+    #[error("RMPP transfer failed: {0}")]
+    RmppError(#[from] umad::rmpp::RmppError),
+    #[error("Unable to watch the umad file descriptor.")]
+    AsyncFdError,
 }
 
 
@@ -19,7 +19,7 @@ pub struct ib_mad {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 #[repr(C)]
 pub struct ib_mad_addr {
     pub qpn: u32,
@@ -38,7 +38,7 @@ pub struct ib_mad_addr {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 #[repr(C)]
 pub struct ib_user_mad {
     pub agent_id: u32,
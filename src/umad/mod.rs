@@ -1,5 +1,8 @@
 pub mod sys;
 pub mod lib;
 pub mod mad;
+pub mod cable;
+pub mod rmpp;
+pub mod agent;
 
 pub use lib::*;
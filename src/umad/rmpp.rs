@@ -0,0 +1,417 @@
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+const MAD_SIZE: usize = 256;
+const SA_CLASS: u8 = 0x03;
+
+//RMPP header, between the common MAD header and the class header
+pub const RMPP_HDR_OFFSET: usize = 24;
+pub const RMPP_HDR_SIZE: usize = 12;
+pub const RMPP_VERSION: u8 = 1;
+
+//RMPPType
+pub const RMPP_TYPE_DATA: u8 = 1;
+pub const RMPP_TYPE_ACK: u8 = 2;
+pub const RMPP_TYPE_STOP: u8 = 3;
+pub const RMPP_TYPE_ABORT: u8 = 4;
+
+//RMPPFlags
+pub const RMPP_FLAG_ACTIVE: u8 = 0x1;
+pub const RMPP_FLAG_FIRST: u8 = 0x2;
+pub const RMPP_FLAG_LAST: u8 = 0x4;
+
+//RMPPStatus
+pub const RMPP_STATUS_NORMAL: u8 = 0;
+pub const RMPP_STATUS_RESX: u8 = 1;
+pub const RMPP_STATUS_T2L: u8 = 118;
+pub const RMPP_STATUS_BAD_LEN: u8 = 119;
+pub const RMPP_STATUS_BAD_SEG: u8 = 120;
+pub const RMPP_STATUS_BADT: u8 = 121;
+pub const RMPP_STATUS_W2S: u8 = 122;
+pub const RMPP_STATUS_S2B: u8 = 123;
+pub const RMPP_STATUS_BAD_STATUS: u8 = 124;
+pub const RMPP_STATUS_UNV: u8 = 125;
+pub const RMPP_STATUS_TMR: u8 = 126;
+pub const RMPP_STATUS_UNSPEC: u8 = 127;
+
+pub const DEFAULT_RMPP_WINDOW: u32 = 64;
+pub const DEFAULT_RMPP_RETRIES: u32 = 3;
+//Longest a whole transfer may take on the receiving side
+pub const DEFAULT_RMPP_TIMEOUT: Duration = Duration::from_secs(40);
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RmppError {
+    #[error("RMPP transfer aborted with status {0}.")]
+    Aborted(u8),
+    #[error("RMPP transfer stopped with status {0}.")]
+    Stopped(u8),
+    #[error("RMPP transfer timed out.")]
+    Timeout,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RmppHeader {
+    pub version: u8,
+    pub rmpp_type: u8,
+    pub resp_time: u8,
+    pub flags: u8,
+    pub status: u8,
+    pub seg_num: u32,
+    //PayloadLength on DATA, NewWindowLast on ACK
+    pub paylen_newwin: u32,
+}
+
+impl RmppHeader {
+    pub fn from_mad(mad: &[u8]) -> RmppHeader {
+        let hdr = &mad[RMPP_HDR_OFFSET..RMPP_HDR_OFFSET + RMPP_HDR_SIZE];
+        RmppHeader {
+            version: hdr[0],
+            rmpp_type: hdr[1],
+            resp_time: hdr[2] >> 3,
+            flags: hdr[2] & 0x7,
+            status: hdr[3],
+            seg_num: u32::from_be_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]),
+            paylen_newwin: u32::from_be_bytes([hdr[8], hdr[9], hdr[10], hdr[11]]),
+        }
+    }
+
+    pub fn write(&self, mad: &mut [u8]) {
+        let hdr = &mut mad[RMPP_HDR_OFFSET..RMPP_HDR_OFFSET + RMPP_HDR_SIZE];
+        hdr[0] = self.version;
+        hdr[1] = self.rmpp_type;
+        hdr[2] = self.resp_time << 3 | (self.flags & 0x7);
+        hdr[3] = self.status;
+        hdr[4..8].copy_from_slice(&self.seg_num.to_be_bytes());
+        hdr[8..12].copy_from_slice(&self.paylen_newwin.to_be_bytes());
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.flags & RMPP_FLAG_ACTIVE != 0
+    }
+}
+
+//Where the segmented data starts. PayloadLength also counts the class
+//header between the RMPP header and this offset, once per segment.
+pub fn rmpp_data_offset(mgmt_class: u8) -> usize {
+    match mgmt_class {
+        SA_CLASS => 56,
+        //Vendor classes with an OUI
+        0x30..=0x4f => 40,
+        _ => RMPP_HDR_OFFSET + RMPP_HDR_SIZE,
+    }
+}
+
+//ACK, STOP or ABORT for a transfer, addressed like one of its segments
+fn control_mad(segment: &[u8], rmpp_type: u8, status: u8, seg_num: u32, newwin: u32) -> Vec<u8> {
+    let mut mad = vec![0; MAD_SIZE];
+    mad[..RMPP_HDR_OFFSET].copy_from_slice(&segment[..RMPP_HDR_OFFSET]);
+    RmppHeader {
+        version: RMPP_VERSION,
+        rmpp_type,
+        flags: RMPP_FLAG_ACTIVE,
+        status,
+        seg_num,
+        paylen_newwin: newwin,
+        ..Default::default()
+    }
+    .write(&mut mad);
+    mad
+}
+
+//What a receiver wants sent back after a segment
+#[derive(Debug, Clone, PartialEq)]
+pub enum RmppReply {
+    //Nothing yet
+    Wait,
+    Ack(Vec<u8>),
+    //The final ACK and the reassembled MAD
+    Done { ack: Vec<u8>, mad: Vec<u8> },
+    //The ABORT or STOP to send, if any, and why the transfer failed
+    Fail(Option<Vec<u8>>, RmppError),
+}
+
+//Receiving side of one transfer. Segments are taken in order, everything
+//else is dropped and left to the sender's retransmission. Each window is
+//ACKed when its last segment arrives.
+#[derive(Debug)]
+pub struct RmppReceiver {
+    data_offset: usize,
+    window: u32,
+    header: Vec<u8>,
+    data: Vec<u8>,
+    expected: u32,
+    window_last: u32,
+    deadline: Instant,
+}
+
+impl RmppReceiver {
+    pub fn new(mgmt_class: u8) -> RmppReceiver {
+        RmppReceiver {
+            data_offset: rmpp_data_offset(mgmt_class),
+            window: DEFAULT_RMPP_WINDOW,
+            header: Vec::new(),
+            data: Vec::new(),
+            expected: 1,
+            //Senders start with a window of one segment
+            window_last: 1,
+            deadline: Instant::now() + DEFAULT_RMPP_TIMEOUT,
+        }
+    }
+
+    pub fn with_window(mut self, window: u32) -> RmppReceiver {
+        self.window = window.max(1);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> RmppReceiver {
+        self.deadline = Instant::now() + timeout;
+        self
+    }
+
+    pub fn segments_received(&self) -> u32 {
+        self.expected - 1
+    }
+
+    fn ack(&self, segment: &[u8]) -> Vec<u8> {
+        let last = self.expected - 1;
+        control_mad(segment, RMPP_TYPE_ACK, RMPP_STATUS_NORMAL, last, self.window_last.max(last))
+    }
+
+    fn abort(&self, segment: &[u8], status: u8) -> RmppReply {
+        RmppReply::Fail(Some(control_mad(segment, RMPP_TYPE_ABORT, status, 0, 0)), RmppError::Aborted(status))
+    }
+
+    pub fn receive(&mut self, mad: &[u8]) -> RmppReply {
+        if mad.len() < MAD_SIZE {
+            return self.abort(mad, RMPP_STATUS_BAD_LEN);
+        }
+
+        let hdr = RmppHeader::from_mad(mad);
+        if hdr.version != RMPP_VERSION {
+            return self.abort(mad, RMPP_STATUS_UNV);
+        }
+        if !hdr.is_active() {
+            return self.abort(mad, RMPP_STATUS_UNSPEC);
+        }
+
+        match hdr.rmpp_type {
+            RMPP_TYPE_DATA => {}
+            RMPP_TYPE_STOP => return RmppReply::Fail(None, RmppError::Stopped(hdr.status)),
+            RMPP_TYPE_ABORT => return RmppReply::Fail(None, RmppError::Aborted(hdr.status)),
+            _ => return self.abort(mad, RMPP_STATUS_BADT),
+        }
+
+        //Retransmitted segments get the ACK again, in case it was lost
+        if hdr.seg_num < self.expected {
+            return match hdr.seg_num {
+                0 => self.abort(mad, RMPP_STATUS_BAD_SEG),
+                _ => RmppReply::Ack(self.ack(mad)),
+            };
+        }
+        if hdr.seg_num > self.expected {
+            return RmppReply::Wait;
+        }
+
+        let first = hdr.flags & RMPP_FLAG_FIRST != 0;
+        if first != (hdr.seg_num == 1) {
+            return self.abort(mad, RMPP_STATUS_BAD_SEG);
+        }
+
+        let last = hdr.flags & RMPP_FLAG_LAST != 0;
+        let class_hdr = self.data_offset - (RMPP_HDR_OFFSET + RMPP_HDR_SIZE);
+        let len = if last {
+            match (hdr.paylen_newwin as usize).checked_sub(class_hdr) {
+                Some(len) if len <= MAD_SIZE - self.data_offset => len,
+                _ => return self.abort(mad, RMPP_STATUS_BAD_LEN),
+            }
+        } else {
+            MAD_SIZE - self.data_offset
+        };
+
+        if first {
+            self.header = mad[..self.data_offset].to_vec();
+        }
+        self.data.extend_from_slice(&mad[self.data_offset..self.data_offset + len]);
+        self.expected += 1;
+
+        if last {
+            let ack = self.ack(mad);
+            return RmppReply::Done { ack, mad: self.reassemble() };
+        }
+
+        if hdr.seg_num >= self.window_last {
+            self.window_last = hdr.seg_num + self.window;
+            return RmppReply::Ack(self.ack(mad));
+        }
+
+        RmppReply::Wait
+    }
+
+    //Give up on a transfer that is taking too long
+    pub fn check_timeout(&self, now: Instant) -> Option<RmppReply> {
+        if now < self.deadline || self.header.is_empty() {
+            return None;
+        }
+        match self.abort(&self.header, RMPP_STATUS_T2L) {
+            RmppReply::Fail(abort, _) => Some(RmppReply::Fail(abort, RmppError::Timeout)),
+            reply => Some(reply),
+        }
+    }
+
+    //The headers of the first segment followed by all the data, marked as a
+    //single segment carrying everything
+    fn reassemble(&mut self) -> Vec<u8> {
+        let class_hdr = self.data_offset - (RMPP_HDR_OFFSET + RMPP_HDR_SIZE);
+
+        let mut mad = std::mem::take(&mut self.header);
+        let mut hdr = RmppHeader::from_mad(&mad);
+        hdr.flags = RMPP_FLAG_ACTIVE | RMPP_FLAG_FIRST | RMPP_FLAG_LAST;
+        hdr.seg_num = 1;
+        hdr.paylen_newwin = (class_hdr + self.data.len()) as u32;
+        hdr.write(&mut mad);
+
+        mad.append(&mut self.data);
+        if mad.len() < MAD_SIZE {
+            mad.resize(MAD_SIZE, 0);
+        }
+        mad
+    }
+}
+
+//Sending side of one transfer. Segments go out up to the window the
+//receiver last ACKed, a timeout goes back to the first unACKed segment.
+#[derive(Debug)]
+pub struct RmppSender {
+    segments: Vec<Vec<u8>>,
+    acked: u32,
+    sent: u32,
+    window_last: u32,
+    retries: u32,
+    max_retries: u32,
+}
+
+impl RmppSender {
+    //mad is the whole message, headers up to the class data offset followed
+    //by all of the data
+    pub fn new(mad: &[u8]) -> RmppSender {
+        let data_offset = rmpp_data_offset(mad[1]);
+        let class_hdr = data_offset - (RMPP_HDR_OFFSET + RMPP_HDR_SIZE);
+        let seg_size = MAD_SIZE - data_offset;
+
+        let data = mad.get(data_offset..).unwrap_or(&[]);
+        let chunks: Vec<&[u8]> = match data.len() {
+            0 => vec![&[]],
+            _ => data.chunks(seg_size).collect(),
+        };
+        let count = chunks.len();
+        let resp_time = RmppHeader::from_mad(mad).resp_time;
+
+        let segments = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let seg_num = i as u32 + 1;
+                let mut flags = RMPP_FLAG_ACTIVE;
+                let mut paylen = 0;
+                if i == 0 {
+                    flags |= RMPP_FLAG_FIRST;
+                    paylen = (data.len() + class_hdr * count) as u32;
+                }
+                if i == count - 1 {
+                    flags |= RMPP_FLAG_LAST;
+                    paylen = (chunk.len() + class_hdr) as u32;
+                }
+
+                let mut segment = vec![0; MAD_SIZE];
+                segment[..data_offset].copy_from_slice(&mad[..data_offset]);
+                segment[data_offset..data_offset + chunk.len()].copy_from_slice(chunk);
+                RmppHeader {
+                    version: RMPP_VERSION,
+                    rmpp_type: RMPP_TYPE_DATA,
+                    resp_time,
+                    flags,
+                    status: RMPP_STATUS_NORMAL,
+                    seg_num,
+                    paylen_newwin: paylen,
+                }
+                .write(&mut segment);
+                segment
+            })
+            .collect();
+
+        RmppSender {
+            segments,
+            acked: 0,
+            sent: 0,
+            window_last: 1,
+            retries: 0,
+            max_retries: DEFAULT_RMPP_RETRIES,
+        }
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> RmppSender {
+        self.max_retries = retries;
+        self
+    }
+
+    pub fn segment_count(&self) -> u32 {
+        self.segments.len() as u32
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.acked == self.segment_count()
+    }
+
+    //Segments that may go out now
+    pub fn next_segments(&mut self) -> Vec<Vec<u8>> {
+        let last = self.window_last.min(self.segment_count());
+        let next = self.segments[self.sent as usize..last.max(self.sent) as usize].to_vec();
+        self.sent = self.sent.max(last);
+        next
+    }
+
+    //Take an ACK, STOP or ABORT from the receiver. Returns whether the whole
+    //transfer has been ACKed.
+    pub fn on_reply(&mut self, mad: &[u8]) -> Result<bool, RmppError> {
+        let hdr = RmppHeader::from_mad(mad);
+        match hdr.rmpp_type {
+            RMPP_TYPE_ACK => {}
+            RMPP_TYPE_STOP => return Err(RmppError::Stopped(hdr.status)),
+            RMPP_TYPE_ABORT => return Err(RmppError::Aborted(hdr.status)),
+            _ => return Err(RmppError::Aborted(RMPP_STATUS_BADT)),
+        }
+
+        if hdr.seg_num > self.sent {
+            return Err(RmppError::Aborted(RMPP_STATUS_S2B));
+        }
+        if hdr.paylen_newwin < hdr.seg_num {
+            return Err(RmppError::Aborted(RMPP_STATUS_W2S));
+        }
+
+        //Stale ACKs don't move anything backwards
+        if hdr.seg_num >= self.acked {
+            if hdr.seg_num > self.acked {
+                self.retries = 0;
+            }
+            self.acked = hdr.seg_num;
+            self.window_last = self.window_last.max(hdr.paylen_newwin);
+        }
+
+        Ok(self.is_done())
+    }
+
+    //No ACK came back in time, resend from the first unACKed segment
+    pub fn on_timeout(&mut self) -> Result<(), RmppError> {
+        self.retries += 1;
+        if self.retries > self.max_retries {
+            return Err(RmppError::Timeout);
+        }
+        self.sent = self.acked;
+        Ok(())
+    }
+
+    pub fn abort(&self, status: u8) -> Vec<u8> {
+        control_mad(&self.segments[0], RMPP_TYPE_ABORT, status, 0, 0)
+    }
+}
@@ -0,0 +1,45 @@
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use std::sync::Arc;
+
+    use rsmad::ibmad::async_port::AsyncMadPort;
+    use rsmad::ibmad::sys::SMI_ATTR_ID_IB_ATTR_NODE_INFO;
+    use rsmad::ibmad::transport::{DrPath, MadAddress, MadRequest};
+    use rsmad::ibmad::NodeInfo;
+    use rsmad::umad::{umad_close_port, umad_open_port};
+
+    //cargo test --features async --test async_tests -- tests::async_query_node_info_success --show-output
+    #[tokio::test]
+    async fn async_query_node_info_success() {
+        rsmad::umad::umad_init();
+
+        let ca_names = rsmad::umad::umad_list_devices().unwrap();
+        let ca_name = ca_names.first().unwrap();
+        let port = umad_open_port(ca_name, 1).unwrap();
+
+        let async_port = Arc::new(AsyncMadPort::new(&port).unwrap());
+        let request = MadRequest::smp_get(MadAddress::DirectedRoute(DrPath::new(&[])), SMI_ATTR_ID_IB_ATTR_NODE_INFO, 0, 200);
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..1000 {
+            let async_port = async_port.clone();
+            let request = request.clone();
+            tasks.spawn(async move { async_port.query(&request).await });
+        }
+
+        let mut guids = Vec::new();
+        while let Some(result) = tasks.join_next().await {
+            let mut data = result.unwrap().unwrap();
+            guids.push(NodeInfo::from_mad_fields(&mut data).guid);
+        }
+
+        assert_eq!(guids.len(), 1000);
+        assert!(guids.iter().all(|g| *g == guids[0]));
+        assert_eq!(async_port.outstanding(), 0);
+        println!("NodeInfo GUID: 0x{:x}", guids[0]);
+
+        drop(async_port);
+        umad_close_port(port).unwrap();
+        rsmad::umad::umad_done();
+    }
+}
@@ -0,0 +1,104 @@
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, rc::Rc, thread, time::{Duration, Instant}};
+
+    use rsmad::ibmad::perf::ExtPerfCounters;
+    use rsmad::ibmad::port::{LinkSpeed, LinkWidth};
+    use rsmad::ibnetdisc::ber::{bit_error_rate, BerMonitor, DEFAULT_MAX_BER};
+    use rsmad::ibnetdisc::fabric::Fabric;
+    use rsmad::ibnetdisc::sampler::{FabricSampler, PortSample};
+    use rsmad::ibsim::Simulator;
+
+    use super::common::{TestFabric, HCA1, LEAF, SPEED_QDR, SPINE};
+
+    //hca1 -- [1] leaf [3] -- [5] spine, the leaf to spine link at 4x QDR
+    fn fabric() -> (Rc<Simulator>, Fabric) {
+        TestFabric::new().with_speed((LEAF, 3), SPEED_QDR).build()
+    }
+
+    fn sample(port: (u64, i32), time: Instant, symbol_errors: u64) -> PortSample {
+        let counters = ExtPerfCounters { symbol_errors, ..Default::default() };
+        PortSample { node_guid: port.0, port, lid: 2, time, counters, legacy: false, rates: None }
+    }
+
+    #[test]
+    fn ber_rate_success() {
+        //4x QDR is 40 Gb/s, 400 errors in 10s is 1e-9
+        let ber = bit_error_rate(400, LinkWidth::X4, LinkSpeed::Qdr, Duration::from_secs(10)).unwrap();
+        assert!((ber - 1e-9).abs() < 1e-18);
+        assert_eq!(bit_error_rate(0, LinkWidth::X1, LinkSpeed::Sdr, Duration::from_secs(1)), Some(0.0));
+        assert_eq!(bit_error_rate(1, LinkWidth::Unknown, LinkSpeed::Qdr, Duration::from_secs(1)), None);
+        assert_eq!(bit_error_rate(1, LinkWidth::X4, LinkSpeed::Qdr, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn ber_monitor_window_success() {
+        let (_sim, fabric) = fabric();
+        let mut monitor = BerMonitor::new(Duration::from_secs(20));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(monitor.add_round(&fabric, &[sample((LEAF, 3), at(0), 100)]).is_empty());
+
+        let bers = monitor.add_round(&fabric, &[sample((LEAF, 3), at(10), 500)]);
+        assert_eq!(bers.len(), 1);
+        assert_eq!((bers[0].width, bers[0].speed), (LinkWidth::X4, LinkSpeed::Qdr));
+        assert_eq!((bers[0].window, bers[0].symbol_errors), (Duration::from_secs(10), 400));
+        assert!((bers[0].ber - 1e-9).abs() < 1e-18);
+        assert!(bers[0].is_marginal(DEFAULT_MAX_BER));
+
+        //The oldest sample falls out once the rest span the window
+        monitor.add_round(&fabric, &[sample((LEAF, 3), at(20), 500)]);
+        let bers = monitor.add_round(&fabric, &[sample((LEAF, 3), at(30), 500)]);
+        assert_eq!((bers[0].window, bers[0].symbol_errors), (Duration::from_secs(20), 0));
+        assert!(!bers[0].is_marginal(DEFAULT_MAX_BER));
+
+        //After a reset the window starts over
+        assert!(monitor.add_round(&fabric, &[sample((LEAF, 3), at(40), 3)]).is_empty());
+        let bers = monitor.add_round(&fabric, &[sample((LEAF, 3), at(50), 7)]);
+        assert_eq!((bers[0].window, bers[0].symbol_errors), (Duration::from_secs(10), 4));
+
+        //Ports the fabric doesn't know have no PortInfo to go by
+        monitor.add_round(&fabric, &[sample((0x1234, 1), at(60), 0)]);
+        assert!(monitor.add_round(&fabric, &[sample((0x1234, 1), at(70), 9)]).is_empty());
+    }
+
+    #[test]
+    fn ber_monitor_fdr10_success() {
+        //The leaf to spine link at 4x FDR10, QDR as far as PortInfo goes
+        let fabric = TestFabric::new().with_fdr10((LEAF, 3)).fabric();
+
+        let mut monitor = BerMonitor::new(Duration::from_secs(20));
+        let start = Instant::now();
+        monitor.add_round(&fabric, &[sample((LEAF, 3), start, 0)]);
+        let bers = monitor.add_round(&fabric, &[sample((LEAF, 3), start + Duration::from_secs(10), 400)]);
+
+        //4x FDR10 is 41.25 Gb/s
+        assert_eq!((bers[0].width, bers[0].speed), (LinkWidth::X4, LinkSpeed::Fdr10));
+        assert!((bers[0].ber - 400.0 / 412.5e9).abs() < 1e-18);
+    }
+
+    #[test]
+    fn ber_monitor_sampler_success() {
+        let (sim, fabric) = fabric();
+        let mut sampler = FabricSampler::new();
+        let mut monitor = BerMonitor::new(Duration::from_secs(60));
+
+        let round = sampler.sample(&fabric);
+        assert!(monitor.add_round(&fabric, &round.samples).is_empty());
+
+        sim.set_counters(HCA1, 1, &HashMap::from([("symbol_errors".to_string(), 50)])).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        let round = sampler.sample(&fabric);
+        let bers = monitor.add_round(&fabric, &round.samples);
+        assert_eq!(bers.len(), 4);
+
+        let hca = bers.iter().find(|b| b.port.0 != LEAF && b.port.0 != SPINE).unwrap();
+        assert_eq!((hca.width, hca.speed, hca.symbol_errors), (LinkWidth::X4, LinkSpeed::Sdr, 50));
+        assert!(hca.is_marginal(DEFAULT_MAX_BER));
+        assert!(bers.iter().filter(|b| b.port != hca.port).all(|b| b.ber == 0.0));
+    }
+}
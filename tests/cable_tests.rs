@@ -13,77 +13,41 @@ mod tests {
 
         match rsmad::umad::umad_open_port(ca_name, 1){
             Ok(port) => {
-                let r = rsmad::umad::cable::get_cable_info(&port, lid, portnum, 0, 0, 20);
-
-                if let Ok(ci) = r {
-                    //Needs to support DDM
-                    let data = ci.data;
-                    let part = data[2].to_le_bytes();
-                    println!("Cable Data: {:?}", data);
-                    println!("Cable Data Bytes 8-15: 0x{:x} 0x{:x} 0x{:x} 0x{:x}",
-                     part[0], part[1], part[2], part[3]);
-
-                    let status = rsmad::umad::cable::get_cable_module_status(&ci);
-                    println!("Cable Module Status: int={} bin={:b}", status, status);
-
-                    let temp = rsmad::umad::cable::get_cable_temperature(&ci);
-                    println!("Cable Temp: {:?}", temp);
-
-                    let vcc = rsmad::umad::cable::get_cable_voltage(&ci);
-                    println!("Cable Voltage: {:?}", vcc);
-                }
-                let _ = rsmad::umad::umad_close_port(port);
-            }
-            Err(_err) =>{
-                assert!(false, "Failed to open HCA: {:?}", ca_name);
-
-            }
-        }
-
-        match rsmad::umad::umad_open_port(ca_name, 1){
-            Ok(port) => {
-                //166-181 Serial Number
-                let r = rsmad::umad::cable::get_cable_info(&port, lid, portnum, 0, 166, 20);
-                if let Ok(ci) = r {
-                    //Needs to support DDM
-                    let serial = rsmad::umad::cable::get_serial_number(&ci);
-                    println!("Cable Serial: {}", serial);
-                }
-                let _ = rsmad::umad::umad_close_port(port);
-            }
-            Err(_err) =>{
-                assert!(false, "Failed to open HCA: {:?}", ca_name);
-
-            }
-        }
-
-        match rsmad::umad::umad_open_port(ca_name, 1){
-            Ok(port) => {
-                //129-144 Vendor
-                let r = rsmad::umad::cable::get_cable_info(&port, lid, portnum, 0, 129, 20);
-                if let Ok(ci) = r {
-                    let serial = rsmad::umad::cable::get_vendor_name(&ci);
-                    println!("Cable Vendor: {}", serial);
+                let agent = rsmad::umad::cable::register_cable_agent(&port).unwrap();
+
+                //Page 0: flags, 129-144 Vendor, 166-181 Serial Number. Page 11h
+                //Byte 128 DPStatus 1->2->4
+                for (page, offset) in [(0, 0), (0, 129), (0, 166), (17, 128)] {
+                    let r = rsmad::umad::cable::get_cable_info(&agent, lid, portnum, page, offset, 20);
+                    let Ok(ci) = r else {
+                        continue;
+                    };
+
+                    match (page, offset) {
+                        (0, 0) => {
+                            //Needs to support DDM
+                            let data = ci.data;
+                            let part = data[2].to_le_bytes();
+                            println!("Cable Data: {:?}", data);
+                            println!("Cable Data Bytes 8-15: 0x{:x} 0x{:x} 0x{:x} 0x{:x}",
+                             part[0], part[1], part[2], part[3]);
+
+                            let status = rsmad::umad::cable::get_cable_module_status(&ci);
+                            println!("Cable Module Status: int={} bin={:b}", status, status);
+
+                            let temp = rsmad::umad::cable::get_cable_temperature(&ci);
+                            println!("Cable Temp: {:?}", temp);
+
+                            let vcc = rsmad::umad::cable::get_cable_voltage(&ci);
+                            println!("Cable Voltage: {:?}", vcc);
+                        }
+                        (0, 129) => println!("Cable Vendor: {}", rsmad::umad::cable::get_vendor_name(&ci)),
+                        (0, 166) => println!("Cable Serial: {}", rsmad::umad::cable::get_serial_number(&ci)),
+                        _ => println!("Cable Data Page {}h {}o: {:?}", page, offset, { ci.data }),
+                    }
                 }
-                let _ = rsmad::umad::umad_close_port(port);
-            }
-            Err(_err) =>{
-                assert!(false, "Failed to open HCA: {:?}", ca_name);
-
-            }
-        }
 
-        match rsmad::umad::umad_open_port(ca_name, 1){
-            Ok(port) => {
-                //Page 11h Byte 128 DPStatus 1->2->4
-                let r = rsmad::umad::cable::get_cable_info(&port, lid, 45, 17, 128, 20);
-                if let Ok(ci) = r {
-                    let data = ci.data;
-                    let part = data[0].to_le_bytes();
-                    println!("Cable Data Page 10h 160o: {:?}", data);
-                    println!("Cable Data Bytes 8-15: 0x{:x} 0x{:x} 0x{:x} 0x{:x}",
-                     part[0], part[1], part[2], part[3]);
-                }
+                drop(agent);
                 let _ = rsmad::umad::umad_close_port(port);
             }
             Err(_err) =>{
@@ -261,7 +225,8 @@ mod tests {
 
         match rsmad::umad::umad_open_port(ca_name, 1){
             Ok(port) => {
-                let r = rsmad::umad::cable::get_cable_info(&port, lid, portnum, 0, 0, 20);
+                let agent = rsmad::umad::cable::register_cable_agent(&port).unwrap();
+                let r = rsmad::umad::cable::get_cable_info(&agent, lid, portnum, 0, 0, 20);
 
                 if let Ok(ci) = r {
                     //Needs to support DDM
@@ -283,6 +248,7 @@ mod tests {
                     let flags = rsmad::umad::cable::get_cable_flags(&ci);
                     println!("Cable Flags: {:?}", flags);
                 }
+                drop(agent);
                 let _ = rsmad::umad::umad_close_port(port);
             }
             Err(_err) =>{
@@ -303,7 +269,8 @@ mod tests {
 
         match rsmad::umad::umad_open_port(ca_name, 1){
             Ok(port) => {
-                let r = rsmad::umad::cable::get_cable_info(&port, lid, portnum, 17, 128, 20);
+                let agent = rsmad::umad::cable::register_cable_agent(&port).unwrap();
+                let r = rsmad::umad::cable::get_cable_info(&agent, lid, portnum, 17, 128, 20);
 
                 if let Ok(ci) = r {
                     //Needs to support DDM
@@ -320,6 +287,7 @@ mod tests {
                     let lane_spec_flags = rsmad::umad::cable::get_lane_specific_flags(&ci);
                     println!("{:?}", lane_spec_flags);
                 }
+                drop(agent);
                 let _ = rsmad::umad::umad_close_port(port);
             }
             Err(_err) =>{
@@ -340,7 +308,8 @@ mod tests {
 
         match rsmad::umad::umad_open_port(ca_name, 1){
             Ok(port) => {
-                let r = rsmad::umad::cable::get_cable_info(&port, lid, portnum, 20, 192, 20);
+                let agent = rsmad::umad::cable::register_cable_agent(&port).unwrap();
+                let r = rsmad::umad::cable::get_cable_info(&agent, lid, portnum, 20, 192, 20);
 
                 if let Ok(ci) = r {
                     //Needs to support DDM
@@ -348,6 +317,7 @@ mod tests {
                     println!("Page 20 Cable Data: {:?}", data);
 
                 }
+                drop(agent);
                 let _ = rsmad::umad::umad_close_port(port);
             }
             Err(_err) =>{
@@ -0,0 +1,91 @@
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use rsmad::ibmad::port::{LinkSpeed, LinkWidth};
+    use rsmad::ibnetdisc::cabling::{CablingError, CablingPlan, Endpoint, RateMismatch};
+    use rsmad::ibnetdisc::fabric::Fabric;
+
+    use super::common::{TestFabric, HCA2, LEAF, SPEED_QDR, SPINE};
+
+    const PLAN: &str = "switch,port,remote,remote port,width,speed
+# rack 4
+leaf01,1,\"node01 HCA-1\",1,4x,SDR
+\"node02 HCA-1\",1,leaf01,2
+leaf01,3,spine01,5,4x,QDR
+
+spine01,1,node03 HCA-1,1,,
+";
+
+    //hca1 -- [1] leaf [3] -- [5] spine, all 4x SDR
+    //hca2 -- [5] leaf [7] -- [7] spine
+    fn fabric() -> Fabric {
+        TestFabric::new().with_ca(HCA2, "node02 HCA-1", 11).with_link((HCA2, 1), (LEAF, 5)).with_link((LEAF, 7), (SPINE, 7)).fabric()
+    }
+
+    fn end(name: &str, port: i32) -> Endpoint {
+        Endpoint { name: name.to_string(), port }
+    }
+
+    #[test]
+    fn cabling_plan_parse_success() {
+        let plan = CablingPlan::from_csv(PLAN).unwrap();
+        assert_eq!(plan.links.len(), 4);
+        assert_eq!((plan.links[0].line, plan.links[0].b.clone()), (3, end("node01 HCA-1", 1)));
+        assert_eq!((plan.links[0].width, plan.links[0].speed), (Some(LinkWidth::X4), Some(LinkSpeed::Sdr)));
+        assert_eq!((plan.links[1].width, plan.links[1].speed), (None, None));
+        assert_eq!(plan.links[3].b, end("node03 HCA-1", 1));
+        assert_eq!(plan.links[3].width, None);
+
+        //A plan needn't have a header
+        assert_eq!(CablingPlan::from_csv("leaf01,1,spine01,1\n").unwrap().links.len(), 1);
+
+        assert!(matches!(CablingPlan::from_csv("leaf01,1,spine01,1\nleaf01,x,spine01,2\n"), Err(CablingError::ParseError(2, _))));
+        assert!(matches!(CablingPlan::from_csv("leaf01,1,spine01\n"), Err(CablingError::ParseError(1, _))));
+        assert!(matches!(CablingPlan::from_csv("leaf01,1,spine01,1,4x,XDR\n"), Err(CablingError::ParseError(1, _))));
+        assert_eq!(CablingPlan::from_csv("leaf01,1,spine01,1,4x,FDR10\n").unwrap().links[0].speed, Some(LinkSpeed::Fdr10));
+        assert!(matches!(CablingPlan::from_file("/nonexistent/cabling.csv"), Err(CablingError::ReadError(_))));
+    }
+
+    #[test]
+    fn cabling_validate_success() {
+        let report = CablingPlan::from_csv(PLAN).unwrap().validate(&fabric());
+        assert!(!report.is_clean());
+
+        assert_eq!(report.miswired.len(), 1);
+        let miswire = &report.miswired[0];
+        assert_eq!(miswire.planned.line, 4);
+        assert_eq!(miswire.actual_a, Some(end("leaf01", 5)));
+        assert_eq!(miswire.actual_b, None);
+
+        assert_eq!(report.missing.iter().map(|l| l.line).collect::<Vec<_>>(), vec![7]);
+        assert_eq!(report.unexpected, vec![(end("leaf01", 7), end("spine01", 7))]);
+
+        assert_eq!(report.wrong_rate.len(), 1);
+        let RateMismatch { planned, width, speed } = &report.wrong_rate[0];
+        assert_eq!((planned.line, *width, *speed), (5, LinkWidth::X4, LinkSpeed::Sdr));
+        assert_eq!(format!("{} -- {}", planned.a, planned.b), "\"leaf01\"[3] -- \"spine01\"[5]");
+    }
+
+    #[test]
+    fn cabling_validate_clean_success() {
+        let plan = "leaf01,1,node01 HCA-1,1,4x,SDR\nnode02 HCA-1,1,leaf01,5\nspine01,5,leaf01,3\nspine01,7,leaf01,7,4x\n";
+        let report = CablingPlan::from_csv(plan).unwrap().validate(&fabric());
+        assert!(report.is_clean(), "{:?}", report);
+    }
+
+    #[test]
+    fn cabling_validate_fdr10_success() {
+        //PortInfo shows QDR on both, only the uplink is FDR10
+        let fabric = TestFabric::new().with_speed((LEAF, 1), SPEED_QDR).with_fdr10((LEAF, 3)).fabric();
+        let plan = "leaf01,1,node01 HCA-1,1,4x,QDR\nleaf01,3,spine01,5,4x,FDR10\n";
+        let report = CablingPlan::from_csv(plan).unwrap().validate(&fabric);
+        assert!(report.is_clean(), "{:?}", report);
+
+        let plan = "leaf01,1,node01 HCA-1,1,4x,FDR10\nleaf01,3,spine01,5,4x,QDR\n";
+        let report = CablingPlan::from_csv(plan).unwrap().validate(&fabric);
+        let wrong: Vec<(usize, LinkSpeed)> = report.wrong_rate.iter().map(|m| (m.planned.line, m.speed)).collect();
+        assert_eq!(wrong, vec![(1, LinkSpeed::Qdr), (2, LinkSpeed::Fdr10)]);
+    }
+}
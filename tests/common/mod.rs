@@ -0,0 +1,118 @@
+#![allow(dead_code)]
+
+use std::rc::Rc;
+
+use rsmad::ibnetdisc::discover::DiscoverConfig;
+use rsmad::ibnetdisc::fabric::Fabric;
+use rsmad::ibsim::Simulator;
+use rsmad::ibsim::topology::{SimLink, Topology, PORT_PHYS_STATE_LINKUP, PORT_STATE_INIT};
+
+pub const HCA1: u64 = 0x0002c90300a1b2c0;
+pub const HCA2: u64 = 0x0002c90300a1b3d0;
+pub const HCA3: u64 = 0x0002c90300a1b4e0;
+pub const LEAF: u64 = 0xfc6a1c0300e4a800;
+pub const SPINE: u64 = 0xfc6a1c0300e4b900;
+
+//PortInfo:LinkSpeedActive
+pub const SPEED_QDR: u8 = 4;
+
+//A simulated fabric, discovered from hca1. Starts out as
+//hca1 -- [1] leaf [3] -- [5] spine
+//with every link 4x SDR and Active.
+pub struct TestFabric {
+    pub topology: Topology,
+    init: Vec<(u64, u8)>,
+}
+
+impl Default for TestFabric {
+    fn default() -> Self {
+        TestFabric::new()
+    }
+}
+
+impl TestFabric {
+    pub fn new() -> TestFabric {
+        let mut topology = Topology::new();
+        topology.add_ca(HCA1, "node01 HCA-1", 1, 10);
+        topology.add_switch(LEAF, "leaf01", 8, 2);
+        topology.add_switch(SPINE, "spine01", 8, 1);
+        topology.link((HCA1, 1), (LEAF, 1));
+        topology.link((LEAF, 3), (SPINE, 5));
+        TestFabric { topology, init: Vec::new() }
+    }
+
+    pub fn with_ca(mut self, guid: u64, node_desc: &str, lid: u16) -> TestFabric {
+        self.topology.add_ca(guid, node_desc, 1, lid);
+        self
+    }
+
+    pub fn with_switch(mut self, guid: u64, node_desc: &str, lid: u16) -> TestFabric {
+        self.topology.add_switch(guid, node_desc, 8, lid);
+        self
+    }
+
+    //Cable a to b, unplugging whatever either end was cabled to
+    pub fn with_link(mut self, a: (u64, u8), b: (u64, u8)) -> TestFabric {
+        self.topology.links.retain(|link| ![a, b].iter().any(|end| on(link, *end)));
+        self.topology.link(a, b);
+        self
+    }
+
+    pub fn with_desc(mut self, guid: u64, node_desc: &str) -> TestFabric {
+        self.topology.node_mut(guid).unwrap().node_desc = node_desc.to_string();
+        self
+    }
+
+    pub fn with_lid(mut self, guid: u64, lid: u16) -> TestFabric {
+        self.topology.node_mut(guid).unwrap().lid = lid;
+        self
+    }
+
+    //LinkSpeedActive of both ends of the link on end
+    pub fn with_speed(mut self, end: (u64, u8), speed: u8) -> TestFabric {
+        for (guid, port) in self.link_ends(end) {
+            self.topology.node_mut(guid).unwrap().port_mut(port).link_speed_active = speed;
+        }
+        self
+    }
+
+    //The link on end at FDR10, which PortInfo shows as QDR
+    pub fn with_fdr10(mut self, end: (u64, u8)) -> TestFabric {
+        self = self.with_speed(end, SPEED_QDR);
+        for (guid, port) in self.link_ends(end) {
+            self.topology.node_mut(guid).unwrap().port_mut(port).mlnx_link_speed_active = 1;
+        }
+        self
+    }
+
+    //Both ends of the link on end left in Init
+    pub fn with_init(mut self, end: (u64, u8)) -> TestFabric {
+        let ends = self.link_ends(end);
+        self.init.extend(ends);
+        self
+    }
+
+    pub fn build(self) -> (Rc<Simulator>, Fabric) {
+        let sim = Rc::new(Simulator::new(self.topology, HCA1, 1).unwrap());
+        for (guid, port) in self.init {
+            sim.set_port_state(guid, port, PORT_STATE_INIT, PORT_PHYS_STATE_LINKUP).unwrap();
+        }
+
+        let mut fabric = Fabric::from_transport("sim0", sim.clone());
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+        (sim, fabric)
+    }
+
+    pub fn fabric(self) -> Fabric {
+        self.build().1
+    }
+
+    fn link_ends(&self, end: (u64, u8)) -> Vec<(u64, u8)> {
+        let link = self.topology.links.iter().find(|link| on(link, end)).unwrap();
+        vec![(link.from.guid, link.from.port), (link.to.guid, link.to.port)]
+    }
+}
+
+fn on(link: &SimLink, (guid, port): (u64, u8)) -> bool {
+    (link.from.guid == guid && link.from.port == port) || (link.to.guid == guid && link.to.port == port)
+}
@@ -0,0 +1,127 @@
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use rsmad::ibnetdisc::diff::{DescChange, LidChange, LinkMove, PortStateChange, TopologyDiff};
+    use rsmad::ibnetdisc::fabric::Fabric;
+    use rsmad::ibnetdisc::snapshot::FabricSnapshot;
+    use rsmad::ibsim::topology::{PORT_PHYS_STATE_LINKUP, PORT_PHYS_STATE_POLLING, PORT_STATE_ACTIVE, PORT_STATE_DOWN, PORT_STATE_INIT};
+
+    use super::common::{TestFabric, HCA1, HCA2, HCA3, LEAF, SPINE};
+
+    //Sorts below the CAs
+    const EDGE: u64 = 0x0000100000000100;
+
+    //hca1 -- [1] leaf [3] -- [5] spine [2] -- hca2
+    fn before() -> Fabric {
+        TestFabric::new().with_ca(HCA2, "node02 HCA-1", 11).with_link((SPINE, 2), (HCA2, 1)).fabric()
+    }
+
+    //hca1 moved to leaf [4] and given an LMC of 1, hca2 swapped for hca3 on
+    //spine [6], the spine renumbered, the leaf renamed and its uplink come
+    //back only to Init
+    fn after() -> Fabric {
+        let mut fabric = TestFabric::new()
+            .with_ca(HCA3, "node03 HCA-1", 12)
+            .with_link((HCA1, 1), (LEAF, 4))
+            .with_link((SPINE, 6), (HCA3, 1))
+            .with_lid(SPINE, 7)
+            .with_desc(LEAF, "leaf01 rack 4")
+            .with_init((LEAF, 3));
+        fabric.topology.node_mut(HCA1).unwrap().port_mut(1).lmc = 1;
+        fabric.fabric()
+    }
+
+    #[test]
+    fn diff_changes_success() {
+        let diff = TopologyDiff::between(&before(), &after());
+
+        assert_eq!(diff.nodes_added.iter().map(|n| n.guid).collect::<Vec<_>>(), vec![HCA3]);
+        assert_eq!(diff.nodes_removed.iter().map(|n| (n.guid, n.node_desc.as_str())).collect::<Vec<_>>(), vec![(HCA2, "node02 HCA-1")]);
+        //Every spine port goes by the spine LID
+        let mut lid_changes = vec![LidChange { port: (HCA1, 1), before: (10, 0), after: (10, 1) }];
+        lid_changes.extend((1..=8).map(|n| LidChange { port: (SPINE, n), before: (1, 0), after: (7, 0) }));
+        assert_eq!(diff.lid_changes, lid_changes);
+        assert_eq!(diff.desc_changes, vec![DescChange { guid: LEAF, before: "leaf01".to_string(), after: "leaf01 rack 4".to_string() }]);
+
+        assert_eq!(diff.links_moved, vec![LinkMove { end: (HCA1, 1), before: (LEAF, 1), after: (LEAF, 4) }]);
+        assert_eq!(diff.links_removed, vec![((HCA2, 1), (SPINE, 2))]);
+        assert_eq!(diff.links_added, vec![((HCA3, 1), (SPINE, 6))]);
+
+        //Ports left without a cable go down, those given one come up
+        let active = (PORT_STATE_ACTIVE as u32, PORT_PHYS_STATE_LINKUP as u32);
+        let init = (PORT_STATE_INIT as u32, PORT_PHYS_STATE_LINKUP as u32);
+        let down = (PORT_STATE_DOWN as u32, PORT_PHYS_STATE_POLLING as u32);
+        let change = |port, before, after| PortStateChange { port, before, after };
+        assert_eq!(
+            diff.port_state_changes,
+            vec![
+                change((LEAF, 1), active, down),
+                change((LEAF, 3), active, init),
+                change((LEAF, 4), down, active),
+                change((SPINE, 2), active, down),
+                change((SPINE, 5), active, init),
+                change((SPINE, 6), down, active),
+            ]
+        );
+
+        let text = diff.to_string();
+        assert!(text.contains(&format!("> link 0x{:016x}[1] moved from 0x{:016x}[1] to 0x{:016x}[4]\n", HCA1, LEAF, LEAF)));
+        assert!(text.contains(&format!("~ port 0x{:016x}[3] Active/LinkUp -> Init/LinkUp\n", LEAF)));
+        assert!(text.contains(&format!("- node 0x{:016x} \"node02 HCA-1\"\n", HCA2)));
+        assert!(text.contains(&format!("~ lid 0x{:016x}[1] 10 lmc 0 -> 10 lmc 1\n", HCA1)));
+        assert!(text.contains(&format!("~ lid 0x{:016x}[5] 1 lmc 0 -> 7 lmc 0\n", SPINE)));
+        assert_eq!(text.lines().count(), 21);
+    }
+
+    #[test]
+    fn diff_swapped_success() {
+        //hca2 on spine [2] is replaced by hca3 on the same port
+        let fabric = |hca: u64, desc: &str, lid: u16| TestFabric::new().with_ca(hca, desc, lid).with_link((SPINE, 2), (hca, 1)).fabric();
+
+        let diff = TopologyDiff::between(&fabric(HCA2, "node02 HCA-1", 11), &fabric(HCA3, "node03 HCA-1", 12));
+        assert!(diff.links_moved.is_empty());
+        assert_eq!(diff.links_removed, vec![((HCA2, 1), (SPINE, 2))]);
+        assert_eq!(diff.links_added, vec![((HCA3, 1), (SPINE, 2))]);
+    }
+
+    #[test]
+    fn diff_switch_first_success() {
+        //The edge switch is the lower end of its links. hca2 moves from [1]
+        //to [4] and hca3 from [2] to [1], the port hca2 left.
+        let fabric = |hca2_port: u8, hca3_port: u8| {
+            TestFabric::new()
+                .with_switch(EDGE, "edge01", 3)
+                .with_ca(HCA2, "node02 HCA-1", 11)
+                .with_ca(HCA3, "node03 HCA-1", 12)
+                .with_link((LEAF, 8), (EDGE, 8))
+                .with_link((EDGE, hca2_port), (HCA2, 1))
+                .with_link((EDGE, hca3_port), (HCA3, 1))
+                .fabric()
+        };
+
+        let diff = TopologyDiff::between(&fabric(1, 2), &fabric(4, 1));
+        assert!(diff.links_added.is_empty() && diff.links_removed.is_empty(), "{}", diff);
+        assert_eq!(
+            diff.links_moved,
+            vec![
+                LinkMove { end: (HCA2, 1), before: (EDGE, 1), after: (EDGE, 4) },
+                LinkMove { end: (HCA3, 1), before: (EDGE, 2), after: (EDGE, 1) },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_unchanged_success() {
+        let fabric = before();
+        let diff = TopologyDiff::between(&fabric, &before());
+        assert!(diff.is_empty(), "{}", diff);
+        assert_eq!(diff.to_string(), "");
+
+        //Against an archived copy of itself
+        let archived = FabricSnapshot::from_json(&FabricSnapshot::from_fabric(&fabric).to_json().unwrap()).unwrap().to_fabric();
+        assert!(TopologyDiff::between(&archived, &fabric).is_empty());
+        assert!(!TopologyDiff::between(&archived, &after()).is_empty());
+    }
+}
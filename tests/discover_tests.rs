@@ -0,0 +1,211 @@
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::{Cell, RefCell}, rc::Rc};
+
+    use rsmad::ibmad::IBSmpError;
+    use rsmad::ibmad::transport::{MadRequest, MadResponse, MadTransport};
+    use rsmad::ibnetdisc::discover::DiscoverConfig;
+    use rsmad::ibnetdisc::fabric::Fabric;
+    use rsmad::ibnetdisc::node::NodeType;
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::{Topology, PORT_PHYS_STATE_POLLING, PORT_STATE_DOWN};
+
+    const HCA1: u64 = 0x0002c90300a1b2c0;
+    const HCA2: u64 = 0x0002c90300a1b3d0;
+    const HCA3: u64 = 0x0002c90300a1b4e0;
+    const LEAF1: u64 = 0xfc6a1c0300e4a800;
+    const LEAF2: u64 = 0xfc6a1c0300e4a900;
+    const SPINE: u64 = 0xfc6a1c0300e4b900;
+
+    //Two leaves with a double link each to one spine. hca3 is dual ported,
+    //one port on each leaf.
+    fn topology() -> Topology {
+        let mut t = Topology::new();
+        t.add_ca(HCA1, "node01 HCA-1", 1, 10);
+        t.add_ca(HCA2, "node02 HCA-1", 1, 11);
+        t.add_ca(HCA3, "node03 HCA-1", 2, 12);
+        t.add_switch(LEAF1, "leaf01", 8, 2);
+        t.add_switch(LEAF2, "leaf02", 8, 3);
+        t.add_switch(SPINE, "spine01", 8, 1);
+        t.link((HCA1, 1), (LEAF1, 1));
+        t.link((HCA2, 1), (LEAF2, 1));
+        t.link((HCA3, 1), (LEAF1, 2));
+        t.link((HCA3, 2), (LEAF2, 2));
+        t.link((LEAF1, 7), (SPINE, 1));
+        t.link((LEAF1, 8), (SPINE, 2));
+        t.link((LEAF2, 7), (SPINE, 3));
+        t.link((LEAF2, 8), (SPINE, 4));
+        t
+    }
+
+    //Wraps the simulator, dropping every nth MAD and recording batch sizes
+    struct LossyTransport {
+        sim: Simulator,
+        drop_every: usize,
+        calls: Cell<usize>,
+        batches: RefCell<Vec<usize>>,
+    }
+
+    impl MadTransport for LossyTransport {
+        fn call(&self, request: &MadRequest) -> Result<MadResponse, IBSmpError> {
+            self.calls.set(self.calls.get() + 1);
+            if self.drop_every != 0 && self.calls.get().is_multiple_of(self.drop_every) {
+                return Err(IBSmpError::SendMADError);
+            }
+            self.sim.call(request)
+        }
+
+        fn call_batch(&self, requests: &[MadRequest]) -> Vec<Result<MadResponse, IBSmpError>> {
+            self.batches.borrow_mut().push(requests.len());
+            requests.iter().map(|r| self.call(r)).collect()
+        }
+    }
+
+    fn lossy(drop_every: usize) -> Rc<LossyTransport> {
+        Rc::new(LossyTransport {
+            sim: Simulator::new(topology(), HCA1, 1).unwrap(),
+            drop_every,
+            calls: Cell::new(0),
+            batches: RefCell::new(Vec::new()),
+        })
+    }
+
+    #[test]
+    fn discover_dr_success() {
+        let sim = Rc::new(Simulator::new(topology(), HCA1, 1).unwrap());
+        let mut fabric = Fabric::from_transport("sim0", sim);
+
+        let report = fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+        assert!(report.failures.is_empty(), "Unexpected failures: {:?}", report.failures);
+
+        assert_eq!(fabric.nodes.len(), 6);
+        assert_eq!(fabric.switches.len(), 3);
+        assert_eq!(fabric.adapters.len(), 3);
+        //8 ports on each switch, plus 4 CA ports
+        assert_eq!(fabric.ports.len(), 28);
+
+        let spine = fabric.nodes[&SPINE].borrow();
+        assert_eq!(spine.node_desc, "spine01");
+        assert_eq!(spine.lid, 1);
+        assert!(matches!(spine.node_type, NodeType::SWITCH));
+
+        let hca3 = fabric.nodes[&HCA3].borrow();
+        assert_eq!(hca3.ports.as_ref().unwrap().len(), 2);
+        assert_eq!(hca3.lid, 12);
+
+        //Both ends of the second leaf01 uplink point at each other
+        let uplink = fabric.ports[&(LEAF1, 8)].borrow();
+        let remote = uplink.remote_port.as_ref().unwrap().upgrade().unwrap();
+        assert_eq!((remote.borrow().guid, remote.borrow().number), (SPINE, 2));
+        let remote_node = uplink.remote_node.as_ref().unwrap().upgrade().unwrap();
+        assert_eq!(remote_node.borrow().guid, SPINE);
+        let back = remote.borrow().remote_port.as_ref().unwrap().upgrade().unwrap();
+        assert_eq!((back.borrow().guid, back.borrow().number), (LEAF1, 8));
+
+        let hca3_port2 = fabric.ports[&(HCA3 + 2, 2)].borrow();
+        assert_eq!(hca3_port2.base_lid, 13);
+        let parent = hca3_port2.parent.as_ref().unwrap().upgrade().unwrap();
+        assert_eq!(parent.borrow().guid, HCA3);
+        let remote = hca3_port2.remote_node.as_ref().unwrap().upgrade().unwrap();
+        assert_eq!(remote.borrow().guid, LEAF2);
+
+        //Uncabled ports are discovered but lead nowhere
+        let unused = fabric.ports[&(LEAF2, 5)].borrow();
+        assert!(unused.remote_port.is_none());
+        assert_eq!(unused.phys_state, PORT_PHYS_STATE_POLLING as u32);
+    }
+
+    #[test]
+    fn discover_dr_lids_success() {
+        //The SM runs on hca1, lid 10, not on any of the switches
+        let sim = Rc::new(Simulator::new(topology(), HCA1, 1).unwrap());
+        let mut fabric = Fabric::from_transport("sim0", sim);
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+
+        assert_eq!(fabric.nodes[&LEAF1].borrow().smalid, 2);
+        assert_eq!(fabric.nodes[&SPINE].borrow().smalid, 1);
+        assert_eq!(fabric.nodes[&HCA1].borrow().smalid, 0);
+
+        //External switch ports answer with LID 0 and go by the switch LID
+        let uplink = fabric.ports[&(LEAF1, 7)].borrow();
+        assert_eq!(uplink.info.as_ref().unwrap().lid, 0);
+        assert_eq!(uplink.base_lid, 2);
+        assert_eq!(fabric.ports[&(SPINE, 3)].borrow().base_lid, 1);
+        assert_eq!(fabric.ports[&(HCA1 + 1, 1)].borrow().base_lid, 10);
+    }
+
+    #[test]
+    fn discover_dr_max_hops_success() {
+        let sim = Rc::new(Simulator::new(topology(), HCA1, 1).unwrap());
+        let mut fabric = Fabric::from_transport("sim0", sim);
+
+        let config = DiscoverConfig { max_hops: 1, ..Default::default() };
+        fabric.discover_dr(&config).unwrap();
+
+        assert_eq!(fabric.nodes.len(), 2);
+        assert!(fabric.nodes.contains_key(&LEAF1));
+    }
+
+    #[test]
+    fn discover_dr_batches_success() {
+        let transport = lossy(0);
+        let mut fabric = Fabric::from_transport("sim0", transport.clone());
+
+        let config = DiscoverConfig { max_smps: 4, ..Default::default() };
+        fabric.discover_dr(&config).unwrap();
+
+        let batches = transport.batches.borrow();
+        assert!(batches.iter().all(|&n| n <= 4));
+        assert!(batches.contains(&4));
+        assert_eq!(fabric.nodes.len(), 6);
+    }
+
+    #[test]
+    fn discover_dr_retries_success() {
+        let transport = lossy(5);
+        let mut fabric = Fabric::from_transport("sim0", transport);
+
+        let report = fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+        assert!(report.failures.is_empty(), "Unexpected failures: {:?}", report.failures);
+        assert_eq!(fabric.nodes.len(), 6);
+        assert_eq!(fabric.ports.len(), 28);
+    }
+
+    #[test]
+    fn discover_dr_partial_success() {
+        let transport = lossy(3);
+        let mut fabric = Fabric::from_transport("sim0", transport);
+
+        let config = DiscoverConfig { retries: 0, ..Default::default() };
+        let report = fabric.discover_dr(&config).unwrap();
+
+        assert!(!report.failures.is_empty());
+        assert!(report.failures.iter().all(|f| matches!(f.error, IBSmpError::SendMADError)));
+        assert!(fabric.nodes.contains_key(&HCA1));
+    }
+
+    #[test]
+    fn discover_dr_link_down_success() {
+        let sim = Rc::new(Simulator::new(topology(), HCA1, 1).unwrap());
+        for port in [7, 8] {
+            sim.set_port_state(LEAF1, port, PORT_STATE_DOWN, PORT_PHYS_STATE_POLLING).unwrap();
+        }
+
+        let mut fabric = Fabric::from_transport("sim0", sim);
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+
+        //leaf02 and hca2 are only reachable through hca3, which doesn't forward
+        assert_eq!(fabric.nodes.len(), 3);
+        assert!(!fabric.nodes.contains_key(&SPINE));
+    }
+
+    #[test]
+    fn discover_dr_no_local_node_failed_success() {
+        let transport = lossy(1);
+        let mut fabric = Fabric::from_transport("sim0", transport);
+
+        let r = fabric.discover_dr(&DiscoverConfig::default());
+        assert!(r.is_err());
+    }
+}
@@ -0,0 +1,164 @@
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use rsmad::ibmad::dispatch::{MadChannel, MadDispatcher};
+    use rsmad::ibmad::perf::ExtPerfCounters;
+    use rsmad::ibmad::sys::GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT;
+    use rsmad::ibmad::transport::{MadRequest, MadTransport};
+    use rsmad::ibmad::IBSmpError;
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::Topology;
+    use rsmad::umad::{mad::ib_user_mad, UmadError};
+
+    const HCA: u64 = 0x0002c90300a1b2c0;
+    const SPINE: u64 = 0xfc6a1c0300e4b900;
+    const LEAVES: u64 = 8;
+
+    fn leaf(i: u64) -> u64 {
+        0xfc6a1c0300e4a800 + (i << 8)
+    }
+
+    //hca -- [1] leaf01 [36] -- [1] spine01, and leaf02-08 [36] -- [2-8] spine01
+    fn simulator() -> Arc<Simulator> {
+        let mut t = Topology::new();
+        t.add_ca(HCA, "node01 HCA-1", 1, 100);
+        t.add_switch(SPINE, "spine01", 36, 1);
+        for i in 0..LEAVES {
+            t.add_switch(leaf(i), &format!("leaf{:02}", i + 1), 36, i as u16 + 2);
+            t.link((leaf(i), 36), (SPINE, i as u8 + 1));
+        }
+        t.link((HCA, 1), (leaf(0), 1));
+        Arc::new(Simulator::new(t, HCA, 1).unwrap())
+    }
+
+    //Keeps what's sent, completing only what the test puts in completions
+    #[derive(Default)]
+    struct HeldChannel {
+        sent: Mutex<Vec<ib_user_mad>>,
+        completions: Mutex<VecDeque<ib_user_mad>>,
+    }
+
+    impl MadChannel for HeldChannel {
+        fn send(&self, umad: &ib_user_mad, _timeout: u32) -> Result<(), UmadError> {
+            self.sent.lock().unwrap().push(*umad);
+            Ok(())
+        }
+
+        fn recv(&self, _timeout: i32) -> Result<ib_user_mad, UmadError> {
+            if let Some(umad) = self.completions.lock().unwrap().pop_front() {
+                return Ok(umad);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+            Err(UmadError::Timeout)
+        }
+    }
+
+    fn rcv_pkts(response: Result<Vec<u8>, IBSmpError>) -> u64 {
+        let mut data = response.unwrap();
+        ExtPerfCounters::from_mad_fields(&mut data).rcv_pkts
+    }
+
+    #[test]
+    fn dispatch_sweep_success() {
+        let sim = simulator();
+        for i in 0..LEAVES {
+            for port in 1..=36 {
+                let counters = HashMap::from([("rcv_pkts".to_string(), i * 100 + port as u64)]);
+                sim.set_counters(leaf(i), port, &counters).unwrap();
+            }
+        }
+
+        let dispatcher = MadDispatcher::new(sim.clone()).with_max_outstanding(16);
+
+        let mut requests = Vec::new();
+        for i in 0..LEAVES {
+            for port in 1..=36 {
+                requests.push(MadRequest::pma_get(i as u16 + 2, GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT, port, 100));
+            }
+        }
+
+        let results = dispatcher.query_batch(&requests);
+        assert_eq!(results.len(), LEAVES as usize * 36);
+        for (n, result) in results.into_iter().enumerate() {
+            assert_eq!(rcv_pkts(result), (n as u64 / 36) * 100 + n as u64 % 36 + 1);
+        }
+        assert_eq!(dispatcher.outstanding(), 0);
+    }
+
+    #[test]
+    fn dispatch_threads_success() {
+        let sim = simulator();
+        for i in 0..LEAVES {
+            let counters = HashMap::from([("rcv_pkts".to_string(), i + 1)]);
+            sim.set_counters(leaf(i), 36, &counters).unwrap();
+        }
+
+        //One dispatcher shared by a thread per leaf
+        let dispatcher = MadDispatcher::new(sim);
+        std::thread::scope(|s| {
+            for i in 0..LEAVES {
+                let dispatcher = &dispatcher;
+                s.spawn(move || {
+                    for _ in 0..50 {
+                        let p = rsmad::ibmad::perfquery(dispatcher, i as i32 + 2, 36, 0, 100).unwrap();
+                        assert_eq!(p.rcv_pkts, i + 1);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(dispatcher.outstanding(), 0);
+    }
+
+    #[test]
+    fn dispatch_retries_success() {
+        let sim = simulator();
+        let request = MadRequest::pma_get(2, GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT, 1, 100);
+
+        let dispatcher = MadDispatcher::new(sim.clone()).with_retries(2);
+        sim.drop_mads(2);
+        assert!(dispatcher.query(&request).is_ok());
+
+        sim.drop_mads(3);
+        assert!(matches!(dispatcher.query(&request), Err(IBSmpError::SendMADError)));
+        assert!(dispatcher.query(&request).is_ok());
+
+        //Without retries every lost MAD fails its request alone
+        drop(dispatcher);
+        let dispatcher = MadDispatcher::new(sim.clone());
+        sim.drop_mads(1);
+        let results = dispatcher.query_batch(&[request.clone(), request.clone(), request.clone()]);
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok() && results[2].is_ok());
+
+        //Nothing answers for an unassigned LID
+        let request = MadRequest::pma_get(999, GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT, 1, 100);
+        assert!(matches!(dispatcher.call(&request), Err(IBSmpError::SendMADError)));
+        assert_eq!(dispatcher.outstanding(), 0);
+    }
+
+    #[test]
+    fn dispatch_dropped_ticket_success() {
+        let channel = Arc::new(HeldChannel::default());
+        let dispatcher = MadDispatcher::new(channel.clone()).with_retries(3);
+        let request = MadRequest::pma_get(2, GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT, 1, 100);
+
+        let ticket = dispatcher.submit(&request);
+        assert_eq!(dispatcher.outstanding(), 1);
+        drop(ticket);
+        assert_eq!(dispatcher.outstanding(), 0);
+
+        //Timing out after it was given up on, it isn't sent again
+        let mut timed_out = channel.sent.lock().unwrap()[0];
+        timed_out.status = libc::ETIMEDOUT as u32;
+        channel.completions.lock().unwrap().push_back(timed_out);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(channel.completions.lock().unwrap().is_empty());
+        assert_eq!(channel.sent.lock().unwrap().len(), 1);
+        assert_eq!(dispatcher.outstanding(), 0);
+    }
+}
@@ -0,0 +1,90 @@
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, rc::Rc, thread, time::Duration};
+
+    use rsmad::ibnetdisc::error_scan::{ErrorScanner, ScanError, Threshold, ThresholdProfile};
+    use rsmad::ibnetdisc::fabric::Fabric;
+    use rsmad::ibnetdisc::node::NodeType;
+    use rsmad::ibsim::Simulator;
+
+    use super::common::{TestFabric, HCA1, LEAF, SPINE};
+
+    fn fabric() -> (Rc<Simulator>, Fabric) {
+        TestFabric::new().build()
+    }
+
+    fn counter(name: &str, value: u64) -> HashMap<String, u64> {
+        HashMap::from([(name.to_string(), value)])
+    }
+
+    #[test]
+    fn error_scan_counts_success() {
+        let (sim, fabric) = fabric();
+        sim.set_counters(LEAF, 3, &counter("symbol_errors", 20)).unwrap();
+        sim.set_counters(SPINE, 5, &counter("symbol_errors", 9)).unwrap();
+        sim.set_counters(HCA1, 1, &counter("link_downed", 10)).unwrap();
+
+        let mut scanner = ErrorScanner::new(ThresholdProfile::ibqueryerrors());
+        let report = scanner.scan(&fabric);
+        assert!(report.failures.is_empty(), "Unexpected failures: {:?}", report.failures);
+        assert_eq!(report.ports.len(), 2);
+
+        let leaf = report.ports.iter().find(|p| p.local.node_guid == LEAF).unwrap();
+        assert_eq!((leaf.local.port, leaf.local.lid, leaf.local.node_desc.as_str()), (3, 2, "leaf01"));
+        let remote = leaf.remote.as_ref().unwrap();
+        assert_eq!((remote.node_guid, remote.port, remote.lid), (SPINE, 5, 1));
+        assert_eq!(remote.node_desc, "spine01");
+        assert_eq!(leaf.violations.len(), 1);
+        assert_eq!(leaf.violations[0].counter, "symbol_errors");
+        assert_eq!(leaf.violations[0].value, 20);
+        assert_eq!(leaf.violations[0].rate, None);
+
+        let hca = report.ports.iter().find(|p| p.local.node_guid == HCA1).unwrap();
+        assert!(matches!(hca.local.node_type, NodeType::CA));
+        assert_eq!(hca.local.lid, 10);
+        assert_eq!(hca.remote.as_ref().unwrap().node_guid, LEAF);
+        assert_eq!(hca.violations[0].counter, "link_downed");
+    }
+
+    #[test]
+    fn error_scan_rates_success() {
+        let (sim, fabric) = fabric();
+        sim.set_counters(LEAF, 3, &counter("rcv_errors", 5)).unwrap();
+
+        let profile = ThresholdProfile::from_json(r#"{"thresholds": {"rcv_errors": {"rate": 100.0}}}"#).unwrap();
+        let mut scanner = ErrorScanner::new(profile);
+
+        //Nothing to compute a rate from yet
+        assert!(scanner.scan(&fabric).ports.is_empty());
+
+        thread::sleep(Duration::from_millis(10));
+        assert!(scanner.scan(&fabric).ports.is_empty());
+
+        sim.set_counters(LEAF, 3, &counter("rcv_errors", 1_000_000)).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        let report = scanner.scan(&fabric);
+        assert_eq!(report.ports.len(), 1);
+        let violation = &report.ports[0].violations[0];
+        assert_eq!(violation.value, 1_000_000);
+        assert!(violation.rate.unwrap() >= 100.0);
+        assert_eq!(violation.threshold, Threshold { count: None, rate: Some(100.0) });
+    }
+
+    #[test]
+    fn error_scan_profile_file_success() {
+        let profile = ThresholdProfile::ibqueryerrors();
+        let path = std::env::temp_dir().join(format!("rsmad-thresholds-{}.json", std::process::id()));
+        std::fs::write(&path, profile.to_json().unwrap()).unwrap();
+
+        let loaded = ThresholdProfile::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), profile);
+
+        let r = ThresholdProfile::from_json(r#"{"thresholds": {"symbol_error": {"count": 1}}}"#);
+        assert!(matches!(r, Err(ScanError::UnknownCounter(name)) if name == "symbol_error"));
+        assert!(matches!(ThresholdProfile::from_file("/nonexistent/thresholds.json"), Err(ScanError::ReadError(_))));
+    }
+}
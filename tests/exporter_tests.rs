@@ -0,0 +1,75 @@
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::{Read, Write}, net::TcpStream, rc::Rc};
+
+    use rsmad::ibnetdisc::exporter::{render, MetricsServer};
+    use rsmad::ibnetdisc::fabric::Fabric;
+    use rsmad::ibnetdisc::sampler::FabricSampler;
+    use rsmad::ibsim::Simulator;
+
+    use super::common::{TestFabric, HCA1, LEAF, SPINE};
+
+    fn fabric() -> (Rc<Simulator>, Fabric) {
+        TestFabric::new().with_desc(LEAF, "leaf01 \"rack 4\"").build()
+    }
+
+    fn get(server: &MetricsServer, path: &str) -> String {
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn exporter_render_success() {
+        let (sim, fabric) = fabric();
+        sim.set_counters(LEAF, 3, &HashMap::from([("xmt_bytes".to_string(), 250), ("symbol_errors".to_string(), 7)])).unwrap();
+
+        let round = FabricSampler::new().sample(&fabric);
+        let text = render(&fabric, &round.samples);
+
+        assert!(text.ends_with("# EOF\n"));
+        assert!(text.contains("# TYPE ib_port_xmt_bytes counter\n# UNIT ib_port_xmt_bytes bytes\n"));
+        assert!(text.contains("# TYPE ib_port_symbol_errors counter\n"));
+
+        let labels = format!(
+            "node_guid=\"0x{:016x}\",node_desc=\"leaf01 \\\"rack 4\\\"\",port=\"3\",lid=\"2\",remote_guid=\"0x{:016x}\",remote_desc=\"spine01\",remote_port=\"5\"",
+            LEAF, SPINE
+        );
+        //4 byte words
+        assert!(text.contains(&format!("ib_port_xmt_bytes_total{{{}}} 1000\n", labels)), "{}", text);
+        assert!(text.contains(&format!("ib_port_symbol_errors_total{{{}}} 7\n", labels)));
+
+        //One line per active port in each of the 28 families
+        assert_eq!(text.lines().filter(|l| l.starts_with("ib_port_")).count(), 4 * 28);
+    }
+
+    #[test]
+    fn exporter_render_empty_success() {
+        let (_sim, fabric) = fabric();
+        assert_eq!(render(&fabric, &[]), "# EOF\n");
+    }
+
+    #[test]
+    fn exporter_server_success() {
+        let (sim, fabric) = fabric();
+        let server = MetricsServer::bind("127.0.0.1:0").unwrap();
+        assert!(get(&server, "/metrics").ends_with("\r\n\r\n# EOF\n"));
+
+        sim.set_counters(HCA1, 1, &HashMap::from([("rcv_pkts".to_string(), 12)])).unwrap();
+        let round = FabricSampler::new().sample(&fabric);
+        server.publish_samples(&fabric, &round.samples);
+
+        let response = get(&server, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\n"));
+        assert!(response.contains(&format!("ib_port_rcv_pkts_total{{node_guid=\"0x{:016x}\"", HCA1)));
+        assert!(response.contains("} 12\n"));
+
+        assert!(get(&server, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
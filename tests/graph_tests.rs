@@ -0,0 +1,92 @@
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use rsmad::ibnetdisc::fabric::Fabric;
+    use rsmad::ibnetdisc::graph::{GraphEdge, TopologyGraph, JSON_SCHEMA};
+
+    use super::common::{TestFabric, HCA1, HCA2, LEAF, SPEED_QDR, SPINE};
+
+    //hca1 -- [1] leaf [3] -- [5] spine, 4x QDR
+    //          leaf [2] -- hca2, Init
+    fn fabric() -> Fabric {
+        TestFabric::new()
+            .with_desc(HCA1, "node01 \"HCA-1\"")
+            .with_ca(HCA2, "node02 HCA-1", 11)
+            .with_link((HCA2, 1), (LEAF, 2))
+            .with_speed((LEAF, 3), SPEED_QDR)
+            .with_init((LEAF, 2))
+            .fabric()
+    }
+
+    fn guid(guid: u64) -> String {
+        format!("0x{:016x}", guid)
+    }
+
+    #[test]
+    fn graph_from_fabric_success() {
+        let graph = TopologyGraph::from_fabric(&fabric());
+
+        let guids: Vec<u64> = graph.nodes.iter().map(|n| n.guid).collect();
+        assert_eq!(guids, vec![HCA1, HCA2, LEAF, SPINE]);
+        assert_eq!((graph.nodes[0].lid, graph.nodes[0].node_type.as_str()), (10, "CA"));
+        assert_eq!((graph.nodes[2].lid, graph.nodes[2].node_type.as_str()), (2, "Switch"));
+        assert_eq!(graph.nodes[3].node_desc, "spine01");
+
+        assert_eq!(graph.edges.len(), 3);
+        assert_eq!(
+            graph.edges[2],
+            GraphEdge {
+                from_guid: LEAF,
+                from_port: 3,
+                to_guid: SPINE,
+                to_port: 5,
+                state: "Active".to_string(),
+                width: Some("4x".to_string()),
+                speed: Some("QDR".to_string()),
+            }
+        );
+        assert_eq!((graph.edges[1].from_guid, graph.edges[1].to_port), (HCA2, 2));
+        assert_eq!(graph.edges[1].state, "Init");
+    }
+
+    #[test]
+    fn graph_dot_gml_success() {
+        let graph = TopologyGraph::from_fabric(&fabric());
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("graph fabric {\n") && dot.ends_with("}\n"));
+        assert!(dot.contains(&format!("\"{0}\" [label=\"leaf01\\n{0}\\nlid 2\", shape=box];\n", guid(LEAF))));
+        assert!(dot.contains(&format!("[label=\"node01 \\\"HCA-1\\\"\\n{}\\nlid 10\", shape=ellipse];\n", guid(HCA1))));
+        assert!(dot.contains(&format!("\"{}\" -- \"{}\" [taillabel=\"3\", headlabel=\"5\", label=\"4x QDR\"];\n", guid(LEAF), guid(SPINE))));
+        assert!(dot.contains(&format!("\"{}\" -- \"{}\" [taillabel=\"1\", headlabel=\"2\", label=\"4x SDR\", style=dashed];\n", guid(HCA2), guid(LEAF))));
+
+        let gml = graph.to_gml();
+        assert!(gml.starts_with("graph [\n  directed 0\n") && gml.ends_with("]\n"));
+        assert!(gml.contains("    id 0\n    label \"node01 &quot;HCA-1&quot;\"\n"));
+        assert!(gml.contains(&format!("    guid \"{}\"\n    lid 1\n    type \"Switch\"\n", guid(SPINE))));
+        assert!(gml.contains("    source 2\n    target 3\n    sourceport 3\n    targetport 5\n    state \"Active\"\n    width \"4x\"\n    speed \"QDR\"\n"));
+        assert_eq!(gml.matches("  edge [\n").count(), 3);
+    }
+
+    #[test]
+    fn graph_json_success() {
+        let graph = TopologyGraph::from_fabric(&fabric());
+        let json = graph.to_json().unwrap();
+        assert_eq!(TopologyGraph::from_json(&json).unwrap(), graph);
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["edges"][2]["speed"], "QDR");
+        assert_eq!(value["nodes"][0]["guid"], guid(HCA1));
+        assert_eq!(value["edges"][2]["to_guid"], guid(SPINE));
+        assert!(TopologyGraph::from_json(&json.replace(&guid(HCA1), "node01")).is_err());
+
+        //The fields written are the ones the schema documents
+        let schema: serde_json::Value = serde_json::from_str(JSON_SCHEMA).unwrap();
+        let node_fields = schema["properties"]["nodes"]["items"]["properties"].as_object().unwrap();
+        assert!(value["nodes"][0].as_object().unwrap().keys().all(|k| node_fields.contains_key(k)));
+        let edge_fields = schema["properties"]["edges"]["items"]["properties"].as_object().unwrap();
+        assert!(value["edges"][2].as_object().unwrap().keys().all(|k| edge_fields.contains_key(k)));
+    }
+}
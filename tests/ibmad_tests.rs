@@ -82,7 +82,7 @@ mod tests {
         let ca_names = rsmad::umad::umad_list_devices().unwrap();
         let ca_name = ca_names.first().unwrap();
         let port = rsmad::ibmad::mad_rpc_open_port(&ca_name, &mgmt_classes).unwrap();
-        let _r = rsmad::ibmad::set_node_desc(&port, 2, 3000);
+        let _r = rsmad::ibmad::set_node_desc(&port, 2, "switch-spine", 3000);
         rsmad::umad::umad_done();
     }
 
@@ -1,9 +1,16 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
     use std::{
-        cell::RefCell, ffi::{c_void, CStr, CString}, mem::{MaybeUninit}, ptr, rc::Rc, slice
+        cell::RefCell, collections::HashMap, ffi::{c_void, CStr, CString}, mem::{MaybeUninit}, ptr, rc::Rc, slice
     };
 
+    use rsmad::ibnetdisc::fabric::{Fabric, FabricError};
+    use rsmad::ibsim::Simulator;
+
+    use super::common::{TestFabric, HCA1, HCA2, LEAF, SPINE};
+
     #[test]
     fn unsafe_ffi_ibnd_discover_fabric_success() {
         rsmad::umad::umad_init();
@@ -123,139 +130,46 @@ mod tests {
         rsmad::umad::umad_done();
     }
 
+    //hca1 -- [1] leaf [3] -- [5] spine
+    //          leaf [2] -- hca2
+    fn fabric() -> (Rc<Simulator>, Fabric) {
+        TestFabric::new().with_ca(HCA2, "node02 HCA-1", 11).with_link((HCA2, 1), (LEAF, 2)).build()
+    }
+
     #[test]
     fn fabric_add_nodes_success() {
-        rsmad::umad::umad_init();
-
-        let port_number = 1;
-        let timeout = 100;
-        let retries = 3;
-        let mkey = 0;
-        let max_smps = 0;
-        let max_hops = 0;
-        let debug = 1;
-
-        let mut fabric = rsmad::ibnetdisc::fabric::Fabric::new("mlx5_0");
-        let r = fabric.discover(port_number,timeout,retries,mkey,max_smps,max_hops,debug);
-        match r {
-            Ok(_) => {
-                println!("Discovery completed successfully");
-            }
-            Err(err) => {
-                println!("Discovery failed: {}", err);
-            }
-        }
-
-        //18188380844304618496
-        //18188380844304618560
-        let sw = fabric.nodes.get(&18188380844304618560);
-
-        if let Some(switch_rc) = sw {
-            let switch_ref = RefCell::borrow_mut(switch_rc);
-            println!("{:?}", switch_ref.node_desc);
-            if let Some(port_ref) = &switch_ref.ports {
-                for p in port_ref {
-                    let local_port: std::cell::Ref<rsmad::ibnetdisc::port::Port> = RefCell::borrow(p);
-                    println!("LocalPort: {:?}", local_port);
-
-                    if let Some(rprp) = &local_port.remote_port {
-                        let r: Option<Rc<RefCell<rsmad::ibnetdisc::port::Port>>> = rprp.upgrade();
-                        if let Some(rp) = r {
-                            let rp_ref = RefCell::borrow(&rp);
-                            println!("  Remote Port: {:?}", rp_ref)
-                        }
-                    }
-                    if let Some(remote_node) = &local_port.remote_node {
-                        let r = remote_node.upgrade();
-                        if let Some(rp) = r {
-                            let rp_ref = RefCell::borrow(&rp);
-                            println!("  Remote Node: {:?}: {:?}", rp_ref.node_desc, rp_ref.guid);
-                        } else {
-                        }
-                    }
-                }
+        let (_, fabric) = fabric();
+        assert_eq!(fabric.nodes.len(), 4);
+        //8 ports on each switch, plus the 2 CA ports
+        assert_eq!(fabric.ports.len(), 18);
+
+        let leaf = RefCell::borrow(&fabric.nodes[&LEAF]);
+        assert_eq!(leaf.node_desc, "leaf01");
+        let ports = leaf.ports.as_ref().unwrap();
+        assert_eq!(ports.len(), 8);
+
+        let mut remotes = Vec::new();
+        for p in ports {
+            let local_port = RefCell::borrow(p);
+            let remote_port = local_port.remote_port.as_ref().and_then(|p| p.upgrade());
+            let remote_node = local_port.remote_node.as_ref().and_then(|n| n.upgrade());
+            if let (Some(rp), Some(rn)) = (remote_port, remote_node) {
+                remotes.push((local_port.number, RefCell::borrow(&rn).guid, RefCell::borrow(&rp).number));
             }
         }
-
-        println!("Port Count: {:?}", fabric.ports.len());
-        println!("Node Count: {:?}", fabric.nodes.len());
-
-        for (_i, rc_node) in fabric.nodes.iter() {
-            let node = RefCell::borrow(&rc_node);
-            println!("Node: {}, LID: {}, TYPE: {:?}", node.node_desc, node.lid, node.node_type);
-
-            if let Some(ports) = &node.ports {
-                for rc_port in ports {
-                    let port = RefCell::borrow(&rc_port);
-
-                    print!("\t[{:0>2}] 0x{:x}", port.number, node.guid);
-                    if let (Some(weak_remote_port), Some(weak_remote_node)) =
-                        (&port.remote_port, &port.remote_node)
-                    {
-                        if let (Some(remote_port), Some(remote_node)) =
-                            (weak_remote_port.upgrade(), weak_remote_node.upgrade())
-                        {
-                            let rp = RefCell::borrow(&remote_port);
-                            let rn = RefCell::borrow(&remote_node);
-
-                            print!(
-                                " - [{:0>2}] LID:{} {} {:?}",
-                                rp.number, rn.lid, rn.node_desc, rn.node_type
-                            );
-                        } else {
-                            print!(" - (Remote port or node not available)");
-                        }
-                    } else {
-                        print!(" - {} {}", port.logical_state, port.phys_state);
-                    }
-                    println!("");
-                }
-            }
-        }
-
-        rsmad::umad::umad_done();
+        assert_eq!(remotes, vec![(1, HCA1, 1), (2, HCA2, 1), (3, SPINE, 5)]);
     }
 
     #[test]
     fn fabric_ports_perfquery_success() {
-        rsmad::umad::umad_init();
-
-        let mut fabric = rsmad::ibnetdisc::fabric::Fabric::new("mlx5_0");
-        let r = fabric.discover(1,1000,3,0,0,0,0);
-        match r {
-            Ok(_) => {
-                println!("Discovery completed successfully");
-            }
-            Err(err) => {
-                println!("Discovery failed: {}", err);
-            }
-        }
+        let (sim, fabric) = fabric();
+        sim.set_counters(LEAF, 2, &HashMap::from([("rcv_pkts".to_string(), 10), ("xmt_pkts".to_string(), 20)])).unwrap();
 
-        //LEAF02 Port 28
-        if let Some(l2_rc) = fabric.nodes.get(&18188380844304618560) {
-            let l2_rc = RefCell::borrow(&l2_rc);
+        let mut pctr = fabric.get_port_perfcounter((LEAF, 2)).unwrap();
+        pctr.set_wait(10);
+        let counters: Vec<(u64, u64)> = pctr.by_ref().take(2).map(|p| (p.rcv_pkts, p.xmt_pkts)).collect();
+        assert_eq!(counters, vec![(10, 20), (10, 20)]);
 
-            if let Some(ports) = &l2_rc.ports {
-
-                for i in 0..ports.len() {
-                    let pctr_result = fabric.get_port_perfcounter((18188380844304618560, i.try_into().unwrap()));
-                    if let Ok(mut pctr) = pctr_result {
-                        pctr.set_wait(100);
-                        let r = pctr.by_ref().take(2);
-                        for (m , p) in r.enumerate() {
-                            println!("{} {:?} {:?}",
-                            i,
-                            p.counters.get("rcv_pkts"),
-                            p.counters.get("xmt_pkts")
-                        );
-                        }
-                    }
-                }
-            }
-        }
-
-        rsmad::umad::umad_done();
+        assert!(matches!(fabric.get_port_perfcounter((LEAF, 9)), Err(FabricError::PortNotFound)));
     }
-
-
 }
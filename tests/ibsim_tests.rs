@@ -0,0 +1,220 @@
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use rsmad::ibmad::{IBSmpError, sys};
+    use rsmad::ibmad::transport::{DrPath, MadAddress, MadRequest, MadTransport, DR_RETURN_PATH_OFFSET};
+    use rsmad::ibsim::{SimError, Simulator, MAD_STATUS_INVALID_ATTR_VALUE, MAD_STATUS_UNSUPPORTED_ATTR};
+    use rsmad::ibsim::topology::{Topology, PORT_PHYS_STATE_LINKUP, PORT_PHYS_STATE_POLLING, PORT_STATE_DOWN, PORT_STATE_INIT};
+
+    const HCA1: u64 = 0x0002c90300a1b2c0;
+    const HCA2: u64 = 0x0002c90300a1b3d0;
+    const LEAF: u64 = 0xfc6a1c0300e4a800;
+    const SPINE: u64 = 0xfc6a1c0300e4b900;
+
+    //hca1 -- [1] leaf [3] -- [5] spine
+    //          leaf [2] -- hca2
+    fn topology() -> Topology {
+        let mut t = Topology::new();
+        t.add_ca(HCA1, "node01 HCA-1", 1, 10);
+        t.add_ca(HCA2, "node02 HCA-1", 2, 11);
+        t.add_switch(LEAF, "leaf01", 36, 2);
+        t.add_switch(SPINE, "spine01", 40, 1);
+        t.link((HCA1, 1), (LEAF, 1));
+        t.link((HCA2, 1), (LEAF, 2));
+        t.link((LEAF, 3), (SPINE, 5));
+        t
+    }
+
+    fn simulator() -> Simulator {
+        Simulator::new(topology(), HCA1, 1).unwrap()
+    }
+
+    #[test]
+    fn sim_dr_node_info_success() {
+        let sim = simulator();
+
+        let local = rsmad::ibmad::send_dr_node_info_mad(&sim, "0", 100).unwrap();
+        assert_eq!(local.guid, HCA1);
+        assert_eq!(local.port_guid, HCA1 + 1);
+        assert_eq!(local.node_type, 1);
+
+        let leaf = rsmad::ibmad::send_dr_node_info_mad(&sim, "0,1", 100).unwrap();
+        assert_eq!(leaf.guid, LEAF);
+        assert_eq!(leaf.node_type, 2);
+        assert_eq!(leaf.num_ports, 36);
+        assert_eq!(leaf.local_port, 1);
+        assert_eq!(leaf.vendor_id, 0x02c9);
+
+        let spine = rsmad::ibmad::send_dr_node_info_mad(&sim, "0,1,3", 100).unwrap();
+        assert_eq!(spine.guid, SPINE);
+        assert_eq!(spine.local_port, 5);
+
+        let hca2 = rsmad::ibmad::send_dr_node_info_mad(&sim, "0,1,2", 100).unwrap();
+        assert_eq!(hca2.guid, HCA2);
+        assert_eq!(hca2.port_guid, HCA2 + 1);
+    }
+
+    #[test]
+    fn sim_dr_hop_pointers_success() {
+        let sim = simulator();
+        let request = MadRequest::smp_get(MadAddress::DirectedRoute(DrPath::new(&[1, 3])), sys::SMI_ATTR_ID_IB_ATTR_NODE_DESC, 0, 100);
+        let mut mad = request.encode(0x1234);
+
+        sim.process_mad(&mut mad, 0xffff).unwrap();
+
+        assert_eq!(mad[3], sys::MAD_METHODS_IB_MAD_METHOD_GET_RESPONSE as u8);
+        assert_eq!(mad[4] & 0x80, 0x80, "Direction bit not set");
+        assert_eq!(mad[6], 0, "Hop pointer should be back at the origin");
+        assert_eq!(mad[7], 2);
+        assert_eq!(&mad[8..16], &0x1234_u64.to_be_bytes());
+        assert_eq!(&mad[DR_RETURN_PATH_OFFSET + 1..DR_RETURN_PATH_OFFSET + 3], &[1, 5]);
+        assert_eq!(&mad[64..71], b"spine01");
+    }
+
+    #[test]
+    fn sim_dr_through_ca_failed_success() {
+        let sim = simulator();
+
+        //hca2 can't forward SMPs back out of its port
+        let r = rsmad::ibmad::send_dr_node_info_mad(&sim, "0,1,2,1", 100);
+        assert!(matches!(r, Err(IBSmpError::SendMADError)), "Unexpected result: {:?}", r);
+
+        //Uncabled switch port
+        let r = rsmad::ibmad::send_dr_node_info_mad(&sim, "0,1,20", 100);
+        assert!(matches!(r, Err(IBSmpError::SendMADError)), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn sim_lid_node_info_success() {
+        let sim = simulator();
+
+        let spine = rsmad::ibmad::send_lid_node_info_mad(&sim, 1, 100).unwrap();
+        assert_eq!(spine.guid, SPINE);
+
+        let hca2 = rsmad::ibmad::send_lid_node_info_mad(&sim, 11, 100).unwrap();
+        assert_eq!(hca2.guid, HCA2);
+
+        let r = rsmad::ibmad::send_lid_node_info_mad(&sim, 99, 100);
+        assert!(matches!(r, Err(IBSmpError::SendMADError)), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn sim_port_state_routing_success() {
+        let sim = simulator();
+
+        //A link in Init still carries DR SMPs but not LID routed MADs
+        sim.set_port_state(SPINE, 5, PORT_STATE_INIT, PORT_PHYS_STATE_LINKUP).unwrap();
+        assert!(rsmad::ibmad::send_dr_node_info_mad(&sim, "0,1,3", 100).is_ok());
+        assert!(rsmad::ibmad::send_lid_node_info_mad(&sim, 1, 100).is_err());
+
+        sim.set_port_state(SPINE, 5, PORT_STATE_DOWN, PORT_PHYS_STATE_POLLING).unwrap();
+        assert!(rsmad::ibmad::send_dr_node_info_mad(&sim, "0,1,3", 100).is_err());
+        assert!(rsmad::ibmad::send_lid_node_info_mad(&sim, 2, 100).is_ok());
+    }
+
+    #[test]
+    fn sim_node_desc_set_success() {
+        let sim = simulator();
+
+        assert_eq!(rsmad::ibmad::send_dr_node_desc_mad(&sim, "0,1", 100).unwrap(), "leaf01");
+
+        rsmad::ibmad::set_node_desc(&sim, 2, "leaf01 rack 4", 100).unwrap();
+        assert_eq!(sim.node_desc(LEAF).unwrap(), "leaf01 rack 4");
+        assert_eq!(rsmad::ibmad::send_dr_node_desc_mad(&sim, "0,1", 100).unwrap(), "leaf01 rack 4");
+    }
+
+    #[test]
+    fn sim_port_info_status_success() {
+        let sim = simulator();
+
+        let request = MadRequest::smp_get(MadAddress::DirectedRoute(DrPath::new(&[1])), sys::SMI_ATTR_ID_IB_ATTR_PORT_INFO, 37, 100);
+        let r = sim.call(&request).unwrap();
+        assert_eq!(r.status, MAD_STATUS_INVALID_ATTR_VALUE);
+
+        let request = MadRequest::smp_get(MadAddress::Lid(10), sys::SMI_ATTR_ID_IB_ATTR_SWITCH_INFO, 0, 100);
+        let r = sim.call(&request).unwrap();
+        assert_eq!(r.status, MAD_STATUS_UNSUPPORTED_ATTR);
+
+        let request = MadRequest::smp_get(MadAddress::DirectedRoute(DrPath::new(&[1])), sys::SMI_ATTR_ID_IB_ATTR_PORT_INFO, 20, 100);
+        let mut data = sim.query(&request).unwrap();
+        let state = unsafe { sys::mad_get_field(data.as_mut_ptr() as *mut std::ffi::c_void, 0, sys::MAD_FIELDS_IB_PORT_STATE_F) };
+        let phys_state = unsafe { sys::mad_get_field(data.as_mut_ptr() as *mut std::ffi::c_void, 0, sys::MAD_FIELDS_IB_PORT_PHYS_STATE_F) };
+        assert_eq!(state, PORT_STATE_DOWN as u32);
+        assert_eq!(phys_state, PORT_PHYS_STATE_POLLING as u32);
+    }
+
+    #[test]
+    fn sim_perfquery_success() {
+        let sim = simulator();
+
+        let counters = HashMap::from([("rcv_pkts".to_string(), 1000), ("xmt_bytes".to_string(), 1 << 40)]);
+        sim.set_counters(LEAF, 1, &counters).unwrap();
+        sim.set_counters(LEAF, 2, &HashMap::from([("rcv_pkts".to_string(), 24)])).unwrap();
+
+        let p = rsmad::ibmad::perfquery(&sim, 2, 1, 0, 100).unwrap();
+        assert_eq!(p.rcv_pkts, 1000);
+        assert_eq!(p.xmt_bytes, 1 << 40);
+        assert_eq!(p.symbol_errors, 0);
+
+        let all = rsmad::ibmad::perfquery(&sim, 2, 0xff, 0, 100).unwrap();
+        assert_eq!(all.rcv_pkts, 1024);
+    }
+
+    #[test]
+    fn sim_topology_json_success() {
+        let json = r#"{
+            "nodes": [
+                {"guid": 1, "node_type": "ca", "node_desc": "a", "num_ports": 1, "lid": 5},
+                {"guid": 2, "node_type": "switch", "node_desc": "sw", "num_ports": 8, "lid": 6,
+                 "ports": [{"number": 4, "state": 2}]}
+            ],
+            "links": [{"from": {"guid": 1, "port": 1}, "to": {"guid": 2, "port": 4}}]
+        }"#;
+
+        let topology = Topology::from_json(json).unwrap();
+        assert_eq!(Topology::from_json(&topology.to_json().unwrap()).unwrap(), topology);
+
+        let sim = Simulator::new(topology, 1, 1).unwrap();
+        assert_eq!(rsmad::ibmad::send_dr_node_info_mad(&sim, "0,1", 100).unwrap().guid, 2);
+        assert!(rsmad::ibmad::send_lid_node_info_mad(&sim, 6, 100).is_err());
+
+        let r = Simulator::from_json(json, 3, 1);
+        assert!(matches!(r, Err(SimError::UnknownPort(3, 1))), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn sim_duplicate_link_failed_success() {
+        let mut t = topology();
+        t.link((HCA2, 1), (SPINE, 6));
+
+        let r = Simulator::new(t, HCA1, 1);
+        assert!(matches!(r, Err(SimError::DuplicateLink(HCA2, 1))), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn sim_fabric_port_perfcounter_success() {
+        let sim = Rc::new(simulator());
+        sim.set_counters(SPINE, 5, &HashMap::from([("xmt_pkts".to_string(), 77)])).unwrap();
+
+        let mut fabric = rsmad::ibnetdisc::fabric::Fabric::from_transport("sim0", sim.clone());
+        let port = rsmad::ibnetdisc::port::Port {
+            guid: SPINE,
+            number: 5,
+            phys_state: PORT_PHYS_STATE_LINKUP as u32,
+            logical_state: 4,
+            base_lid: 1,
+            info: None,
+            remote_port: None,
+            remote_node: None,
+            parent: None,
+        };
+        fabric.ports.insert((SPINE, 5), Rc::new(RefCell::new(port)));
+
+        let pctr = fabric.get_port_perfcounter((SPINE, 5)).unwrap();
+        let samples: Vec<_> = pctr.take(2).collect();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].xmt_pkts, 77);
+    }
+}
@@ -0,0 +1,183 @@
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use rsmad::ibmad::notice::*;
+    use rsmad::ibmad::sa::{Gid, InformInfo, SaClient, SaRecord};
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::{Topology, PORT_PHYS_STATE_LINKUP, PORT_PHYS_STATE_POLLING, PORT_STATE_ACTIVE, PORT_STATE_DOWN};
+    use rsmad::umad::UmadError;
+
+    const HCA1: u64 = 0x0002c90300a1b2c0;
+    const HCA2: u64 = 0x0002c90300a1b3d0;
+    const LEAF: u64 = 0xfc6a1c0300e4a800;
+
+    //hca1 -- [1] leaf01 [2] -- hca2
+    fn simulator() -> Arc<Simulator> {
+        let mut t = Topology::new();
+        t.add_ca(HCA1, "node01 HCA-1", 1, 10);
+        t.add_ca(HCA2, "node02 HCA-1", 1, 20);
+        t.add_switch(LEAF, "leaf01", 8, 2);
+        t.link((HCA1, 1), (LEAF, 1));
+        t.link((HCA2, 1), (LEAF, 2));
+        Arc::new(Simulator::new(t, HCA1, 1).unwrap())
+    }
+
+    fn gid(guid: u64) -> Gid {
+        let mut gid = [0; 16];
+        gid[..8].copy_from_slice(&0xfe80_0000_0000_0000_u64.to_be_bytes());
+        gid[8..].copy_from_slice(&guid.to_be_bytes());
+        gid
+    }
+
+    #[test]
+    fn notice_link_flap_success() {
+        let sim = simulator();
+        let sa = SaClient::new(Rc::new(sim.clone()), 10);
+        let receiver = NoticeReceiver::new(sim.clone());
+
+        for trap in [TRAP_LINK_STATE_CHANGE, TRAP_GID_IN_SERVICE, TRAP_GID_OUT_OF_SERVICE] {
+            let info = sa.subscribe(trap).unwrap();
+            assert_eq!(info.trap_number, trap);
+            assert!(info.subscribe);
+        }
+
+        sim.set_port_state(LEAF, 2, PORT_STATE_DOWN, PORT_PHYS_STATE_POLLING).unwrap();
+        let notice = receiver.recv(100).unwrap();
+        assert_eq!(notice.issuer_lid, 2);
+        assert_eq!(notice.producer_type, NOTICE_PRODUCER_SWITCH);
+        assert_eq!(notice.event(), NoticeEvent::LinkStateChange { lid: 2 });
+
+        //No change, no trap
+        sim.set_port_state(LEAF, 2, PORT_STATE_DOWN, PORT_PHYS_STATE_POLLING).unwrap();
+        assert!(matches!(receiver.recv(10), Err(UmadError::Timeout)));
+
+        sim.set_port_state(HCA2, 1, PORT_STATE_DOWN, PORT_PHYS_STATE_POLLING).unwrap();
+        sim.set_port_state(HCA2, 1, PORT_STATE_ACTIVE, PORT_PHYS_STATE_LINKUP).unwrap();
+        let events: Vec<NoticeEvent> = receiver.events().take(2).collect();
+        assert_eq!(events, vec![
+            NoticeEvent::GidOutOfService { gid: gid(HCA2 + 1) },
+            NoticeEvent::GidInService { gid: gid(HCA2 + 1) },
+        ]);
+        assert_eq!(sim.reports_acked(), 3);
+
+        assert!(!sa.unsubscribe(TRAP_LINK_STATE_CHANGE).unwrap().subscribe);
+        sim.set_port_state(LEAF, 2, PORT_STATE_ACTIVE, PORT_PHYS_STATE_LINKUP).unwrap();
+        assert!(matches!(receiver.recv(10), Err(UmadError::Timeout)));
+    }
+
+    #[test]
+    fn notice_port_error_traps_success() {
+        let sim = simulator();
+        let sa = SaClient::new(Rc::new(sim.clone()), 10);
+        let receiver = NoticeReceiver::new(sim.clone());
+        sa.subscribe(InformInfo::ALL_TRAPS).unwrap();
+
+        let mut notice = Notice { notice_type: NOTICE_TYPE_URGENT, producer_type: NOTICE_PRODUCER_SWITCH, issuer_lid: 2, ..Default::default() };
+        notice.data_details[2..4].copy_from_slice(&2_u16.to_be_bytes());
+        notice.data_details[4] = 7;
+        for trap in [TRAP_LINK_INTEGRITY, TRAP_BUFFER_OVERRUN, TRAP_FLOW_CONTROL_WATCHDOG] {
+            sim.notify(&Notice { trap_number: trap, ..notice.clone() });
+        }
+
+        let mut capability = Notice { trap_number: TRAP_CAPABILITY_CHANGE, issuer_lid: 20, ..Default::default() };
+        capability.data_details[2..4].copy_from_slice(&20_u16.to_be_bytes());
+        capability.data_details[6..10].copy_from_slice(&0x2651_084a_u32.to_be_bytes());
+        capability.data_details[10..12].copy_from_slice(&CHANGE_NODE_DESCRIPTION.to_be_bytes());
+        sim.notify(&capability);
+
+        let mut guid_change = Notice { trap_number: TRAP_SYSTEM_IMAGE_GUID_CHANGE, ..Default::default() };
+        guid_change.data_details[2..4].copy_from_slice(&20_u16.to_be_bytes());
+        guid_change.data_details[6..14].copy_from_slice(&HCA2.to_be_bytes());
+        sim.notify(&guid_change);
+
+        let vendor = Notice { is_generic: false, producer_type: 0x02c9, trap_number: 0x1234, ..Default::default() };
+        sim.notify(&vendor);
+
+        let events: Vec<NoticeEvent> = receiver.events().take(6).collect();
+        assert_eq!(events[0], NoticeEvent::LinkIntegrity { lid: 2, port: 7 });
+        assert_eq!(events[1], NoticeEvent::BufferOverrun { lid: 2, port: 7 });
+        assert_eq!(events[2], NoticeEvent::FlowControlWatchdog { lid: 2, port: 7 });
+        assert_eq!(events[3], NoticeEvent::CapabilityChange {
+            lid: 20,
+            capability_mask: 0x2651_084a,
+            capability_mask2: 0,
+            change_flags: CHANGE_NODE_DESCRIPTION,
+            other_local_changes: false,
+        });
+        assert_eq!(events[4], NoticeEvent::SystemImageGuidChange { lid: 20, system_image_guid: HCA2 });
+        assert_eq!(events[5], NoticeEvent::Other(vendor));
+    }
+
+    #[test]
+    fn notice_codec_success() {
+        let mut notice = Notice {
+            is_generic: true,
+            notice_type: NOTICE_TYPE_URGENT,
+            producer_type: NOTICE_PRODUCER_SWITCH,
+            trap_number: TRAP_LINK_STATE_CHANGE,
+            toggle: true,
+            count: 0x1234,
+            issuer_lid: 0xc001,
+            issuer_gid: gid(LEAF),
+            ..Default::default()
+        };
+        notice.data_details[0..2].copy_from_slice(&0xc001_u16.to_be_bytes());
+
+        let mut data = [0; NOTICE_SIZE];
+        notice.encode(&mut data);
+        assert_eq!(data[..10], [0x81, 0, 0, 2, 0, 128, 0xc0, 0x01, 0x92, 0x34]);
+        assert_eq!(Notice::decode(&data), notice);
+
+        let info = InformInfo { lid_range_end: 100, qpn: 0x123456, ..InformInfo::trap(TRAP_LINK_INTEGRITY, true) };
+        let mut data = [0; 36];
+        info.encode(&mut data);
+        assert_eq!(data[26..32], [0, 129, 0x12, 0x34, 0x56, 18]);
+        assert_eq!(InformInfo::decode(&data), info);
+        assert!(info.matches(TRAP_LINK_INTEGRITY));
+        assert!(!info.matches(TRAP_BUFFER_OVERRUN));
+    }
+
+    //Notice as the IBA lays it out, IssuerLID at byte 6 and then
+    //NoticeToggle/NoticeCount
+    #[test]
+    fn notice_decode_spec_layout_success() {
+        let mut data = [0_u8; NOTICE_SIZE];
+        data[..10].copy_from_slice(&[0x81, 0x00, 0x00, 0x02, 0x00, 0x81, 0x00, 0x2a, 0x80, 0x05]);
+        data[13] = 9;
+        data[14] = 3;
+        data[64..80].copy_from_slice(&gid(LEAF));
+
+        let notice = Notice::decode(&data);
+        assert!(notice.is_generic);
+        assert_eq!((notice.notice_type, notice.producer_type, notice.trap_number), (NOTICE_TYPE_URGENT, NOTICE_PRODUCER_SWITCH, TRAP_LINK_INTEGRITY));
+        assert_eq!((notice.issuer_lid, notice.toggle, notice.count), (0x2a, true, 5));
+        assert_eq!(notice.issuer_gid, gid(LEAF));
+        assert_eq!(notice.event(), NoticeEvent::LinkIntegrity { lid: 9, port: 3 });
+
+        let mut encoded = [0; NOTICE_SIZE];
+        notice.encode(&mut encoded);
+        assert_eq!(encoded, data);
+    }
+
+    //Trap 144 DataDetails: LIDAddr at 2, CapabilityMask at 6, ChangeFlags at
+    //10 and CapabilityMask2 at 12
+    #[test]
+    fn notice_capability_change_layout_success() {
+        let mut data = [0_u8; NOTICE_SIZE];
+        data[..10].copy_from_slice(&[0x84, 0x00, 0x00, 0x01, 0x00, 0x90, 0x00, 0x14, 0x00, 0x01]);
+        data[10..24].copy_from_slice(&[0, 0, 0x00, 0x14, 0, 0x01, 0x26, 0x51, 0x08, 0x4a, 0x00, 0x21, 0x00, 0x04]);
+
+        let notice = Notice::decode(&data);
+        assert_eq!((notice.issuer_lid, notice.count), (20, 1));
+        assert_eq!(notice.event(), NoticeEvent::CapabilityChange {
+            lid: 20,
+            capability_mask: 0x2651_084a,
+            capability_mask2: 0x0004,
+            change_flags: CHANGE_NODE_DESCRIPTION | CHANGE_CAPABILITY_MASK2,
+            other_local_changes: true,
+        });
+    }
+}
@@ -0,0 +1,235 @@
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rsmad::ibmad::perf::{CounterSelect, ExtPerfCounters, PMA_CAP2_ADDL_PORT_COUNTERS_EXT};
+    use rsmad::ibmad::sys::{IB_PM_ALL_PORT_SELECT, IB_PM_EXT_WIDTH_NOIETF_SUP, IB_PM_EXT_WIDTH_SUPPORTED, IB_PM_PC_XMIT_WAIT_SUP};
+    use rsmad::ibmad::IBSmpError;
+    use rsmad::ibsim::{SimError, Simulator};
+    use rsmad::ibsim::topology::Topology;
+
+    const HCA: u64 = 0x0002c90300a1b2c0;
+    const MODERN: u64 = 0xfc6a1c0300e4a800;
+    const LEGACY: u64 = 0xfc6a1c0300e4b900;
+    const NOIETF: u64 = 0xfc6a1c0300e4ca00;
+
+    //hca -- [1] modern [2] -- [1] legacy
+    //          modern [3] -- [1] noietf
+    fn simulator() -> Simulator {
+        let mut t = Topology::new();
+        t.add_ca(HCA, "node01 HCA-1", 1, 10);
+        t.add_switch(MODERN, "modern", 8, 1);
+        let legacy = t.add_switch(LEGACY, "legacy", 8, 2);
+        legacy.pma_cap_mask = IB_PM_ALL_PORT_SELECT as u16;
+        legacy.pma_cap_mask2 = 0;
+        let noietf = t.add_switch(NOIETF, "noietf", 8, 3);
+        noietf.pma_cap_mask = (IB_PM_ALL_PORT_SELECT | IB_PM_EXT_WIDTH_NOIETF_SUP | IB_PM_PC_XMIT_WAIT_SUP) as u16;
+        noietf.pma_cap_mask2 = 0;
+        t.link((HCA, 1), (MODERN, 1));
+        t.link((MODERN, 2), (LEGACY, 1));
+        t.link((MODERN, 3), (NOIETF, 1));
+        Simulator::new(t, HCA, 1).unwrap()
+    }
+
+    fn counters() -> HashMap<String, u64> {
+        HashMap::from([
+            ("xmt_bytes".to_string(), 1 << 40),
+            ("rcv_pkts".to_string(), 1000),
+            ("rcv_upkts".to_string(), 900),
+            ("symbol_errors".to_string(), 70000),
+            ("link_downed".to_string(), 3),
+            ("xmit_waits".to_string(), 42),
+        ])
+    }
+
+    #[test]
+    fn perf_class_port_info_success() {
+        let sim = simulator();
+
+        let modern = rsmad::ibmad::pma_class_port_info(&sim, 1, 100).unwrap();
+        assert!(modern.all_port_select());
+        assert!(modern.extended_width());
+        assert!(modern.xmit_wait());
+        assert!(modern.additional_ext_counters());
+
+        let legacy = rsmad::ibmad::pma_class_port_info(&sim, 2, 100).unwrap();
+        assert!(legacy.all_port_select());
+        assert!(!legacy.extended_width());
+        assert!(!legacy.extended_width_noietf());
+        assert!(!legacy.xmit_wait());
+        assert!(!legacy.additional_ext_counters());
+
+        let noietf = rsmad::ibmad::pma_class_port_info(&sim, 3, 100).unwrap();
+        assert!(!noietf.extended_width());
+        assert!(noietf.extended_width_noietf());
+        assert_eq!(noietf.capability_mask as u32 & IB_PM_EXT_WIDTH_SUPPORTED, 0);
+        assert_eq!(noietf.capability_mask2 & PMA_CAP2_ADDL_PORT_COUNTERS_EXT, 0);
+    }
+
+    #[test]
+    fn perf_legacy_saturates_success() {
+        let sim = simulator();
+        sim.set_counters(MODERN, 1, &counters()).unwrap();
+
+        let legacy = rsmad::ibmad::perfquery_legacy(&sim, 1, 1, 0, 100).unwrap();
+        assert_eq!(legacy.port_select, 1);
+        assert_eq!(legacy.xmt_bytes, u32::MAX);
+        assert_eq!(legacy.rcv_pkts, 1000);
+        assert_eq!(legacy.symbol_errors, u16::MAX);
+        assert_eq!(legacy.link_downed, 3);
+        assert_eq!(legacy.xmit_waits, 42);
+    }
+
+    #[test]
+    fn perf_auto_legacy_success() {
+        let sim = simulator();
+        sim.set_counters(LEGACY, 1, &counters()).unwrap();
+
+        //Without PortCountersExtended the extended query is refused
+        assert!(matches!(rsmad::ibmad::perfquery(&sim, 2, 1, 0, 100), Err(IBSmpError::MadStatusError(_))));
+
+        let p = rsmad::ibmad::perfquery_auto(&sim, 2, 1, 0, 100).unwrap();
+        assert_eq!(p.xmt_bytes, u32::MAX as u64);
+        assert_eq!(p.rcv_pkts, 1000);
+        assert_eq!(p.rcv_upkts, 0);
+        assert_eq!(p.symbol_errors, u16::MAX as u64);
+        assert_eq!(p.link_downed, 3);
+        //Not supported, whatever the PMA answers
+        assert_eq!(p.xmit_waits, 0);
+    }
+
+    #[test]
+    fn perf_auto_noietf_success() {
+        let sim = simulator();
+        sim.set_counters(NOIETF, 1, &counters()).unwrap();
+
+        let p = rsmad::ibmad::perfquery_auto(&sim, 3, 1, 0, 100).unwrap();
+        assert_eq!(p.xmt_bytes, 1 << 40);
+        assert_eq!(p.rcv_pkts, 1000);
+        assert_eq!(p.rcv_upkts, 0);
+        //From PortCounters, as this PMA doesn't fill them in the extended one
+        assert_eq!(p.symbol_errors, u16::MAX as u64);
+        assert_eq!(p.link_downed, 3);
+        assert_eq!(p.xmit_waits, 42);
+    }
+
+    #[test]
+    fn perf_auto_extended_success() {
+        let sim = simulator();
+        sim.set_counters(MODERN, 1, &counters()).unwrap();
+
+        let cpi = rsmad::ibmad::pma_class_port_info(&sim, 1, 100).unwrap();
+        let p = rsmad::ibmad::perfquery_with_cpi(&sim, 1, 1, &cpi, 0, 100).unwrap();
+        assert_eq!(p, rsmad::ibmad::perfquery(&sim, 1, 1, 0, 100).unwrap());
+        assert_eq!(p.xmt_bytes, 1 << 40);
+        assert_eq!(p.rcv_upkts, 900);
+        assert_eq!(p.symbol_errors, 70000);
+    }
+
+    #[test]
+    fn perf_reset_errors_success() {
+        let sim = simulator();
+        sim.set_counters(MODERN, 1, &counters()).unwrap();
+        sim.set_counters(MODERN, 2, &counters()).unwrap();
+
+        let after = rsmad::ibmad::reset_ext_port_counters(&sim, 1, 1, CounterSelect::Errors, 0, 100).unwrap();
+        assert_eq!(after.symbol_errors, 0);
+        assert_eq!(after.xmit_waits, 0);
+        assert_eq!(after.xmt_bytes, 1 << 40);
+
+        //Other ports keep theirs
+        let p = rsmad::ibmad::perfquery(&sim, 1, 2, 0, 100).unwrap();
+        assert_eq!(p.symbol_errors, 70000);
+    }
+
+    #[test]
+    fn perf_reset_all_ports_success() {
+        let sim = simulator();
+        sim.set_counters(MODERN, 1, &counters()).unwrap();
+        sim.set_counters(MODERN, 2, &counters()).unwrap();
+
+        rsmad::ibmad::reset_counters(&sim, 1, 0xff, CounterSelect::Data, 0, 100).unwrap();
+        for portnum in [1, 2] {
+            let p = rsmad::ibmad::perfquery(&sim, 1, portnum, 0, 100).unwrap();
+            assert_eq!(p.xmt_bytes, 0);
+            assert_eq!(p.rcv_pkts, 0);
+            assert_eq!(p.rcv_upkts, 0);
+            assert_eq!(p.link_downed, 3);
+        }
+
+        rsmad::ibmad::reset_counters(&sim, 1, 0xff, CounterSelect::All, 0, 100).unwrap();
+        let all = rsmad::ibmad::perfquery(&sim, 1, 0xff, 0, 100).unwrap();
+        assert!(all.iter().all(|(_, value)| value == 0));
+    }
+
+    #[test]
+    fn perf_reset_legacy_success() {
+        let sim = simulator();
+        sim.set_counters(LEGACY, 1, &counters()).unwrap();
+        sim.set_counters(NOIETF, 1, &counters()).unwrap();
+
+        let after = rsmad::ibmad::reset_port_counters(&sim, 2, 1, CounterSelect::Errors, 0, 100).unwrap();
+        assert_eq!(after.symbol_errors, 0);
+        assert_eq!(after.link_downed, 0);
+        assert_eq!(after.rcv_pkts, 1000);
+
+        //Without the additional counters the errors are cleared through
+        //PortCounters alone
+        rsmad::ibmad::reset_counters(&sim, 3, 1, CounterSelect::All, 0, 100).unwrap();
+        let p = rsmad::ibmad::perfquery_auto(&sim, 3, 1, 0, 100).unwrap();
+        assert!(p.iter().all(|(_, value)| value == 0));
+    }
+
+    #[test]
+    fn perf_reset_no_all_port_select_success() {
+        let mut t = Topology::new();
+        t.add_ca(HCA, "node01 HCA-1", 1, 10);
+        t.add_switch(MODERN, "modern", 8, 1).pma_cap_mask = IB_PM_EXT_WIDTH_SUPPORTED as u16;
+        t.link((HCA, 1), (MODERN, 1));
+        let sim = Simulator::new(t, HCA, 1).unwrap();
+        sim.set_counters(MODERN, 1, &counters()).unwrap();
+
+        let r = rsmad::ibmad::reset_counters(&sim, 1, 0xff, CounterSelect::All, 0, 100);
+        assert!(matches!(r, Err(IBSmpError::MadStatusError(_))));
+        rsmad::ibmad::reset_counters(&sim, 1, 1, CounterSelect::All, 0, 100).unwrap();
+        let p = rsmad::ibmad::perfquery(&sim, 1, 1, 0, 100).unwrap();
+        assert_eq!(p.xmt_bytes, 0);
+    }
+
+    #[test]
+    fn perf_counters_fields_success() {
+        let mut c = ExtPerfCounters { xmt_bytes: 100, symbol_errors: 2, ..Default::default() };
+        assert_eq!(c.iter().count(), 28);
+        assert_eq!(c.iter().next(), Some(("xmt_bytes", 100)));
+        assert_eq!(c.get("symbol_errors"), Some(2));
+        assert_eq!(c.get("xmt_byte"), None);
+
+        *c.get_mut("qp1_drops").unwrap() = 7;
+        assert_eq!(c.qp1_drops, 7);
+
+        let json = serde_json::to_string(&c).unwrap();
+        assert_eq!(serde_json::from_str::<ExtPerfCounters>(&json).unwrap(), c);
+    }
+
+    #[test]
+    fn perf_counters_delta_success() {
+        let before = ExtPerfCounters { xmt_bytes: 100, rcv_pkts: 50, link_downed: 4, ..Default::default() };
+        let after = ExtPerfCounters { xmt_bytes: 160, rcv_pkts: 50, link_downed: 1, ..Default::default() };
+
+        let delta = after.delta(&before);
+        assert_eq!(delta.xmt_bytes, 60);
+        assert_eq!(delta.rcv_pkts, 0);
+        //Reset in between
+        assert_eq!(delta.link_downed, 0);
+        assert_eq!(after - before, delta);
+    }
+
+    #[test]
+    fn perf_set_unknown_counter_success() {
+        let sim = simulator();
+
+        let r = sim.set_counters(MODERN, 1, &HashMap::from([("rcv_packets".to_string(), 1)]));
+        assert!(matches!(r, Err(SimError::UnknownCounter(name)) if name == "rcv_packets"));
+    }
+}
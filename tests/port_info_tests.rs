@@ -0,0 +1,93 @@
+
+#[cfg(test)]
+mod tests {
+    use rsmad::ibmad::IBSmpError;
+    use rsmad::ibmad::port::{LinkSpeed, LinkWidth, MlnxExtPortInfo, PortInfo};
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::{Topology, PORT_PHYS_STATE_POLLING, PORT_STATE_DOWN};
+
+    const HCA1: u64 = 0x0002c90300a1b2c0;
+    const HCA2: u64 = 0x0002c90300a1b3d0;
+    const LEAF: u64 = 0xfc6a1c0300e4a800;
+
+    fn simulator() -> Simulator {
+        let mut t = Topology::new();
+        t.add_ca(HCA1, "node01 HCA-1", 1, 10);
+        t.add_ca(HCA2, "node02 HCA-1", 1, 12).port_mut(1).lmc = 1;
+        let leaf = t.add_switch(LEAF, "leaf01", 36, 2);
+        leaf.port_mut(2).link_speed_ext_active = 4;
+        leaf.port_mut(2).link_width_active = 2;
+        t.link((HCA1, 1), (LEAF, 1));
+        t.link((HCA2, 1), (LEAF, 2));
+        Simulator::new(t, HCA1, 1).unwrap()
+    }
+
+    #[test]
+    fn dr_port_info_success() {
+        let sim = simulator();
+
+        let info = rsmad::ibmad::send_dr_port_info_mad(&sim, "0,1", 2, 100).unwrap();
+        assert_eq!(info.local_port, 2);
+        //Only switch port 0 carries the switch LID
+        assert_eq!(info.lid, 0);
+        assert!(info.is_active());
+        assert!(info.is_link_up());
+        assert_eq!(info.link_width(), LinkWidth::X4);
+        assert_eq!(info.link_speed(), LinkSpeed::Hdr);
+        assert_eq!(info.link_speed_ext_supported, 0x0f);
+        assert_eq!(info.mtu_cap_bytes(), 4096);
+        assert_eq!(info.neighbor_mtu_bytes(), 4096);
+        assert_eq!(info.data_vls(), 8);
+    }
+
+    #[test]
+    fn lid_port_info_success() {
+        let sim = simulator();
+
+        let info = rsmad::ibmad::send_lid_port_info_mad(&sim, 12, 1, 100).unwrap();
+        assert_eq!(info.lid, 12);
+        assert_eq!(info.lmc, 1);
+        assert_eq!(info.link_speed(), LinkSpeed::Sdr);
+        assert_eq!(info.cap_mask & rsmad::ibmad::port::IS_EXTENDED_SPEEDS_SUPPORTED, 0);
+    }
+
+    #[test]
+    fn port_info_down_success() {
+        let sim = simulator();
+        sim.set_port_state(LEAF, 30, PORT_STATE_DOWN, PORT_PHYS_STATE_POLLING).unwrap();
+
+        let info = rsmad::ibmad::send_dr_port_info_mad(&sim, "0,1", 30, 100).unwrap();
+        assert!(!info.is_active());
+        assert!(!info.is_link_up());
+    }
+
+    #[test]
+    fn port_info_bad_port_failed_success() {
+        let sim = simulator();
+
+        let r = rsmad::ibmad::send_dr_port_info_mad(&sim, "0,1", 37, 100);
+        assert!(matches!(r, Err(IBSmpError::MadStatusError(_))), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn link_speed_fdr10_success() {
+        let info = PortInfo { link_speed_active: 4, link_width_active: 2, ..Default::default() };
+        let ext = MlnxExtPortInfo { link_speed_active: 1, ..Default::default() };
+
+        assert_eq!(info.link_speed(), LinkSpeed::Qdr);
+        assert_eq!(info.link_speed_mlnx(&ext), LinkSpeed::Fdr10);
+        assert_eq!(info.link_speed_mlnx(&MlnxExtPortInfo::default()), LinkSpeed::Qdr);
+        assert_eq!(format!("{} {}", info.link_width(), info.link_speed_mlnx(&ext)), "4x FDR10");
+    }
+
+    #[test]
+    fn link_speed_ext_ignored_success() {
+        //LinkSpeedExtActive means nothing without IsExtendedSpeedsSupported
+        let info = PortInfo { link_speed_active: 2, link_speed_ext_active: 2, ..Default::default() };
+        assert_eq!(info.link_speed(), LinkSpeed::Ddr);
+
+        let info = PortInfo { cap_mask: rsmad::ibmad::port::IS_EXTENDED_SPEEDS_SUPPORTED, ..info };
+        assert_eq!(info.link_speed(), LinkSpeed::Edr);
+        assert_eq!(LinkWidth::from_link_width_active(16).lanes(), 2);
+    }
+}
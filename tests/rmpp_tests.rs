@@ -0,0 +1,290 @@
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    use rsmad::ibmad::sa::SaClient;
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::Topology;
+    use rsmad::umad::rmpp::*;
+
+    const SWITCH: u64 = 0xfc6a1c0300e4a800;
+    const HCA: u64 = 0x0002c90300a1b200;
+
+    //An SA GetTable response with len bytes of data
+    fn message(len: usize) -> Vec<u8> {
+        let mut mad = vec![0; 56 + len];
+        mad[0] = 1;
+        mad[1] = 0x03;
+        mad[2] = 2;
+        mad[3] = 0x92;
+        mad[12..16].copy_from_slice(&0x1234u32.to_be_bytes());
+        mad[44..46].copy_from_slice(&2u16.to_be_bytes());
+        for (i, byte) in mad[56..].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        mad
+    }
+
+    //Run a transfer, returning the reassembled MAD and the ACKs seen
+    fn transfer(sender: &mut RmppSender, receiver: &mut RmppReceiver, drops: &mut Vec<u32>) -> (Vec<u8>, Vec<RmppHeader>) {
+        let mut acks = Vec::new();
+        loop {
+            let segments = sender.next_segments();
+            if segments.is_empty() {
+                sender.on_timeout().unwrap();
+                continue;
+            }
+
+            for segment in segments {
+                let seg_num = RmppHeader::from_mad(&segment).seg_num;
+                if let Some(i) = drops.iter().position(|&d| d == seg_num) {
+                    drops.remove(i);
+                    continue;
+                }
+
+                match receiver.receive(&segment) {
+                    RmppReply::Wait => {}
+                    RmppReply::Ack(ack) => {
+                        acks.push(RmppHeader::from_mad(&ack));
+                        assert!(!sender.on_reply(&ack).unwrap());
+                    }
+                    RmppReply::Done { ack, mad } => {
+                        acks.push(RmppHeader::from_mad(&ack));
+                        assert!(sender.on_reply(&ack).unwrap());
+                        return (mad, acks);
+                    }
+                    RmppReply::Fail(_, e) => panic!("Transfer failed: {}", e),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rmpp_segments_success() {
+        let mut sender = RmppSender::new(&message(1000));
+        assert_eq!(sender.segment_count(), 5);
+
+        //The sender starts with a window of one
+        let segments = sender.next_segments();
+        assert_eq!(segments.len(), 1);
+        assert!(sender.next_segments().is_empty());
+
+        let hdr = RmppHeader::from_mad(&segments[0]);
+        assert_eq!(hdr.rmpp_type, RMPP_TYPE_DATA);
+        assert_eq!(hdr.flags, RMPP_FLAG_ACTIVE | RMPP_FLAG_FIRST);
+        assert_eq!(hdr.seg_num, 1);
+        //Data and the SA header of every segment
+        assert_eq!(hdr.paylen_newwin, 1000 + 5 * 20);
+        assert_eq!(segments[0].len(), 256);
+        assert_eq!(&segments[0][..24], &message(1000)[..24]);
+
+        //Ten bytes in the last segment
+        let mut sender = RmppSender::new(&message(410));
+        assert_eq!(sender.segment_count(), 3);
+        let mut receiver = RmppReceiver::new(0x03).with_window(8);
+        let first = sender.next_segments().remove(0);
+        let RmppReply::Ack(ack) = receiver.receive(&first) else {
+            panic!("No ACK for the first segment");
+        };
+        sender.on_reply(&ack).unwrap();
+
+        let rest = sender.next_segments();
+        assert_eq!(rest.len(), 2);
+        let hdr = RmppHeader::from_mad(&rest[1]);
+        assert_eq!(hdr.flags, RMPP_FLAG_ACTIVE | RMPP_FLAG_LAST);
+        assert_eq!(hdr.paylen_newwin, 10 + 20);
+        assert_eq!(RmppHeader::from_mad(&rest[0]).paylen_newwin, 0);
+
+        //A message with no data still goes out as one segment
+        assert_eq!(RmppSender::new(&message(0)).segment_count(), 1);
+    }
+
+    #[test]
+    fn rmpp_reassembly_success() {
+        let original = message(2000);
+        let mut sender = RmppSender::new(&original);
+        let mut receiver = RmppReceiver::new(0x03).with_window(4);
+
+        let (mad, acks) = transfer(&mut sender, &mut receiver, &mut Vec::new());
+        assert_eq!(mad[..24], original[..24]);
+        assert_eq!(mad[36..], original[36..]);
+        assert_eq!(receiver.segments_received(), 10);
+
+        //ACKed at the end of each window
+        let acked: Vec<(u32, u32)> = acks.iter().map(|a| (a.seg_num, a.paylen_newwin)).collect();
+        assert_eq!(acked, vec![(1, 5), (5, 9), (9, 13), (10, 13)]);
+
+        //Marked as one segment with all the data
+        let hdr = RmppHeader::from_mad(&mad);
+        assert_eq!(hdr.flags, RMPP_FLAG_ACTIVE | RMPP_FLAG_FIRST | RMPP_FLAG_LAST);
+        assert_eq!(hdr.paylen_newwin, 2020);
+
+        //Short messages are padded to a full MAD
+        let mut sender = RmppSender::new(&message(30));
+        let mut receiver = RmppReceiver::new(0x03);
+        let (mad, _) = transfer(&mut sender, &mut receiver, &mut Vec::new());
+        assert_eq!(mad.len(), 256);
+        assert_eq!(&mad[36..86], &message(30)[36..]);
+    }
+
+    #[test]
+    fn rmpp_retransmit_success() {
+        let original = message(2000);
+        let mut sender = RmppSender::new(&original);
+        let mut receiver = RmppReceiver::new(0x03).with_window(4);
+
+        //Segment 3 is lost, 4 and 5 are out of order and dropped until the
+        //sender times out and goes back to 2
+        let (mad, acks) = transfer(&mut sender, &mut receiver, &mut vec![3, 7]);
+        assert_eq!(mad[36..], original[36..]);
+        assert_eq!(acks.first().map(|a| a.seg_num), Some(1));
+        assert_eq!(acks.last().map(|a| a.seg_num), Some(10));
+
+        //A segment seen before is ACKed again
+        let mut sender = RmppSender::new(&original);
+        let mut receiver = RmppReceiver::new(0x03);
+        let first = sender.next_segments().remove(0);
+        assert!(matches!(receiver.receive(&first), RmppReply::Ack(_)));
+        let RmppReply::Ack(ack) = receiver.receive(&first) else {
+            panic!("No ACK for a duplicate");
+        };
+        assert_eq!(RmppHeader::from_mad(&ack).seg_num, 1);
+        assert_eq!(receiver.segments_received(), 1);
+    }
+
+    #[test]
+    fn rmpp_abort_success() {
+        let mut sender = RmppSender::new(&message(1000));
+        let mut segment = sender.next_segments().remove(0);
+
+        let mut bad = segment.clone();
+        bad[24] = 2;
+        let RmppReply::Fail(Some(abort), e) = RmppReceiver::new(0x03).receive(&bad) else {
+            panic!("Bad version accepted");
+        };
+        assert_eq!(e, RmppError::Aborted(RMPP_STATUS_UNV));
+        let hdr = RmppHeader::from_mad(&abort);
+        assert_eq!((hdr.rmpp_type, hdr.status), (RMPP_TYPE_ABORT, RMPP_STATUS_UNV));
+        assert_eq!(&abort[..24], &segment[..24]);
+        assert_eq!(sender.on_reply(&abort), Err(RmppError::Aborted(RMPP_STATUS_UNV)));
+
+        //The first segment has to be flagged as such
+        let mut bad = segment.clone();
+        bad[26] &= !RMPP_FLAG_FIRST;
+        assert!(matches!(RmppReceiver::new(0x03).receive(&bad), RmppReply::Fail(_, RmppError::Aborted(RMPP_STATUS_BAD_SEG))));
+
+        //A last segment longer than a MAD
+        let mut bad = message(10);
+        bad.resize(256, 0);
+        let mut hdr = RmppHeader::from_mad(&RmppSender::new(&message(10)).next_segments()[0]);
+        hdr.paylen_newwin = 300;
+        hdr.write(&mut bad);
+        assert!(matches!(RmppReceiver::new(0x03).receive(&bad), RmppReply::Fail(_, RmppError::Aborted(RMPP_STATUS_BAD_LEN))));
+
+        //The sender stops the transfer
+        let mut receiver = RmppReceiver::new(0x03);
+        assert!(matches!(receiver.receive(&segment), RmppReply::Ack(_)));
+        let abort = sender.abort(RMPP_STATUS_UNSPEC);
+        assert_eq!(receiver.receive(&abort), RmppReply::Fail(None, RmppError::Aborted(RMPP_STATUS_UNSPEC)));
+
+        //And the receiver runs out of room
+        RmppHeader { version: RMPP_VERSION, rmpp_type: RMPP_TYPE_STOP, flags: RMPP_FLAG_ACTIVE, status: RMPP_STATUS_RESX, ..Default::default() }.write(&mut segment);
+        assert_eq!(sender.on_reply(&segment), Err(RmppError::Stopped(RMPP_STATUS_RESX)));
+    }
+
+    #[test]
+    fn rmpp_bad_ack_success() {
+        let mut sender = RmppSender::new(&message(1000));
+        let segment = sender.next_segments().remove(0);
+
+        //ACK of a segment that was never sent
+        let mut ack = segment.clone();
+        RmppHeader { version: RMPP_VERSION, rmpp_type: RMPP_TYPE_ACK, flags: RMPP_FLAG_ACTIVE, seg_num: 3, paylen_newwin: 5, ..Default::default() }.write(&mut ack);
+        assert_eq!(sender.on_reply(&ack), Err(RmppError::Aborted(RMPP_STATUS_S2B)));
+
+        //Window ending before the ACKed segment
+        RmppHeader { version: RMPP_VERSION, rmpp_type: RMPP_TYPE_ACK, flags: RMPP_FLAG_ACTIVE, seg_num: 1, paylen_newwin: 0, ..Default::default() }.write(&mut ack);
+        assert_eq!(sender.on_reply(&ack), Err(RmppError::Aborted(RMPP_STATUS_W2S)));
+    }
+
+    #[test]
+    fn rmpp_timeout_success() {
+        let mut sender = RmppSender::new(&message(1000)).with_retries(2);
+        let segment = sender.next_segments().remove(0);
+
+        //Each timeout resends the window, until the retries run out
+        for _ in 0..2 {
+            sender.on_timeout().unwrap();
+            assert_eq!(sender.next_segments(), vec![segment.clone()]);
+        }
+        assert_eq!(sender.on_timeout(), Err(RmppError::Timeout));
+
+        let mut receiver = RmppReceiver::new(0x03).with_timeout(Duration::ZERO);
+        assert_eq!(receiver.check_timeout(Instant::now()), None);
+        receiver.receive(&segment);
+
+        let Some(RmppReply::Fail(Some(abort), RmppError::Timeout)) = receiver.check_timeout(Instant::now()) else {
+            panic!("Transfer did not time out");
+        };
+        assert_eq!(RmppHeader::from_mad(&abort).status, RMPP_STATUS_T2L);
+
+        let receiver = RmppReceiver::new(0x03);
+        assert_eq!(receiver.check_timeout(Instant::now()), None);
+    }
+
+    //One switch with 60 CAs, too many node records for one MAD
+    fn simulator() -> Rc<Simulator> {
+        let mut t = Topology::new();
+        t.add_switch(SWITCH, "switch01", 64, 1);
+        for i in 1..=60 {
+            t.add_ca(HCA + i, &format!("node{:02} HCA-1", i), 1, 100 + i as u16);
+            t.link((HCA + i, 1), (SWITCH, i as u8));
+        }
+        Rc::new(Simulator::new(t, HCA + 1, 1).unwrap())
+    }
+
+    #[test]
+    fn sim_rmpp_table_success() {
+        let sim = simulator();
+        let sa = SaClient::new(sim.clone(), 101);
+
+        let records = sa.node_records().unwrap();
+        assert_eq!(records.len(), 61);
+        assert_eq!(records[0].node_desc, "switch01");
+        assert_eq!(records[60].lid, 160);
+        assert_eq!(records[60].node_desc, "node60 HCA-1");
+
+        assert_eq!(sa.port_info_records().unwrap().len(), 65 + 60);
+
+        //Lost segments are sent again
+        sim.drop_rmpp_segment(2);
+        sim.drop_rmpp_segment(30);
+        assert_eq!(sa.node_records().unwrap(), records);
+    }
+
+    #[test]
+    fn sim_rmpp_first_segment_success() {
+        let sim = simulator();
+        let sa = SaClient::new(sim.clone(), 101);
+        let request = rsmad::ibmad::transport::MadRequest::new(
+            rsmad::ibmad::transport::MadAddress::Lid(101),
+            rsmad::ibmad::sys::MAD_CLASSES_IB_SA_CLASS,
+            rsmad::ibmad::sys::MAD_METHODS_IB_MAD_METHOD_GET_TABLE,
+            rsmad::ibmad::sa::SA_ATTR_NODE_RECORD,
+            0,
+            sa.timeout,
+        );
+
+        let mut mad = request.encode(1);
+        let whole = sim.respond(&mad, 101).unwrap();
+        assert_eq!(whole.len(), 56 + 61 * 112);
+
+        sim.process_mad(&mut mad, 101).unwrap();
+        let hdr = RmppHeader::from_mad(&mad);
+        assert_eq!(hdr.flags, RMPP_FLAG_ACTIVE | RMPP_FLAG_FIRST);
+        assert_eq!(hdr.paylen_newwin as usize, 61 * 112 + 35 * 20);
+        assert_eq!(&mad[56..], &whole[56..256]);
+    }
+}
@@ -0,0 +1,150 @@
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use rsmad::ibmad::switch::LFT_NO_PORT;
+    use rsmad::ibnetdisc::discover::DiscoverConfig;
+    use rsmad::ibnetdisc::fabric::{Fabric, FabricError};
+    use rsmad::ibnetdisc::routing::{Hop, RouteProblem};
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::{Topology, PORT_PHYS_STATE_LINKUP, PORT_STATE_INIT};
+
+    const HCA1: u64 = 0x0002c90300a1b2c0;
+    const HCA2: u64 = 0x0002c90300a1b3d0;
+    const HCA3: u64 = 0x0002c90300a1b4e0;
+    const LEAF1: u64 = 0xfc6a1c0300e4a800;
+    const LEAF2: u64 = 0xfc6a1c0300e4a900;
+    const SPINE: u64 = 0xfc6a1c0300e4b900;
+
+    //hca1 -- [1] leaf01 [7,8] == [1,2] spine01 [3] -- [7] leaf02 [1] -- hca2
+    //hca3 is dual ported, port 1 on leaf01 and port 2 on leaf02
+    fn simulator() -> Rc<Simulator> {
+        let mut t = Topology::new();
+        t.add_ca(HCA1, "node01 HCA-1", 1, 10);
+        t.add_ca(HCA2, "node02 HCA-1", 1, 100).port_mut(1).lmc = 1;
+        t.add_ca(HCA3, "node03 HCA-1", 2, 20);
+        t.add_switch(LEAF1, "leaf01", 8, 2);
+        t.add_switch(LEAF2, "leaf02", 8, 3);
+        t.add_switch(SPINE, "spine01", 8, 1);
+        t.link((HCA1, 1), (LEAF1, 1));
+        t.link((HCA2, 1), (LEAF2, 1));
+        t.link((HCA3, 1), (LEAF1, 2));
+        t.link((HCA3, 2), (LEAF2, 2));
+        t.link((LEAF1, 7), (SPINE, 1));
+        t.link((LEAF1, 8), (SPINE, 2));
+        t.link((LEAF2, 7), (SPINE, 3));
+        Rc::new(Simulator::new(t, HCA1, 1).unwrap())
+    }
+
+    fn fabric(sim: &Rc<Simulator>) -> Fabric {
+        let mut fabric = Fabric::from_transport("sim0", sim.clone());
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+        assert!(fabric.fetch_lfts(100).is_empty());
+        fabric
+    }
+
+    fn hop(guid: u64, in_port: Option<u8>, out_port: Option<u8>) -> Hop {
+        Hop { guid, in_port, out_port }
+    }
+
+    #[test]
+    fn trace_route_success() {
+        let fabric = fabric(&simulator());
+
+        let route = fabric.trace_route(10, 101).unwrap();
+        assert!(route.is_complete(), "Unexpected problem: {:?}", route.problem);
+        assert_eq!(route.hops, vec![
+            hop(HCA1, None, Some(1)),
+            hop(LEAF1, Some(1), Some(7)),
+            hop(SPINE, Some(1), Some(3)),
+            hop(LEAF2, Some(7), Some(1)),
+            hop(HCA2, Some(1), None),
+        ]);
+
+        //And back, to a switch
+        let route = fabric.trace_route(101, 2).unwrap();
+        assert!(route.is_complete());
+        assert_eq!(route.hops.len(), 4);
+        assert_eq!(route.hops[3], hop(LEAF1, Some(7), None));
+    }
+
+    #[test]
+    fn trace_route_dual_port_success() {
+        let fabric = fabric(&simulator());
+
+        //The second port of hca3 is only reached through leaf02
+        let route = fabric.trace_route(10, 21).unwrap();
+        assert!(route.is_complete());
+        assert_eq!(route.hops.last(), Some(&hop(HCA3, Some(2), None)));
+        assert_eq!(route.hops.len(), 5);
+
+        let route = fabric.trace_route(20, 20).unwrap();
+        assert_eq!(route.hops, vec![hop(HCA3, None, None)]);
+    }
+
+    #[test]
+    fn trace_route_missing_entry_success() {
+        let sim = simulator();
+        sim.set_lft_entry(SPINE, 100, LFT_NO_PORT).unwrap();
+        let fabric = fabric(&sim);
+
+        let route = fabric.trace_route(10, 100).unwrap();
+        assert_eq!(route.problem, Some(RouteProblem::MissingLftEntry { guid: SPINE, lid: 100 }));
+        assert_eq!(route.hops.last(), Some(&hop(SPINE, Some(1), None)));
+
+        //The other LMC LID is still routed
+        assert!(fabric.trace_route(10, 101).unwrap().is_complete());
+    }
+
+    #[test]
+    fn trace_route_loop_success() {
+        let sim = simulator();
+        sim.set_lft_entry(SPINE, 100, 2).unwrap();
+        let fabric = fabric(&sim);
+
+        let route = fabric.trace_route(10, 100).unwrap();
+        assert_eq!(route.problem, Some(RouteProblem::Loop { guid: LEAF1 }));
+        assert_eq!(route.hops, vec![
+            hop(HCA1, None, Some(1)),
+            hop(LEAF1, Some(1), Some(7)),
+            hop(SPINE, Some(1), Some(2)),
+            hop(LEAF1, Some(8), None),
+        ]);
+    }
+
+    #[test]
+    fn trace_route_port_not_active_success() {
+        let sim = simulator();
+        sim.set_port_state(LEAF2, 1, PORT_STATE_INIT, PORT_PHYS_STATE_LINKUP).unwrap();
+        let fabric = fabric(&sim);
+
+        let route = fabric.trace_route(10, 100).unwrap();
+        assert_eq!(route.problem, Some(RouteProblem::PortNotActive { guid: LEAF2, port: 1 }));
+        assert_eq!(route.hops.last(), Some(&hop(LEAF2, Some(7), Some(1))));
+    }
+
+    #[test]
+    fn trace_route_wrong_destination_success() {
+        let sim = simulator();
+        //hca3 port 2 LID sent to hca3 port 1
+        sim.set_lft_entry(LEAF1, 21, 2).unwrap();
+        let fabric = fabric(&sim);
+
+        let route = fabric.trace_route(10, 21).unwrap();
+        assert_eq!(route.problem, Some(RouteProblem::WrongDestination { guid: HCA3 }));
+    }
+
+    #[test]
+    fn trace_route_failed_success() {
+        let sim = simulator();
+        let mut fabric = Fabric::from_transport("sim0", sim);
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+
+        let route = fabric.trace_route(10, 100).unwrap();
+        assert_eq!(route.problem, Some(RouteProblem::NoForwardingTable { guid: LEAF1 }));
+
+        assert!(matches!(fabric.trace_route(10, 999), Err(FabricError::LidNotFound)));
+        assert!(matches!(fabric.trace_route(999, 10), Err(FabricError::LidNotFound)));
+    }
+}
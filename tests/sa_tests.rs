@@ -0,0 +1,265 @@
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use rsmad::ibmad::IBSmpError;
+    use rsmad::ibmad::sa::*;
+    use rsmad::ibmad::switch::LFT_NO_PORT;
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::{Topology, PORT_PHYS_STATE_LINKUP, PORT_STATE_ACTIVE, PORT_STATE_INIT};
+
+    const HCA1: u64 = 0x0002c90300a1b2c0;
+    const HCA2: u64 = 0x0002c90300a1b3d0;
+    const HCA3: u64 = 0x0002c90300a1b4e0;
+    const LEAF1: u64 = 0xfc6a1c0300e4a800;
+    const LEAF2: u64 = 0xfc6a1c0300e4a900;
+    const SPINE: u64 = 0xfc6a1c0300e4b900;
+
+    //hca1 -- [1] leaf01 [7,8] == [1,2] spine01 [3] -- [7] leaf02 [1] -- hca2
+    //hca3 is dual ported, port 1 on leaf01 and port 2 on leaf02
+    fn simulator() -> Rc<Simulator> {
+        let mut t = Topology::new();
+        t.add_ca(HCA1, "node01 HCA-1", 1, 10);
+        t.add_ca(HCA2, "node02 HCA-1", 1, 100).port_mut(1).lmc = 1;
+        t.add_ca(HCA3, "node03 HCA-1", 2, 20);
+        t.add_switch(LEAF1, "leaf01", 8, 2);
+        t.add_switch(LEAF2, "leaf02", 8, 3);
+        t.add_switch(SPINE, "spine01", 8, 1);
+        t.link((HCA1, 1), (LEAF1, 1));
+        t.link((HCA2, 1), (LEAF2, 1));
+        t.link((HCA3, 1), (LEAF1, 2));
+        t.link((HCA3, 2), (LEAF2, 2));
+        t.link((LEAF1, 7), (SPINE, 1));
+        t.link((LEAF1, 8), (SPINE, 2));
+        t.link((LEAF2, 7), (SPINE, 3));
+        Rc::new(Simulator::new(t, HCA1, 1).unwrap())
+    }
+
+    fn gid(guid: u64) -> Gid {
+        let mut gid = [0; 16];
+        gid[..8].copy_from_slice(&0xfe80_0000_0000_0000_u64.to_be_bytes());
+        gid[8..].copy_from_slice(&guid.to_be_bytes());
+        gid
+    }
+
+    #[test]
+    fn sa_node_record_success() {
+        let sa = SaClient::new(simulator(), 10);
+
+        let record = sa.node_record(10).unwrap();
+        assert_eq!(record.lid, 10);
+        assert_eq!(record.node_desc, "node01 HCA-1");
+        assert_eq!(record.node_info.guid, HCA1);
+        assert_eq!(record.node_info.port_guid, HCA1 + 1);
+        assert_eq!(record.node_info.local_port, 1);
+        assert_eq!(record.node_info.node_type, 1);
+
+        //The second port of hca3
+        let record = sa.node_record(21).unwrap();
+        assert_eq!(record.node_info.port_guid, HCA3 + 2);
+        assert_eq!(record.node_info.local_port, 2);
+
+        let record = sa.node_record(3).unwrap();
+        assert_eq!(record.node_desc, "leaf02");
+        assert_eq!(record.node_info.node_type, 2);
+        assert_eq!(record.node_info.num_ports, 8);
+
+        let r = sa.node_record(999);
+        assert!(matches!(r, Err(IBSmpError::MadStatusError(SA_STATUS_NO_RECORDS))), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn sa_comp_mask_success() {
+        let sa = SaClient::new(simulator(), 10);
+
+        let template = NodeRecord { node_desc: "spine01".to_string(), ..Default::default() };
+        let records = sa.get_table(&template, NodeRecord::COMP_NODE_DESC).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].lid, 1);
+        assert_eq!(records[0].node_info.guid, SPINE);
+
+        //Both ports of hca3 match its node GUID
+        let mut template = NodeRecord::default();
+        template.node_info.guid = HCA3;
+        let r = sa.get(&template, NodeRecord::COMP_NODE_GUID);
+        assert!(matches!(r, Err(IBSmpError::MadStatusError(SA_STATUS_TOO_MANY_RECORDS))), "Unexpected result: {:?}", r);
+
+        template.node_info.port_guid = HCA3 + 2;
+        let record = sa.get(&template, NodeRecord::COMP_NODE_GUID | NodeRecord::COMP_PORT_GUID).unwrap();
+        assert_eq!(record.lid, 21);
+
+        let template = NodeRecord { lid: 999, ..Default::default() };
+        assert!(sa.get_table(&template, NodeRecord::COMP_LID).unwrap().is_empty());
+    }
+
+    #[test]
+    fn sa_port_info_record_success() {
+        let sim = simulator();
+        sim.set_port_state(LEAF1, 8, PORT_STATE_INIT, PORT_PHYS_STATE_LINKUP).unwrap();
+        let sa = SaClient::new(sim, 10);
+
+        let record = sa.port_info_record(2, 7).unwrap();
+        assert_eq!(record.lid, 2);
+        assert_eq!(record.port_num, 7);
+        assert_eq!(record.port_info.state, PORT_STATE_ACTIVE);
+        assert_eq!(record.port_info.sm_lid, 10);
+
+        let record = sa.port_info_record(2, 8).unwrap();
+        assert_eq!(record.port_info.state, PORT_STATE_INIT);
+
+        let record = sa.port_info_record(100, 1).unwrap();
+        assert_eq!(record.port_info.lid, 100);
+        assert_eq!(record.port_info.lmc, 1);
+
+        assert!(sa.port_info_record(10, 2).is_err());
+    }
+
+    #[test]
+    fn sa_link_records_success() {
+        let sim = simulator();
+        let sa = SaClient::new(sim.clone(), 10);
+
+        let records = sa.link_records().unwrap();
+        assert_eq!(records.len(), 14);
+        assert!(records.contains(&LinkRecord { from_lid: 10, from_port: 1, to_port: 1, to_lid: 2 }));
+        assert!(records.contains(&LinkRecord { from_lid: 2, from_port: 1, to_port: 1, to_lid: 10 }));
+        assert!(records.contains(&LinkRecord { from_lid: 3, from_port: 7, to_port: 3, to_lid: 1 }));
+        assert!(records.contains(&LinkRecord { from_lid: 21, from_port: 2, to_port: 2, to_lid: 3 }));
+
+        //Only links with both ends Active are listed
+        sim.set_port_state(LEAF1, 8, PORT_STATE_INIT, PORT_PHYS_STATE_LINKUP).unwrap();
+        let records = sa.link_records().unwrap();
+        assert_eq!(records.len(), 12);
+        assert!(!records.iter().any(|r| r.from_lid == 2 && r.from_port == 8));
+
+        let template = LinkRecord { from_lid: 2, ..Default::default() };
+        let records = sa.get_table(&template, LinkRecord::COMP_FROM_LID).unwrap();
+        assert_eq!(records.iter().map(|r| r.from_port).collect::<Vec<u8>>(), vec![1, 2, 7]);
+    }
+
+    #[test]
+    fn sa_path_records_success() {
+        let sim = simulator();
+        let sa = SaClient::new(sim.clone(), 10);
+
+        let records = sa.path_records(10, 101).unwrap();
+        assert_eq!(records.len(), 1);
+        let path = &records[0];
+        assert_eq!((path.slid, path.dlid), (10, 101));
+        assert_eq!(path.sgid, gid(HCA1 + 1));
+        assert_eq!(path.dgid, gid(HCA2 + 1));
+        assert_eq!(path.pkey, 0xffff);
+        assert_eq!(path.mtu, 5);
+        assert!(path.reversible);
+
+        //To a switch
+        let records = sa.path_records(21, 1).unwrap();
+        assert_eq!(records[0].dgid, gid(SPINE));
+
+        assert!(sa.path_records(10, 999).unwrap().is_empty());
+
+        sim.set_lft_entry(SPINE, 100, LFT_NO_PORT).unwrap();
+        assert!(sa.path_records(10, 100).unwrap().is_empty());
+        assert_eq!(sa.path_records(10, 101).unwrap().len(), 1);
+
+        let template = PathRecord { dlid: 100, ..Default::default() };
+        let r = sa.get_table(&template, PathRecord::COMP_DLID);
+        assert!(matches!(r, Err(IBSmpError::MadStatusError(SA_STATUS_INSUFFICIENT_COMPONENTS))), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn sa_sm_info_success() {
+        let sim = simulator();
+
+        let records = SaClient::new(sim.clone(), 10).sm_info_records().unwrap();
+        assert_eq!(records, vec![SmInfoRecord { lid: 10, guid: HCA1 + 1, sm_state: 3, ..Default::default() }]);
+
+        //The SA moves with the SM
+        sim.set_sm(LEAF2, 0).unwrap();
+        assert!(matches!(SaClient::new(sim.clone(), 10).sm_info_records(), Err(IBSmpError::SendMADError)));
+
+        let sa = SaClient::new(sim, 3);
+        let records = sa.sm_info_records().unwrap();
+        assert_eq!(records[0].lid, 3);
+        assert_eq!(records[0].guid, LEAF2);
+        assert_eq!(sa.port_info_record(20, 1).unwrap().port_info.sm_lid, 3);
+    }
+
+    #[test]
+    fn sa_guid_pkey_records_success() {
+        let sa = SaClient::new(simulator(), 10);
+
+        let records = sa.guid_info_records(21).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].guids[0], HCA3 + 2);
+        assert_eq!(records[0].guids[1], 0);
+
+        let records = sa.pkey_table_records(21).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].port_num, 2);
+        assert_eq!(records[0].pkeys[0], 0xffff);
+
+        let records = sa.pkey_table_records(1).unwrap();
+        assert_eq!(records[0].port_num, 0);
+
+        assert!(sa.mcmember_records().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sa_record_codec_success() {
+        let path = PathRecord {
+            service_id: 0x1122334455667788,
+            dgid: gid(HCA2),
+            sgid: gid(HCA1),
+            dlid: 100,
+            slid: 10,
+            raw_traffic: true,
+            flow_label: 0xabcde,
+            hop_limit: 64,
+            tclass: 3,
+            reversible: true,
+            num_path: 4,
+            pkey: 0x8001,
+            qos_class: 0x123,
+            sl: 5,
+            mtu_selector: 2,
+            mtu: 4,
+            rate_selector: 1,
+            rate: 16,
+            pkt_life_selector: 2,
+            pkt_life: 18,
+            preference: 1,
+        };
+
+        let mut data = [0; 64];
+        path.encode(&mut data);
+        assert_eq!(data[40..44], [0, 100, 0, 10]);
+        assert_eq!(PathRecord::decode(&data), path);
+
+        let member = McMemberRecord {
+            mgid: gid(1),
+            port_gid: gid(HCA1 + 1),
+            qkey: 0x80010000,
+            mlid: 0xc001,
+            pkey: 0xffff,
+            sl: 2,
+            scope: 2,
+            join_state: 1,
+            proxy_join: true,
+            ..Default::default()
+        };
+        let mut data = [0; 52];
+        member.encode(&mut data);
+        assert_eq!(McMemberRecord::decode(&data), member);
+
+        //Records are padded to the attribute offset
+        let mut table = vec![0; 3 * 16];
+        for (i, chunk) in table.chunks_exact_mut(16).enumerate() {
+            LinkRecord { from_lid: i as u16 + 1, from_port: 1, to_port: 2, to_lid: 7 }.encode(chunk);
+        }
+        let records: Vec<LinkRecord> = records_from_table(&table, 2);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].from_lid, 3);
+    }
+}
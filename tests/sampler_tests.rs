@@ -0,0 +1,149 @@
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, rc::Rc, thread, time::{Duration, Instant}};
+
+    use rsmad::ibmad::perf::ExtPerfCounters;
+    use rsmad::ibnetdisc::discover::DiscoverConfig;
+    use rsmad::ibnetdisc::fabric::Fabric;
+    use rsmad::ibnetdisc::sampler::{FabricSampler, PortRates, PortSample};
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::{PORT_PHYS_STATE_LINKUP, PORT_PHYS_STATE_POLLING, PORT_STATE_ACTIVE, PORT_STATE_DOWN};
+
+    use super::common::{TestFabric, HCA2, LEAF, SPINE};
+
+    //hca1 -- [1] leaf [3] -- [5] spine
+    //          leaf [2] -- hca2
+    fn fabric() -> (Rc<Simulator>, Fabric) {
+        TestFabric::new().with_ca(HCA2, "node02 HCA-1", 11).with_link((HCA2, 1), (LEAF, 2)).build()
+    }
+
+    fn sample(time: Instant, counters: ExtPerfCounters) -> PortSample {
+        PortSample { node_guid: LEAF, port: (LEAF, 1), lid: 2, time, counters, legacy: false, rates: None }
+    }
+
+    fn legacy(time: Instant, counters: ExtPerfCounters) -> PortSample {
+        PortSample { legacy: true, ..sample(time, counters) }
+    }
+
+    #[test]
+    fn sampler_rates_success() {
+        let (sim, fabric) = fabric();
+        let mut sampler = FabricSampler::new();
+
+        let first = sampler.sample(&fabric);
+        assert!(first.failures.is_empty(), "Unexpected failures: {:?}", first.failures);
+        assert_eq!(first.samples.len(), 6);
+        assert!(first.samples.iter().all(|s| s.rates.is_none()));
+
+        sim.set_counters(SPINE, 5, &HashMap::from([("xmt_bytes".to_string(), 1000), ("xmit_waits".to_string(), 30)])).unwrap();
+        thread::sleep(Duration::from_millis(20));
+
+        let second = sampler.sample(&fabric);
+        let spine = second.samples.iter().find(|s| s.port == (SPINE, 5)).unwrap();
+        assert_eq!(spine.node_guid, SPINE);
+        assert_eq!(spine.lid, 1);
+
+        let rates = spine.rates.as_ref().unwrap();
+        let secs = rates.interval.as_secs_f64();
+        assert!(rates.interval >= Duration::from_millis(20));
+        //Counted in 4 byte words
+        assert_eq!(rates.xmt_bytes, 4000.0 / secs);
+        assert_eq!(rates.xmit_waits, 30.0 / secs);
+        assert_eq!(rates.rcv_pkts, 0.0);
+        assert!(!rates.reset);
+    }
+
+    #[test]
+    fn sampler_reset_success() {
+        let t0 = Instant::now();
+        let before = sample(t0, ExtPerfCounters { xmt_bytes: 1000, rcv_pkts: 500, ..Default::default() });
+        let after = sample(t0 + Duration::from_secs(2), ExtPerfCounters { xmt_bytes: 1500, rcv_pkts: 700, ..Default::default() });
+
+        let rates = PortRates::between(&before, &after).unwrap();
+        assert_eq!(rates.xmt_bytes, 1000.0);
+        assert_eq!(rates.rcv_pkts, 100.0);
+        assert!(!rates.reset);
+
+        //Cleared in between, only what came after the reset is counted
+        let cleared = sample(t0 + Duration::from_secs(2), ExtPerfCounters { xmt_bytes: 100, rcv_pkts: 20, ..Default::default() });
+        let rates = PortRates::between(&before, &cleared).unwrap();
+        assert!(rates.reset);
+        assert_eq!(rates.xmt_bytes, 200.0);
+        assert_eq!(rates.rcv_pkts, 10.0);
+
+        //Only xmit_waits was cleared, the other counters keep their deltas
+        let before = sample(t0, ExtPerfCounters { xmt_bytes: 1000, xmit_waits: 300, ..Default::default() });
+        let partial = sample(t0 + Duration::from_secs(2), ExtPerfCounters { xmt_bytes: 1500, xmit_waits: 40, ..Default::default() });
+        let rates = PortRates::between(&before, &partial).unwrap();
+        assert!(rates.reset);
+        assert_eq!(rates.xmt_bytes, 1000.0);
+        assert_eq!(rates.xmit_waits, 20.0);
+        assert!(rates.saturated.is_empty());
+
+        assert!(PortRates::between(&after, &before).is_none());
+        assert!(PortRates::between(&before, &before).is_none());
+    }
+
+    #[test]
+    fn sampler_saturated_success() {
+        let t0 = Instant::now();
+        let max = u32::MAX as u64;
+        let first = ExtPerfCounters { xmt_bytes: max - 100, rcv_bytes: max, rcv_pkts: 10, ..Default::default() };
+        let second = ExtPerfCounters { xmt_bytes: max, rcv_bytes: max, rcv_pkts: 30, ..Default::default() };
+
+        //Legacy counters stop at the maximum, what went past it is unknown
+        let rates = PortRates::between(&legacy(t0, first), &legacy(t0 + Duration::from_secs(1), second)).unwrap();
+        assert_eq!(rates.saturated, vec!["xmt_bytes", "rcv_bytes"]);
+        assert!(rates.xmt_bytes.is_nan());
+        assert!(rates.rcv_bytes.is_nan());
+        assert_eq!(rates.rcv_pkts, 20.0);
+        assert!(!rates.reset);
+
+        //64 bit counters just pass through the same values
+        let rates = PortRates::between(&sample(t0, first), &sample(t0 + Duration::from_secs(1), second)).unwrap();
+        assert!(rates.saturated.is_empty());
+        assert_eq!((rates.xmt_bytes, rates.rcv_bytes), (400.0, 0.0));
+    }
+
+    #[test]
+    fn sampler_ports_change_success() {
+        let (sim, mut fabric) = fabric();
+        let mut sampler = FabricSampler::new();
+        assert_eq!(sampler.sample(&fabric).samples.len(), 6);
+
+        sim.set_port_state(HCA2, 1, PORT_STATE_DOWN, PORT_PHYS_STATE_POLLING).unwrap();
+        sim.set_port_state(LEAF, 2, PORT_STATE_DOWN, PORT_PHYS_STATE_POLLING).unwrap();
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+
+        let round = sampler.sample(&fabric);
+        assert_eq!(round.samples.len(), 4);
+        assert!(round.samples.iter().all(|s| s.rates.is_some()));
+        assert!(!sampler.last().contains_key(&(LEAF, 2)));
+
+        sim.set_port_state(HCA2, 1, PORT_STATE_ACTIVE, PORT_PHYS_STATE_LINKUP).unwrap();
+        sim.set_port_state(LEAF, 2, PORT_STATE_ACTIVE, PORT_PHYS_STATE_LINKUP).unwrap();
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+
+        //Back, but with nothing to compute rates from
+        let round = sampler.sample(&fabric);
+        assert_eq!(round.samples.len(), 6);
+        let back: Vec<_> = round.samples.iter().filter(|s| s.rates.is_none()).map(|s| s.node_guid).collect();
+        assert_eq!(back.len(), 2);
+        assert!(back.contains(&HCA2) && back.contains(&LEAF));
+    }
+
+    #[test]
+    fn sampler_interval_success() {
+        let (_sim, fabric) = fabric();
+        let mut sampler = FabricSampler::new().with_interval(Duration::from_millis(30));
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            sampler.next_round(&fabric);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(60));
+    }
+}
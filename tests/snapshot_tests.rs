@@ -0,0 +1,85 @@
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use rsmad::ibnetdisc::fabric::Fabric;
+    use rsmad::ibnetdisc::node::NodeType;
+    use rsmad::ibnetdisc::snapshot::{FabricSnapshot, SnapshotError};
+
+    use super::common::{TestFabric, HCA2, LEAF, SPINE};
+
+    //hca1 -- [1] leaf [3] -- [5] spine [2] -- hca2
+    fn fabric() -> Fabric {
+        let fabric = TestFabric::new().with_ca(HCA2, "node02 HCA-1", 11).with_link((SPINE, 2), (HCA2, 1)).fabric();
+        assert!(fabric.fetch_lfts(100).is_empty());
+        fabric
+    }
+
+    #[test]
+    fn snapshot_round_trip_success() {
+        let fabric = fabric();
+        let json = FabricSnapshot::from_fabric(&fabric).to_json().unwrap();
+
+        let loaded = FabricSnapshot::from_json(&json).unwrap().to_fabric();
+        assert_eq!(FabricSnapshot::from_fabric(&loaded).to_json().unwrap(), json);
+
+        assert_eq!(loaded.hca_name, "sim0");
+        assert_eq!((loaded.nodes.len(), loaded.ports.len()), (fabric.nodes.len(), fabric.ports.len()));
+        assert_eq!((loaded.adapters.len(), loaded.switches.len()), (2, 2));
+
+        let leaf = loaded.nodes[&LEAF].borrow();
+        assert!(matches!(leaf.node_type, NodeType::SWITCH));
+        assert_eq!((leaf.lid, leaf.num_ports, leaf.node_desc.as_str()), (2, 8, "leaf01"));
+        assert_eq!(leaf.switch_info, fabric.nodes[&LEAF].borrow().switch_info);
+        assert_eq!(leaf.lft, fabric.nodes[&LEAF].borrow().lft);
+        assert!(leaf.lft.is_some());
+
+        //The graph is rebuilt, down to the back pointers
+        let ports = leaf.ports.as_ref().unwrap();
+        let port = ports.iter().find(|p| p.borrow().number == 3).unwrap().borrow();
+        assert!(Rc::ptr_eq(&port.parent.as_ref().unwrap().upgrade().unwrap(), &loaded.nodes[&LEAF]));
+        assert!(Rc::ptr_eq(&port.remote_node.as_ref().unwrap().upgrade().unwrap(), &loaded.nodes[&SPINE]));
+        let remote = port.remote_port.as_ref().unwrap().upgrade().unwrap();
+        assert!(Rc::ptr_eq(&remote, &loaded.ports[&(SPINE, 5)]));
+        assert_eq!(port.info, fabric.ports[&(LEAF, 3)].borrow().info);
+
+        //Routing works offline as it did on the fabric
+        let route = loaded.trace_route(10, 11).unwrap();
+        assert_eq!(route.hops.len(), fabric.trace_route(10, 11).unwrap().hops.len());
+    }
+
+    #[test]
+    fn snapshot_file_success() {
+        let snapshot = FabricSnapshot::from_fabric(&fabric());
+        let path = std::env::temp_dir().join(format!("rsmad-snapshot-{}.json", std::process::id()));
+        snapshot.save(&path).unwrap();
+
+        let loaded = FabricSnapshot::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().to_json().unwrap(), snapshot.to_json().unwrap());
+
+        assert!(matches!(FabricSnapshot::load("/nonexistent/snapshot.json"), Err(SnapshotError::ReadError(_))));
+        assert!(matches!(FabricSnapshot::from_json("{\"nodes\": 1}"), Err(SnapshotError::ParseError(_))));
+    }
+
+    #[test]
+    fn snapshot_hex_guids_success() {
+        let json = FabricSnapshot::from_fabric(&fabric()).to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let guid = |guid: u64| format!("0x{:016x}", guid);
+
+        assert_eq!(value["nodes"][2]["guid"], guid(LEAF));
+        assert!(value["nodes"][2]["ports"].as_array().unwrap().contains(&serde_json::json!([guid(LEAF), 3])));
+        assert_eq!(value["switches"], serde_json::json!([guid(LEAF), guid(SPINE)]));
+
+        let leaf3 = value["ports"].as_array().unwrap().iter().find(|p| p["key"] == serde_json::json!([guid(LEAF), 3])).unwrap();
+        assert_eq!(leaf3["port"]["parent"], guid(LEAF));
+        assert_eq!(leaf3["port"]["remote_node"], guid(SPINE));
+        assert_eq!(leaf3["port"]["remote_port"], serde_json::json!([guid(SPINE), 5]));
+
+        assert!(matches!(FabricSnapshot::from_json(&json.replace(&guid(LEAF), "leaf01")), Err(SnapshotError::ParseError(_))));
+    }
+}
@@ -0,0 +1,159 @@
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use rsmad::ibmad::IBSmpError;
+    use rsmad::ibmad::switch::{LinearForwardingTable, SwitchInfo, LFT_NO_PORT};
+    use rsmad::ibnetdisc::discover::DiscoverConfig;
+    use rsmad::ibnetdisc::fabric::{Fabric, FabricError};
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::Topology;
+
+    const HCA1: u64 = 0x0002c90300a1b2c0;
+    const HCA2: u64 = 0x0002c90300a1b3d0;
+    const LEAF1: u64 = 0xfc6a1c0300e4a800;
+    const LEAF2: u64 = 0xfc6a1c0300e4a900;
+    const SPINE: u64 = 0xfc6a1c0300e4b900;
+
+    //hca1 -- [1] leaf01 [7,8] == [1,2] spine01 [3] -- [7] leaf02 [1] -- hca2
+    fn simulator() -> Simulator {
+        let mut t = Topology::new();
+        t.add_ca(HCA1, "node01 HCA-1", 1, 10);
+        t.add_ca(HCA2, "node02 HCA-1", 1, 100).port_mut(1).lmc = 2;
+        t.add_switch(LEAF1, "leaf01", 8, 2);
+        t.add_switch(LEAF2, "leaf02", 8, 3);
+        t.add_switch(SPINE, "spine01", 8, 1);
+        t.link((HCA1, 1), (LEAF1, 1));
+        t.link((HCA2, 1), (LEAF2, 1));
+        t.link((LEAF1, 7), (SPINE, 1));
+        t.link((LEAF1, 8), (SPINE, 2));
+        t.link((LEAF2, 7), (SPINE, 3));
+        Simulator::new(t, HCA1, 1).unwrap()
+    }
+
+    #[test]
+    fn switch_info_success() {
+        let sim = simulator();
+
+        let info = rsmad::ibmad::send_dr_switch_info_mad(&sim, "0,1,7", 100).unwrap();
+        assert_eq!(info.linear_fdb_cap, 0xc000);
+        assert_eq!(info.multicast_fdb_cap, 0x1000);
+        //hca2 owns 100-103
+        assert_eq!(info.linear_fdb_top, 103);
+        assert_eq!(info.lft_blocks(), 2);
+        assert_eq!(info.lifetime, 18);
+        assert!(info.enhanced_port0);
+        assert!(!info.port_state_change);
+
+        assert_eq!(rsmad::ibmad::send_lid_switch_info_mad(&sim, 3, 100).unwrap(), info);
+
+        let r = rsmad::ibmad::send_lid_switch_info_mad(&sim, 10, 100);
+        assert!(matches!(r, Err(IBSmpError::MadStatusError(_))), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn lft_blocks_success() {
+        let sim = simulator();
+
+        let block = rsmad::ibmad::send_lid_lft_block_mad(&sim, 2, 0, 100).unwrap();
+        assert_eq!(block.len(), 64);
+        assert_eq!(block[0], LFT_NO_PORT);
+        assert_eq!(block[1], 7);
+        assert_eq!(block[2], 0);
+        assert_eq!(block[3], 7);
+        assert_eq!(block[10], 1);
+        assert_eq!(block[11], LFT_NO_PORT);
+
+        let block = rsmad::ibmad::send_lid_lft_block_mad(&sim, 2, 1, 100).unwrap();
+        assert_eq!(&block[100 - 64..104 - 64], &[7, 7, 7, 7]);
+        assert_eq!(block[104 - 64], LFT_NO_PORT);
+    }
+
+    #[test]
+    fn lft_read_success() {
+        let sim = simulator();
+
+        let (info, lft) = rsmad::ibmad::send_dr_lft_mad(&sim, "0,1,7", 100).unwrap();
+        assert_eq!(lft.top(), info.linear_fdb_top);
+        assert_eq!(lft.egress_port(2), Some(1));
+        assert_eq!(lft.egress_port(3), Some(3));
+        assert_eq!(lft.egress_port(1), Some(0));
+        assert_eq!(lft.egress_port(10), Some(1));
+        assert_eq!(lft.egress_port(50), None);
+        assert_eq!(lft.egress_port(500), None);
+
+        //Both LMC LIDs of hca2
+        let routes: Vec<(u16, u8)> = lft.routes().collect();
+        assert_eq!(routes, vec![(1, 0), (2, 1), (3, 3), (10, 1), (100, 3), (101, 3), (102, 3), (103, 3)]);
+
+        assert_eq!(rsmad::ibmad::send_lid_lft_mad(&sim, 1, 100).unwrap().1, lft);
+    }
+
+    #[test]
+    fn lft_from_blocks_success() {
+        let blocks = vec![vec![LFT_NO_PORT; 64], vec![3; 64]];
+        let lft = LinearForwardingTable::from_blocks(&blocks, 70);
+
+        assert_eq!(lft.ports.len(), 71);
+        assert_eq!(lft.egress_port(63), None);
+        assert_eq!(lft.egress_port(70), Some(3));
+        assert_eq!(lft.egress_port(71), None);
+
+        let info = SwitchInfo { linear_fdb_cap: 48, linear_fdb_top: 100, ..Default::default() };
+        assert_eq!(info.lft_blocks(), 1);
+    }
+
+    #[test]
+    fn fabric_lft_dump_success() {
+        let sim = Rc::new(simulator());
+        let mut fabric = Fabric::from_transport("sim0", sim);
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+
+        //SwitchInfo comes with discovery, the LFT has to be fetched
+        let leaf = fabric.nodes[&LEAF1].clone();
+        assert_eq!(leaf.borrow().switch_info.as_ref().unwrap().linear_fdb_top, 103);
+        assert!(leaf.borrow().lft.is_none());
+        assert!(matches!(fabric.dump_lft(LEAF1), Err(FabricError::NoForwardingTable)));
+
+        assert!(fabric.fetch_lfts(100).is_empty());
+        assert_eq!(leaf.borrow().lft.as_ref().unwrap().egress_port(100), Some(7));
+        assert!(fabric.nodes[&HCA1].borrow().lft.is_none());
+
+        let dump = fabric.dump_lft(LEAF1).unwrap();
+        let expected = format!(
+            "Unicast lids [0x0-0x67] of switch Lid 2 guid 0x{:016x} (leaf01):\n\
+             \x20 Lid  Out   Destination\n\
+             \x20      Port     Info \n\
+             0x0001 007 : (Switch portguid 0x{:016x}: 'spine01')\n\
+             0x0002 000 : (Switch portguid 0x{:016x}: 'leaf01')\n\
+             0x0003 007 : (Switch portguid 0x{:016x}: 'leaf02')\n\
+             0x000a 001 : (Channel Adapter portguid 0x{:016x}: 'node01 HCA-1')\n\
+             0x0064 007 : (Channel Adapter portguid 0x{:016x}: 'node02 HCA-1')\n\
+             0x0065 007 : (Channel Adapter portguid 0x{:016x}: 'node02 HCA-1')\n\
+             0x0066 007 : (Channel Adapter portguid 0x{:016x}: 'node02 HCA-1')\n\
+             0x0067 007 : (Channel Adapter portguid 0x{:016x}: 'node02 HCA-1')\n\
+             8 valid lids dumped \n",
+            LEAF1, SPINE, LEAF1, LEAF2, HCA1 + 1, HCA2 + 1, HCA2 + 1, HCA2 + 1, HCA2 + 1
+        );
+        assert_eq!(dump, expected);
+
+        assert!(matches!(fabric.dump_lft(0x1234), Err(FabricError::NodeNotFound)));
+    }
+
+    #[test]
+    fn fabric_lft_fetch_failed_success() {
+        let sim = Rc::new(simulator());
+        let mut fabric = Fabric::from_transport("sim0", sim.clone());
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+
+        //leaf02 is no longer routed from leaf01
+        sim.set_lft_entry(LEAF1, 3, LFT_NO_PORT).unwrap();
+
+        let failures = fabric.fetch_lfts(100);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, LEAF2);
+        assert!(fabric.nodes[&LEAF2].borrow().lft.is_none());
+        assert!(fabric.nodes[&SPINE].borrow().lft.is_some());
+    }
+}
@@ -0,0 +1,151 @@
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use rsmad::ibmad::port::{LinkSpeed, LinkWidth};
+    use rsmad::ibnetdisc::discover::DiscoverConfig;
+    use rsmad::ibnetdisc::fabric::Fabric;
+    use rsmad::ibnetdisc::node::NodeType;
+    use rsmad::ibnetdisc::topology_file::{load_topology, parse_topology, save_topology, write_topology, TopologyFileError};
+    use rsmad::ibsim::Simulator;
+    use rsmad::ibsim::topology::Topology;
+
+    const HCA1: u64 = 0x0002c90300a1b2c0;
+    const HCA2: u64 = 0x0002c90300a1b3d0;
+    const LEAF: u64 = 0xfc6a1c0300e4a800;
+    const SPINE: u64 = 0xfc6a1c0300e4b900;
+
+    //As written by ibnetdiscover
+    const CAPTURED: &str = "#
+# Topology file: generated on Tue Mar  4 10:12:31 2025
+#
+# Initiated from node 248a070300f8f4a4 port 248a070300f8f4a4
+
+vendid=0x2c9
+devid=0xcf08
+sysimgguid=0x248a070300f8f520
+switchguid=0x248a070300f8f520(248a070300f8f520)
+Switch\t36 \"S-248a070300f8f520\"\t\t# \"MF0;sw01:MSB7700/U1\" enhanced port 0 lid 3 lmc 0
+[1]\t\"H-248a070300f8f4a4\"[1](248a070300f8f4a4) \t\t# \"node01 HCA-1\" lid 4 4xEDR
+[2]\t\"H-248a070300f8f4b0\"[2](248a070300f8f4b1) \t\t# \"node02 HCA-1\" lid 5 4xEDR
+
+vendid=0x2c9
+devid=0x1013
+sysimgguid=0x248a070300f8f4a7
+caguid=0x248a070300f8f4a4
+Ca\t2 \"H-248a070300f8f4a4\"\t\t# \"node01 HCA-1\"
+[1](248a070300f8f4a4) \t\"S-248a070300f8f520\"[1]\t\t# lid 4 lmc 0 \"MF0;sw01:MSB7700/U1\" lid 3 4xEDR
+
+vendid=0x2c9
+devid=0x1013
+sysimgguid=0x248a070300f8f4b3
+caguid=0x248a070300f8f4b0
+Ca\t2 \"H-248a070300f8f4b0\"\t\t# \"node02 HCA-1\"
+[2](248a070300f8f4b1) \t\"S-248a070300f8f520\"[2]\t\t# lid 5 lmc 0 \"MF0;sw01:MSB7700/U1\" lid 3 4xEDR
+";
+
+    //hca1 -- [1] leaf [3] -- [5] spine, 4x QDR
+    //          leaf [2] -- hca2
+    fn fabric() -> Fabric {
+        let mut t = Topology::new();
+        t.add_ca(HCA1, "node01 HCA-1", 1, 10);
+        t.add_ca(HCA2, "node02 HCA-1", 1, 11);
+        t.add_switch(LEAF, "leaf01", 8, 2).port_mut(3).link_speed_active = 4;
+        t.add_switch(SPINE, "spine01", 8, 1).port_mut(5).link_speed_active = 4;
+        t.link((HCA1, 1), (LEAF, 1));
+        t.link((HCA2, 1), (LEAF, 2));
+        t.link((LEAF, 3), (SPINE, 5));
+
+        let sim = Rc::new(Simulator::new(t, HCA1, 1).unwrap());
+        let mut fabric = Fabric::from_transport("sim0", sim);
+        fabric.discover_dr(&DiscoverConfig::default()).unwrap();
+        fabric
+    }
+
+    #[test]
+    fn topology_file_round_trip_success() {
+        let fabric = fabric();
+        let text = write_topology(&fabric);
+        assert!(text.contains(&format!("switchguid=0x{:016x}({:x})\nSwitch\t8 \"S-{:016x}\"\t\t# \"leaf01\" enhanced port 0 lid 2 lmc 0\n", LEAF, LEAF, LEAF)));
+        assert!(text.contains(&format!("[3]\t\"S-{:016x}\"[5]\t\t# \"spine01\" lid 1 4xQDR\n", SPINE)));
+
+        let loaded = parse_topology(&text).unwrap();
+        assert_eq!(write_topology(&loaded), text);
+        assert_eq!(loaded.nodes.len(), 4);
+        assert_eq!(loaded.ports.len(), fabric.ports.values().filter(|p| p.borrow().remote_port.is_some()).count());
+        assert_eq!((loaded.switches.len(), loaded.adapters.len()), (2, 2));
+
+        for (key, port) in &loaded.ports {
+            let port = port.borrow();
+            let original = fabric.ports[key].borrow();
+            assert_eq!(port.base_lid, original.base_lid, "{:x?}", key);
+            let (info, original_info) = (port.info.as_ref().unwrap(), original.info.as_ref().unwrap());
+            assert_eq!((info.link_width(), info.link_speed()), (original_info.link_width(), original_info.link_speed()));
+
+            let remote = port.remote_port.as_ref().unwrap().upgrade().unwrap();
+            let original_remote = original.remote_port.as_ref().unwrap().upgrade().unwrap();
+            assert_eq!((remote.borrow().guid, remote.borrow().number), (original_remote.borrow().guid, original_remote.borrow().number));
+        }
+
+        let hca = loaded.nodes[&HCA1].borrow();
+        assert_eq!((hca.lid, hca.node_desc.as_str(), hca.num_ports), (10, "node01 HCA-1", 1));
+    }
+
+    #[test]
+    fn topology_file_captured_success() {
+        let fabric = parse_topology(CAPTURED).unwrap();
+        assert_eq!(fabric.nodes.len(), 3);
+
+        let switch = fabric.nodes[&0x248a070300f8f520].borrow();
+        assert!(matches!(switch.node_type, NodeType::SWITCH));
+        assert_eq!((switch.lid, switch.num_ports, switch.dev_id, switch.vendor_id), (3, 36, 0xcf08, 0x2c9));
+        assert_eq!(switch.node_desc, "MF0;sw01:MSB7700/U1");
+        assert!(switch.switch_info.as_ref().unwrap().enhanced_port0);
+        assert_eq!(switch.system_guid, 0x248a070300f8f520);
+        assert_eq!(fabric.nodes[&0x248a070300f8f4b0].borrow().system_guid, 0x248a070300f8f4b3);
+
+        //A CA port has a GUID of its own
+        let port = fabric.ports[&(0x248a070300f8f4b1, 2)].borrow();
+        assert_eq!(port.base_lid, 5);
+        let info = port.info.as_ref().unwrap();
+        assert_eq!((info.link_width(), info.link_speed()), (LinkWidth::X4, LinkSpeed::Edr));
+        assert!(info.is_active());
+        let remote = port.remote_port.as_ref().unwrap().upgrade().unwrap();
+        assert_eq!((remote.borrow().guid, remote.borrow().number), (0x248a070300f8f520, 2));
+        let remote_node = port.remote_node.as_ref().unwrap().upgrade().unwrap();
+        assert_eq!(remote_node.borrow().lid, 3);
+        assert_eq!(fabric.nodes[&0x248a070300f8f4b0].borrow().lid, 5);
+
+        //What ibnetdiscover would write back
+        let text = write_topology(&fabric);
+        let expected: Vec<&str> = CAPTURED.lines().skip(5).collect();
+        let written: Vec<&str> = text.lines().skip(4).collect();
+        assert_eq!(written, expected);
+
+        //PortInfo can't tell FDR10 from QDR, it comes back as QDR
+        let fdr10 = parse_topology(&CAPTURED.replace("4xEDR", "4xFDR10")).unwrap();
+        let info = fdr10.ports[&(0x248a070300f8f4b1, 2)].borrow().info.clone().unwrap();
+        assert_eq!((info.link_width(), info.link_speed()), (LinkWidth::X4, LinkSpeed::Qdr));
+        assert!(write_topology(&fdr10).contains("\"node02 HCA-1\" lid 5 4xQDR\n"));
+    }
+
+    #[test]
+    fn topology_file_errors_success() {
+        let path = std::env::temp_dir().join(format!("rsmad-topology-{}.txt", std::process::id()));
+        save_topology(&parse_topology(CAPTURED).unwrap(), &path).unwrap();
+        let loaded = load_topology(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().ports.len(), 4);
+
+        assert!(matches!(load_topology("/nonexistent/topology.txt"), Err(TopologyFileError::ReadError(_))));
+
+        //The other end of a link has to be in the file
+        let partial: String = CAPTURED.lines().take(21).collect::<Vec<_>>().join("\n");
+        let r = parse_topology(&partial);
+        assert!(matches!(r, Err(TopologyFileError::ParseError(12, _))), "{:?}", r.err());
+
+        assert!(matches!(parse_topology("[1]\t\"S-0000000000000001\"[1]\n"), Err(TopologyFileError::ParseError(1, _))));
+        assert!(matches!(parse_topology("Switch\tmany \"S-0000000000000001\"\n"), Err(TopologyFileError::ParseError(1, _))));
+    }
+}
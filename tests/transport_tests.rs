@@ -0,0 +1,130 @@
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use rsmad::ibmad::{IBSmpError, sys};
+    use rsmad::ibmad::transport::{DrPath, MadAddress, MadRequest, MadResponse, MadTransport};
+
+    struct MockTransport {
+        requests: RefCell<Vec<MadRequest>>,
+        response: Option<MadResponse>,
+    }
+
+    impl MockTransport {
+        fn new(response: Option<MadResponse>) -> MockTransport {
+            MockTransport {
+                requests: RefCell::new(Vec::new()),
+                response,
+            }
+        }
+    }
+
+    impl MadTransport for MockTransport {
+        fn call(&self, request: &MadRequest) -> Result<MadResponse, IBSmpError> {
+            self.requests.borrow_mut().push(request.clone());
+            self.response.clone().ok_or(IBSmpError::SendMADError)
+        }
+    }
+
+    #[test]
+    fn dr_path_parse_success() {
+        let path = DrPath::parse("0,1,1,1,45").unwrap();
+        assert_eq!(path.hops, vec![1, 1, 1, 45]);
+        assert_eq!(path.to_string(), "0,1,1,1,45");
+
+        let local = DrPath::parse("0").unwrap();
+        assert!(local.hops.is_empty());
+
+        assert!(matches!(DrPath::parse("0,1,x"), Err(IBSmpError::DRMADPathError)));
+        assert!(matches!(DrPath::parse(""), Err(IBSmpError::DRMADPathError)));
+
+        //63 hops is as long as a path gets
+        let longest = DrPath::new(&[1; 62]).push(2).unwrap();
+        assert_eq!((longest.hops.len(), longest.hops[62]), (63, 2));
+        assert!(matches!(longest.push(1), Err(IBSmpError::DRMADPathError)));
+        assert_eq!(DrPath::parse(&longest.to_string()).unwrap(), longest);
+    }
+
+    #[test]
+    fn mock_send_dr_node_desc_mad_success() {
+        let mut data = vec![0; 64];
+        data[..12].copy_from_slice(b"switch-leaf1");
+        let transport = MockTransport::new(Some(MadResponse { status: 0, data, ..Default::default() }));
+
+        let r = rsmad::ibmad::send_dr_node_desc_mad(&transport, "0,1,1,1,45", 200);
+        assert_eq!(r.unwrap(), "switch-leaf1");
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].mgmt_class, sys::MAD_CLASSES_IB_SMI_DIRECT_CLASS);
+        assert_eq!(requests[0].method, sys::MAD_METHODS_IB_MAD_METHOD_GET);
+        assert_eq!(requests[0].attr_id, sys::SMI_ATTR_ID_IB_ATTR_NODE_DESC);
+        assert_eq!(requests[0].address, MadAddress::DirectedRoute(DrPath::new(&[1, 1, 1, 45])));
+        assert_eq!(requests[0].timeout, 200);
+    }
+
+    #[test]
+    fn mock_send_lid_node_info_mad_request_success() {
+        let transport = MockTransport::new(Some(MadResponse { status: 0, data: vec![0; 64], ..Default::default() }));
+
+        let r = rsmad::ibmad::send_lid_node_info_mad(&transport, 132, 3000);
+        assert!(r.is_ok());
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests[0].mgmt_class, sys::MAD_CLASSES_IB_SMI_CLASS);
+        assert_eq!(requests[0].attr_id, sys::SMI_ATTR_ID_IB_ATTR_NODE_INFO);
+        assert_eq!(requests[0].address, MadAddress::Lid(132));
+    }
+
+    #[test]
+    fn mock_perfquery_request_success() {
+        let transport = MockTransport::new(Some(MadResponse { status: 0, data: vec![0; 192], ..Default::default() }));
+
+        let r = rsmad::ibmad::perfquery(&transport, 2, 45, 0, 3000);
+        assert!(r.is_ok());
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests[0].mgmt_class, sys::MAD_CLASSES_IB_PERFORMANCE_CLASS);
+        assert_eq!(requests[0].attr_id, sys::GSI_ATTR_ID_IB_GSI_PORT_COUNTERS_EXT);
+        assert_eq!(requests[0].address, MadAddress::Lid(2));
+    }
+
+    #[test]
+    fn mock_bad_status_failed_success() {
+        let transport = MockTransport::new(Some(MadResponse { status: 0x1c, data: vec![0; 64], ..Default::default() }));
+
+        let r = rsmad::ibmad::send_dr_node_info_mad(&transport, "0,1", 200);
+        assert!(matches!(r, Err(IBSmpError::MadStatusError(0x1c))), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn mock_no_response_failed_success() {
+        let transport = MockTransport::new(None);
+
+        let r = rsmad::ibmad::send_dr_node_desc_mad(&transport, "0,1", 200);
+        assert!(matches!(r, Err(IBSmpError::SendMADError)), "Unexpected result: {:?}", r);
+    }
+
+    #[test]
+    fn mock_bad_dr_path_failed_success() {
+        let transport = MockTransport::new(None);
+
+        let r = rsmad::ibmad::send_dr_node_info_mad(&transport, "0,1,abc", 200);
+        assert!(matches!(r, Err(IBSmpError::DRMADPathError)), "Unexpected result: {:?}", r);
+        assert!(transport.requests.borrow().is_empty());
+    }
+
+    #[test]
+    fn mock_set_node_desc_request_success() {
+        let transport = MockTransport::new(Some(MadResponse { status: 0, data: vec![0; 64], ..Default::default() }));
+
+        let r = rsmad::ibmad::set_node_desc(&transport, 2, "leaf01 rack 4", 3000);
+        assert!(r.is_ok());
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests[0].method, sys::MAD_METHODS_IB_MAD_METHOD_SET);
+        assert_eq!(&requests[0].data[..14], b"leaf01 rack 4\0");
+        assert_eq!(requests[0].data.len(), 64);
+    }
+}
@@ -2,6 +2,7 @@
 #[cfg(test)]
 mod tests {
     use rsmad::umad::{umad_close_port, umad_open_port};
+    use rsmad::umad::agent::UmadAgent;
 
 
     #[test]
@@ -85,4 +86,29 @@ mod tests {
         rsmad::umad::umad_done();
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn umad_agent_register_success() {
+        rsmad::umad::umad_init();
+
+        let ca_names = rsmad::umad::umad_list_devices().unwrap();
+
+        ca_names.iter().for_each(|c|{
+            let port = umad_open_port(c, 1).unwrap();
+
+            //PerfMgt, taking unsolicited Get and Set
+            let agent = UmadAgent::register(&port, 0x04, 1, &[0x01, 0x02]).unwrap();
+            assert!(agent.id() >= 0);
+            assert!(agent.fd() >= 0);
+
+            //Nothing is sent to us
+            let result = agent.recv(10);
+            assert!(matches!(result, Err(rsmad::umad::UmadError::Timeout)), "Unexpected result: {:?}", result.map(|m| m.agent_id));
+
+            drop(agent);
+            let _ = umad_close_port(port);
+        });
+
+        rsmad::umad::umad_done();
+    }
+}